@@ -0,0 +1,65 @@
+use byteorder::{LittleEndian, WriteBytesExt};
+use criterion::{criterion_group, criterion_main, Criterion};
+use dalvik::{bytecode::ByteCodeDecoder, Dex, ParseTimings};
+use std::{fs, io::Cursor};
+
+/// Number of parses to measure to find the per-phase breakdown of the fastest one.
+const MEASURED_PARSES: usize = 20;
+
+fn parse(c: &mut Criterion) {
+    let bytes = fs::read("test.dex").expect("could not read test.dex");
+
+    let fastest = (0..MEASURED_PARSES)
+        .map(|_| {
+            Dex::parse_bytes_measured(&bytes)
+                .expect("could not parse test.dex")
+                .1
+        })
+        .min_by_key(ParseTimings::total)
+        .expect("no parses were measured");
+    println!(
+        "time per phase of the fastest parse of test.dex:\n{}",
+        fastest
+    );
+
+    let _ = c.bench_function("parse test.dex", |b| {
+        b.iter(|| Dex::from_reader(&bytes[..], bytes.len()).expect("could not parse test.dex"))
+    });
+}
+
+fn decode(c: &mut Criterion) {
+    let dex = Dex::from_file("test.dex").expect("could not parse test.dex");
+    let code: Vec<&[u16]> = dex
+        .code_items()
+        .map(|(_, code_item)| code_item.insns())
+        .collect();
+
+    let _ = c.bench_function("decode test.dex from code units", |b| {
+        b.iter(|| {
+            code.iter()
+                .flat_map(|&units| ByteCodeDecoder::<_, LittleEndian>::from_code_units(units))
+                .count()
+        })
+    });
+
+    // The bytes are copied to a buffer as part of the measurement, as callers of the cursor
+    // based decoder had to do before decoding code units directly.
+    let _ = c.bench_function("decode test.dex from a byte cursor", |b| {
+        b.iter(|| {
+            code.iter()
+                .flat_map(|&units| {
+                    let mut bytes = Vec::with_capacity(units.len() * 2);
+                    for &unit in units {
+                        bytes
+                            .write_u16::<LittleEndian>(unit)
+                            .expect("could not write to a vector");
+                    }
+                    ByteCodeDecoder::<_, LittleEndian>::new(Cursor::new(bytes))
+                })
+                .count()
+        })
+    });
+}
+
+criterion_group!(benches, parse, decode);
+criterion_main!(benches);
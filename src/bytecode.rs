@@ -1,9 +1,13 @@
 //! Representation of the Dalvik bytecodes and utilities to decode them
 
+pub use crate::error::DecodeError;
+
+use crate::error::Result;
 use byteorder::{ByteOrder, LittleEndian, ReadBytesExt};
 use std::{
     fmt::Debug,
-    io::{self, Read},
+    io::{self, Cursor, Read},
+    iter::Filter,
     marker::PhantomData,
 };
 
@@ -31,10 +35,14 @@ pub enum ByteCode {
     Const4(u8, i32),
     Const16(u8, i32),
     Const(u8, i32),
+    /// `const/high16`, with the materialized 32-bit value: the 16-bit literal of the instruction
+    /// shifted 16 bits to the left.
     ConstHigh16(u8, i32),
     ConstWide16(u8, i64),
     ConstWide32(u8, i64),
     ConstWide(u8, i64),
+    /// `const-wide/high16`, with the materialized 64-bit value: the 16-bit literal of the
+    /// instruction shifted 48 bits to the left.
     ConstWideHigh16(u8, i64),
     ConstString(u8, StringReference),
     ConstStringJumbo(u8, StringReference),
@@ -47,6 +55,7 @@ pub enum ByteCode {
     NewInstance(u8, TypeReference),
     NewArray(u8, u8, TypeReference),
     FilledNewArray(Vec<u8>, TypeReference),
+    /// `filled-new-array/range`, with the first register and the number of registers.
     FilledNewArrayRange(u16, u8, TypeReference),
     FillArrayData(u8, i32),
     Throw(u8),
@@ -62,6 +71,7 @@ pub enum ByteCode {
     Instance(ArrayOperation, u8, u8, FieldReference),
     Static(ArrayOperation, u8, FieldReference),
     Invoke(InvokeKind, Vec<u8>, MethodReference),
+    /// `invoke-kind/range`, with the first register and the number of registers.
     InvokeRange(InvokeKind, u16, u8, MethodReference),
     Unary(UnaryOperation, u8, u8),
     Binary(BinaryOperation, u8, u8, u8),
@@ -69,8 +79,10 @@ pub enum ByteCode {
     BinaryLit16(BinaryOperation, u8, u8, i16),
     BinaryLit8(BinaryOperation, u8, u8, i8),
     InvokePolymorphic(Vec<u8>, MethodReference, PrototypeReference),
+    /// `invoke-polymorphic/range`, with the first register and the number of registers.
     InvokePolymorphicRange(u16, u8, MethodReference, PrototypeReference),
     InvokeCustom(Vec<u8>, CallSiteReference),
+    /// `invoke-custom/range`, with the first register and the number of registers.
     InvokeCustomRange(u16, u8, CallSiteReference),
 }
 
@@ -151,7 +163,7 @@ impl ToString for TestType {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[allow(missing_docs)]
 pub enum ArrayOperation {
     Get,
@@ -455,6 +467,36 @@ impl ToString for BinaryOperation {
     }
 }
 
+/// Implements `opcode_index()` for the given enums of operations, whose variants are declared in
+/// the order of their opcodes, followed by `Unknown`.
+macro_rules! impl_opcode_index {
+    ($($operation:ty),+) => {
+        $(
+            impl $operation {
+                /// Gets the position of the operation in its group of opcodes, or `None` if it is
+                /// unknown.
+                #[allow(clippy::cast_possible_truncation)]
+                fn opcode_index(self) -> Option<u8> {
+                    if let Self::Unknown = self {
+                        None
+                    } else {
+                        Some(self as u8)
+                    }
+                }
+            }
+        )+
+    };
+}
+
+impl_opcode_index!(
+    CompareType,
+    TestType,
+    ArrayOperation,
+    InvokeKind,
+    UnaryOperation,
+    BinaryOperation
+);
+
 /// String index on the Dex string table
 pub type StringReference = u32;
 /// Class index on the Dex class table
@@ -470,6 +512,455 @@ pub type PrototypeReference = u32;
 /// Call site index on the Dex call site table
 pub type CallSiteReference = u32;
 
+/// Reference to an item of one of the Dex tables, tagged with the kind of the table.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Reference {
+    /// Reference to a string.
+    String(StringReference),
+    /// Reference to a type.
+    Type(TypeReference),
+    /// Reference to a field.
+    Field(FieldReference),
+    /// Reference to a method.
+    Method(MethodReference),
+    /// Reference to a prototype.
+    Proto(PrototypeReference),
+    /// Reference to a call site.
+    CallSite(CallSiteReference),
+}
+
+/// Resolution of the items referenced by instructions to their textual representation.
+///
+/// It is implemented by `Dex`, but consumers can provide their own resolvers, for example to
+/// resolve references across all the dex files of a multidex application.
+pub trait ReferenceResolver {
+    /// Gets the representation of the string at the given index, quoted and escaped.
+    fn string(&self, index: StringReference) -> String;
+
+    /// Gets the descriptor of the type at the given index, such as `Ljava/lang/Object;`.
+    fn type_name(&self, index: TypeReference) -> String;
+
+    /// Gets the representation of the field at the given index, as `Lclass;.name:type`.
+    fn field(&self, index: FieldReference) -> String;
+
+    /// Gets the representation of the method at the given index, as `Lclass;.name:(II)V`.
+    fn method(&self, index: MethodReference) -> String;
+
+    /// Gets the signature of the prototype at the given index, such as `(II)V`.
+    fn proto(&self, index: PrototypeReference) -> String;
+
+    /// Gets the representation of the item a reference points to, if it can be resolved.
+    ///
+    /// Call sites are not resolved.
+    fn resolve(&self, reference: Reference) -> Option<String> {
+        match reference {
+            Reference::String(i) => Some(self.string(i)),
+            Reference::Type(i) => Some(self.type_name(i)),
+            Reference::Field(i) => Some(self.field(i)),
+            Reference::Method(i) => Some(self.method(i)),
+            Reference::Proto(i) => Some(self.proto(i)),
+            Reference::CallSite(_) => None,
+        }
+    }
+}
+
+impl ByteCode {
+    /// Renders the instruction, resolving the item it references, if any.
+    ///
+    /// The resolved item replaces the raw reference, which is kept in a trailing comment, as
+    /// `dexdump` does.
+    pub fn to_string_resolved(&self, resolver: &dyn ReferenceResolver) -> String {
+        let text = self.to_string();
+        let reference = if let Some(reference) = self.reference() {
+            reference
+        } else {
+            return text;
+        };
+        let resolved = if let Some(resolved) = resolver.resolve(reference) {
+            resolved
+        } else {
+            return text;
+        };
+        let (prefixes, index): (&[&str], u32) = match reference {
+            Reference::String(i) => (&["string"], i),
+            Reference::Type(i) => (&["type", "class"], i),
+            Reference::Field(i) => (&["field"], i),
+            Reference::Method(i) => (&["method"], i),
+            Reference::Proto(i) => (&["proto"], i),
+            Reference::CallSite(_) => return text,
+        };
+        for prefix in prefixes {
+            let raw = format!("{}@{}", prefix, index);
+            if text.contains(&raw) {
+                return format!("{} // {}", text.replacen(&raw, &resolved, 1), raw);
+            }
+        }
+        text
+    }
+
+    /// Checks if the instruction is a method invocation, of any kind.
+    pub fn is_invoke(&self) -> bool {
+        matches!(
+            self,
+            Self::Invoke(..)
+                | Self::InvokeRange(..)
+                | Self::InvokePolymorphic(..)
+                | Self::InvokePolymorphicRange(..)
+                | Self::InvokeCustom(..)
+                | Self::InvokeCustomRange(..)
+        )
+    }
+
+    /// Checks if the instruction leaves a result to be read by a following `move-result*`
+    /// instruction.
+    pub fn produces_result(&self) -> bool {
+        self.is_invoke()
+            || matches!(
+                self,
+                Self::FilledNewArray(..) | Self::FilledNewArrayRange(..)
+            )
+    }
+
+    /// Checks if the instruction is a `move-result*` instruction.
+    pub fn is_move_result(&self) -> bool {
+        matches!(
+            self,
+            Self::MoveResult(_) | Self::MoveResultWide(_) | Self::MoveResultObject(_)
+        )
+    }
+
+    /// Checks if the instruction reads or writes an instance or a static field.
+    pub fn is_field_op(&self) -> bool {
+        matches!(self, Self::Instance(..) | Self::Static(..))
+    }
+
+    /// Checks if the instruction loads a constant string.
+    pub fn is_const_string(&self) -> bool {
+        matches!(self, Self::ConstString(..) | Self::ConstStringJumbo(..))
+    }
+
+    /// Gets the value loaded by a numeric `const` instruction, if the instruction is one.
+    ///
+    /// The value is the constant as stored in the destination register, sign extended to 64
+    /// bits. For the `high16` variants, this is the literal of the instruction already shifted
+    /// to the high bits of the value.
+    pub fn value(&self) -> Option<i64> {
+        match self {
+            Self::Const4(_, value)
+            | Self::Const16(_, value)
+            | Self::Const(_, value)
+            | Self::ConstHigh16(_, value) => Some(i64::from(*value)),
+            Self::ConstWide16(_, value)
+            | Self::ConstWide32(_, value)
+            | Self::ConstWide(_, value)
+            | Self::ConstWideHigh16(_, value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Gets the 64-bit value loaded by a `const-wide` instruction, if the instruction is one.
+    ///
+    /// The `/16` and `/32` variants sign extend their literal, and the `high16` variant places
+    /// its literal in the highest 16 bits, so this is the value as stored in the destination
+    /// register pair. Unlike `value()`, this is `None` for the 32-bit `const` instructions.
+    pub fn wide_value(&self) -> Option<i64> {
+        match self {
+            Self::ConstWide16(_, value)
+            | Self::ConstWide32(_, value)
+            | Self::ConstWide(_, value)
+            | Self::ConstWideHigh16(_, value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Encodes the instruction as code units, as the decoder reads them.
+    ///
+    /// Unused bits of the encoding are set to zero. Returns `None` if the instruction cannot be
+    /// encoded, because its operation is unknown or an operand does not fit in its field, such
+    /// as a register over `v15` in a 4-bit register field.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::too_many_lines
+    )]
+    pub fn encode(&self) -> Option<Vec<u16>> {
+        let units = match self {
+            Self::Nop => vec![0x00],
+            Self::Move(dest, src) => format12x(0x01, *dest, *src)?,
+            Self::MoveFrom16(dest, src) => format22x(0x02, *dest, *src),
+            Self::Move16(dest, src) => format32x(0x03, *dest, *src),
+            Self::MoveWide(dest, src) => format12x(0x04, *dest, *src)?,
+            Self::MoveWideFrom16(dest, src) => format22x(0x05, *dest, *src),
+            Self::MoveWide16(dest, src) => format32x(0x06, *dest, *src),
+            Self::MoveObject(dest, src) => format12x(0x07, *dest, *src)?,
+            Self::MoveObjectFrom16(dest, src) => format22x(0x08, *dest, *src),
+            Self::MoveObject16(dest, src) => format32x(0x09, *dest, *src),
+            Self::MoveResult(reg) => vec![unit(0x0A, *reg)],
+            Self::MoveResultWide(reg) => vec![unit(0x0B, *reg)],
+            Self::MoveResultObject(reg) => vec![unit(0x0C, *reg)],
+            Self::MoveException(reg) => vec![unit(0x0D, *reg)],
+            Self::ReturnVoid => vec![0x0E],
+            Self::Return(reg) => vec![unit(0x0F, *reg)],
+            Self::ReturnWide(reg) => vec![unit(0x10, *reg)],
+            Self::ReturnObject(reg) => vec![unit(0x11, *reg)],
+            Self::Const4(reg, literal) => {
+                if *literal < -8 || *literal > 7 {
+                    return None;
+                }
+                format12x(0x12, *reg, (*literal & 0xF) as u8)?
+            }
+            Self::Const16(reg, literal) => format21s(0x13, *reg, i64::from(*literal))?,
+            Self::Const(reg, literal) => format31(0x14, *reg, *literal as u32),
+            Self::ConstHigh16(reg, literal) => {
+                if literal & 0xFFFF != 0 {
+                    return None;
+                }
+                vec![unit(0x15, *reg), (literal >> 16) as u16]
+            }
+            Self::ConstWide16(reg, literal) => format21s(0x16, *reg, *literal)?,
+            Self::ConstWide32(reg, literal) => {
+                if *literal < i64::from(i32::min_value()) || *literal > i64::from(i32::max_value())
+                {
+                    return None;
+                }
+                format31(0x17, *reg, *literal as u32)
+            }
+            Self::ConstWide(reg, literal) => {
+                let literal = *literal as u64;
+                vec![
+                    unit(0x18, *reg),
+                    literal as u16,
+                    (literal >> 16) as u16,
+                    (literal >> 32) as u16,
+                    (literal >> 48) as u16,
+                ]
+            }
+            Self::ConstWideHigh16(reg, literal) => {
+                if literal & 0xFFFF_FFFF_FFFF != 0 {
+                    return None;
+                }
+                vec![unit(0x19, *reg), (literal >> 48) as u16]
+            }
+            Self::ConstString(reg, reference) => format21c(0x1A, *reg, *reference)?,
+            Self::ConstStringJumbo(reg, reference) => format31(0x1B, *reg, *reference),
+            Self::ConstClass(reg, reference) => format21c(0x1C, *reg, *reference)?,
+            Self::MonitorEnter(reg) => vec![unit(0x1D, *reg)],
+            Self::MonitorExit(reg) => vec![unit(0x1E, *reg)],
+            Self::CheckCast(reg, reference) => format21c(0x1F, *reg, *reference)?,
+            Self::InstanceOf(dest, src, reference) => format22c(0x20, *dest, *src, *reference)?,
+            Self::ArrayLength(dest, src) => format12x(0x21, *dest, *src)?,
+            Self::NewInstance(reg, reference) => format21c(0x22, *reg, *reference)?,
+            Self::NewArray(dest, size, reference) => format22c(0x23, *dest, *size, *reference)?,
+            Self::FilledNewArray(registers, reference) => format35c(0x24, registers, *reference)?,
+            Self::FilledNewArrayRange(first, amount, reference) => {
+                format3rc(0x25, *first, *amount, *reference)?
+            }
+            Self::FillArrayData(reg, offset) => format31(0x26, *reg, *offset as u32),
+            Self::Throw(reg) => vec![unit(0x27, *reg)],
+            Self::Goto(offset) => vec![unit(0x28, *offset as u8)],
+            Self::Goto16(offset) => vec![0x29, *offset as u16],
+            Self::Goto32(offset) => vec![0x2A, *offset as u16, (*offset >> 16) as u16],
+            Self::PackedSwitch(reg, offset) => format31(0x2B, *reg, *offset as u32),
+            Self::SparseSwitch(reg, offset) => format31(0x2C, *reg, *offset as u32),
+            Self::Compare(compare, dest, first, second) => {
+                format23x(0x2D + compare.opcode_index()?, *dest, *first, *second)
+            }
+            Self::If(test, first, second, offset) => format22c(
+                0x32 + test.opcode_index()?,
+                *first,
+                *second,
+                u32::from(*offset as u16),
+            )?,
+            Self::If0(test, reg, offset) => {
+                vec![unit(0x38 + test.opcode_index()?, *reg), *offset as u16]
+            }
+            Self::Array(operation, value, array, index) => {
+                format23x(0x44 + operation.opcode_index()?, *value, *array, *index)
+            }
+            Self::Instance(operation, value, object, reference) => format22c(
+                0x52 + operation.opcode_index()?,
+                *value,
+                *object,
+                *reference,
+            )?,
+            Self::Static(operation, value, reference) => {
+                format21c(0x60 + operation.opcode_index()?, *value, *reference)?
+            }
+            Self::Invoke(kind, registers, reference) => {
+                format35c(0x6E + kind.opcode_index()?, registers, *reference)?
+            }
+            Self::InvokeRange(kind, first, amount, reference) => {
+                format3rc(0x74 + kind.opcode_index()?, *first, *amount, *reference)?
+            }
+            Self::Unary(operation, dest, src) => {
+                format12x(0x7B + operation.opcode_index()?, *dest, *src)?
+            }
+            Self::Binary(operation, dest, first, second) => {
+                format23x(0x90 + operation.opcode_index()?, *dest, *first, *second)
+            }
+            Self::Binary2Addr(operation, dest, src) => {
+                format12x(0xB0 + operation.opcode_index()?, *dest, *src)?
+            }
+            Self::BinaryLit16(operation, dest, src, literal) => {
+                let index = operation.opcode_index().filter(|&index| index < 8)?;
+                format22c(0xD0 + index, *dest, *src, u32::from(*literal as u16))?
+            }
+            Self::BinaryLit8(operation, dest, src, literal) => {
+                let index = operation.opcode_index().filter(|&index| index < 11)?;
+                vec![unit(0xD8 + index, *dest), unit(*src, *literal as u8)]
+            }
+            Self::InvokePolymorphic(registers, method, prototype) => {
+                let mut units = format35c(0xFA, registers, *method)?;
+                units.push(reference_unit(*prototype)?);
+                units
+            }
+            Self::InvokePolymorphicRange(first, amount, method, prototype) => {
+                let mut units = format3rc(0xFB, *first, *amount, *method)?;
+                units.push(reference_unit(*prototype)?);
+                units
+            }
+            Self::InvokeCustom(registers, call_site) => format35c(0xFC, registers, *call_site)?,
+            Self::InvokeCustomRange(first, amount, call_site) => {
+                format3rc(0xFD, *first, *amount, *call_site)?
+            }
+        };
+        Some(units)
+    }
+
+    /// Gets the reference to a Dex table item used by the instruction, if any.
+    ///
+    /// For `invoke-polymorphic` instructions, which reference both a method and a prototype, the
+    /// method reference is returned.
+    pub fn reference(&self) -> Option<Reference> {
+        match self {
+            Self::ConstString(_, reference) | Self::ConstStringJumbo(_, reference) => {
+                Some(Reference::String(*reference))
+            }
+            Self::ConstClass(_, reference)
+            | Self::CheckCast(_, reference)
+            | Self::InstanceOf(_, _, reference)
+            | Self::NewInstance(_, reference)
+            | Self::NewArray(_, _, reference)
+            | Self::FilledNewArray(_, reference)
+            | Self::FilledNewArrayRange(_, _, reference) => Some(Reference::Type(*reference)),
+            Self::Instance(_, _, _, reference) | Self::Static(_, _, reference) => {
+                Some(Reference::Field(*reference))
+            }
+            Self::Invoke(_, _, reference)
+            | Self::InvokeRange(_, _, _, reference)
+            | Self::InvokePolymorphic(_, reference, _)
+            | Self::InvokePolymorphicRange(_, _, reference, _) => {
+                Some(Reference::Method(*reference))
+            }
+            Self::InvokeCustom(_, reference) | Self::InvokeCustomRange(_, _, reference) => {
+                Some(Reference::CallSite(*reference))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Builds a code unit from its low and high bytes.
+fn unit(low: u8, high: u8) -> u16 {
+    u16::from(low) | u16::from(high) << 8
+}
+
+/// Gets the code unit holding a 16-bit reference, if the reference fits in it.
+fn reference_unit(reference: u32) -> Option<u16> {
+    if reference > 0xFFFF {
+        None
+    } else {
+        #[allow(clippy::cast_possible_truncation)]
+        Some(reference as u16)
+    }
+}
+
+/// Encodes an instruction with two 4-bit operands, as in the `12x` and `11n` formats.
+fn format12x(opcode: u8, a: u8, b: u8) -> Option<Vec<u16>> {
+    if a > 0xF || b > 0xF {
+        None
+    } else {
+        Some(vec![unit(opcode, b << 4 | a)])
+    }
+}
+
+/// Encodes an instruction of the `22x` format.
+fn format22x(opcode: u8, a: u8, b: u16) -> Vec<u16> {
+    vec![unit(opcode, a), b]
+}
+
+/// Encodes an instruction of the `32x` format.
+fn format32x(opcode: u8, a: u16, b: u16) -> Vec<u16> {
+    vec![u16::from(opcode), a, b]
+}
+
+/// Encodes an instruction of the `21s` format, if the literal fits in 16 bits.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn format21s(opcode: u8, a: u8, literal: i64) -> Option<Vec<u16>> {
+    if literal < i64::from(i16::min_value()) || literal > i64::from(i16::max_value()) {
+        None
+    } else {
+        Some(vec![unit(opcode, a), literal as u16])
+    }
+}
+
+/// Encodes an instruction of the `21c` format.
+fn format21c(opcode: u8, a: u8, reference: u32) -> Option<Vec<u16>> {
+    Some(vec![unit(opcode, a), reference_unit(reference)?])
+}
+
+/// Encodes an instruction of the `22c`, `22t` and `22s` formats.
+fn format22c(opcode: u8, a: u8, b: u8, reference: u32) -> Option<Vec<u16>> {
+    let mut units = format12x(opcode, a, b)?;
+    units.push(reference_unit(reference)?);
+    Some(units)
+}
+
+/// Encodes an instruction of the `23x` format.
+fn format23x(opcode: u8, a: u8, b: u8, c: u8) -> Vec<u16> {
+    vec![unit(opcode, a), unit(b, c)]
+}
+
+/// Encodes an instruction with a 32-bit operand, as in the `31i`, `31t` and `31c` formats.
+#[allow(clippy::cast_possible_truncation)]
+fn format31(opcode: u8, a: u8, operand: u32) -> Vec<u16> {
+    vec![unit(opcode, a), operand as u16, (operand >> 16) as u16]
+}
+
+/// Encodes an instruction of the `35c` format.
+fn format35c(opcode: u8, registers: &[u8], reference: u32) -> Option<Vec<u16>> {
+    if registers.len() > 5 || registers.iter().any(|&register| register > 0xF) {
+        return None;
+    }
+    let register = |index: usize| u16::from(registers.get(index).copied().unwrap_or(0));
+    #[allow(clippy::cast_possible_truncation)]
+    let count = registers.len() as u8;
+    Some(vec![
+        unit(opcode, count << 4 | registers.get(4).copied().unwrap_or(0)),
+        reference_unit(reference)?,
+        register(0) | register(1) << 4 | register(2) << 8 | register(3) << 12,
+    ])
+}
+
+/// Encodes an instruction of the `3rc` format.
+fn format3rc(opcode: u8, first: u16, amount: u8, reference: u32) -> Option<Vec<u16>> {
+    Some(vec![
+        unit(opcode, amount),
+        reference_unit(reference)?,
+        first,
+    ])
+}
+
+/// Lists the registers of a `/range` instruction, as in `v1, v2, v3`.
+fn range_registers(first: u16, amount: u8) -> String {
+    let first = u32::from(first);
+    // The last register can be v65535, so the end of the range may not fit in a `u16`.
+    (first..first + u32::from(amount))
+        .map(|register| format!("v{}", register))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 impl ToString for ByteCode {
     fn to_string(&self) -> String {
         match self {
@@ -537,12 +1028,9 @@ impl ToString for ByteCode {
                 )
             }
             Self::FilledNewArrayRange(first_reg, amount, reference) => {
-                let str_register: Vec<String> = (*first_reg..=(*first_reg + u16::from(*amount)))
-                    .map(|r| format!("v{}", r))
-                    .collect();
                 format!(
                     "filled-new-array/range {{{}}}, type@{}",
-                    str_register.join(", "),
+                    range_registers(*first_reg, *amount),
                     reference
                 )
             }
@@ -584,13 +1072,10 @@ impl ToString for ByteCode {
                 )
             }
             Self::InvokeRange(ref invoke_kind, first_reg, amount, reference) => {
-                let str_register: Vec<String> = (*first_reg..(*first_reg + u16::from(*amount)))
-                    .map(|r| format!("v{}", r))
-                    .collect();
                 format!(
                     "{}/range {{{}}}, method@{}",
                     invoke_kind.to_string(),
-                    str_register.join(", "),
+                    range_registers(*first_reg, *amount),
                     reference
                 )
             }
@@ -634,12 +1119,9 @@ impl ToString for ByteCode {
                 )
             }
             Self::InvokePolymorphicRange(first_reg, amount, method, proto) => {
-                let str_register: Vec<String> = (*first_reg..(*first_reg + u16::from(*amount)))
-                    .map(|r| format!("v{}", r))
-                    .collect();
                 format!(
                     "invoke-polymorphic/range {{{}}}, method@{} proto@{}",
-                    str_register.join(", "),
+                    range_registers(*first_reg, *amount),
                     method,
                     proto
                 )
@@ -654,12 +1136,9 @@ impl ToString for ByteCode {
                 )
             }
             Self::InvokeCustomRange(first_reg, amount, call_site) => {
-                let str_register: Vec<String> = (*first_reg..(*first_reg + u16::from(*amount)))
-                    .map(|r| format!("v{}", r))
-                    .collect();
                 format!(
                     "invoke-custom/range {{{}}}, call_site@{}",
-                    str_register.join(", "),
+                    range_registers(*first_reg, *amount),
                     call_site
                 )
             }
@@ -667,39 +1146,280 @@ impl ToString for ByteCode {
     }
 }
 
+/// Configuration of a `ByteCodeDecoder`.
+///
+/// By default, opcodes are decoded as described in the Dalvik bytecode specification. Some
+/// obfuscators and vendor-modified runtimes remap opcodes, so that the opcode found in the
+/// bytecode is not the standard one. For those cases, each opcode can be overridden to be
+/// decoded as a different standard opcode, which determines both the format of the instruction
+/// and the resulting `ByteCode`.
+///
+/// Overrides are not transitive: if opcode `0x12` is decoded as `0x13` and opcode `0x13` as
+/// `0x12`, the two opcodes are just swapped.
+#[derive(Debug, Clone, Copy)]
+pub struct DecoderConfig {
+    opcodes: [u8; 256],
+}
+
+impl DecoderConfig {
+    /// Creates a new configuration that decodes all the opcodes as standard opcodes.
+    pub fn new() -> Self {
+        let mut opcodes = [0_u8; 256];
+        for (opcode, standard) in (0..=u8::max_value()).zip(opcodes.iter_mut()) {
+            *standard = opcode;
+        }
+        Self { opcodes }
+    }
+
+    /// Decodes the given opcode as if it was the given standard opcode.
+    #[must_use]
+    pub fn with_opcode(mut self, opcode: u8, standard_opcode: u8) -> Self {
+        self.opcodes[opcode as usize] = standard_opcode;
+        self
+    }
+
+    /// Gets the standard opcode the given opcode will be decoded as.
+    pub fn standard_opcode(&self, opcode: u8) -> u8 {
+        self.opcodes[opcode as usize]
+    }
+}
+
+impl Default for DecoderConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Implementations of the distinct bytecodes data layouts.
 ///
 /// It will read from the source and return the data de-structured.
 #[derive(Debug)]
 pub struct ByteCodeDecoder<R: Read + Debug, B: ByteOrder = LittleEndian> {
     cursor: R,
+    config: DecoderConfig,
     byte_order: PhantomData<B>,
 }
 
 impl<R: Read + Debug, B: ByteOrder> ByteCodeDecoder<R, B> {
     /// Creates a new `ByteCodeDecoder` given a `Read` input
     pub fn new(cursor: R) -> Self {
+        Self::with_config(cursor, DecoderConfig::default())
+    }
+
+    /// Creates a new `ByteCodeDecoder` given a `Read` input and a decoder configuration.
+    ///
+    /// This allows decoding bytecode with non-standard opcodes. See `DecoderConfig` for more
+    /// information.
+    pub fn with_config(cursor: R, config: DecoderConfig) -> Self {
         Self {
             cursor,
+            config,
             byte_order: PhantomData,
         }
     }
 
-    fn format10x(&mut self) -> Result<(), io::Error> {
+    /// Gets a reference to the underlying reader.
+    ///
+    /// This can be used to know the position of the next instruction in the reader.
+    pub fn get_ref(&self) -> &R {
+        &self.cursor
+    }
+
+    /// Decodes the next instruction.
+    ///
+    /// Returns `Ok(None)` once the end of the input is reached between two instructions. Unlike
+    /// the `Iterator` implementation, which stops at the first error, this reports why the
+    /// instruction could not be decoded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the next instruction cannot be decoded.
+    pub fn next_instruction(&mut self) -> Result<Option<ByteCode>> {
+        let opcode = match self.cursor.read_u8() {
+            Ok(opcode) => opcode,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(DecodeError::from(e).into()),
+        };
+
+        let bytecode = match self.config.standard_opcode(opcode) {
+            0x00 => self.format10x().map(|_| ByteCode::Nop),
+            0x01 => self.format12x().map(|(d, s)| ByteCode::Move(d, s)),
+            0x02 => self.format22x().map(|(d, s)| ByteCode::MoveFrom16(d, s)),
+            0x03 => self.format32x().map(|(d, s)| ByteCode::Move16(d, s)),
+            0x04 => self.format12x().map(|(d, s)| ByteCode::MoveWide(d, s)),
+            0x05 => self
+                .format22x()
+                .map(|(d, s)| ByteCode::MoveWideFrom16(d, s)),
+            0x06 => self.format32x().map(|(d, s)| ByteCode::MoveWide16(d, s)),
+            0x07 => self.format12x().map(|(d, s)| ByteCode::MoveObject(d, s)),
+            0x08 => self
+                .format22x()
+                .map(|(d, s)| ByteCode::MoveObjectFrom16(d, s)),
+            0x09 => self.format32x().map(|(d, s)| ByteCode::MoveObject16(d, s)),
+            0x0A => self.format11x().map(ByteCode::MoveResult),
+            0x0B => self.format11x().map(ByteCode::MoveResultWide),
+            0x0C => self.format11x().map(ByteCode::MoveResultObject),
+            0x0D => self.format11x().map(ByteCode::MoveException),
+            0x0E => self.format10x().map(|_| ByteCode::ReturnVoid),
+            0x0F => self.format11x().map(ByteCode::Return),
+            0x10 => self.format11x().map(ByteCode::ReturnWide),
+            0x11 => self.format11x().map(ByteCode::ReturnObject),
+            0x12 => self
+                .format11n()
+                .map(|(reg, lit)| ByteCode::Const4(reg, lit)),
+            0x13 => self
+                .format21s()
+                .map(|(reg, lit)| ByteCode::Const16(reg, lit)),
+            0x14 => self.format31i().map(|(reg, lit)| ByteCode::Const(reg, lit)),
+            0x15 => self
+                .format21hw()
+                .map(|(reg, lit)| ByteCode::ConstHigh16(reg, lit)),
+            0x16 => self
+                .format21s()
+                .map(|(reg, lit)| ByteCode::ConstWide16(reg, i64::from(lit))),
+            0x17 => self
+                .format31i()
+                .map(|(reg, lit)| ByteCode::ConstWide32(reg, i64::from(lit))),
+            0x18 => self
+                .format51l()
+                .map(|(reg, lit)| ByteCode::ConstWide(reg, lit)),
+            0x19 => self
+                .format21hd()
+                .map(|(reg, lit)| ByteCode::ConstWideHigh16(reg, lit)),
+            0x1A => self.format21c().map(|(reg, reference)| {
+                ByteCode::ConstString(reg, StringReference::from(reference))
+            }),
+            0x1B => self
+                .format31c()
+                .map(|(reg, reference)| ByteCode::ConstStringJumbo(reg, reference)),
+            0x1C => self
+                .format21c()
+                .map(|(reg, reference)| ByteCode::ConstClass(reg, ClassReference::from(reference))),
+            0x1D => self.format11x().map(ByteCode::MonitorEnter),
+            0x1E => self.format11x().map(ByteCode::MonitorExit),
+            0x1F => self
+                .format21c()
+                .map(|(reg, reference)| ByteCode::CheckCast(reg, TypeReference::from(reference))),
+            0x20 => self.format22c().map(|(dest, src, reference)| {
+                ByteCode::InstanceOf(dest, src, TypeReference::from(reference))
+            }),
+            0x21 => self
+                .format12x()
+                .map(|(dest, src)| ByteCode::ArrayLength(dest, src)),
+            0x22 => self.format21c().map(|(dest, reference)| {
+                ByteCode::NewInstance(dest, TypeReference::from(reference))
+            }),
+            0x23 => self.format22c().map(|(dest, size, reference)| {
+                ByteCode::NewArray(dest, size, TypeReference::from(reference))
+            }),
+            0x24 => self.format35c().map(|(registers, reference)| {
+                ByteCode::FilledNewArray(registers, TypeReference::from(reference))
+            }),
+            0x25 => self.format3rc().map(|(first, amount, reference)| {
+                ByteCode::FilledNewArrayRange(first, amount, TypeReference::from(reference))
+            }),
+            0x26 => self
+                .format31t()
+                .map(|(reg, offset)| ByteCode::FillArrayData(reg, offset)),
+            0x27 => self.format11x().map(ByteCode::Throw),
+            0x28 => self.format10t().map(ByteCode::Goto),
+            0x29 => self.format20t().map(ByteCode::Goto16),
+            0x2A => self.format30t().map(ByteCode::Goto32),
+            0x2B => self
+                .format31t()
+                .map(|(reg, offset)| ByteCode::PackedSwitch(reg, offset)),
+            0x2C => self
+                .format31t()
+                .map(|(reg, offset)| ByteCode::SparseSwitch(reg, offset)),
+            a @ 0x2D..=0x31 => self
+                .format23x()
+                .map(|(dest, op1, op2)| ByteCode::Compare(CompareType::from(a), dest, op1, op2)),
+            a @ 0x32..=0x37 => self
+                .format22t()
+                .map(|(dest, src, offset)| ByteCode::If(TestType::from(a), dest, src, offset)),
+            a @ 0x38..=0x3D => self
+                .format21t()
+                .map(|(dest, offset)| ByteCode::If0(TestType::from(a), dest, offset)),
+            a @ 0x44..=0x51 => self
+                .format23x()
+                .map(|(dest, op1, op2)| ByteCode::Array(ArrayOperation::from(a), dest, op1, op2)),
+            a @ 0x52..=0x5f => self.format22c().map(|(dest, op1, reference)| {
+                ByteCode::Instance(
+                    ArrayOperation::from(a),
+                    dest,
+                    op1,
+                    FieldReference::from(reference),
+                )
+            }),
+            a @ 0x60..=0x6d => self.format21c().map(|(dest, reference)| {
+                ByteCode::Static(
+                    ArrayOperation::from(a),
+                    dest,
+                    FieldReference::from(reference),
+                )
+            }),
+            a @ 0x6e..=0x72 => self.format35c().map(|(registers, reference)| {
+                ByteCode::Invoke(
+                    InvokeKind::from(a),
+                    registers,
+                    MethodReference::from(reference),
+                )
+            }),
+            a @ 0x74..=0x78 => self.format3rc().map(|(first, amount, reference)| {
+                ByteCode::InvokeRange(
+                    InvokeKind::from(a),
+                    first,
+                    amount,
+                    FieldReference::from(reference),
+                )
+            }),
+            op @ 0x7b..=0x8f => self
+                .format12x()
+                .map(|(dest, src)| ByteCode::Unary(UnaryOperation::from(op), dest, src)),
+            op @ 0x90..=0xaf => self.format23x().map(|(dest, src1, src2)| {
+                ByteCode::Binary(BinaryOperation::from(op), dest, src1, src2)
+            }),
+            op @ 0xb0..=0xcf => self.format12x().map(|(src_dest, src)| {
+                ByteCode::Binary2Addr(BinaryOperation::from(op), src_dest, src)
+            }),
+            op @ 0xd0..=0xd7 => self.format22s().map(|(dest, src, literal)| {
+                ByteCode::BinaryLit16(BinaryOperation::from(op), dest, src, literal)
+            }),
+            op @ 0xd8..=0xe2 => self.format22b().map(|(dest, src, literal)| {
+                ByteCode::BinaryLit8(BinaryOperation::from(op), dest, src, literal)
+            }),
+            0xfa => self.format45cc().map(|(registers, method, proto)| {
+                ByteCode::InvokePolymorphic(registers, u32::from(method), u32::from(proto))
+            }),
+            0xfb => self.format4rcc().map(|(first, amount, method, proto)| {
+                ByteCode::InvokePolymorphicRange(first, amount, u32::from(method), u32::from(proto))
+            }),
+            0xfc => self.format35c().map(|(registers, call_site)| {
+                ByteCode::InvokeCustom(registers, u32::from(call_site))
+            }),
+            0xfd => self.format3rc().map(|(first, amount, call_site)| {
+                ByteCode::InvokeCustomRange(first, amount, u32::from(call_site))
+            }),
+            _ => Err(DecodeError::UnknownOpcode(opcode)),
+        }?;
+        Ok(Some(bytecode))
+    }
+
+    fn format10x(&mut self) -> Result<(), DecodeError> {
         let _ = self.cursor.read_u8()?;
 
         Ok(())
     }
 
-    fn format10t(&mut self) -> Result<i8, io::Error> {
+    fn format10t(&mut self) -> Result<i8, DecodeError> {
         Ok(self.cursor.read_i8()?)
     }
 
-    fn format11x(&mut self) -> Result<u8, io::Error> {
+    fn format11x(&mut self) -> Result<u8, DecodeError> {
         Ok(self.cursor.read_u8()?)
     }
 
-    fn format11n(&mut self) -> Result<(u8, i32), io::Error> {
+    fn format11n(&mut self) -> Result<(u8, i32), DecodeError> {
         let current_byte = self.cursor.read_u8()?;
 
         let literal = i32::from((current_byte & 0xF0) as i8 >> 4);
@@ -708,7 +1428,7 @@ impl<R: Read + Debug, B: ByteOrder> ByteCodeDecoder<R, B> {
         Ok((register, literal))
     }
 
-    fn format12x(&mut self) -> Result<(u8, u8), io::Error> {
+    fn format12x(&mut self) -> Result<(u8, u8), DecodeError> {
         let current_byte = self.cursor.read_u8()?;
 
         let source = (current_byte & 0xF0) >> 4;
@@ -717,7 +1437,7 @@ impl<R: Read + Debug, B: ByteOrder> ByteCodeDecoder<R, B> {
         Ok((dest, source))
     }
 
-    fn format20t(&mut self) -> Result<i16, io::Error>
+    fn format20t(&mut self) -> Result<i16, DecodeError>
     where
         B: ByteOrder,
     {
@@ -726,7 +1446,7 @@ impl<R: Read + Debug, B: ByteOrder> ByteCodeDecoder<R, B> {
 
         Ok(literal)
     }
-    fn format21t(&mut self) -> Result<(u8, i16), io::Error>
+    fn format21t(&mut self) -> Result<(u8, i16), DecodeError>
     where
         B: ByteOrder,
     {
@@ -736,35 +1456,35 @@ impl<R: Read + Debug, B: ByteOrder> ByteCodeDecoder<R, B> {
         Ok((dest, offset))
     }
 
-    fn format21s(&mut self) -> Result<(u8, i32), io::Error> {
+    fn format21s(&mut self) -> Result<(u8, i32), DecodeError> {
         let dest = self.cursor.read_u8()?;
         let literal = self.cursor.read_i16::<B>()?;
 
         Ok((dest, i32::from(literal)))
     }
 
-    fn format21hw(&mut self) -> Result<(u8, i32), io::Error> {
+    fn format21hw(&mut self) -> Result<(u8, i32), DecodeError> {
         let dest = self.cursor.read_u8()?;
         let literal = (i32::from(self.cursor.read_i16::<B>()?)) << 16;
 
         Ok((dest, literal))
     }
 
-    fn format21hd(&mut self) -> Result<(u8, i64), io::Error> {
+    fn format21hd(&mut self) -> Result<(u8, i64), DecodeError> {
         let dest = self.cursor.read_u8()?;
         let literal = (i64::from(self.cursor.read_i16::<B>()?)) << 48;
 
         Ok((dest, literal))
     }
 
-    fn format21c(&mut self) -> Result<(u8, u16), io::Error> {
+    fn format21c(&mut self) -> Result<(u8, u16), DecodeError> {
         let dest = self.cursor.read_u8()?;
         let literal = self.cursor.read_u16::<B>()?;
 
         Ok((dest, literal))
     }
 
-    fn format22c(&mut self) -> Result<(u8, u8, u16), io::Error> {
+    fn format22c(&mut self) -> Result<(u8, u8, u16), DecodeError> {
         let current_byte = self.cursor.read_u8()?;
 
         let source = (current_byte & 0xF0) >> 4;
@@ -774,14 +1494,14 @@ impl<R: Read + Debug, B: ByteOrder> ByteCodeDecoder<R, B> {
         Ok((dest, source, reference))
     }
 
-    fn format22x(&mut self) -> Result<(u8, u16), io::Error> {
+    fn format22x(&mut self) -> Result<(u8, u16), DecodeError> {
         let dest = self.cursor.read_u8()?;
         let source = self.cursor.read_u16::<B>()?;
 
         Ok((dest, source))
     }
 
-    fn format22t(&mut self) -> Result<(u8, u8, i16), io::Error> {
+    fn format22t(&mut self) -> Result<(u8, u8, i16), DecodeError> {
         let current_byte = self.cursor.read_u8()?;
 
         let source = (current_byte & 0xF0) >> 4;
@@ -791,353 +1511,740 @@ impl<R: Read + Debug, B: ByteOrder> ByteCodeDecoder<R, B> {
         Ok((dest, source, offset))
     }
 
-    fn format22s(&mut self) -> Result<(u8, u8, i16), io::Error> {
-        self.format22t()
+    fn format22s(&mut self) -> Result<(u8, u8, i16), DecodeError> {
+        self.format22t()
+    }
+
+    fn format22b(&mut self) -> Result<(u8, u8, i8), DecodeError> {
+        let dest = self.cursor.read_u8()?;
+        let operand1 = self.cursor.read_u8()?;
+        let literal = self.cursor.read_i8()?;
+
+        Ok((dest, operand1, literal))
+    }
+
+    fn format23x(&mut self) -> Result<(u8, u8, u8), DecodeError> {
+        let dest = self.cursor.read_u8()?;
+        let operand1 = self.cursor.read_u8()?;
+        let operand2 = self.cursor.read_u8()?;
+
+        Ok((dest, operand1, operand2))
+    }
+
+    fn format30t(&mut self) -> Result<i32, DecodeError> {
+        let _ = self.cursor.read_u8()?;
+        let literal = self.cursor.read_i32::<B>()?;
+
+        Ok(literal)
+    }
+
+    fn format31i(&mut self) -> Result<(u8, i32), DecodeError> {
+        let dest = self.cursor.read_u8()?;
+        let literal = self.cursor.read_i32::<B>()?;
+
+        Ok((dest, literal))
+    }
+
+    fn format31t(&mut self) -> Result<(u8, i32), DecodeError> {
+        let dest = self.cursor.read_u8()?;
+        let literal = self.cursor.read_i32::<B>()?;
+
+        Ok((dest, literal))
+    }
+
+    fn format31c(&mut self) -> Result<(u8, u32), DecodeError> {
+        let dest = self.cursor.read_u8()?;
+        let reference = self.cursor.read_u32::<B>()?;
+
+        Ok((dest, reference))
+    }
+
+    fn format32x(&mut self) -> Result<(u16, u16), DecodeError> {
+        let _ = self.cursor.read_u8()?;
+        let dest = self.cursor.read_u16::<B>()?;
+        let source = self.cursor.read_u16::<B>()?;
+
+        Ok((dest, source))
+    }
+
+    fn format35c(&mut self) -> Result<(Vec<u8>, u16), DecodeError> {
+        let mut arguments = Vec::new();
+        let first_byte = self.cursor.read_u8()?;
+
+        let count = (first_byte & 0xF0) >> 4;
+        let last_register = first_byte & 0xF;
+
+        let reference = self.cursor.read_u16::<B>()?;
+
+        let reg_array = self.read_4bit_array(4)?;
+        arguments.extend(reg_array);
+        arguments.push(last_register);
+
+        let final_arguments = arguments.into_iter().take(count as usize).collect();
+
+        Ok((final_arguments, reference))
+    }
+
+    fn format3rc(&mut self) -> Result<(u16, u8, u16), DecodeError> {
+        let amount = self.cursor.read_u8()?;
+        let reference = self.cursor.read_u16::<LittleEndian>()?;
+        let first = self.cursor.read_u16::<LittleEndian>()?;
+        if u32::from(first) + u32::from(amount) > 0x1_0000 {
+            return Err(DecodeError::RegisterOutOfRange {
+                first,
+                count: amount,
+            });
+        }
+
+        Ok((first, amount, reference))
+    }
+
+    fn format45cc(&mut self) -> Result<(Vec<u8>, u16, u16), DecodeError> {
+        let (registers, method_ref) = self.format35c()?;
+        let proto_ref = self.cursor.read_u16::<B>()?;
+
+        Ok((registers, method_ref, proto_ref))
+    }
+
+    fn format4rcc(&mut self) -> Result<(u16, u8, u16, u16), DecodeError> {
+        let (first, amount, method_ref) = self.format3rc()?;
+        let proto_ref = self.cursor.read_u16::<B>()?;
+
+        Ok((first, amount, method_ref, proto_ref))
+    }
+
+    fn format51l(&mut self) -> Result<(u8, i64), DecodeError> {
+        let dest = self.cursor.read_u8()?;
+        let source = self.cursor.read_i64::<B>()?;
+
+        Ok((dest, source))
+    }
+
+    fn read_4bit_array(&mut self, amount: u8) -> Result<Vec<u8>, DecodeError> {
+        let mut values = Vec::new();
+
+        for _ in 0..(amount / 2) {
+            let current = self.cursor.read_u8()?;
+
+            let high = (current & 0xF0) >> 4;
+            let low = current & 0xF;
+
+            values.push(low);
+            values.push(high);
+        }
+
+        Ok(values)
+    }
+}
+
+impl<'a, B: ByteOrder> ByteCodeDecoder<CodeUnits<'a, B>, B> {
+    /// Creates a new `ByteCodeDecoder` that decodes the given code units.
+    ///
+    /// The code units are read directly from the slice, without copying them to a byte buffer.
+    /// Decoding never reads past the end of the slice: if the last instruction claims more code
+    /// units than remain, `next_instruction()` returns `DecodeError::UnexpectedEof` and the
+    /// decoder is left at the end of the slice.
+    pub fn from_code_units(units: &'a [u16]) -> Self {
+        Self::new(CodeUnits::new(units))
+    }
+}
+
+impl<R: Read + Debug + Position, B: ByteOrder> ByteCodeDecoder<R, B> {
+    /// Converts the decoder into an iterator that also yields the program counter of each
+    /// instruction.
+    ///
+    /// Program counters are measured in 16-bit code units from the start of the code.
+    pub fn with_pcs(self) -> Instructions<R, B> {
+        Instructions { decoder: self }
+    }
+}
+
+/// Reader that knows its current position.
+pub trait Position {
+    /// Gets the current position of the reader, in bytes.
+    fn position(&self) -> u64;
+}
+
+impl<T: AsRef<[u8]>> Position for Cursor<T> {
+    fn position(&self) -> u64 {
+        Cursor::position(self)
+    }
+}
+
+/// Reader over a slice of 16-bit code units.
+///
+/// It yields the bytes of each code unit in the given byte order, so that a `ByteCodeDecoder`
+/// with the same byte order reads back the original code units.
+#[derive(Debug, Clone, Copy)]
+pub struct CodeUnits<'a, B: ByteOrder = LittleEndian> {
+    units: &'a [u16],
+    position: usize,
+    byte_order: PhantomData<B>,
+}
+
+impl<'a, B: ByteOrder> CodeUnits<'a, B> {
+    /// Creates a new reader over the given code units.
+    pub fn new(units: &'a [u16]) -> Self {
+        Self {
+            units,
+            position: 0,
+            byte_order: PhantomData,
+        }
+    }
+}
+
+impl<B: ByteOrder> Read for CodeUnits<'_, B> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = buf.len().min(self.units.len() * 2 - self.position);
+        for byte in &mut buf[..read] {
+            let mut bytes = [0; 2];
+            B::write_u16(&mut bytes, self.units[self.position / 2]);
+            *byte = bytes[self.position % 2];
+            self.position += 1;
+        }
+
+        Ok(read)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        if self.read(buf)? == buf.len() {
+            Ok(())
+        } else {
+            Err(io::ErrorKind::UnexpectedEof.into())
+        }
+    }
+}
+
+impl<B: ByteOrder> Position for CodeUnits<'_, B> {
+    fn position(&self) -> u64 {
+        self.position as u64
+    }
+}
+
+/// Iterator over decoded instructions, along with their program counters.
+///
+/// It is created with `ByteCodeDecoder::with_pcs()`.
+#[derive(Debug)]
+pub struct Instructions<R: Read + Debug + Position, B: ByteOrder = LittleEndian> {
+    decoder: ByteCodeDecoder<R, B>,
+}
+
+impl<R: Read + Debug + Position, B: ByteOrder> Iterator for Instructions<R, B> {
+    type Item = (u32, ByteCode);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        #[allow(clippy::cast_possible_truncation)]
+        let pc = (self.decoder.get_ref().position() / 2) as u32;
+        self.decoder.next().map(|bytecode| (pc, bytecode))
+    }
+}
+
+/// Filter function over instructions with their program counters.
+pub type InstructionPredicate = fn(&(u32, ByteCode)) -> bool;
+
+/// Adapters to filter instruction iterators by instruction family.
+///
+/// It is implemented for all iterators yielding instructions along with their program counters,
+/// such as `Instructions`.
+pub trait InstructionFilter: Iterator<Item = (u32, ByteCode)> + Sized {
+    /// Yields only method invocations.
+    fn filter_invokes(self) -> Filter<Self, InstructionPredicate> {
+        self.filter(|(_, bytecode)| bytecode.is_invoke())
+    }
+
+    /// Yields only instance and static field reads and writes.
+    fn filter_field_ops(self) -> Filter<Self, InstructionPredicate> {
+        self.filter(|(_, bytecode)| bytecode.is_field_op())
+    }
+
+    /// Yields only constant string loads.
+    fn filter_const_strings(self) -> Filter<Self, InstructionPredicate> {
+        self.filter(|(_, bytecode)| bytecode.is_const_string())
+    }
+}
+
+impl<I: Iterator<Item = (u32, ByteCode)>> InstructionFilter for I {}
+
+/// Finds the `move-result*` instructions that do not immediately follow an instruction
+/// producing a result.
+///
+/// These instructions are only valid right after a method invocation or a `filled-new-array`
+/// instruction, so any other placement is a sign of corrupt bytecode. Returns the program
+/// counters of the misplaced instructions.
+pub fn validate_move_result_placement(instructions: &[(u32, ByteCode)]) -> Vec<u32> {
+    let mut previous: Option<&ByteCode> = None;
+    let mut misplaced = Vec::new();
+    for (pc, bytecode) in instructions {
+        if bytecode.is_move_result()
+            && !matches!(previous, Some(previous) if previous.produces_result())
+        {
+            misplaced.push(*pc);
+        }
+        previous = Some(bytecode);
+    }
+    misplaced
+}
+
+/// Computes a fingerprint of an instruction sequence that does not depend on registers or
+/// indexes, so that it can be compared across dex files.
+///
+/// Each instruction is normalized to its mnemonic, as in `invoke-virtual`, followed by the kind
+/// of item it references, if any (string, type, field, method, prototype or call site). Every
+/// other operand, such as registers, literals, branch offsets and the concrete indexes of the
+/// references, is ignored. The normalized sequence is hashed with 64-bit FNV-1a, so the hash is
+/// stable across platforms and versions of this crate.
+pub fn content_hash<I>(instructions: I) -> u64
+where
+    I: IntoIterator<Item = ByteCode>,
+{
+    const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let mut hash = FNV_OFFSET_BASIS;
+    let mut write = |bytes: &[u8]| {
+        for &byte in bytes {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+    };
+    for bytecode in instructions {
+        let text = bytecode.to_string();
+        let mnemonic = text.split(' ').next().unwrap_or_default();
+        let reference_kind = match bytecode.reference() {
+            None => 0,
+            Some(Reference::String(_)) => 1,
+            Some(Reference::Type(_)) => 2,
+            Some(Reference::Field(_)) => 3,
+            Some(Reference::Method(_)) => 4,
+            Some(Reference::Proto(_)) => 5,
+            Some(Reference::CallSite(_)) => 6,
+        };
+        // The separator keeps mnemonics from running into each other.
+        write(mnemonic.as_bytes());
+        write(&[0, reference_kind]);
+    }
+    hash
+}
+
+/// Identifier of the `fill-array-data-payload` pseudo-instruction.
+const FILL_ARRAY_DATA_PAYLOAD_IDENT: u16 = 0x0300;
+
+/// Element values of an array payload, by element width.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ArrayElements {
+    I8(Box<[i8]>),
+    I16(Box<[i16]>),
+    I32(Box<[i32]>),
+    I64(Box<[i64]>),
+}
+
+/// Data of a `fill-array-data-payload` pseudo-instruction.
+///
+/// The payload of a `fill-array-data` instruction at a given program counter starts at that
+/// program counter plus the offset of the instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArrayPayload {
+    elements: ArrayElements,
+}
+
+impl ArrayPayload {
+    /// Creates the payload from the elements data, with the given element width in bytes.
+    ///
+    /// The data must be in little endian byte order, and its length must be a multiple of the
+    /// element width, which can only be 1, 2, 4 or 8 bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the element width is not valid, or if the length of the data is not a
+    /// multiple of it.
+    pub fn new(element_width: u16, data: &[u8]) -> Result<Self> {
+        if !matches!(element_width, 1 | 2 | 4 | 8) {
+            return Err(DecodeError::BadPayload(format!(
+                "invalid array payload element width: {}",
+                element_width
+            ))
+            .into());
+        }
+        let chunks = data.chunks_exact(usize::from(element_width));
+        if !chunks.remainder().is_empty() {
+            return Err(DecodeError::BadPayload(format!(
+                "array payload data length ({} bytes) is not a multiple of the element width \
+                     ({} bytes)",
+                data.len(),
+                element_width
+            ))
+            .into());
+        }
+
+        #[allow(clippy::cast_possible_wrap)]
+        let elements = match element_width {
+            1 => ArrayElements::I8(data.iter().map(|&byte| byte as i8).collect()),
+            2 => ArrayElements::I16(chunks.map(LittleEndian::read_i16).collect()),
+            4 => ArrayElements::I32(chunks.map(LittleEndian::read_i32).collect()),
+            _ => ArrayElements::I64(chunks.map(LittleEndian::read_i64).collect()),
+        };
+        Ok(Self { elements })
+    }
+
+    /// Reads the payload from the code units of a method, starting at the pseudo-instruction.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the code units do not start with a valid array payload.
+    pub fn from_code_units(units: &[u16]) -> Result<Self> {
+        if units.len() < 4 {
+            return Err(DecodeError::UnexpectedEof.into());
+        }
+        if units[0] != FILL_ARRAY_DATA_PAYLOAD_IDENT {
+            return Err(DecodeError::BadPayload(format!(
+                "invalid array payload identifier: {:#06x}",
+                units[0]
+            ))
+            .into());
+        }
+        let element_width = units[1];
+        let size = u64::from(units[2]) | u64::from(units[3]) << 16;
+        let data_len = size * u64::from(element_width);
+        if data_len > 2 * (units.len() as u64 - 4) {
+            return Err(DecodeError::UnexpectedEof.into());
+        }
+
+        #[allow(clippy::cast_possible_truncation)]
+        let data: Vec<u8> = units[4..]
+            .iter()
+            .flat_map(|unit| unit.to_le_bytes().to_vec())
+            .take(data_len as usize)
+            .collect();
+        Self::new(element_width, &data)
+    }
+
+    /// Gets the width of each element, in bytes.
+    pub fn element_width(&self) -> u16 {
+        match self.elements {
+            ArrayElements::I8(_) => 1,
+            ArrayElements::I16(_) => 2,
+            ArrayElements::I32(_) => 4,
+            ArrayElements::I64(_) => 8,
+        }
+    }
+
+    /// Gets the number of elements in the payload.
+    pub fn len(&self) -> usize {
+        match &self.elements {
+            ArrayElements::I8(elements) => elements.len(),
+            ArrayElements::I16(elements) => elements.len(),
+            ArrayElements::I32(elements) => elements.len(),
+            ArrayElements::I64(elements) => elements.len(),
+        }
+    }
+
+    /// Checks if the payload has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets the elements, if they are 1 byte wide (`byte` or `boolean` arrays).
+    pub fn as_i8_slice(&self) -> Option<&[i8]> {
+        match &self.elements {
+            ArrayElements::I8(elements) => Some(elements),
+            _ => None,
+        }
+    }
+
+    /// Gets the elements, if they are 2 bytes wide (`short` or `char` arrays).
+    pub fn as_i16_slice(&self) -> Option<&[i16]> {
+        match &self.elements {
+            ArrayElements::I16(elements) => Some(elements),
+            _ => None,
+        }
+    }
+
+    /// Gets the elements, if they are 4 bytes wide (`int` or `float` arrays).
+    pub fn as_i32_slice(&self) -> Option<&[i32]> {
+        match &self.elements {
+            ArrayElements::I32(elements) => Some(elements),
+            _ => None,
+        }
+    }
+
+    /// Gets the elements, if they are 8 bytes wide (`long` or `double` arrays).
+    pub fn as_i64_slice(&self) -> Option<&[i64]> {
+        match &self.elements {
+            ArrayElements::I64(elements) => Some(elements),
+            _ => None,
+        }
     }
+}
 
-    fn format22b(&mut self) -> Result<(u8, u8, i8), io::Error> {
-        let dest = self.cursor.read_u8()?;
-        let operand1 = self.cursor.read_u8()?;
-        let literal = self.cursor.read_i8()?;
+impl<R: Read + Debug, B: ByteOrder> Iterator for ByteCodeDecoder<R, B> {
+    type Item = ByteCode;
 
-        Ok((dest, operand1, literal))
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_instruction().ok().flatten()
     }
+}
 
-    fn format23x(&mut self) -> Result<(u8, u8, u8), io::Error> {
-        let dest = self.cursor.read_u8()?;
-        let operand1 = self.cursor.read_u8()?;
-        let operand2 = self.cursor.read_u8()?;
+#[cfg(test)]
+mod tests {
+    use super::{
+        content_hash, validate_move_result_placement, ArrayPayload, ByteCode, ByteCodeDecoder,
+        DecoderConfig, FieldReference, LittleEndian, MethodReference, Position, PrototypeReference,
+        Reference, ReferenceResolver, StringReference, TypeReference,
+    };
+    use crate::error::{DecodeError, Error};
+    use matches::matches;
 
-        Ok((dest, operand1, operand2))
-    }
+    #[test]
+    fn it_can_decode_noop() {
+        let raw_opcode: &[u8] = &[0x00, 0x00];
+        let mut d: ByteCodeDecoder<_, LittleEndian> = ByteCodeDecoder::new(raw_opcode);
 
-    fn format30t(&mut self) -> Result<i32, io::Error> {
-        let _ = self.cursor.read_u8()?;
-        let literal = self.cursor.read_i32::<B>()?;
+        let opcode = d.nth(0).unwrap();
 
-        Ok(literal)
+        assert!(matches!(opcode, ByteCode::Nop));
+        assert_eq!("nop", opcode.to_string());
     }
 
-    fn format31i(&mut self) -> Result<(u8, i32), io::Error> {
-        let dest = self.cursor.read_u8()?;
-        let literal = self.cursor.read_i32::<B>()?;
+    #[test]
+    fn it_can_decode_array_payloads() {
+        // new byte[] { 1, -1, 3 }
+        let payload =
+            ArrayPayload::from_code_units(&[0x0300, 0x0001, 0x0003, 0x0000, 0xFF01, 0x0003])
+                .unwrap();
+        assert_eq!(Some(&[1_i8, -1, 3][..]), payload.as_i8_slice());
+        assert_eq!(None, payload.as_i16_slice());
+        assert_eq!(3, payload.len());
 
-        Ok((dest, literal))
-    }
+        // new short[] { -2, 300 }
+        let payload =
+            ArrayPayload::from_code_units(&[0x0300, 0x0002, 0x0002, 0x0000, 0xFFFE, 0x012C])
+                .unwrap();
+        assert_eq!(Some(&[-2_i16, 300][..]), payload.as_i16_slice());
+        assert_eq!(2, payload.element_width());
 
-    fn format31t(&mut self) -> Result<(u8, i32), io::Error> {
-        let dest = self.cursor.read_u8()?;
-        let literal = self.cursor.read_i32::<B>()?;
+        // new int[] { 70000, -1 }
+        let payload = ArrayPayload::from_code_units(&[
+            0x0300, 0x0004, 0x0002, 0x0000, 0x1170, 0x0001, 0xFFFF, 0xFFFF,
+        ])
+        .unwrap();
+        assert_eq!(Some(&[70000_i32, -1][..]), payload.as_i32_slice());
+        assert_eq!(None, payload.as_i64_slice());
 
-        Ok((dest, literal))
+        // new long[] { 0x1_0000_0000 }
+        let payload = ArrayPayload::from_code_units(&[
+            0x0300, 0x0008, 0x0001, 0x0000, 0x0000, 0x0000, 0x0001, 0x0000,
+        ])
+        .unwrap();
+        assert_eq!(Some(&[0x1_0000_0000_i64][..]), payload.as_i64_slice());
+        assert_eq!(None, payload.as_i8_slice());
     }
 
-    fn format31c(&mut self) -> Result<(u8, u32), io::Error> {
-        let dest = self.cursor.read_u8()?;
-        let reference = self.cursor.read_u32::<B>()?;
+    #[test]
+    fn it_validates_array_payloads() {
+        assert!(matches!(
+            ArrayPayload::new(4, &[0x01, 0x02, 0x03]),
+            Err(Error::Decode(DecodeError::BadPayload(_)))
+        ));
+        assert!(matches!(
+            ArrayPayload::new(3, &[0x01, 0x02, 0x03]),
+            Err(Error::Decode(DecodeError::BadPayload(_)))
+        ));
+        assert!(ArrayPayload::new(2, &[]).unwrap().is_empty());
 
-        Ok((dest, reference))
+        // Wrong identifier, and an int array with a missing element.
+        assert!(matches!(
+            ArrayPayload::from_code_units(&[0x0100, 0x0001, 0x0000, 0x0000]),
+            Err(Error::Decode(DecodeError::BadPayload(_)))
+        ));
+        assert!(matches!(
+            ArrayPayload::from_code_units(&[0x0300, 0x0004, 0x0002, 0x0000, 0x0001, 0x0000]),
+            Err(Error::Decode(DecodeError::UnexpectedEof))
+        ));
     }
 
-    fn format32x(&mut self) -> Result<(u16, u16), io::Error> {
-        let dest = self.cursor.read_u16::<B>()?;
-        let source = self.cursor.read_u16::<B>()?;
+    #[test]
+    fn it_stops_at_the_end_of_the_code_units() {
+        // `nop`, followed by a `const-string v1` missing its string index.
+        let units = [0x0000, 0x011A];
+        let mut d = ByteCodeDecoder::<_, LittleEndian>::from_code_units(&units);
+        assert!(matches!(d.next_instruction(), Ok(Some(ByteCode::Nop))));
+        assert!(matches!(
+            d.next_instruction(),
+            Err(Error::Decode(DecodeError::UnexpectedEof))
+        ));
+        assert_eq!(4, d.get_ref().position());
+        assert!(matches!(d.next_instruction(), Ok(None)));
 
-        Ok((dest, source))
+        let pcs: Vec<_> = ByteCodeDecoder::<_, LittleEndian>::from_code_units(&units)
+            .with_pcs()
+            .map(|(pc, _)| pc)
+            .collect();
+        assert_eq!(vec![0], pcs);
     }
 
-    fn format35c(&mut self) -> Result<(Vec<u8>, u16), io::Error> {
-        let mut arguments = Vec::new();
-        let first_byte = self.cursor.read_u8()?;
-
-        let count = (first_byte & 0xF0) >> 4;
-        let last_register = first_byte & 0xF;
+    #[test]
+    fn it_reports_decode_errors() {
+        // `const-string` without its string index.
+        let mut d: ByteCodeDecoder<_, LittleEndian> = ByteCodeDecoder::new(&[0x1A, 0x01][..]);
+        assert!(matches!(
+            d.next_instruction(),
+            Err(Error::Decode(DecodeError::UnexpectedEof))
+        ));
 
-        let reference = self.cursor.read_u16::<B>()?;
+        // 0x3e is one of the unused opcodes.
+        let mut d: ByteCodeDecoder<_, LittleEndian> = ByteCodeDecoder::new(&[0x3E, 0x00][..]);
+        assert!(matches!(
+            d.next_instruction(),
+            Err(Error::Decode(DecodeError::UnknownOpcode(0x3E)))
+        ));
 
-        let reg_array = self.read_4bit_array(4)?;
-        arguments.extend(reg_array);
-        arguments.push(last_register);
+        // `invoke-virtual/range` with 2 registers starting at v65535.
+        let raw_opcode: &[u8] = &[0x74, 0x02, 0x01, 0x00, 0xFF, 0xFF];
+        let mut d: ByteCodeDecoder<_, LittleEndian> = ByteCodeDecoder::new(raw_opcode);
+        assert!(matches!(
+            d.next_instruction(),
+            Err(Error::Decode(DecodeError::RegisterOutOfRange {
+                first: 0xFFFF,
+                count: 2
+            }))
+        ));
+
+        let mut d: ByteCodeDecoder<_, LittleEndian> = ByteCodeDecoder::new(&[0x0E, 0x00][..]);
+        assert!(matches!(
+            d.next_instruction(),
+            Ok(Some(ByteCode::ReturnVoid))
+        ));
+        assert!(matches!(d.next_instruction(), Ok(None)));
+    }
 
-        let final_arguments = arguments.into_iter().take(count as usize).collect();
+    /// Resolver with a single string and a single type, ignoring the rest.
+    struct InMemoryResolver;
 
-        Ok((final_arguments, reference))
-    }
+    impl ReferenceResolver for InMemoryResolver {
+        fn string(&self, index: StringReference) -> String {
+            match index {
+                0xFFFF => format!("{:?}", "hello"),
+                _ => "<unknown>".to_owned(),
+            }
+        }
 
-    fn format3rc(&mut self) -> Result<(u16, u8, u16), io::Error> {
-        let amount = self.cursor.read_u8()?;
-        let reference = self.cursor.read_u16::<LittleEndian>()?;
-        let first = self.cursor.read_u16::<LittleEndian>()?;
+        fn type_name(&self, index: TypeReference) -> String {
+            match index {
+                3 => "Lcom/example/Foo;".to_owned(),
+                _ => "<unknown>".to_owned(),
+            }
+        }
 
-        Ok((first, amount - 1, reference))
-    }
+        fn field(&self, _: FieldReference) -> String {
+            "<unknown>".to_owned()
+        }
 
-    fn format45cc(&mut self) -> Result<(Vec<u8>, u16, u16), io::Error> {
-        let (registers, method_ref) = self.format35c()?;
-        let proto_ref = self.cursor.read_u16::<B>()?;
+        fn method(&self, _: MethodReference) -> String {
+            "<unknown>".to_owned()
+        }
 
-        Ok((registers, method_ref, proto_ref))
+        fn proto(&self, _: PrototypeReference) -> String {
+            "<unknown>".to_owned()
+        }
     }
 
-    fn format4rcc(&mut self) -> Result<(u16, u8, u16, u16), io::Error> {
-        let (first, amount, method_ref) = self.format3rc()?;
-        let proto_ref = self.cursor.read_u16::<B>()?;
+    #[test]
+    fn it_hashes_instructions_ignoring_registers_and_indexes() {
+        let hash = |raw: &[u8]| {
+            content_hash(ByteCodeDecoder::<_, LittleEndian>::new(raw).collect::<Vec<_>>())
+        };
+        // const/4, const-string, invoke-virtual and return-void.
+        let original = hash(&[
+            0x12, 0x10, 0x1A, 0x01, 0x03, 0x00, 0x6E, 0x20, 0x05, 0x00, 0x10, 0x00, 0x0E, 0x00,
+        ]);
+        let renumbered = hash(&[
+            0x12, 0x32, 0x1A, 0x03, 0x09, 0x00, 0x6E, 0x20, 0x07, 0x00, 0x32, 0x00, 0x0E, 0x00,
+        ]);
+        // Same, but with invoke-static.
+        let static_invoke = hash(&[
+            0x12, 0x10, 0x1A, 0x01, 0x03, 0x00, 0x71, 0x20, 0x05, 0x00, 0x10, 0x00, 0x0E, 0x00,
+        ]);
 
-        Ok((first, amount, method_ref, proto_ref))
+        assert_eq!(original, renumbered);
+        assert_ne!(original, static_invoke);
+        assert_ne!(original, hash(&[]));
     }
 
-    fn format51l(&mut self) -> Result<(u8, i64), io::Error> {
-        let dest = self.cursor.read_u8()?;
-        let source = self.cursor.read_i64::<B>()?;
+    #[test]
+    fn it_renders_instructions_with_a_custom_resolver() {
+        // const-string v1, string@65535; new-instance v0, type@3; return-void
+        let units = [0x011A, 0xFFFF, 0x0022, 0x0003, 0x000e];
+        let rendered: Vec<_> = ByteCodeDecoder::<_, LittleEndian>::from_code_units(&units)
+            .map(|bytecode| bytecode.to_string_resolved(&InMemoryResolver))
+            .collect();
 
-        Ok((dest, source))
+        assert_eq!(
+            vec![
+                "const-string v1, \"hello\" // string@65535",
+                "new-instance v0, Lcom/example/Foo; // type@3",
+                "return-void",
+            ],
+            rendered
+        );
     }
 
-    fn read_4bit_array(&mut self, amount: u8) -> Result<Vec<u8>, io::Error> {
-        let mut values = Vec::new();
+    #[test]
+    fn it_validates_move_result_placement() {
+        // invoke-static {}, method@0x12; move-result v0; return v0
+        let units = [0x0071, 0x0012, 0x0000, 0x000a, 0x000f];
+        let instructions: Vec<_> = ByteCodeDecoder::<_, LittleEndian>::from_code_units(&units)
+            .with_pcs()
+            .collect();
+        assert!(validate_move_result_placement(&instructions).is_empty());
 
-        for _ in 0..(amount / 2) {
-            let current = self.cursor.read_u8()?;
+        // move-result v0; const/4 v0, #0; move-result-object v0; return v0
+        let units = [0x000a, 0x0012, 0x000c, 0x000f];
+        let instructions: Vec<_> = ByteCodeDecoder::<_, LittleEndian>::from_code_units(&units)
+            .with_pcs()
+            .collect();
+        assert_eq!(vec![0, 2], validate_move_result_placement(&instructions));
+    }
 
-            let high = (current & 0xF0) >> 4;
-            let low = current & 0xF;
+    #[test]
+    fn it_can_decode_code_units() {
+        // new-instance v0, type@0x139; invoke-direct {v0}, method@0x567; return-void
+        let units = [0x0022, 0x0139, 0x1070, 0x0567, 0x0000, 0x000e];
 
-            values.push(low);
-            values.push(high);
-        }
+        let instructions: Vec<_> = ByteCodeDecoder::<_, LittleEndian>::from_code_units(&units)
+            .with_pcs()
+            .collect();
 
-        Ok(values)
+        let pcs: Vec<_> = instructions.iter().map(|(pc, _)| *pc).collect();
+        assert_eq!(vec![0, 2, 5], pcs);
+        assert!(matches!(instructions[0].1, ByteCode::NewInstance(0, 0x139)));
+        assert!(matches!(instructions[1].1, ByteCode::Invoke(_, _, 0x567)));
+        assert!(matches!(instructions[2].1, ByteCode::ReturnVoid));
     }
-}
 
-impl<R: Read + Debug, B: ByteOrder> Iterator for ByteCodeDecoder<R, B> {
-    type Item = ByteCode;
+    #[test]
+    fn it_can_decode_with_swapped_opcodes() {
+        let raw_opcode: &[u8] = &[0x00, 0x00, 0x0e, 0x00];
+        let config = DecoderConfig::new()
+            .with_opcode(0x00, 0x0e)
+            .with_opcode(0x0e, 0x00);
+        let d: ByteCodeDecoder<_, LittleEndian> = ByteCodeDecoder::with_config(raw_opcode, config);
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let byte = self.cursor.read_u8();
+        let opcodes: Vec<_> = d.collect();
 
-        match byte {
-            Ok(0x00) => self.format10x().ok().map(|_| ByteCode::Nop),
-            Ok(0x01) => self.format12x().ok().map(|(d, s)| ByteCode::Move(d, s)),
-            Ok(0x02) => self
-                .format22x()
-                .ok()
-                .map(|(d, s)| ByteCode::MoveFrom16(d, s)),
-            Ok(0x03) => self.format32x().ok().map(|(d, s)| ByteCode::Move16(d, s)),
-            Ok(0x04) => self.format12x().ok().map(|(d, s)| ByteCode::MoveWide(d, s)),
-            Ok(0x05) => self
-                .format22x()
-                .ok()
-                .map(|(d, s)| ByteCode::MoveWideFrom16(d, s)),
-            Ok(0x06) => self
-                .format32x()
-                .ok()
-                .map(|(d, s)| ByteCode::MoveWide16(d, s)),
-            Ok(0x07) => self
-                .format12x()
-                .ok()
-                .map(|(d, s)| ByteCode::MoveObject(d, s)),
-            Ok(0x08) => self
-                .format22x()
-                .ok()
-                .map(|(d, s)| ByteCode::MoveObjectFrom16(d, s)),
-            Ok(0x09) => self
-                .format32x()
-                .ok()
-                .map(|(d, s)| ByteCode::MoveObject16(d, s)),
-            Ok(0x0A) => self.format11x().ok().map(ByteCode::MoveResult),
-            Ok(0x0B) => self.format11x().ok().map(ByteCode::MoveResultWide),
-            Ok(0x0C) => self.format11x().ok().map(ByteCode::MoveResultObject),
-            Ok(0x0D) => self.format11x().ok().map(ByteCode::MoveException),
-            Ok(0x0E) => self.format10x().ok().map(|_| ByteCode::ReturnVoid),
-            Ok(0x0F) => self.format11x().ok().map(ByteCode::Return),
-            Ok(0x10) => self.format11x().ok().map(ByteCode::ReturnWide),
-            Ok(0x11) => self.format11x().ok().map(ByteCode::ReturnObject),
-            Ok(0x12) => self
-                .format11n()
-                .ok()
-                .map(|(reg, lit)| ByteCode::Const4(reg, lit)),
-            Ok(0x13) => self
-                .format21s()
-                .ok()
-                .map(|(reg, lit)| ByteCode::Const16(reg, lit)),
-            Ok(0x14) => self
-                .format31i()
-                .ok()
-                .map(|(reg, lit)| ByteCode::Const(reg, lit)),
-            Ok(0x15) => self
-                .format21hw()
-                .ok()
-                .map(|(reg, lit)| ByteCode::ConstHigh16(reg, lit)),
-            Ok(0x16) => self
-                .format21s()
-                .ok()
-                .map(|(reg, lit)| ByteCode::ConstWide16(reg, i64::from(lit))),
-            Ok(0x17) => self
-                .format31i()
-                .ok()
-                .map(|(reg, lit)| ByteCode::ConstWide32(reg, i64::from(lit))),
-            Ok(0x18) => self
-                .format51l()
-                .ok()
-                .map(|(reg, lit)| ByteCode::ConstWide(reg, lit)),
-            Ok(0x19) => self
-                .format21hd()
-                .ok()
-                .map(|(reg, lit)| ByteCode::ConstWideHigh16(reg, lit)),
-            Ok(0x1A) => self.format21c().ok().map(|(reg, reference)| {
-                ByteCode::ConstString(reg, StringReference::from(reference))
-            }),
-            Ok(0x1B) => self
-                .format31c()
-                .ok()
-                .map(|(reg, reference)| ByteCode::ConstStringJumbo(reg, reference)),
-            Ok(0x1C) => self
-                .format21c()
-                .ok()
-                .map(|(reg, reference)| ByteCode::ConstClass(reg, ClassReference::from(reference))),
-            Ok(0x1D) => self.format11x().ok().map(ByteCode::MonitorEnter),
-            Ok(0x1E) => self.format11x().ok().map(ByteCode::MonitorExit),
-            Ok(0x1F) => self
-                .format21c()
-                .ok()
-                .map(|(reg, reference)| ByteCode::CheckCast(reg, TypeReference::from(reference))),
-            Ok(0x20) => self.format22c().ok().map(|(dest, src, reference)| {
-                ByteCode::InstanceOf(dest, src, TypeReference::from(reference))
-            }),
-            Ok(0x21) => self
-                .format12x()
-                .ok()
-                .map(|(dest, src)| ByteCode::ArrayLength(dest, src)),
-            Ok(0x22) => self.format21c().ok().map(|(dest, reference)| {
-                ByteCode::NewInstance(dest, TypeReference::from(reference))
-            }),
-            Ok(0x23) => self.format22c().ok().map(|(dest, size, reference)| {
-                ByteCode::NewArray(dest, size, TypeReference::from(reference))
-            }),
-            Ok(0x24) => self.format35c().ok().map(|(registers, reference)| {
-                ByteCode::FilledNewArray(registers, TypeReference::from(reference))
-            }),
-            Ok(0x25) => self.format3rc().ok().map(|(first, amount, reference)| {
-                ByteCode::FilledNewArrayRange(first, amount, TypeReference::from(reference))
-            }),
-            Ok(0x26) => self
-                .format31t()
-                .ok()
-                .map(|(reg, offset)| ByteCode::FillArrayData(reg, offset)),
-            Ok(0x27) => self.format11x().ok().map(ByteCode::Throw),
-            Ok(0x28) => self.format10t().ok().map(ByteCode::Goto),
-            Ok(0x29) => self.format20t().ok().map(ByteCode::Goto16),
-            Ok(0x2A) => self.format30t().ok().map(ByteCode::Goto32),
-            Ok(0x2B) => self
-                .format31t()
-                .ok()
-                .map(|(reg, offset)| ByteCode::PackedSwitch(reg, offset)),
-            Ok(0x2C) => self
-                .format31t()
-                .ok()
-                .map(|(reg, offset)| ByteCode::SparseSwitch(reg, offset)),
-            Ok(a @ 0x2D..=0x31) => self
-                .format23x()
-                .ok()
-                .map(|(dest, op1, op2)| ByteCode::Compare(CompareType::from(a), dest, op1, op2)),
-            Ok(a @ 0x32..=0x37) => self
-                .format22t()
-                .ok()
-                .map(|(dest, src, offset)| ByteCode::If(TestType::from(a), dest, src, offset)),
-            Ok(a @ 0x38..=0x3D) => self
-                .format21t()
-                .ok()
-                .map(|(dest, offset)| ByteCode::If0(TestType::from(a), dest, offset)),
-            Ok(a @ 0x44..=0x51) => self
-                .format23x()
-                .ok()
-                .map(|(dest, op1, op2)| ByteCode::Array(ArrayOperation::from(a), dest, op1, op2)),
-            Ok(a @ 0x52..=0x5f) => self.format22c().ok().map(|(dest, op1, reference)| {
-                ByteCode::Instance(
-                    ArrayOperation::from(a),
-                    dest,
-                    op1,
-                    FieldReference::from(reference),
-                )
-            }),
-            Ok(a @ 0x60..=0x6d) => self.format21c().ok().map(|(dest, reference)| {
-                ByteCode::Static(
-                    ArrayOperation::from(a),
-                    dest,
-                    FieldReference::from(reference),
-                )
-            }),
-            Ok(a @ 0x6e..=0x72) => self.format35c().ok().map(|(registers, reference)| {
-                ByteCode::Invoke(
-                    InvokeKind::from(a),
-                    registers,
-                    MethodReference::from(reference),
-                )
-            }),
-            Ok(a @ 0x74..=0x78) => self.format3rc().ok().map(|(first, amount, reference)| {
-                ByteCode::InvokeRange(
-                    InvokeKind::from(a),
-                    first,
-                    amount,
-                    FieldReference::from(reference),
-                )
-            }),
-            Ok(op @ 0x7b..=0x8f) => self
-                .format12x()
-                .ok()
-                .map(|(dest, src)| ByteCode::Unary(UnaryOperation::from(op), dest, src)),
-            Ok(op @ 0x90..=0xaf) => self.format23x().ok().map(|(dest, src1, src2)| {
-                ByteCode::Binary(BinaryOperation::from(op), dest, src1, src2)
-            }),
-            Ok(op @ 0xb0..=0xcf) => self.format12x().ok().map(|(src_dest, src)| {
-                ByteCode::Binary2Addr(BinaryOperation::from(op), src_dest, src)
-            }),
-            Ok(op @ 0xd0..=0xd7) => self.format22s().ok().map(|(dest, src, literal)| {
-                ByteCode::BinaryLit16(BinaryOperation::from(op), dest, src, literal)
-            }),
-            Ok(op @ 0xd8..=0xe2) => self.format22b().ok().map(|(dest, src, literal)| {
-                ByteCode::BinaryLit8(BinaryOperation::from(op), dest, src, literal)
-            }),
-            Ok(0xfa) => self.format45cc().ok().map(|(registers, method, proto)| {
-                ByteCode::InvokePolymorphic(registers, u32::from(method), u32::from(proto))
-            }),
-            Ok(0xfb) => self
-                .format4rcc()
-                .ok()
-                .map(|(first, amount, method, proto)| {
-                    ByteCode::InvokePolymorphicRange(
-                        first,
-                        amount,
-                        u32::from(method),
-                        u32::from(proto),
-                    )
-                }),
-            Ok(0xfc) => self.format35c().ok().map(|(registers, call_site)| {
-                ByteCode::InvokeCustom(registers, u32::from(call_site))
-            }),
-            Ok(0xfd) => self.format3rc().ok().map(|(first, amount, call_site)| {
-                ByteCode::InvokeCustomRange(first, amount, u32::from(call_site))
-            }),
-            _ => None,
-        }
+        assert_eq!(2, opcodes.len());
+        assert!(matches!(opcodes[0], ByteCode::ReturnVoid));
+        assert!(matches!(opcodes[1], ByteCode::Nop));
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::{ByteCode, ByteCodeDecoder, LittleEndian};
-    use matches::matches;
 
     #[test]
-    fn it_can_decode_noop() {
-        let raw_opcode: &[u8] = &[0x00, 0x00];
+    fn it_can_get_the_method_reference_of_an_invoke() {
+        let raw_opcode: &[u8] = &[0x6e, 0x10, 0x34, 0x12, 0x05, 0x00];
         let mut d: ByteCodeDecoder<_, LittleEndian> = ByteCodeDecoder::new(raw_opcode);
 
         let opcode = d.nth(0).unwrap();
 
-        assert!(matches!(opcode, ByteCode::Nop));
-        assert_eq!("nop", opcode.to_string());
+        assert_eq!(Some(Reference::Method(0x1234)), opcode.reference());
+        assert_eq!(None, ByteCode::Nop.reference());
     }
 
     #[test]
@@ -1175,7 +2282,7 @@ mod tests {
 
     #[test]
     fn it_can_decode_move_16() {
-        let raw_opcode: &[u8] = &[0x03, 0xAA, 0x01, 0x12, 0x34];
+        let raw_opcode: &[u8] = &[0x03, 0x00, 0xAA, 0x01, 0x12, 0x34];
         let mut d: ByteCodeDecoder<_, LittleEndian> = ByteCodeDecoder::new(raw_opcode);
 
         let opcode = d.nth(0).unwrap();
@@ -1208,7 +2315,7 @@ mod tests {
 
     #[test]
     fn it_can_decode_move_wide_16() {
-        let raw_opcode: &[u8] = &[0x06, 0xAA, 0x01, 0x12, 0x34];
+        let raw_opcode: &[u8] = &[0x06, 0x00, 0xAA, 0x01, 0x12, 0x34];
         let mut d: ByteCodeDecoder<_, LittleEndian> = ByteCodeDecoder::new(raw_opcode);
 
         let opcode = d.nth(0).unwrap();
@@ -1241,7 +2348,7 @@ mod tests {
 
     #[test]
     fn it_can_decode_move_object_16() {
-        let raw_opcode: &[u8] = &[0x09, 0xAA, 0x01, 0x12, 0x34];
+        let raw_opcode: &[u8] = &[0x09, 0x00, 0xAA, 0x01, 0x12, 0x34];
         let mut d: ByteCodeDecoder<_, LittleEndian> = ByteCodeDecoder::new(raw_opcode);
 
         let opcode = d.nth(0).unwrap();
@@ -1380,6 +2487,14 @@ mod tests {
 
         assert_eq!("const/high16 v68, #-65536", opcode.to_string());
         assert!(matches!(opcode, ByteCode::ConstHigh16(r, i) if r == 0x44 && i == -65536));
+        assert_eq!(Some(-65536), opcode.value());
+
+        let raw_opcode: &[u8] = &[0x15, 0x00, 0x80, 0x3F];
+        let mut d: ByteCodeDecoder<_, LittleEndian> = ByteCodeDecoder::new(raw_opcode);
+
+        // `1.0f32`, as loaded by the compiler.
+        let opcode = d.nth(0).unwrap();
+        assert_eq!(Some(0x3F80_0000), opcode.value());
     }
 
     #[test]
@@ -1431,6 +2546,32 @@ mod tests {
         assert!(matches!(
             opcode,
             ByteCode::ConstWideHigh16(r, i) if r == 1 && i == -281_474_976_710_656));
+        assert_eq!(Some(-281_474_976_710_656), opcode.value());
+    }
+
+    #[test]
+    fn it_gets_the_value_of_wide_constants() {
+        let raw_opcodes: &[u8] = &[
+            0x16, 0x00, 0xFE, 0xFF, // const-wide/16 v0, #-2
+            0x17, 0x00, 0x00, 0x00, 0x00, 0x80, // const-wide/32 v0, #-2147483648
+            0x18, 0x00, 0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01, // const-wide
+            0x19, 0x00, 0x00, 0x40, // const-wide/high16 v0, #0x4000000000000000
+            0x14, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, // const v0, #-1
+        ];
+        let values: Vec<_> = ByteCodeDecoder::<_, LittleEndian>::new(raw_opcodes)
+            .map(|opcode| opcode.wide_value())
+            .collect();
+
+        assert_eq!(
+            vec![
+                Some(-2),
+                Some(-2_147_483_648),
+                Some(0x0102_0304_0506_0708),
+                Some(0x4000_0000_0000_0000),
+                None,
+            ],
+            values
+        );
     }
 
     #[test]
@@ -1647,7 +2788,7 @@ mod tests {
                 start,
                 amount,
                 reference
-            ) if start == 1 && amount == 2 && reference == 8738));
+            ) if start == 1 && amount == 3 && reference == 8738));
     }
 
     #[test]
@@ -1841,7 +2982,7 @@ mod tests {
         let opcode = d.nth(0).unwrap();
 
         assert_eq!(
-            "invoke-interface/range {v512, v513, v514, v515, v516, v517, v518, v519}, method@256",
+            "invoke-interface/range {v512, v513, v514, v515, v516, v517, v518, v519, v520}, method@256",
             opcode.to_string()
         );
         assert!(matches!(
@@ -1851,7 +2992,7 @@ mod tests {
                 first_reg,
                 amount,
                 reference
-            ) if first_reg == 512 && amount == 8 && reference == 256));
+            ) if first_reg == 512 && amount == 9 && reference == 256));
     }
 
     #[test]
@@ -1955,13 +3096,13 @@ mod tests {
         let opcode = d.nth(0).unwrap();
 
         assert_eq!(
-            "invoke-polymorphic/range {v1, v2, v3}, method@16 proto@1",
+            "invoke-polymorphic/range {v1, v2, v3, v4}, method@16 proto@1",
             opcode.to_string()
         );
         assert!(matches!(
             opcode,
             ByteCode::InvokePolymorphicRange(start, amount, method, proto
-        ) if method == 16 && proto == 1 && start == 1 && amount == 3));
+        ) if method == 16 && proto == 1 && start == 1 && amount == 4));
     }
 
     #[test]
@@ -1989,12 +3130,147 @@ mod tests {
         let opcode = d.nth(0).unwrap();
 
         assert_eq!(
-            "invoke-custom/range {v1, v2, v3}, call_site@16",
+            "invoke-custom/range {v1, v2, v3, v4}, call_site@16",
             opcode.to_string()
         );
         assert!(matches!(
             opcode,
             ByteCode::InvokeCustomRange(first, amount, call_site
-        ) if first == 1 && amount == 3 && call_site == 16));
+        ) if first == 1 && amount == 4 && call_site == 16));
+    }
+
+    #[test]
+    fn it_lists_every_register_of_range_instructions() {
+        let cases: &[(&[u8], &str)] = &[
+            (
+                &[0x74, 0x01, 0x05, 0x00, 0x07, 0x00],
+                "invoke-virtual/range {v7}, method@5",
+            ),
+            (
+                &[0x77, 0x00, 0x05, 0x00, 0x07, 0x00],
+                "invoke-static/range {}, method@5",
+            ),
+            (
+                &[0x25, 0x02, 0x05, 0x00, 0xFE, 0xFF],
+                "filled-new-array/range {v65534, v65535}, type@5",
+            ),
+            (
+                &[0xfb, 0x02, 0x05, 0x00, 0x07, 0x00, 0x01, 0x00],
+                "invoke-polymorphic/range {v7, v8}, method@5 proto@1",
+            ),
+            (
+                &[0xfd, 0x01, 0x05, 0x00, 0x07, 0x00],
+                "invoke-custom/range {v7}, call_site@5",
+            ),
+        ];
+        for (raw_opcode, expected) in cases {
+            let mut d: ByteCodeDecoder<_, LittleEndian> = ByteCodeDecoder::new(*raw_opcode);
+            let opcode = d.nth(0).unwrap();
+            assert_eq!(*expected, opcode.to_string());
+
+            let units: Vec<u16> = raw_opcode
+                .chunks(2)
+                .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+                .collect();
+            assert_eq!(Some(units), opcode.encode());
+        }
+    }
+
+    #[test]
+    fn it_encodes_every_instruction_format() {
+        let instructions: &[&[u16]] = &[
+            &[0x0000],                                 // 10x: nop
+            &[0x2101],                                 // 12x: move v1, v2
+            &[0xF312],                                 // 11n: const/4 v3, #-1
+            &[0x050F],                                 // 11x: return v5
+            &[0xFE28],                                 // 10t: goto -2
+            &[0x0029, 0x8000],                         // 20t: goto/16 -32768
+            &[0x0A02, 0x1234],                         // 22x: move/from16 v10, v4660
+            &[0x0338, 0xFFF0],                         // 21t: if-eqz v3, -16
+            &[0x0213, 0x8001],                         // 21s: const/16 v2, #-32767
+            &[0x0115, 0x1234],                         // 21h: const/high16 v1, #0x12340000
+            &[0x0119, 0x4321],                         // 21h: const-wide/high16 v1
+            &[0x011A, 0xBEEF],                         // 21c: const-string v1, string@48879
+            &[0x0190, 0x0302],                         // 23x: add-int v1, v2, v3
+            &[0x01D8, 0x8002],                         // 22b: add-int/lit8 v1, v2, #-128
+            &[0x2132, 0x0010],                         // 22t: if-eq v1, v2, +16
+            &[0x21D0, 0x7FFF],                         // 22s: add-int/lit16 v1, v2, #32767
+            &[0x2152, 0x0007],                         // 22c: iget v1, v2, field@7
+            &[0x0003, 0x0100, 0x0200],                 // 32x: move/16 v256, v512
+            &[0x002A, 0xFFFF, 0x7FFF],                 // 30t: goto/32
+            &[0x042B, 0x0010, 0x0001],                 // 31t: packed-switch v4
+            &[0x0314, 0x5678, 0x1234],                 // 31i: const v3, #0x12345678
+            &[0x031B, 0x0001, 0x0002],                 // 31c: const-string/jumbo v3
+            &[0x5F6E, 0x0042, 0x4321],                 // 35c: invoke-virtual {v1-v4, v15}
+            &[0x0024, 0x0003, 0x0000],                 // 35c: filled-new-array {}
+            &[0x0374, 0x0042, 0x0010],                 // 3rc: invoke-virtual/range {v16..v18}
+            &[0x20FA, 0x0001, 0x0021, 0x0003],         // 45cc: invoke-polymorphic
+            &[0x04FB, 0x0001, 0x0100, 0x0003],         // 4rcc: invoke-polymorphic/range
+            &[0x0118, 0x4444, 0x3333, 0x2222, 0x1111], // 51l: const-wide v1
+        ];
+
+        for &units in instructions {
+            let mut d = ByteCodeDecoder::<_, LittleEndian>::from_code_units(units);
+            let opcode = d.next_instruction().unwrap().unwrap();
+            assert_eq!(units.len() as u64 * 2, d.get_ref().position());
+            assert_eq!(
+                Some(units.to_vec()),
+                opcode.encode(),
+                "{}",
+                opcode.to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn it_does_not_encode_operands_out_of_range() {
+        assert_eq!(None, ByteCode::Move(16, 0).encode());
+        assert_eq!(None, ByteCode::Const4(0, 8).encode());
+        assert_eq!(None, ByteCode::ConstHigh16(0, 1).encode());
+        assert_eq!(None, ByteCode::ConstString(0, 0x1_0000).encode());
+        assert_eq!(
+            None,
+            ByteCode::Invoke(super::InvokeKind::Static, vec![0; 6], 0).encode()
+        );
+        assert_eq!(
+            None,
+            ByteCode::Compare(super::CompareType::Unknown, 0, 1, 2).encode()
+        );
+    }
+
+    #[test]
+    fn it_round_trips_decoded_instructions() {
+        // Deterministic xorshift sequence, so that failures can be reproduced.
+        let mut state = 0x2545_F491_4F6C_DD1D_u64;
+        let mut next_unit = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u16
+        };
+
+        for _ in 0..20_000 {
+            let units = [
+                next_unit(),
+                next_unit(),
+                next_unit(),
+                next_unit(),
+                next_unit(),
+            ];
+            let mut d = ByteCodeDecoder::<_, LittleEndian>::from_code_units(&units);
+            let opcode = match d.next_instruction() {
+                Ok(Some(opcode)) => opcode,
+                _ => continue,
+            };
+            let encoded = opcode
+                .encode()
+                .expect("decoded instructions can be encoded");
+            assert_eq!(encoded.len() as u64 * 2, d.get_ref().position());
+
+            let mut d = ByteCodeDecoder::<_, LittleEndian>::from_code_units(&encoded);
+            let decoded = d.next_instruction().unwrap().unwrap();
+            assert_eq!(opcode.to_string(), decoded.to_string());
+            assert_eq!(Some(encoded), decoded.encode());
+        }
     }
 }
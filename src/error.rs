@@ -1,7 +1,179 @@
 //! Errors module
 
 use crate::sizes::HEADER_SIZE;
-use std::{error::Error, fmt};
+use std::{error::Error as StdError, fmt, io, result};
+
+/// Result of the fallible operations of the crate.
+pub type Result<T, E = Error> = result::Result<T, E>;
+
+/// Error of any of the operations of the crate.
+///
+/// All the fallible functions of the crate return this error, with one variant for each family
+/// of errors, so that the cause can be matched on. Errors reading an item of a dex file are
+/// wrapped in a `Parse::SectionRead` error, with the section and the offset of the item, and
+/// `Error::root_cause()` gives the error without its location.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// I/O error.
+    Io(io::Error),
+    /// Invalid file size.
+    InvalidFileSize(InvalidFileSize),
+    /// Error parsing the header.
+    Header(Header),
+    /// Error parsing the dex file.
+    Parse(Parse),
+    /// Error decoding bytecode.
+    Decode(DecodeError),
+    /// Error writing a dex file.
+    Write(Write),
+    /// Error parsing a mapping file.
+    Mapping(Mapping),
+    /// Error verifying the arguments of an invoke.
+    Arity(ArityError),
+    /// Error serializing the parsed structure cache.
+    #[cfg(feature = "cache")]
+    CacheEncode(rmp_serde::encode::Error),
+    /// Error deserializing the parsed structure cache.
+    #[cfg(feature = "cache")]
+    CacheDecode(rmp_serde::decode::Error),
+    /// The blocking task parsing a dex file failed.
+    #[cfg(feature = "tokio")]
+    Task(tokio::task::JoinError),
+}
+
+impl Error {
+    /// Gets the root cause of the error, without the location of the item that could not be
+    /// read.
+    ///
+    /// For a `Parse::SectionRead` error, this is the error it wraps, and for the rest of errors,
+    /// the error itself.
+    pub fn root_cause(&self) -> &Self {
+        match self {
+            Self::Parse(Parse::SectionRead { source, .. }) => source.root_cause(),
+            _ => self,
+        }
+    }
+
+    /// Gets the message of the error followed by the messages of the errors that caused it.
+    pub(crate) fn chain_message(&self) -> String {
+        let mut message = self.to_string();
+        let mut source = self.source();
+        while let Some(error) = source {
+            message.push_str(": ");
+            message.push_str(&error.to_string());
+            source = error.source();
+        }
+        message
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => error.fmt(f),
+            Self::InvalidFileSize(error) => error.fmt(f),
+            Self::Header(error) => error.fmt(f),
+            Self::Parse(error) => error.fmt(f),
+            Self::Decode(error) => error.fmt(f),
+            Self::Write(error) => error.fmt(f),
+            Self::Mapping(error) => error.fmt(f),
+            Self::Arity(error) => error.fmt(f),
+            #[cfg(feature = "cache")]
+            Self::CacheEncode(error) => write!(f, "could not write the cache: {}", error),
+            #[cfg(feature = "cache")]
+            Self::CacheDecode(error) => write!(f, "could not read the cache: {}", error),
+            #[cfg(feature = "tokio")]
+            Self::Task(error) => write!(f, "the dex file parsing task failed: {}", error),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::Io(error) => error.source(),
+            Self::Parse(error) => error.source(),
+            Self::Decode(error) => error.source(),
+            #[cfg(feature = "cache")]
+            Self::CacheEncode(error) => Some(error),
+            #[cfg(feature = "cache")]
+            Self::CacheDecode(error) => Some(error),
+            #[cfg(feature = "tokio")]
+            Self::Task(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<InvalidFileSize> for Error {
+    fn from(error: InvalidFileSize) -> Self {
+        Self::InvalidFileSize(error)
+    }
+}
+
+impl From<Header> for Error {
+    fn from(error: Header) -> Self {
+        Self::Header(error)
+    }
+}
+
+impl From<Parse> for Error {
+    fn from(error: Parse) -> Self {
+        Self::Parse(error)
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(error: DecodeError) -> Self {
+        Self::Decode(error)
+    }
+}
+
+impl From<Write> for Error {
+    fn from(error: Write) -> Self {
+        Self::Write(error)
+    }
+}
+
+impl From<Mapping> for Error {
+    fn from(error: Mapping) -> Self {
+        Self::Mapping(error)
+    }
+}
+
+impl From<ArityError> for Error {
+    fn from(error: ArityError) -> Self {
+        Self::Arity(error)
+    }
+}
+
+#[cfg(feature = "cache")]
+impl From<rmp_serde::encode::Error> for Error {
+    fn from(error: rmp_serde::encode::Error) -> Self {
+        Self::CacheEncode(error)
+    }
+}
+
+#[cfg(feature = "cache")]
+impl From<rmp_serde::decode::Error> for Error {
+    fn from(error: rmp_serde::decode::Error) -> Self {
+        Self::CacheDecode(error)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl From<tokio::task::JoinError> for Error {
+    fn from(error: tokio::task::JoinError) -> Self {
+        Self::Task(error)
+    }
+}
 
 /// Invalid file size.
 #[derive(Debug, Copy, Clone)]
@@ -23,7 +195,7 @@ impl fmt::Display for InvalidFileSize {
     }
 }
 
-impl Error for InvalidFileSize {}
+impl StdError for InvalidFileSize {}
 
 /// Errors coming from header parsing.
 #[derive(Debug, Clone)]
@@ -94,7 +266,7 @@ impl fmt::Display for Header {
     }
 }
 
-impl Error for Header {}
+impl StdError for Header {}
 
 /// Parsing errors.
 #[derive(Debug)]
@@ -121,9 +293,27 @@ pub enum Parse {
     /// Unknown type index.
     UnknownTypeIndex(u32),
 
+    /// Unknown prototype index.
+    UnknownPrototypeIndex(u32),
+
+    /// Unknown field index.
+    UnknownFieldIndex(u32),
+
+    /// Unknown method index.
+    UnknownMethodIndex(u32),
+
+    /// Type of a class, superclass or interface that is not a class, with its descriptor.
+    NotAClass(String),
+
     /// Invalid type descriptor.
     InvalidTypeDescriptor(String),
 
+    /// `void` type in an item where it is not allowed, with the item name from the dex format
+    /// specification.
+    ///
+    /// `void` is only valid as a return type.
+    UnexpectedVoid(&'static str),
+
     /// Invalid shorty type.
     InvalidShortyType(char),
 
@@ -139,28 +329,91 @@ pub enum Parse {
     /// Invalid visibility modifier.
     InvalidVisibility(u8),
 
+    /// Invalid method handle type.
+    InvalidMethodHandleType(u16),
+
     /// Invalid value found.
     InvalidValue {
         /// Error string.
         error: String,
     },
 
+    /// Invalid call site found.
+    InvalidCallSite {
+        /// Error string.
+        error: String,
+    },
+
+    /// String data that is not valid MUTF-8.
+    InvalidMutf8,
+
     /// String size mismatch.
     StringSizeMismatch {
-        /// Expected string size.
+        /// Expected string size, in UTF-16 code units.
         expected_size: u32,
-        /// Actual string size.
+        /// Actual string size, in UTF-16 code units.
         actual_size: usize,
     },
 
+    /// The number of strings declared in the header does not match the one in the map.
+    StringCountMismatch {
+        /// Number of strings declared in the header.
+        declared: usize,
+        /// Number of strings in the string ID section of the map.
+        actual: usize,
+    },
+
     /// Invalid LEB128 number.
     InvalidLeb128,
 
+    /// LEB128 number out of the `u32` range, with the given fifth byte.
+    Leb128OutOfRange(u8),
+
+    /// Invalid or incompatible parsed structure cache.
+    InvalidCache {
+        /// Error string.
+        error: String,
+    },
+
     /// Generic error in dex map.
     Map {
         /// Error String.
         error: String,
     },
+
+    /// String data offset outside of the data section.
+    StringOffsetOutOfRange {
+        /// Index of the string in the string IDs list.
+        index: usize,
+        /// Offset of the string data.
+        offset: u32,
+    },
+
+    /// The file is a compact dex file, which is not supported.
+    CompactDexUnsupported,
+
+    /// A size read from the file exceeds what the file could hold, or a configured limit.
+    ImplausibleSize {
+        /// Name of the size, as named in the dex format specification.
+        size_name: &'static str,
+        /// Size read from the file.
+        size: u64,
+        /// Maximum accepted size.
+        limit: u64,
+    },
+
+    /// Error reading an item of a section of the dex file.
+    ///
+    /// The error the item could not be read because of is its source, and it can be matched on
+    /// with `Error::root_cause()`.
+    SectionRead {
+        /// Name of the section, as named in the dex format specification.
+        section: &'static str,
+        /// Offset of the item that could not be read.
+        offset: u32,
+        /// Error the item could not be read because of.
+        source: Box<Error>,
+    },
 }
 
 impl fmt::Display for Parse {
@@ -178,9 +431,20 @@ impl fmt::Display for Parse {
             ),
             Self::UnknownStringIndex(index) => write!(f, "there is no string with index {}", index),
             Self::UnknownTypeIndex(index) => write!(f, "there is no type with index {}", index),
+            Self::UnknownPrototypeIndex(index) => {
+                write!(f, "there is no prototype with index {}", index)
+            }
+            Self::UnknownFieldIndex(index) => write!(f, "there is no field with index {}", index),
+            Self::UnknownMethodIndex(index) => {
+                write!(f, "there is no method with index {}", index)
+            }
+            Self::NotAClass(descriptor) => write!(f, "`{}` is not a class type", descriptor),
             Self::InvalidTypeDescriptor(descriptor) => {
                 write!(f, "invalid type descriptor: `{}`", descriptor)
             }
+            Self::UnexpectedVoid(item) => {
+                write!(f, "`void` is not a valid type in a `{}`", item)
+            }
             Self::InvalidShortyType(shorty_type) => {
                 write!(f, "invalid shorty type: `{}`", shorty_type)
             }
@@ -194,22 +458,245 @@ impl fmt::Display for Parse {
             Self::InvalidVisibility(visibility) => {
                 write!(f, "invalid visibility modifier: {:#04x}", visibility)
             }
+            Self::InvalidMethodHandleType(handle_type) => {
+                write!(f, "invalid method handle type: {:#06x}", handle_type)
+            }
             Self::InvalidValue { error } => write!(f, "invalid value: {}", error),
+            Self::InvalidCallSite { error } => write!(f, "invalid call site: {}", error),
+            Self::InvalidMutf8 => write!(f, "invalid MUTF-8 sequence in string data"),
             Self::StringSizeMismatch {
                 expected_size,
                 actual_size,
             } => write!(
                 f,
-                "string size mismatch: expected {} characters, found {}",
+                "string size mismatch: expected {} UTF-16 code units, found {}",
                 expected_size, actual_size
             ),
             Self::InvalidLeb128 => write!(
                 f,
                 "invalid leb128: a leb128 with more than 5 bytes was found"
             ),
+            Self::Leb128OutOfRange(byte) => write!(
+                f,
+                "leb128 out of range: the fifth byte ({:#04x}) has bits set above the 32nd bit",
+                byte
+            ),
+            Self::InvalidCache { error } => write!(f, "invalid cache: {}", error),
             Self::Map { error } => write!(f, "error in dex map: {}", error),
+            Self::StringCountMismatch { declared, actual } => write!(
+                f,
+                "the header declares {} strings, but the map declares {}",
+                declared, actual
+            ),
+            Self::StringOffsetOutOfRange { index, offset } => write!(
+                f,
+                "the data of the string with index {} is at offset {:#010x}, outside of the data \
+                 section",
+                index, offset
+            ),
+            Self::CompactDexUnsupported => write!(
+                f,
+                "the file is a compact dex (cdex) file, which is not supported"
+            ),
+            Self::ImplausibleSize {
+                size_name,
+                size,
+                limit,
+            } => write!(
+                f,
+                "implausible `{}`: {} is larger than the maximum of {}",
+                size_name, size, limit
+            ),
+            Self::SectionRead {
+                section, offset, ..
+            } => write!(f, "could not read `{}` at offset {:#010x}", section, offset),
+        }
+    }
+}
+
+impl StdError for Parse {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::SectionRead { source, .. } => Some(&**source),
+            _ => None,
+        }
+    }
+}
+
+/// Errors writing a dex file.
+#[derive(Debug, Clone)]
+pub enum Write {
+    /// There is no class with the given descriptor.
+    UnknownClass(String),
+
+    /// A member of a class does not match any field or method ID.
+    UnknownMember {
+        /// Descriptor of the class.
+        class: String,
+        /// Name of the member.
+        name: String,
+    },
+
+    /// A string or type used by the written items is not in the string IDs list.
+    MissingString(String),
+
+    /// A try item references a catch handler at an offset that would change when written.
+    NonCanonicalHandlers {
+        /// Offset of the catch handler in the original code item.
+        handler_offset: u16,
+    },
+}
+
+impl fmt::Display for Write {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownClass(descriptor) => {
+                write!(f, "there is no class with descriptor `{}`", descriptor)
+            }
+            Self::UnknownMember { class, name } => write!(
+                f,
+                "the member `{}` of class `{}` does not match any field or method ID",
+                name, class
+            ),
+            Self::MissingString(string) => {
+                write!(f, "the string `{}` is not in the string IDs list", string)
+            }
+            Self::NonCanonicalHandlers { handler_offset } => write!(
+                f,
+                "the catch handler at offset {:#06x} is not encoded with the shortest LEB128 \
+                 encodings",
+                handler_offset
+            ),
+        }
+    }
+}
+
+impl StdError for Write {}
+
+/// Errors coming from bytecode decoding.
+#[derive(Debug)]
+pub enum DecodeError {
+    /// The input ended in the middle of an instruction.
+    UnexpectedEof,
+
+    /// The opcode is not used by any instruction.
+    UnknownOpcode(u8),
+
+    /// Malformed payload pseudo-instruction.
+    BadPayload(String),
+
+    /// The register range of the instruction goes past the last register, `v65535`.
+    RegisterOutOfRange {
+        /// First register of the range.
+        first: u16,
+        /// Number of registers in the range.
+        count: u8,
+    },
+
+    /// I/O error reading the input.
+    Io(io::Error),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedEof => {
+                write!(f, "unexpected end of input in the middle of an instruction")
+            }
+            Self::UnknownOpcode(opcode) => write!(f, "unknown opcode: {:#04x}", opcode),
+            Self::BadPayload(error) => write!(f, "invalid payload: {}", error),
+            Self::RegisterOutOfRange { first, count } => write!(
+                f,
+                "register range of {} registers starting at v{} goes past the last register",
+                count, first
+            ),
+            Self::Io(error) => write!(f, "could not read the bytecode: {}", error),
+        }
+    }
+}
+
+impl StdError for DecodeError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DecodeError {
+    fn from(error: io::Error) -> Self {
+        if error.kind() == io::ErrorKind::UnexpectedEof {
+            Self::UnexpectedEof
+        } else {
+            Self::Io(error)
+        }
+    }
+}
+
+/// Errors coming from mapping file parsing.
+#[derive(Debug, Clone)]
+pub enum Mapping {
+    /// A line of the mapping file could not be parsed.
+    InvalidLine {
+        /// Number of the line, starting at 1.
+        line: usize,
+        /// Contents of the line.
+        content: String,
+    },
+}
+
+impl fmt::Display for Mapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidLine { line, content } => {
+                write!(f, "invalid mapping at line {}: `{}`", line, content)
+            }
+        }
+    }
+}
+
+impl StdError for Mapping {}
+
+/// Errors coming from the verification of the number of arguments of an invoke.
+#[derive(Debug, Clone)]
+pub enum ArityError {
+    /// The instruction is not an `invoke-kind` or `invoke-kind/range` instruction.
+    NotAnInvoke,
+
+    /// The method reference does not point to a method or prototype in the dex file.
+    UnknownMethod {
+        /// Index of the method.
+        method: u32,
+    },
+
+    /// The number of argument registers does not match the prototype of the method.
+    Mismatch {
+        /// Signature of the method, as in `Lclass;.name:(II)V`.
+        method: String,
+        /// Number of registers the prototype needs, including `this` for instance methods.
+        expected: u32,
+        /// Number of registers passed by the instruction.
+        found: u32,
+    },
+}
+
+impl fmt::Display for ArityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAnInvoke => write!(f, "the instruction is not a method invocation"),
+            Self::UnknownMethod { method } => write!(f, "unknown method: method@{}", method),
+            Self::Mismatch {
+                method,
+                expected,
+                found,
+            } => write!(
+                f,
+                "invocation of {} passes {} argument registers, but the method needs {}",
+                method, found, expected
+            ),
         }
     }
 }
 
-impl Error for Parse {}
+impl StdError for ArityError {}
@@ -1,14 +1,15 @@
 //! Module containing the Dex file header.
 
 use crate::{
-    error,
+    error::{self, Result},
     sizes::{
         CLASS_DEF_ITEM_SIZE, FIELD_ID_ITEM_SIZE, HEADER_SIZE, METHOD_ID_ITEM_SIZE,
         PROTO_ID_ITEM_SIZE, STRING_ID_ITEM_SIZE, TYPE_ID_ITEM_SIZE,
     },
 };
-use anyhow::{Context, Result};
 use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
+#[cfg(feature = "cache")]
+use serde::{Deserialize, Serialize};
 use std::{
     fmt, fs,
     io::{BufReader, Read},
@@ -21,8 +22,12 @@ pub const ENDIAN_CONSTANT: u32 = 0x12_34_56_78;
 /// Endianness constant representing big endian file.
 pub const REVERSE_ENDIAN_CONSTANT: u32 = 0x78_56_34_12;
 
+/// First bytes of the magic number of compact dex files (`cdex`), used by ART.
+const COMPACT_DEX_MAGIC: [u8; 4] = [0x63, 0x64, 0x65, 0x78];
+
 /// Dex header representation structure.
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
 pub struct Header {
     magic: [u8; 8],
     checksum: u32,
@@ -46,20 +51,23 @@ pub struct Header {
     class_defs_size: u32,
     class_defs_offset: Option<u32>,
     data_size: u32,
-    data_offset: u32,
+    data_offset: Option<u32>,
 }
 
 impl Header {
     /// Obtains the header from a Dex file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, if its header is not valid, or if its size
+    /// does not match the one declared in the header.
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let f = fs::File::open(path).context("could not open file")?;
-        let file_size = f.metadata().context("could not read file metadata")?.len();
+        let f = fs::File::open(path)?;
+        let file_size = f.metadata()?.len();
         if file_size < u64::from(HEADER_SIZE) || file_size > (u64::from(u32::max_value())) {
             return Err(error::InvalidFileSize { file_size }.into());
         }
-        let header = Self::from_reader(BufReader::new(f)).context(error::Header::Generic {
-            error: "there was an error reading the header of the dex file".to_owned(),
-        })?;
+        let header = Self::from_reader(BufReader::new(f))?;
         if file_size == u64::from(header.get_file_size()) {
             Ok(header)
         } else {
@@ -72,36 +80,32 @@ impl Header {
     }
 
     /// Obtains the header from a Dex file reader.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the reader ends before the end of the header, or if the header is not
+    /// valid, for example because of a wrong magic number or a compact dex file.
     pub fn from_reader<R: Read>(mut reader: R) -> Result<Self> {
         // Magic number
         let mut magic = [0_u8; 8];
-        reader
-            .read_exact(&mut magic)
-            .context("could not read dex magic number")?;
+        reader.read_exact(&mut magic)?;
+        if magic[0..4] == COMPACT_DEX_MAGIC {
+            return Err(error::Parse::CompactDexUnsupported.into());
+        }
         if !Self::is_magic_valid(magic) {
             return Err(error::Header::IncorrectMagic { dex_magic: magic }.into());
         }
         // Checksum
-        let mut checksum = reader
-            .read_u32::<LittleEndian>()
-            .context("could not read file checksum")?;
+        let mut checksum = reader.read_u32::<LittleEndian>()?;
         // Signature
         let mut signature = [0_u8; 20];
-        reader
-            .read_exact(&mut signature)
-            .context("could not read file signature")?;
+        reader.read_exact(&mut signature)?;
         // File size
-        let mut file_size = reader
-            .read_u32::<LittleEndian>()
-            .context("could not read file size")?;
+        let mut file_size = reader.read_u32::<LittleEndian>()?;
         // Header size
-        let mut header_size = reader
-            .read_u32::<LittleEndian>()
-            .context("could not read header size")?;
+        let mut header_size = reader.read_u32::<LittleEndian>()?;
         // Endian tag
-        let endian_tag = reader
-            .read_u32::<LittleEndian>()
-            .context("could not read endian tag")?;
+        let endian_tag = reader.read_u32::<LittleEndian>()?;
 
         // Check endianness
         if endian_tag == REVERSE_ENDIAN_CONSTANT {
@@ -162,13 +166,9 @@ impl Header {
         let mut current_offset = HEADER_SIZE;
 
         // Link size
-        let link_size = reader
-            .read_u32::<E>()
-            .context("could not read the link section size")?;
+        let link_size = reader.read_u32::<E>()?;
         // Link offset
-        let link_offset = reader
-            .read_u32::<E>()
-            .context("could not read the link section offset")?;
+        let link_offset = reader.read_u32::<E>()?;
         if link_size == 0 && link_offset != 0 {
             return Err(error::Parse::OffsetMismatch {
                 offset_name: "link_offset",
@@ -179,9 +179,7 @@ impl Header {
         }
 
         // Map offset
-        let map_offset = reader
-            .read_u32::<E>()
-            .context("could not read the map section offset")?;
+        let map_offset = reader.read_u32::<E>()?;
         if map_offset == 0x0000_0000 {
             return Err(error::Parse::InvalidOffset {
                 desc: "`map_offset` was 0x00000000, and it can never be zero".to_owned(),
@@ -190,13 +188,9 @@ impl Header {
         }
 
         // String IDs size
-        let string_ids_size = reader
-            .read_u32::<E>()
-            .context("could not read the string IDs list size")?;
+        let string_ids_size = reader.read_u32::<E>()?;
         // String IDs offset
-        let string_ids_offset = reader
-            .read_u32::<E>()
-            .context("could not read the string IDs list offset")?;
+        let string_ids_offset = reader.read_u32::<E>()?;
         if string_ids_size > 0 && string_ids_offset != current_offset {
             return Err(error::Parse::OffsetMismatch {
                 offset_name: "string_ids_offset",
@@ -213,16 +207,18 @@ impl Header {
             }
             .into());
         }
-        current_offset += string_ids_size * STRING_ID_ITEM_SIZE;
+        current_offset = list_end(
+            "string_ids_size",
+            current_offset,
+            string_ids_size,
+            STRING_ID_ITEM_SIZE,
+            file_size,
+        )?;
 
         // Types IDs size
-        let type_ids_size = reader
-            .read_u32::<E>()
-            .context("could not read the type IDs list size")?;
+        let type_ids_size = reader.read_u32::<E>()?;
         // Types IDs offset
-        let type_ids_offset = reader
-            .read_u32::<E>()
-            .context("could not read the type IDs list offset")?;
+        let type_ids_offset = reader.read_u32::<E>()?;
         if type_ids_size > 0 && type_ids_offset != current_offset {
             return Err(error::Parse::OffsetMismatch {
                 offset_name: "type_ids_offset",
@@ -239,16 +235,18 @@ impl Header {
             }
             .into());
         }
-        current_offset += type_ids_size * TYPE_ID_ITEM_SIZE;
+        current_offset = list_end(
+            "type_ids_size",
+            current_offset,
+            type_ids_size,
+            TYPE_ID_ITEM_SIZE,
+            file_size,
+        )?;
 
         // Prototype IDs size
-        let prototype_ids_size = reader
-            .read_u32::<E>()
-            .context("could not read the prototype IDs list size")?;
+        let prototype_ids_size = reader.read_u32::<E>()?;
         // Prototype IDs offset
-        let prototype_ids_offset = reader
-            .read_u32::<E>()
-            .context("could not read the prototype IDs list offset")?;
+        let prototype_ids_offset = reader.read_u32::<E>()?;
         if prototype_ids_size > 0 && prototype_ids_offset != current_offset {
             return Err(error::Parse::OffsetMismatch {
                 offset_name: "prototype_ids_offset",
@@ -265,16 +263,18 @@ impl Header {
             }
             .into());
         }
-        current_offset += prototype_ids_size * PROTO_ID_ITEM_SIZE;
+        current_offset = list_end(
+            "proto_ids_size",
+            current_offset,
+            prototype_ids_size,
+            PROTO_ID_ITEM_SIZE,
+            file_size,
+        )?;
 
         // Field IDs size
-        let field_ids_size = reader
-            .read_u32::<E>()
-            .context("could not read the field IDs list size")?;
+        let field_ids_size = reader.read_u32::<E>()?;
         // Field IDs offset
-        let field_ids_offset = reader
-            .read_u32::<E>()
-            .context("could not read the field IDs list offset")?;
+        let field_ids_offset = reader.read_u32::<E>()?;
         if field_ids_size > 0 && field_ids_offset != current_offset {
             return Err(error::Parse::OffsetMismatch {
                 offset_name: "field_ids_offset",
@@ -291,16 +291,18 @@ impl Header {
             }
             .into());
         }
-        current_offset += field_ids_size * FIELD_ID_ITEM_SIZE;
+        current_offset = list_end(
+            "field_ids_size",
+            current_offset,
+            field_ids_size,
+            FIELD_ID_ITEM_SIZE,
+            file_size,
+        )?;
 
         // Method IDs size
-        let method_ids_size = reader
-            .read_u32::<E>()
-            .context("could not read the method IDs list size")?;
+        let method_ids_size = reader.read_u32::<E>()?;
         // Method IDs offset
-        let method_ids_offset = reader
-            .read_u32::<E>()
-            .context("could not read the method IDs list offset")?;
+        let method_ids_offset = reader.read_u32::<E>()?;
         if method_ids_size > 0 && method_ids_offset != current_offset {
             return Err(error::Parse::OffsetMismatch {
                 offset_name: "method_ids_offset",
@@ -317,16 +319,18 @@ impl Header {
             }
             .into());
         }
-        current_offset += method_ids_size * METHOD_ID_ITEM_SIZE;
+        current_offset = list_end(
+            "method_ids_size",
+            current_offset,
+            method_ids_size,
+            METHOD_ID_ITEM_SIZE,
+            file_size,
+        )?;
 
         // Class defs size
-        let class_defs_size = reader
-            .read_u32::<E>()
-            .context("could not read the class definitions list size")?;
+        let class_defs_size = reader.read_u32::<E>()?;
         // Class defs offset
-        let class_defs_offset = reader
-            .read_u32::<E>()
-            .context("could not read the class definitions list offset")?;
+        let class_defs_offset = reader.read_u32::<E>()?;
         if class_defs_size > 0 && class_defs_offset != current_offset {
             return Err(error::Parse::OffsetMismatch {
                 offset_name: "class_defs_offset",
@@ -343,12 +347,16 @@ impl Header {
             }
             .into());
         }
-        current_offset += class_defs_size * CLASS_DEF_ITEM_SIZE;
+        current_offset = list_end(
+            "class_defs_size",
+            current_offset,
+            class_defs_size,
+            CLASS_DEF_ITEM_SIZE,
+            file_size,
+        )?;
 
         // Data size
-        let data_size = reader
-            .read_u32::<E>()
-            .context("could not read the data section size")?;
+        let data_size = reader.read_u32::<E>()?;
         if data_size & 0b11 != 0 {
             return Err(error::Header::Generic {
                 error: format!(
@@ -360,9 +368,7 @@ impl Header {
         }
 
         // Data offset
-        let data_offset = reader
-            .read_u32::<E>()
-            .context("could not read the data section offset")?;
+        let data_offset = reader.read_u32::<E>()?;
         if data_offset != current_offset {
             // return Err(Error::mismatched_offsets("data_offset", data_offset, current_offset));
             // TODO seems that there is more information after the class definitions.
@@ -443,7 +449,7 @@ impl Header {
             class_defs_size,
             class_defs_offset: some_if(class_defs_offset, class_defs_size > 0),
             data_size,
-            data_offset,
+            data_offset: some_if(data_offset, data_size > 0),
         })
     }
 
@@ -484,6 +490,22 @@ impl Header {
         self.file_size
     }
 
+    /// Gets the range of bytes of the file covered by the checksum, as `(start, end)` offsets.
+    ///
+    /// The Adler-32 checksum covers the whole file except the magic number and the checksum
+    /// itself, from offset 12 to the end of the file.
+    pub fn checksum_range(&self) -> (u32, u32) {
+        (12, self.file_size)
+    }
+
+    /// Gets the range of bytes of the file covered by the signature, as `(start, end)` offsets.
+    ///
+    /// The SHA-1 signature covers the whole file except the magic number, the checksum and the
+    /// signature itself, from offset 32 to the end of the file.
+    pub fn signature_range(&self) -> (u32, u32) {
+        (32, self.file_size)
+    }
+
     /// Gets header size, in bytes.
     ///
     /// This must be 0x70.
@@ -519,11 +541,13 @@ impl Header {
     }
 
     /// Gets the map section offset.
+    ///
+    /// Unlike the rest of offsets, this one is never zero, since all dex files have a map.
     pub fn get_map_offset(&self) -> u32 {
         self.map_offset
     }
 
-    /// Gets the string IDs list size.
+    /// Gets the string IDs list size, in number of items.
     pub fn get_string_ids_size(&self) -> u32 {
         self.string_ids_size
     }
@@ -533,7 +557,7 @@ impl Header {
         self.string_ids_offset
     }
 
-    /// Gets the type IDs list size.
+    /// Gets the type IDs list size, in number of items.
     pub fn get_type_ids_size(&self) -> u32 {
         self.type_ids_size
     }
@@ -543,7 +567,7 @@ impl Header {
         self.type_ids_offset
     }
 
-    /// Gets the prototype IDs list size.
+    /// Gets the prototype IDs list size, in number of items.
     pub fn get_prototype_ids_size(&self) -> u32 {
         self.prototype_ids_size
     }
@@ -553,7 +577,7 @@ impl Header {
         self.prototype_ids_offset
     }
 
-    /// Gets the field IDs list size.
+    /// Gets the field IDs list size, in number of items.
     pub fn get_field_ids_size(&self) -> u32 {
         self.field_ids_size
     }
@@ -563,7 +587,7 @@ impl Header {
         self.field_ids_offset
     }
 
-    /// Gets the method IDs list size.
+    /// Gets the method IDs list size, in number of items.
     pub fn get_method_ids_size(&self) -> u32 {
         self.method_ids_size
     }
@@ -573,7 +597,7 @@ impl Header {
         self.method_ids_offset
     }
 
-    /// Gets the class definition list size.
+    /// Gets the class definition list size, in number of items.
     pub fn get_class_defs_size(&self) -> u32 {
         self.class_defs_size
     }
@@ -589,10 +613,18 @@ impl Header {
     }
 
     /// Gets the data section offset.
-    pub fn get_data_offset(&self) -> u32 {
+    pub fn get_data_offset(&self) -> Option<u32> {
         self.data_offset
     }
 
+    /// Gets the range of bytes of the data section, as `(start, end)` offsets.
+    ///
+    /// The end is exclusive, so the range is empty if the file has no data section.
+    pub fn data_range(&self) -> (u32, u32) {
+        let start = self.data_offset.unwrap_or(0);
+        (start, start.saturating_add(self.data_size))
+    }
+
     // /// Verifies the file at the given path.
     // pub fn verify_file<P: AsRef<Path>>(&self, path: P) -> bool {
     //     unimplemented!() // TODO
@@ -696,7 +728,27 @@ impl fmt::Debug for Header {
                 String::from("no classes")
             },
             self.data_size,
-            self.data_offset
+            self.data_offset.unwrap_or(0)
         )
     }
 }
+
+/// Gets the offset of the end of an ID list, checking that the list fits in the file.
+#[allow(clippy::cast_possible_truncation)]
+fn list_end(
+    size_name: &'static str,
+    offset: u32,
+    size: u32,
+    item_size: u32,
+    file_size: u32,
+) -> Result<u32, error::Parse> {
+    let end = u64::from(offset) + u64::from(size) * u64::from(item_size);
+    if end > u64::from(file_size) {
+        return Err(error::Parse::ImplausibleSize {
+            size_name,
+            size: u64::from(size),
+            limit: u64::from(file_size.saturating_sub(offset) / item_size),
+        });
+    }
+    Ok(end as u32)
+}
@@ -0,0 +1,146 @@
+//! String sharing between dex files.
+
+#[cfg(feature = "cache")]
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, sync::Arc};
+
+/// Pool of strings shared by the dex files parsed with it.
+///
+/// The dex files of an application, or of the libraries of an SDK, have many strings in common,
+/// such as class, method and field names. Parsing them with `Dex::from_reader_with_interner()`
+/// and the same interner makes each distinct string be stored only once, as it is read, and the
+/// names of the classes and their members point to the same storage.
+#[derive(Debug, Default, Clone)]
+pub struct StringInterner {
+    strings: HashSet<Arc<str>>,
+}
+
+impl StringInterner {
+    /// Creates a new, empty, string interner.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets the number of distinct strings in the interner.
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Checks if the interner has no strings.
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// Gets the shared copy of the given string, adding it to the interner if it is new.
+    pub(crate) fn intern(&mut self, string: &str) -> Arc<str> {
+        if let Some(shared) = self.strings.get(string) {
+            return Arc::clone(shared);
+        }
+        let shared: Arc<str> = Arc::from(string);
+        let _ = self.strings.insert(Arc::clone(&shared));
+        shared
+    }
+}
+
+/// String table of a dex file.
+#[derive(Debug)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
+pub(crate) enum StringTable {
+    /// Strings owned by the dex file, the default.
+    Owned(Vec<String>),
+    /// Strings shared with other dex files through a `StringInterner`.
+    Shared(Vec<Arc<str>>),
+}
+
+impl StringTable {
+    /// Adds a string at the end of the table, sharing it through the interner if the table is
+    /// shared.
+    pub(crate) fn push(&mut self, string: String, interner: Option<&mut StringInterner>) {
+        match self {
+            Self::Owned(strings) => strings.push(string),
+            Self::Shared(strings) => strings.push(match interner {
+                Some(interner) => interner.intern(&string),
+                None => Arc::from(string),
+            }),
+        }
+    }
+
+    /// Gets the number of strings in the table.
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            Self::Owned(strings) => strings.len(),
+            Self::Shared(strings) => strings.len(),
+        }
+    }
+
+    /// Gets the string at the given index, if it exists.
+    pub(crate) fn get(&self, index: usize) -> Option<&str> {
+        match self {
+            Self::Owned(strings) => strings.get(index).map(String::as_str),
+            Self::Shared(strings) => strings.get(index).map(|string| &**string),
+        }
+    }
+
+    /// Gets the string at the given index as a name, if it exists.
+    ///
+    /// Shared strings are not copied, so that names are stored once for all the dex files
+    /// sharing them. Owned strings are copied.
+    pub(crate) fn get_name(&self, index: usize) -> Option<Arc<str>> {
+        match self {
+            Self::Owned(strings) => strings.get(index).map(|string| Arc::from(string.as_str())),
+            Self::Shared(strings) => strings.get(index).cloned(),
+        }
+    }
+
+    /// Iterates over the strings of the table, in order.
+    pub(crate) fn iter(&self) -> impl ExactSizeIterator<Item = &str> + DoubleEndedIterator {
+        (0..self.len()).map(move |index| match self {
+            Self::Owned(strings) => strings[index].as_str(),
+            Self::Shared(strings) => &*strings[index],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StringInterner, StringTable};
+    use std::sync::Arc;
+
+    #[test]
+    fn it_shares_equal_strings() {
+        let mut interner = StringInterner::new();
+        assert!(interner.is_empty());
+
+        let first = interner.intern("Ljava/lang/Object;");
+        let second = interner.intern("Ljava/lang/Object;");
+        let other = interner.intern("<init>");
+
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(!Arc::ptr_eq(&first, &other));
+        assert_eq!(2, interner.len());
+    }
+
+    #[test]
+    fn it_shares_the_strings_of_shared_tables() {
+        let mut interner = StringInterner::new();
+        let mut shared = StringTable::Shared(Vec::new());
+        let mut owned = StringTable::Owned(Vec::new());
+        for table in &mut [&mut shared, &mut owned] {
+            table.push("<init>".to_owned(), Some(&mut interner));
+            table.push("foo".to_owned(), Some(&mut interner));
+        }
+
+        assert_eq!(2, interner.len());
+        assert_eq!(vec!["<init>", "foo"], shared.iter().collect::<Vec<_>>());
+        assert_eq!(vec!["<init>", "foo"], owned.iter().collect::<Vec<_>>());
+        assert_eq!(Some("foo"), owned.get(1));
+        assert_eq!(None, shared.get(2));
+
+        let name = shared.get_name(0).unwrap();
+        assert!(Arc::ptr_eq(&name, &interner.intern("<init>")));
+        assert!(!Arc::ptr_eq(
+            &owned.get_name(0).unwrap(),
+            &interner.intern("<init>")
+        ));
+    }
+}
@@ -0,0 +1,207 @@
+//! Java source skeleton generation.
+
+use crate::{types::AccessFlags, Class, Method};
+use std::fmt::Write;
+
+/// Body of all the stubbed methods, as in the Android SDK stubs.
+const STUB_BODY: &str = "throw new RuntimeException(\"Stub!\");";
+
+/// Generates the Java source skeleton of a class.
+///
+/// Returns the path of the source file, relative to the root of the source tree, and the
+/// stubbed source code of the class.
+pub(crate) fn skeleton(class: &Class) -> (String, String) {
+    let name = class.name().trim_end_matches(';');
+    let (package, simple_name) = match name.rfind('/') {
+        Some(i) => (Some(&name[..i]), &name[i + 1..]),
+        None => (None, name),
+    };
+    let package_name = package.map(|package| package.replace('/', "."));
+
+    let mut imports = Vec::new();
+    let mut declaration = String::new();
+    let access_flags = class.access_flags();
+    if access_flags.contains(AccessFlags::ACC_PUBLIC) {
+        declaration.push_str("public ");
+    }
+    let is_interface = class.is_interface();
+    let is_enum = class.is_enum();
+    if class.is_annotation() {
+        declaration.push_str("@interface ");
+    } else if is_interface {
+        declaration.push_str("interface ");
+    } else if is_enum {
+        declaration.push_str("enum ");
+    } else {
+        if class.is_abstract() {
+            declaration.push_str("abstract ");
+        }
+        if access_flags.contains(AccessFlags::ACC_FINAL) {
+            declaration.push_str("final ");
+        }
+        declaration.push_str("class ");
+    }
+    declaration.push_str(simple_name);
+
+    if let Some(superclass) = class.superclass() {
+        if !is_interface && !is_enum && superclass != "java/lang/Object;" {
+            declaration.push_str(" extends ");
+            declaration.push_str(&referenced_class(
+                superclass,
+                package_name.as_deref(),
+                &mut imports,
+            ));
+        }
+    }
+    // Annotations implicitly extend `java.lang.annotation.Annotation`.
+    let interfaces = class
+        .interfaces()
+        .iter()
+        .filter(|interface| {
+            !class.is_annotation() || interface.as_str() != "java/lang/annotation/Annotation;"
+        })
+        .map(|interface| referenced_class(interface, package_name.as_deref(), &mut imports))
+        .collect::<Vec<_>>();
+    if !interfaces.is_empty() {
+        declaration.push_str(if is_interface {
+            " extends "
+        } else {
+            " implements "
+        });
+        declaration.push_str(&interfaces.join(", "));
+    }
+
+    let mut source = String::new();
+    if let Some(package_name) = &package_name {
+        let _ = writeln!(source, "package {};\n", package_name);
+    }
+    imports.sort();
+    imports.dedup();
+    for import in &imports {
+        let _ = writeln!(source, "import {};", import);
+    }
+    if !imports.is_empty() {
+        source.push('\n');
+    }
+    let _ = writeln!(source, "{} {{", declaration);
+    for method in class.declared_methods() {
+        if method.name() == "<clinit>" {
+            continue;
+        }
+        source.push('\n');
+        method_skeleton(&mut source, method, simple_name, is_interface);
+    }
+    source.push_str("}\n");
+
+    let path = format!("{}.java", name);
+    (path, source)
+}
+
+/// Writes the stubbed declaration of a method.
+fn method_skeleton(source: &mut String, method: &Method, class_name: &str, in_interface: bool) {
+    let access_flags = method.access_flags();
+    source.push_str("    ");
+    if !in_interface {
+        if access_flags.contains(AccessFlags::ACC_PUBLIC) {
+            source.push_str("public ");
+        } else if access_flags.contains(AccessFlags::ACC_PROTECTED) {
+            source.push_str("protected ");
+        } else if access_flags.contains(AccessFlags::ACC_PRIVATE) {
+            source.push_str("private ");
+        }
+    }
+    if access_flags.contains(AccessFlags::ACC_STATIC) {
+        source.push_str("static ");
+    }
+    if access_flags.contains(AccessFlags::ACC_ABSTRACT) && !in_interface {
+        source.push_str("abstract ");
+    }
+    if access_flags.contains(AccessFlags::ACC_FINAL) {
+        source.push_str("final ");
+    }
+    if access_flags.contains(AccessFlags::ACC_DECLARED_SYNCHRONIZED) {
+        source.push_str("synchronized ");
+    }
+    if access_flags.contains(AccessFlags::ACC_NATIVE) {
+        source.push_str("native ");
+    }
+
+    if method.name() == "<init>" {
+        source.push_str(class_name);
+    } else {
+        let _ = write!(
+            source,
+            "{} {}",
+            java_type(&method.return_type().to_string()),
+            method.name()
+        );
+    }
+    let parameters = method
+        .parameters()
+        .iter()
+        .enumerate()
+        .map(|(i, parameter)| format!("{} p{}", java_type(&parameter.to_string()), i))
+        .collect::<Vec<_>>();
+    let _ = write!(source, "({})", parameters.join(", "));
+
+    if access_flags.intersects(AccessFlags::ACC_ABSTRACT | AccessFlags::ACC_NATIVE) {
+        source.push_str(";\n");
+    } else {
+        let _ = writeln!(source, " {{\n        {}\n    }}", STUB_BODY);
+    }
+}
+
+/// Gets the name to use for a class referenced in a declaration, adding it to the imports if
+/// it is not in the same package or in `java.lang`.
+fn referenced_class(name: &str, package: Option<&str>, imports: &mut Vec<String>) -> String {
+    let qualified_name = name.trim_end_matches(';').replace('/', ".");
+    match qualified_name.rfind('.') {
+        Some(i) => {
+            let class_package = &qualified_name[..i];
+            if class_package != "java.lang" && Some(class_package) != package {
+                imports.push(qualified_name.clone());
+            }
+            qualified_name[i + 1..].to_owned()
+        }
+        None => qualified_name,
+    }
+}
+
+/// Converts a type, as displayed by `Type`, to its name in Java source code.
+///
+/// Classes in `java.lang` use their simple name, and the rest are fully qualified.
+fn java_type(name: &str) -> String {
+    let (element, dimensions) = match (name.rfind('['), name.ends_with(']')) {
+        (Some(i), true) => (
+            &name[..i],
+            name[i + 1..name.len() - 1].parse::<usize>().unwrap_or(1),
+        ),
+        _ => (name, 0),
+    };
+    let element = element.trim_end_matches(';').replace('/', ".");
+    let prefix = "java.lang.";
+    let element = if element.starts_with(prefix) && !element[prefix.len()..].contains('.') {
+        element[prefix.len()..].to_owned()
+    } else {
+        element
+    };
+    format!("{}{}", element, "[]".repeat(dimensions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::java_type;
+
+    #[test]
+    fn it_converts_types_to_java() {
+        assert_eq!("int", java_type("int"));
+        assert_eq!("String", java_type("java/lang/String;"));
+        assert_eq!("android.os.Parcel", java_type("android/os/Parcel;"));
+        assert_eq!(
+            "java.lang.reflect.Method",
+            java_type("java/lang/reflect/Method;")
+        );
+        assert_eq!("byte[][]", java_type("byte[2]"));
+        assert_eq!("Object[]", java_type("java/lang/Object;[1]"));
+    }
+}
@@ -0,0 +1,64 @@
+//! Byte layout of the parsed items of a dex file.
+
+use std::collections::HashMap;
+
+/// Item of a dex file whose location is recorded in a `Layout`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LayoutItem {
+    /// `string_data_item` of the string with the given index in the string IDs list.
+    StringData(usize),
+    /// `class_def_item` of the class with the given index in `Dex::types()`.
+    ClassDef(usize),
+    /// `method_id_item` with the given index in the method IDs list.
+    MethodId(usize),
+    /// `code_item` at the given offset.
+    CodeItem(u32),
+}
+
+/// Location of the parsed items in a dex file.
+///
+/// It is only recorded if enabled with `ParseOptions::with_record_layout()`, and it can be used
+/// to map the parsed structure back to the bytes of the file, for example to highlight an item
+/// in a hex viewer.
+#[derive(Debug, Default, Clone)]
+pub struct Layout {
+    items: HashMap<LayoutItem, (u32, u32)>,
+}
+
+impl Layout {
+    /// Creates a new, empty, layout.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets the offset and the length in bytes of the given item, if it was parsed.
+    pub fn get(&self, item: LayoutItem) -> Option<(u32, u32)> {
+        self.items.get(&item).copied()
+    }
+
+    /// Gets the number of items in the layout.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Checks if the layout has no items.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Gets an iterator over the items in the layout, along with their offset and length, in
+    /// arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (LayoutItem, (u32, u32))> + '_ {
+        self.items.iter().map(|(&item, &location)| (item, location))
+    }
+
+    /// Records the location of an item, given the offsets of its first byte and of the byte
+    /// after its end.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn record(&mut self, item: LayoutItem, start: u64, end: u64) {
+        // Offsets come from a dex file, so they always fit in 32 bits.
+        let _ = self
+            .items
+            .insert(item, (start as u32, end.saturating_sub(start) as u32));
+    }
+}
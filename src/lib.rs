@@ -28,63 +28,717 @@
 #![warn(unused)]
 #![allow(clippy::must_use_candidate, rustdoc)]
 
-pub use crate::header::Header;
+#[cfg(feature = "regex")]
+pub use crate::network::NetworkIndicators;
 use crate::{
+    bytecode::{
+        ArrayOperation, ByteCode, ByteCodeDecoder, CodeUnits, FieldReference, InstructionFilter,
+        Instructions, InvokeKind, MethodReference, PrototypeReference, Reference,
+        ReferenceResolver, StringReference, TypeReference,
+    },
+    error::Result,
+    intern::StringTable,
     read::DexReader,
-    sizes::HEADER_SIZE,
-    types::{AccessFlags, Type},
+    sizes::{
+        CALL_SITE_ID_ITEM_SIZE, CLASS_DEF_ITEM_SIZE, FIELD_ID_ITEM_SIZE, HEADER_SIZE,
+        METHOD_HANDLE_ITEM_SIZE, METHOD_ID_ITEM_SIZE, PROTO_ID_ITEM_SIZE, STRING_ID_ITEM_SIZE,
+        TYPE_ID_ITEM_SIZE,
+    },
+    types::{
+        read::{CallSite, CodeItem, FieldIdData, MapItem, MethodHandleData, MethodIdData},
+        AccessFlags, Annotation, EncodedAnnotation, HiddenApiFlag, ItemType, Prototype, Type,
+        Value,
+    },
 };
-use anyhow::{Context, Result};
+pub use crate::{
+    header::Header,
+    intern::StringInterner,
+    layout::{Layout, LayoutItem},
+    mapping::ProguardMapping,
+    multidex::MultiDex,
+    strings::LazyStrings,
+    timings::ParseTimings,
+};
+use byteorder::LittleEndian;
+#[cfg(feature = "cache")]
+use serde::{Deserialize, Serialize};
 use std::{
-    fs,
-    io::{prelude::BufRead, BufReader},
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
+    convert::TryFrom,
+    fmt, fs,
+    io::{self, prelude::*, BufReader},
+    mem,
+    ops::Range,
     path::Path,
+    sync::Arc,
+    time::Instant,
     u32,
 };
 
 pub mod bytecode;
 pub mod error;
 pub mod header;
+pub mod intern;
+mod java;
+pub mod layout;
+pub mod mapping;
+pub mod multidex;
+#[cfg(feature = "regex")]
+pub mod network;
 mod read;
 mod sizes;
+pub mod strings;
+pub mod timings;
 pub mod types;
+#[cfg(feature = "extract")]
+mod write;
+
+/// Magic number at the start of the parsed structure cache.
+#[cfg(feature = "cache")]
+const CACHE_MAGIC: [u8; 8] = *b"dexcache";
+/// Version of the parsed structure cache format.
+///
+/// It must be increased every time the parsed structure changes.
+#[cfg(feature = "cache")]
+const CACHE_VERSION: u32 = 11;
+
+/// Entropy of the data section, in bits per byte, above which `Dex::is_likely_packed()` considers
+/// it to hold encrypted or compressed data.
+///
+/// The data section of regular dex files is around 5 bits per byte, while encrypted data is
+/// close to the maximum of 8.
+pub const PACKED_ENTROPY_THRESHOLD: f64 = 7.2;
+
+/// Methods whose invocation is considered reflection usage by `Dex::uses_reflection()`.
+///
+/// Each method is identified by the descriptor of its class and its name, as in
+/// `Ljava/lang/Class;.forName`, so that all its overloads are included.
+pub const REFLECTION_METHODS: &[&str] = &[
+    "Ljava/lang/Class;.forName",
+    "Ljava/lang/Class;.getMethod",
+    "Ljava/lang/Class;.getDeclaredMethod",
+    "Ljava/lang/Class;.getField",
+    "Ljava/lang/Class;.getDeclaredField",
+    "Ljava/lang/Class;.getConstructor",
+    "Ljava/lang/Class;.getDeclaredConstructor",
+    "Ljava/lang/Class;.newInstance",
+    "Ljava/lang/reflect/Method;.invoke",
+    "Ljava/lang/reflect/Constructor;.newInstance",
+    "Ljava/lang/reflect/Field;.get",
+    "Ljava/lang/reflect/Field;.set",
+    "Ljava/lang/reflect/AccessibleObject;.setAccessible",
+];
+
+/// Options to parse dex files.
+///
+/// Sizes read from the dex file are always checked against the size of the file before
+/// allocating memory for the items, but untrusted files can still make the parser use a lot of
+/// memory within that bound. These options allow setting stricter limits.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParseOptions {
+    max_insns_per_method: Option<u32>,
+    retain_raw_bytes: bool,
+    record_layout: bool,
+    lenient: bool,
+}
+
+impl ParseOptions {
+    /// Creates new parsing options, without any limit.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits the number of code units of the bytecode of each method.
+    ///
+    /// Code items over the limit are not read, and the error is recorded in the class of the
+    /// method, as with any other code item that cannot be read.
+    #[must_use]
+    pub fn with_max_insns_per_method(mut self, max_insns_per_method: u32) -> Self {
+        self.max_insns_per_method = Some(max_insns_per_method);
+        self
+    }
+
+    /// Gets the maximum number of code units of the bytecode of each method, if limited.
+    pub fn max_insns_per_method(&self) -> Option<u32> {
+        self.max_insns_per_method
+    }
+
+    /// Keeps the contents of the file in memory after parsing it, so that they can be retrieved
+    /// with `Dex::raw_bytes()`.
+    #[must_use]
+    pub fn with_retain_raw_bytes(mut self, retain_raw_bytes: bool) -> Self {
+        self.retain_raw_bytes = retain_raw_bytes;
+        self
+    }
+
+    /// Checks if the contents of the file are kept in memory after parsing it.
+    pub fn retain_raw_bytes(&self) -> bool {
+        self.retain_raw_bytes
+    }
+
+    /// Records the offset and length of the parsed items, so that they can be retrieved with
+    /// `Dex::layout()`.
+    #[must_use]
+    pub fn with_record_layout(mut self, record_layout: bool) -> Self {
+        self.record_layout = record_layout;
+        self
+    }
+
+    /// Checks if the offset and length of the parsed items are recorded.
+    pub fn record_layout(&self) -> bool {
+        self.record_layout
+    }
+
+    /// Checks if recoverable anomalies are recorded as warnings instead of failing the parsing.
+    ///
+    /// This is only set by `Dex::from_file_lenient()` and `Dex::from_reader_lenient()`, which
+    /// are the ones returning the warnings.
+    pub(crate) fn lenient(&self) -> bool {
+        self.lenient
+    }
+}
 
 /// Dex file representation.
 #[derive(Debug)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
 pub struct Dex {
     header: Header,
-    strings: Vec<String>,
+    strings: StringTable,
+    type_ids: Vec<Type>,
+    prototypes: Vec<Prototype>,
+    field_ids: Vec<FieldIdData>,
+    method_ids: Vec<MethodIdData>,
+    method_handles: Vec<MethodHandleData>,
+    call_sites: Vec<CallSite>,
     types: Vec<Class>,
+    code_items: Vec<(u32, CodeItem)>,
+    map: Vec<MapItem>,
+    hierarchy: ClassHierarchy,
+    link_data: Option<Box<[u8]>>,
+    #[cfg_attr(feature = "cache", serde(skip))]
+    raw_bytes: Option<Box<[u8]>>,
+    #[cfg_attr(feature = "cache", serde(skip))]
+    layout: Option<Layout>,
 }
 
 impl Dex {
     /// Reads the Dex data structure from the given path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, if its size is not plausible for a dex file,
+    /// or if it is not a valid dex file.
     pub fn from_file<P>(path: P) -> Result<Self>
     where
         P: AsRef<Path>,
     {
-        let file = fs::File::open(path).context("could not open file")?;
-        let file_size = file
-            .metadata()
-            .context("could not read file metadata")?
-            .len();
-        if file_size < u64::from(HEADER_SIZE) || file_size > u64::from(u32::max_value()) {
-            return Err(error::InvalidFileSize { file_size }.into());
-        }
-        Self::from_reader(BufReader::new(file), file_size as usize)
+        let (reader, file_size) = open_file(path)?;
+        Self::from_reader(reader, file_size)
+    }
+
+    /// Reads the Dex data structure from the given path, along with the recoverable anomalies
+    /// found in it.
+    ///
+    /// See `Dex::from_reader_lenient()` for more information.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, or if it has anomalies that leave no way to
+    /// continue parsing.
+    pub fn from_file_lenient<P>(path: P) -> Result<(Self, Vec<Warning>)>
+    where
+        P: AsRef<Path>,
+    {
+        let (reader, file_size) = open_file(path)?;
+        Self::from_reader_lenient(reader, file_size)
     }
 
     /// Loads a new Dex data structure from the given reader.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the reader fails, or if its contents are not a valid dex file.
     pub fn from_reader<R, S>(reader: R, size: S) -> Result<Self>
     where
         R: BufRead,
         S: Into<Option<usize>>,
     {
-        let mut dex_reader =
-            DexReader::from_read(reader, size.into()).context("could not create reader")?;
-        dex_reader.read_data().context("could not read dex file")?;
+        Self::from_reader_with_options(reader, size, ParseOptions::default())
+    }
+
+    /// Loads a new Dex data structure from the given reader, with the given parsing options.
+    ///
+    /// This allows limiting the resources used to parse untrusted files. See `ParseOptions` for
+    /// more information.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the dex file cannot be read or is not valid, or if it exceeds the limits
+    /// of the options.
+    pub fn from_reader_with_options<R, S>(reader: R, size: S, options: ParseOptions) -> Result<Self>
+    where
+        R: BufRead,
+        S: Into<Option<usize>>,
+    {
+        let mut dex_reader = DexReader::from_read(reader, size.into(), options)?;
+        dex_reader.read_data(&|_| true)?;
+
+        Self::try_from(dex_reader)
+    }
+
+    /// Parses a dex file from its contents, measuring the time spent in each phase of the
+    /// parsing.
+    ///
+    /// This is meant to find out which phase is slow for a given file. See `ParseTimings` for
+    /// more information.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the dex file is not valid.
+    pub fn parse_bytes_measured(bytes: &[u8]) -> Result<(Self, ParseTimings)> {
+        let start = Instant::now();
+        let mut dex_reader = DexReader::from_read(bytes, bytes.len(), ParseOptions::default())?;
+        let header = start.elapsed();
+        dex_reader.read_data(&|_| true)?;
+
+        let finish_start = Instant::now();
+        let mut timings = dex_reader.timings;
+        let dex = Self::try_from(dex_reader)?;
+        timings.header = header;
+        timings.finish += finish_start.elapsed();
+        Ok((dex, timings))
+    }
+
+    /// Loads a new Dex data structure from the given reader, sharing the storage of its strings
+    /// with the rest of the dex files loaded with the same interner.
+    ///
+    /// This reduces memory usage when many dex files of the same application are kept in memory,
+    /// since most of their strings are repeated. Each string is replaced by its shared copy as
+    /// soon as it is decoded, and the names of the classes, methods and fields point to the
+    /// shared copies.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the dex file cannot be read or is not valid.
+    pub fn from_reader_with_interner<R, S>(
+        reader: R,
+        size: S,
+        interner: &mut StringInterner,
+    ) -> Result<Self>
+    where
+        R: BufRead,
+        S: Into<Option<usize>>,
+    {
+        let mut dex_reader = DexReader::from_read(reader, size.into(), ParseOptions::default())?;
+        dex_reader.share_strings(mem::take(interner));
+        let read = dex_reader.read_data(&|_| true);
+        // The interner is given back even if the file is not valid.
+        *interner = dex_reader.take_interner().unwrap_or_default();
+        read?;
+
+        let mut dex = Self::try_from(dex_reader)?;
+        // Class names are not in the string table, so they are shared once the classes are built.
+        for class in &mut dex.types {
+            class.name = interner.intern(&class.name);
+        }
+        Ok(dex)
+    }
+
+    /// Loads a new Dex data structure from the given reader, along with the recoverable anomalies
+    /// found in it.
+    ///
+    /// Strings whose declared size does not match their contents are kept instead of failing the
+    /// parsing, and they are reported along with the bytes that do not belong to any section,
+    /// the link section, the classes that could not be fully read and the issues found by
+    /// `Dex::validate()`. This is useful to analyze malformed or malicious files, which the
+    /// runtime may still accept. Anomalies that leave no way to continue parsing are still
+    /// errors.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the dex file cannot be read, or if it has anomalies that leave no way to
+    /// continue parsing.
+    pub fn from_reader_lenient<R, S>(reader: R, size: S) -> Result<(Self, Vec<Warning>)>
+    where
+        R: BufRead,
+        S: Into<Option<usize>>,
+    {
+        let options = ParseOptions {
+            lenient: true,
+            ..ParseOptions::default()
+        };
+        let mut dex_reader = DexReader::from_read(reader, size.into(), options)?;
+        dex_reader.read_data(&|_| true)?;
+        let mut warnings = mem::take(&mut dex_reader.warnings);
+
+        let dex = Self::try_from(dex_reader)?;
+        warnings.extend(dex.types.iter().filter_map(|class| {
+            class.parse_error().map(|error| Warning::UnreadableClass {
+                class: class.name().to_owned(),
+                error: error.to_owned(),
+            })
+        }));
+        warnings.extend(dex.validate().into_iter().map(Warning::Issue));
+        Ok((dex, warnings))
+    }
+
+    /// Lists the descriptors of the classes defined in the dex file in the given reader, such as
+    /// `Lcom/example/Foo;`, in class definition order.
+    ///
+    /// This is much faster than parsing the dex file, since only the header and the items needed
+    /// to get each descriptor are read, and it is meant to index large amounts of files. Errors
+    /// in the header are returned right away, and errors reading a class descriptor are yielded
+    /// in its place.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the dex file cannot be read or its header is not valid.
+    pub fn iter_class_descriptors<R, S>(
+        reader: R,
+        size: S,
+    ) -> Result<impl Iterator<Item = Result<String>>>
+    where
+        R: BufRead,
+        S: Into<Option<usize>>,
+    {
+        let mut dex_reader = DexReader::from_read(reader, size.into(), ParseOptions::default())?;
+        let class_count = dex_reader.header.get_class_defs_size();
+        Ok((0..class_count).map(move |index| dex_reader.read_class_descriptor(index as usize)))
+    }
+
+    /// Calls the given function with the index and the contents of each string of the dex file
+    /// in the given reader, in string ID order.
+    ///
+    /// Strings are decoded one at a time, and the list of strings is never built, so this is
+    /// meant to scan large dex files for strings with little memory usage, although the contents
+    /// of the file are still read into memory. Classes and code are not parsed at all.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the dex file cannot be read, or if a string ID or its data is not valid.
+    /// Errors returned by the given function stop the iteration and are returned as they are.
+    pub fn for_each_string<R, S, F>(reader: R, size: S, mut f: F) -> Result<()>
+    where
+        R: BufRead,
+        S: Into<Option<usize>>,
+        F: FnMut(u32, &str),
+    {
+        let mut dex_reader = DexReader::from_read(reader, size.into(), ParseOptions::default())?;
+        for index in 0..dex_reader.header.get_string_ids_size() {
+            let string = dex_reader.read_string_at(index as usize)?;
+            f(index, &string);
+        }
+        Ok(())
+    }
+
+    /// Loads a new Dex data structure from the given reader, parsing only the classes whose
+    /// descriptor, such as `Lcom/example/Foo;`, passes the given filter.
+    ///
+    /// The rest of the classes are skipped, along with their members and code, which makes
+    /// parsing much faster when only a few packages are of interest. The string, type, field,
+    /// method and prototype lists are still read in full, so references to skipped classes and
+    /// their members can still be resolved to their names.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the dex file cannot be read or is not valid.
+    pub fn from_reader_filtered<R, S, F>(reader: R, size: S, class_filter: F) -> Result<Self>
+    where
+        R: BufRead,
+        S: Into<Option<usize>>,
+        F: Fn(&str) -> bool,
+    {
+        let mut dex_reader = DexReader::from_read(reader, size.into(), ParseOptions::default())?;
+        dex_reader.read_data(&class_filter)?;
+
+        Self::try_from(dex_reader)
+    }
+
+    /// Loads a new Dex data structure from the given asynchronous reader.
+    ///
+    /// The contents of the dex file are read asynchronously into memory, and then they are parsed
+    /// in a blocking task by using `tokio::task::spawn_blocking()`, since parsing is CPU-bound.
+    /// This means that the asynchronous executor threads are not blocked either by I/O or by the
+    /// parsing, but it must be called within a Tokio runtime. If a size is given, only that many
+    /// bytes are read from the reader.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the dex file cannot be read or is not valid, or if the parsing task
+    /// fails.
+    #[cfg(feature = "tokio")]
+    pub async fn from_async_read<R, S>(reader: R, size: S) -> Result<Self>
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        S: Into<Option<usize>>,
+    {
+        use tokio::io::AsyncReadExt;
+
+        let size = size.into();
+        let mut bytes = Vec::with_capacity(size.unwrap_or_default());
+        if let Some(size) = size {
+            let _ = reader.take(size as u64).read_to_end(&mut bytes).await?;
+        } else {
+            let mut reader = reader;
+            let _ = reader.read_to_end(&mut bytes).await?;
+        }
+
+        tokio::task::spawn_blocking(move || Self::from_reader(io::Cursor::new(bytes), size)).await?
+    }
+
+    /// Writes the parsed structure to the given writer, so that it can be loaded again with
+    /// `Dex::from_cache()` without parsing the dex file.
+    ///
+    /// The cache is a compact binary serialization of the parsed structure. It is versioned, but
+    /// it is not stable across versions of this crate, so it should only be used as a cache.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the structure cannot be serialized or written.
+    #[cfg(feature = "cache")]
+    pub fn to_cache<W>(&self, mut writer: W) -> Result<()>
+    where
+        W: Write,
+    {
+        writer
+            .write_all(&CACHE_MAGIC)
+            .and_then(|()| writer.write_all(&CACHE_VERSION.to_le_bytes()))?;
+        rmp_serde::encode::write(&mut writer, self)?;
+        Ok(())
+    }
+
+    /// Loads the parsed structure from a cache written with `Dex::to_cache()`.
+    ///
+    /// The cache must have been written by the same version of this crate.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache cannot be read, or if it was not written by this version of
+    /// the crate.
+    #[cfg(feature = "cache")]
+    pub fn from_cache<R>(mut reader: R) -> Result<Self>
+    where
+        R: Read,
+    {
+        let mut header = [0; 12];
+        reader.read_exact(&mut header)?;
+        if header[..8] != CACHE_MAGIC {
+            return Err(error::Parse::InvalidCache {
+                error: "incorrect magic number".to_owned(),
+            }
+            .into());
+        }
+        let mut version = [0; 4];
+        version.copy_from_slice(&header[8..]);
+        let version = u32::from_le_bytes(version);
+        if version != CACHE_VERSION {
+            return Err(error::Parse::InvalidCache {
+                error: format!(
+                    "the cache version is {}, but only version {} is supported",
+                    version, CACHE_VERSION
+                ),
+            }
+            .into());
+        }
+        Ok(rmp_serde::decode::from_read(reader)?)
+    }
+
+    /// Loads a new Dex data structure from a dex file embedded in the given reader.
+    ///
+    /// The dex file is considered to start `base_offset` bytes after the current position of the
+    /// reader, and all the offsets in the dex file are relative to that origin. If a size is
+    /// given, only that many bytes are read from the origin, so that any data after the embedded
+    /// dex file is ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the reader cannot seek to the origin, or if the dex file cannot be read or
+    /// is not valid.
+    pub fn from_reader_at<R, S>(mut reader: R, base_offset: u64, size: S) -> Result<Self>
+    where
+        R: BufRead,
+        S: Into<Option<usize>>,
+    {
+        let skipped = io::copy(&mut reader.by_ref().take(base_offset), &mut io::sink())?;
+        if skipped < base_offset {
+            return Err(error::Parse::InvalidOffset {
+                desc: format!(
+                    "the dex file should start at offset {:#010x}, but the reader ended at \
+                     offset {:#010x}",
+                    base_offset, skipped
+                ),
+            }
+            .into());
+        }
+
+        if let Some(size) = size.into() {
+            Self::from_reader(reader.take(size as u64), size)
+        } else {
+            Self::from_reader(reader, None)
+        }
+    }
+
+    /// Gets the header of the dex file.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Gets the contents of the dex file, if they were kept in memory when parsing it.
+    ///
+    /// They are only kept if enabled with `ParseOptions::with_retain_raw_bytes()`, and they are
+    /// not stored in the cache. Along with `Header::checksum_range()` and
+    /// `Header::signature_range()`, they can be used to verify the checksum and signature.
+    pub fn raw_bytes(&self) -> Option<&[u8]> {
+        self.raw_bytes.as_deref()
+    }
+
+    /// Gets the SHA-256 hash of the whole dex file, if its contents were kept in memory when
+    /// parsing it.
+    ///
+    /// Unlike the SHA-1 signature in the header, this covers the header too, and it is the
+    /// identifier used by malware databases. The contents are only kept if enabled with
+    /// `ParseOptions::with_retain_raw_bytes()`.
+    #[cfg(feature = "sha2")]
+    pub fn content_sha256(&self) -> Option<[u8; 32]> {
+        use sha2::{Digest, Sha256};
+
+        self.raw_bytes
+            .as_deref()
+            .map(|raw_bytes| Sha256::digest(raw_bytes).into())
+    }
+
+    /// Gets the Shannon entropy of the data section, in bits per byte, if the contents of the
+    /// file were kept in memory when parsing it.
+    ///
+    /// The entropy goes from 0, if all bytes are equal, to 8, if all byte values are equally
+    /// frequent, as in encrypted or compressed data. The contents are only kept if enabled with
+    /// `ParseOptions::with_retain_raw_bytes()`.
+    pub fn data_section_entropy(&self) -> Option<f64> {
+        let raw_bytes = self.raw_bytes.as_deref()?;
+        let start = self.header.get_data_offset()? as usize;
+        let end = start.saturating_add(self.header.get_data_size() as usize);
+        let data = raw_bytes.get(start..end.min(raw_bytes.len()))?;
+        Some(shannon_entropy(data))
+    }
+
+    /// Checks if the data section looks encrypted or compressed, as packers leave it, if the
+    /// contents of the file were kept in memory when parsing it.
+    ///
+    /// This is just a heuristic, comparing `Dex::data_section_entropy()` with
+    /// `PACKED_ENTROPY_THRESHOLD`: a packed file with a small payload can stay below it, and a
+    /// regular file with large embedded compressed resources can go above it. It is meant as a
+    /// cheap signal to decide which files to look at first.
+    pub fn is_likely_packed(&self) -> Option<bool> {
+        self.data_section_entropy()
+            .map(|entropy| entropy > PACKED_ENTROPY_THRESHOLD)
+    }
+
+    /// Gets the location of the parsed items in the dex file, if it was recorded when parsing it.
+    ///
+    /// It is only recorded if enabled with `ParseOptions::with_record_layout()`, and it is not
+    /// stored in the cache.
+    pub fn layout(&self) -> Option<&Layout> {
+        self.layout.as_ref()
+    }
+
+    /// Gets the contents of the link section, if the file has one.
+    ///
+    /// The link section is used by statically linked files, and its format is unspecified, so
+    /// it is returned unparsed. It is almost always missing.
+    pub fn link_data(&self) -> Option<&[u8]> {
+        self.link_data.as_deref()
+    }
+
+    /// Gets the number of strings declared in the header of the dex file.
+    ///
+    /// This is the number of strings the file claims to have. When parsing leniently, it can
+    /// differ from the number of strings actually read, `strings().len()`, if the header was
+    /// tampered with. See `Warning::StringCountMismatch` for more information.
+    pub fn declared_string_count(&self) -> usize {
+        self.header.get_string_ids_size() as usize
+    }
+
+    /// Gets the strings in the Dalvik information structure, in the order of the string table.
+    ///
+    /// This works the same for files parsed with a `StringInterner`, whose strings are stored in
+    /// the storage shared with the rest of the files parsed with it.
+    pub fn strings(&self) -> impl ExactSizeIterator<Item = &str> + DoubleEndedIterator + '_ {
+        self.strings.iter()
+    }
+
+    /// Gets the string at the given index of the string table.
+    ///
+    /// Strings are decoded when the file is parsed, so this is a bounds checked lookup in the
+    /// string table, using the `u32` indexes found in the bytecode. To decode strings on demand
+    /// without parsing the rest of the file, use `LazyStrings` instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no string at the given index.
+    pub fn string_at(&self, index: u32) -> Result<&str> {
+        self.strings
+            .get(index as usize)
+            .ok_or_else(|| error::Parse::UnknownStringIndex(index).into())
+    }
+
+    /// Checks if any string in the string table contains the given substring.
+    ///
+    /// This only looks at the string table, so it is a cheap way of checking if the dex file
+    /// references a given literal, such as a URL, a permission or a class name.
+    pub fn contains_string(&self, needle: &str) -> bool {
+        self.strings.iter().any(|string| string.contains(needle))
+    }
+
+    /// Finds the strings in the string table that match the given regular expression.
+    #[cfg(feature = "regex")]
+    pub fn find_strings(&self, pattern: &regex::Regex) -> Vec<&str> {
+        self.strings
+            .iter()
+            .filter(|string| pattern.is_match(string))
+            .collect()
+    }
+
+    /// Extracts the URLs, hostnames and IP addresses found in the string table.
+    ///
+    /// See `NetworkIndicators` for the patterns used to find them.
+    #[cfg(feature = "regex")]
+    pub fn extract_network_indicators(&self) -> NetworkIndicators {
+        NetworkIndicators::from_strings(self.strings.iter())
+    }
+
+    /// Gets the range of bytes of the map of the dex file, as `(start, end)` offsets.
+    ///
+    /// The map starts at `Header::get_map_offset()`, and its size depends on its number of
+    /// items, which is only known once the file is parsed: a 4-byte count followed by 12 bytes
+    /// per item.
+    pub fn map_range(&self) -> (u32, u32) {
+        let start = self.header.get_map_offset();
+        #[allow(clippy::cast_possible_truncation)]
+        let size = 4 + 12 * self.map.len() as u32;
+        (start, start.saturating_add(size))
+    }
+
+    /// Gets the items of the map of the dex file, in the order of the map.
+    ///
+    /// Each one describes a section of the file, including the non-standard ones some vendors
+    /// add, as `ItemType::Unknown`, when parsing leniently. These are not read by the parser,
+    /// but their bytes can be found with `MapItem::offset()` in `Dex::raw_bytes()`.
+    pub fn map_items(&self) -> &[MapItem] {
+        &self.map
+    }
 
-        Ok(dex_reader.into())
+    /// Gets the number of items of each section, as declared in the map of the dex file, in the
+    /// order of the map.
+    ///
+    /// These are the declared counts, which can be compared with the parsed items to detect
+    /// inconsistencies, often found in packed or tampered dex files. Maps with repeated item
+    /// types, which lenient parsing accepts, give one entry per map item.
+    pub fn section_sizes(&self) -> Vec<(ItemType, usize)> {
+        self.map
+            .iter()
+            .map(|item| (item.item_type(), item.size() as usize))
+            .collect()
     }
 
     /// Gets the list of types in the Dalvik information structure.
@@ -92,143 +746,2245 @@ impl Dex {
         &self.types
     }
 
-    // /// Ads the file in the given path to the current Dex data structure.
-    // pub fn add_file<P: AsRef<Path>>(path: P) -> Result<()> {
-    //     unimplemented!() // TODO
-    // }
-    //
-    // /// Verifies the file at the given path.
-    // pub fn verify_file<P: AsRef<Path>>(&self, path: P) -> bool {
-    //     self.header.verify_file(path) // TODO
-    // }
-    //
-    // /// Verifies the file in the given reader.
-    // ///
-    // /// The reader should be positioned at the start of the file.
-    // pub fn verify_reader<R: Read>(&self, reader: R) -> bool {
-    //     self.header.verify_reader(reader) // TODO
-    // }
-}
+    /// Gets the classes in the given package, such as `com.example`, or in its subpackages,
+    /// leaving out the classes generated by the build tools.
+    ///
+    /// The generated `R` resource classes, along with their nested classes such as `R$layout`,
+    /// and `BuildConfig` are left out, so that only the classes with the logic of the
+    /// application are returned. An empty package returns the classes of all packages. The
+    /// full list of classes is still available through `types()`.
+    pub fn application_classes(&self, package_prefix: &str) -> Vec<&Class> {
+        let package = package_prefix.replace('.', "/");
+        self.types
+            .iter()
+            .filter(|class| {
+                let name = class.name();
+                let in_package = package.is_empty()
+                    || (name.starts_with(&package) && name[package.len()..].starts_with('/'));
+                let simple_name = name.rsplit('/').next().unwrap_or(name);
+                in_package
+                    && simple_name != "R;"
+                    && !simple_name.starts_with("R$")
+                    && simple_name != "BuildConfig;"
+            })
+            .collect()
+    }
 
-impl From<DexReader> for Dex {
-    fn from(reader: DexReader) -> Self {
-        let types = reader
-            .classes
+    /// Gets the class types referenced by the dex file that are not defined in it.
+    ///
+    /// These are the framework and library classes the dex file depends on. Primitive and array
+    /// types are not included.
+    pub fn external_types(&self) -> Vec<&Type> {
+        let defined: HashSet<_> = self.types.iter().map(Class::name).collect();
+        self.type_ids
             .iter()
-            .map(|class| {
-                Class {
-                    name: match reader
-                        .types
-                        .get(class.class_index() as usize)
-                        .expect("class name not found")
-                    {
-                        Type::FullyQualifiedName(s) => s.clone(),
-                        _ => unreachable!("class name should be a fully qualified name"),
-                    },
-                    access_flags: class.access_flags(),
-                    superclass: if let Some(i) = class.superclass_index() {
-                        match reader.types.get(i as usize).expect("superclass not found") {
-                            Type::FullyQualifiedName(s) => Some(s.clone()),
-                            _ => unreachable!("superclass name should be a fully qualified name"),
-                        }
-                    } else {
-                        None
-                    },
-                    interfaces: class
-                        .interfaces()
-                        .iter()
-                        .map(|t| match t {
-                            Type::FullyQualifiedName(s) => s.clone(),
-                            _ => unreachable!("it should be a class name"),
-                        })
-                        .collect(),
-                    source_file: if let Some(i) = class.source_file_index() {
-                        Some(
-                            reader
-                                .strings
-                                .get(i as usize)
-                                .expect("source file not found")
-                                .clone(),
-                        )
-                    } else {
-                        None
-                    },
-                    // annotations: Option<AnnotationsDirectory>,
-                    // static_fields: Vec<Field>,
-                    // instance_fields: Vec<Field>,
-                    // direct_methods: Vec<Method>,
-                    // virtual_methods: Vec<Method>,
-                    // static_values: Option<Box<[Value]>>,
-                }
+            .filter(|type_id| match type_id {
+                Type::FullyQualifiedName(name) => !defined.contains(name.as_str()),
+                _ => false,
             })
-            .collect();
-        //eprintln!("{:#X?}", types);
-        // unimplemented!();
-        Self {
-            header: reader.header,
-            strings: reader.strings,
-            types,
+            .collect()
+    }
+
+    /// Finds the class with the given descriptor, such as `Lcom/example/Foo;`, if it is defined
+    /// in the dex file.
+    pub fn find_class(&self, descriptor: &str) -> Option<&Class> {
+        if !descriptor.starts_with('L') {
+            return None;
         }
+        let name = &descriptor[1..];
+        self.types.iter().find(|class| class.name() == name)
     }
-}
 
-/// Java class representation.
-#[derive(Debug, Clone)]
-pub struct Class {
-    name: String,
-    access_flags: AccessFlags,
-    superclass: Option<String>,
-    interfaces: Box<[String]>,
-    source_file: Option<String>,
-    // annotations: Option<AnnotationsDirectory>,
-    // static_fields: Vec<Field>,
-    // instance_fields: Vec<Field>,
-    // direct_methods: Vec<Method>,
-    // virtual_methods: Vec<Method>,
-    // static_values: Option<Box<[Value]>>,
-}
+    /// Gets the superclasses of the given class that are defined in the dex file, starting with
+    /// its direct superclass.
+    ///
+    /// The chain stops at the first superclass that is not defined in the dex file, such as
+    /// `java.lang.Object` or a framework class. Malformed dex files could have cycles in their
+    /// class hierarchy, so the chain stops before the first class that was already in it, and
+    /// `Dex::validate()` reports the classes in a cycle.
+    pub fn superclass_chain(&self, class: &Class) -> Vec<&Class> {
+        let mut chain: Vec<&Class> = Vec::new();
+        let mut visited = HashSet::new();
+        let _ = visited.insert(class.name());
+        let mut current = class;
+        while let Some(superclass) = current
+            .superclass()
+            .and_then(|superclass| self.find_class(&format!("L{}", superclass)))
+        {
+            if !visited.insert(superclass.name()) {
+                break;
+            }
+            chain.push(superclass);
+            current = superclass;
+        }
+        chain
+    }
 
-impl Class {
-    /// Gets the name of the class.
-    pub fn name(&self) -> &str {
-        self.name.as_str()
+    /// Gets the classes defined in the dex file that extend the class with the given descriptor,
+    /// such as `Landroid/app/Activity;`, directly or indirectly.
+    ///
+    /// Only classes defined in this dex file are returned, but the given class does not need to
+    /// be defined in it.
+    pub fn subclasses_of(&self, descriptor: &str) -> Vec<&Class> {
+        if !descriptor.starts_with('L') {
+            return Vec::new();
+        }
+        self.subclass_indexes(&descriptor[1..])
+            .into_iter()
+            .map(|index| &self.types[index])
+            .collect()
     }
 
-    /// Gets the access flags of the class.
-    pub fn access_flags(&self) -> AccessFlags {
-        self.access_flags
+    /// Gets the classes defined in the dex file that implement the interface with the given
+    /// descriptor, such as `Ljava/lang/Runnable;`.
+    ///
+    /// This includes the classes implementing the interface through an interface extending it,
+    /// and the subclasses of the implementing classes. Interfaces are not returned, and only
+    /// classes defined in this dex file are, but the given interface does not need to be defined
+    /// in it.
+    pub fn implementors_of(&self, interface: &str) -> Vec<&Class> {
+        if !interface.starts_with('L') {
+            return Vec::new();
+        }
+        let mut interfaces = vec![&interface[1..]];
+        let mut implementors = Vec::new();
+        let mut found = HashSet::new();
+        let mut i = 0;
+        while let Some(&interface) = interfaces.get(i) {
+            i += 1;
+            for &index in self
+                .hierarchy
+                .implementors
+                .get(interface)
+                .into_iter()
+                .flatten()
+            {
+                let class = &self.types[index];
+                if class.is_interface() {
+                    if !interfaces.contains(&class.name()) {
+                        interfaces.push(class.name());
+                    }
+                    continue;
+                }
+                for index in Some(index)
+                    .into_iter()
+                    .chain(self.subclass_indexes(class.name()))
+                {
+                    if found.insert(index) {
+                        implementors.push(&self.types[index]);
+                    }
+                }
+            }
+        }
+        implementors
     }
 
-    /// Gets the superclass of the class, if any.
-    pub fn superclass(&self) -> Option<&String> {
-        self.superclass.as_ref()
+    /// Gets the indexes of the classes extending the class with the given name, directly or
+    /// indirectly.
+    fn subclass_indexes(&self, name: &str) -> Vec<usize> {
+        let mut subclasses = Vec::new();
+        // Malformed dex files could have cycles in their class hierarchy, which would make a
+        // class its own subclass.
+        let mut found = HashSet::new();
+        let mut names = vec![name];
+        while let Some(current) = names.pop() {
+            for &index in self.hierarchy.subclasses.get(current).into_iter().flatten() {
+                if self.types[index].name() != name && found.insert(index) {
+                    subclasses.push(index);
+                    names.push(self.types[index].name());
+                }
+            }
+        }
+        subclasses
     }
 
-    /// Gets the list of interfaces implemented by the class.
-    pub fn interfaces(&self) -> &[String] {
-        &self.interfaces
+    /// Gets the classes in the Dalvik information structure, sorted by name.
+    ///
+    /// The order of the classes in the dex file is not alphabetical. The original order is still
+    /// available through `types()`.
+    pub fn classes_sorted(&self) -> Vec<&Class> {
+        let mut classes: Vec<_> = self.types.iter().collect();
+        classes.sort();
+        classes
     }
 
-    /// Gets the name of the source file where the class was implemented.
-    pub fn source_file(&self) -> Option<&String> {
-        self.source_file.as_ref()
+    /// Generates a textual summary of the dex file, for quick inspection.
+    ///
+    /// The report starts with the version, size, checksum and signature of the file and the
+    /// number of items of each ID list, followed by a line per class, in the order of the file,
+    /// with its access flags, its superclass and its number of interfaces, fields and methods.
+    /// The format is stable, so that reports can be compared between versions of a file.
+    pub fn report(&self) -> String {
+        use std::fmt::Write as _;
+
+        let header = &self.header;
+        let mut report = String::new();
+        let _ = writeln!(
+            report,
+            "dex version {:03}, {} endian, {} bytes",
+            header.get_dex_version(),
+            if header.is_little_endian() {
+                "little"
+            } else {
+                "big"
+            },
+            header.get_file_size()
+        );
+        let _ = write!(
+            report,
+            "checksum {:#010x}, signature ",
+            header.get_checksum()
+        );
+        for byte in header.get_signature() {
+            let _ = write!(report, "{:02x}", byte);
+        }
+        report.push('\n');
+        let _ = writeln!(report, "strings: {}", self.strings.len());
+        let _ = writeln!(report, "types: {}", self.type_ids.len());
+        let _ = writeln!(report, "prototypes: {}", self.prototypes.len());
+        let _ = writeln!(report, "fields: {}", self.field_ids.len());
+        let _ = writeln!(report, "methods: {}", self.method_ids.len());
+        let _ = writeln!(report, "classes: {}", self.types.len());
+
+        for class in &self.types {
+            let _ = write!(report, "L{} [{}]", class.name(), class.access_flags());
+            if let Some(superclass) = class.superclass() {
+                let _ = write!(report, " extends L{}", superclass);
+            }
+            let _ = writeln!(
+                report,
+                ", interfaces: {}, fields: {}, methods: {}",
+                class.interfaces().len(),
+                class.static_fields().len() + class.instance_fields().len(),
+                class.methods().count()
+            );
+        }
+        report
     }
-}
 
-/// Class field structure.
-#[derive(Debug, Clone)]
-pub struct Field {
-    access_flags: AccessFlags,
-    field_type: String,
-    name: String,
-}
+    /// Generates the Java source tree skeleton of the classes in the dex file.
+    ///
+    /// Returns, for each class, the path of its source file relative to the root of the source
+    /// tree and its source code, with all the method bodies stubbed. Static initializers and
+    /// fields are not included.
+    pub fn to_java_skeletons(&self) -> Vec<(String, String)> {
+        self.types.iter().map(java::skeleton).collect()
+    }
 
-/// Class method structure.
-#[derive(Debug, Clone)]
-pub struct Method {
-    access_flags: AccessFlags,
-    name: String,
-    return_type: String,
-    parameters: Box<[String]>,
-    // TODO: code
+    /// Extracts the class with the given descriptor, such as `Lcom/example/Foo;`, as a standalone
+    /// dex file.
+    ///
+    /// The new dex file keeps all the ID lists of this one, so that the bytecode of the class
+    /// does not need to be rewritten, but it only defines the requested class. Annotations,
+    /// static values and debug information are not included. This is useful to build reduced
+    /// test cases when a class triggers a bug.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the class is not defined in the dex file.
+    #[cfg(feature = "extract")]
+    pub fn extract_class(&self, descriptor: &str) -> Result<Vec<u8>> {
+        write::extract_class(self, descriptor)
+    }
+
+    /// Renames the classes, fields and methods according to the given ProGuard or R8 mapping.
+    ///
+    /// Only the names of the parsed `Class`, `Field` and `Method` structures, and the types they
+    /// use, are rewritten. The ID lists are left untouched, so resolved references and
+    /// disassembled code keep the obfuscated names.
+    pub fn apply_mapping(&mut self, mapping: &ProguardMapping) {
+        mapping.apply(&mut self.types);
+        self.hierarchy = ClassHierarchy::new(&self.types);
+    }
+
+    /// Gets all the code items in the dex file, along with their offsets.
+    ///
+    /// Most code items are the code of the methods of the classes in the dex file, but some of
+    /// them may not be referenced by any method. This can happen, for example, when a packer
+    /// inserts dead code in the dex file.
+    pub fn code_items(&self) -> impl Iterator<Item = (u32, &CodeItem)> {
+        self.code_items
+            .iter()
+            .map(|(offset, code_item)| (*offset, code_item))
+    }
+
+    /// Gets the code item at the given offset, if there is one.
+    pub fn code_item(&self, offset: u32) -> Option<&CodeItem> {
+        self.code_items
+            .binary_search_by_key(&offset, |(item_offset, _)| *item_offset)
+            .ok()
+            .map(|index| &self.code_items[index].1)
+    }
+
+    /// Gets all the methods of all the classes in the Dalvik information structure.
+    ///
+    /// To skip compiler-generated methods, filter them with `Method::is_synthetic_or_bridge()`.
+    pub fn methods(&self) -> impl Iterator<Item = &Method> {
+        self.types.iter().flat_map(Class::methods)
+    }
+
+    /// Gets the native methods of all the classes, along with their class.
+    ///
+    /// Native methods have no code in the dex file, since they are implemented in the native
+    /// libraries of the application. Their symbols can be found with `Method::jni_short_name()`
+    /// and `Method::jni_long_name()`.
+    pub fn native_methods(&self) -> Vec<(&Class, &Method)> {
+        self.types
+            .iter()
+            .flat_map(|class| class.methods().map(move |method| (class, method)))
+            .filter(|(_, method)| method.access_flags.contains(AccessFlags::ACC_NATIVE))
+            .collect()
+    }
+
+    /// Checks if any method invokes one of the reflection methods in `REFLECTION_METHODS`.
+    pub fn uses_reflection(&self) -> bool {
+        self.invocations_of(REFLECTION_METHODS).next().is_some()
+    }
+
+    /// Gets the invocations of the reflection methods in `REFLECTION_METHODS`.
+    ///
+    /// See `Dex::call_sites_of()` for the details of each call site.
+    pub fn reflection_call_sites(&self) -> Vec<(&Method, u32, String)> {
+        self.call_sites_of(REFLECTION_METHODS)
+    }
+
+    /// Gets the invocations of the given methods, identified by the descriptor of their class
+    /// and their name, as in `Ljava/lang/Class;.forName`.
+    ///
+    /// Each call site is given as the calling method, the program counter of the invoke
+    /// instruction and the signature of the invoked method, as in
+    /// `Ljava/lang/Class;.forName:(Ljava/lang/String;)Ljava/lang/Class;`. Only the method
+    /// references are checked, so methods inherited by subclasses of the given classes are not
+    /// found.
+    pub fn call_sites_of(&self, targets: &[&str]) -> Vec<(&Method, u32, String)> {
+        self.invocations_of(targets)
+            .map(|(method, pc, reference)| {
+                (
+                    method,
+                    pc,
+                    self.resolve_reference(Reference::Method(reference)),
+                )
+            })
+            .collect()
+    }
+
+    /// Gets the instructions reading or writing the field with the given descriptor, as in
+    /// `Landroid/os/Build$VERSION;.SDK_INT:I`.
+    ///
+    /// Each access is given as the class and the method accessing the field, the program counter
+    /// of the instruction and the kind of access, such as `ArrayOperation::Get` for an `iget` or
+    /// `sget` of an `int`. As with `Dex::call_sites_of()`, only the field references are checked,
+    /// so accesses to the field through a subclass are not found.
+    pub fn accessors_of(
+        &self,
+        field_descriptor: &str,
+    ) -> Vec<(&Class, &Method, u32, ArrayOperation)> {
+        #[allow(clippy::cast_possible_truncation)]
+        let references: HashSet<FieldReference> = (0..self.field_ids.len() as FieldReference)
+            .filter(|&index| self.resolve_reference(Reference::Field(index)) == field_descriptor)
+            .collect();
+        // Skip decoding the code if the field is never referenced.
+        if references.is_empty() {
+            return Vec::new();
+        }
+
+        let references = &references;
+        self.types
+            .iter()
+            .flat_map(|class| class.methods().map(move |method| (class, method)))
+            .flat_map(|(class, method)| {
+                method
+                    .instructions(self)
+                    .filter_field_ops()
+                    .filter_map(move |(pc, bytecode)| match bytecode {
+                        ByteCode::Instance(operation, _, _, reference)
+                        | ByteCode::Static(operation, _, reference)
+                            if references.contains(&reference) =>
+                        {
+                            Some((class, method, pc, operation))
+                        }
+                        _ => None,
+                    })
+            })
+            .collect()
+    }
+
+    /// Iterates over the invocations of the given methods, along with the calling method and
+    /// the program counter of the instruction.
+    fn invocations_of(
+        &self,
+        targets: &[&str],
+    ) -> impl Iterator<Item = (&Method, u32, MethodReference)> {
+        #[allow(clippy::cast_possible_truncation)]
+        let references: HashSet<MethodReference> = self
+            .method_ids
+            .iter()
+            .enumerate()
+            .filter(|(_, method_id)| {
+                let name = format!(
+                    "{}.{}",
+                    self.resolve_type_descriptor(method_id.class_index()),
+                    self.resolve_string(method_id.name_index())
+                );
+                targets.contains(&name.as_str())
+            })
+            .map(|(index, _)| index as MethodReference)
+            .collect();
+
+        // Skip decoding the code if the methods are never referenced.
+        let never_referenced = references.is_empty();
+        self.methods()
+            .filter(move |_| !never_referenced)
+            .flat_map(move |method| {
+                method
+                    .instructions(self)
+                    .filter_invokes()
+                    .map(move |(pc, bytecode)| (method, pc, bytecode))
+            })
+            .filter_map(move |(method, pc, bytecode)| match bytecode.reference() {
+                Some(Reference::Method(reference)) if references.contains(&reference) => {
+                    Some((method, pc, reference))
+                }
+                _ => None,
+            })
+    }
+
+    /// Checks the structural consistency of the dex file.
+    ///
+    /// This checks that field and method IDs only reference existing strings, types and
+    /// prototypes, that superclass and interface names are well-formed, that prototypes match
+    /// their short form descriptors, that code items are in the data section and that the map
+    /// has no duplicate, unsorted or overlapping entries. An empty list means that no issues were
+    /// found.
+    pub fn validate(&self) -> Vec<Issue> {
+        let mut issues = Vec::new();
+        self.check_map(&mut issues);
+        self.check_ids(&mut issues);
+        self.check_class_names(&mut issues);
+        self.check_inheritance(&mut issues);
+        self.check_prototypes(&mut issues);
+        self.check_code_items(&mut issues);
+        issues
+    }
+
+    /// Adds an issue to the list for each duplicate, unsorted or overlapping map entry.
+    fn check_map(&self, issues: &mut Vec<Issue>) {
+        // Packers sometimes add bogus map entries to confuse parsers.
+        let mut item_types = HashSet::with_capacity(self.map.len());
+        for item in &self.map {
+            if !item_types.insert(item.item_type()) {
+                issues.push(Issue::DuplicateMapItem {
+                    item_type: item.item_type(),
+                });
+            }
+        }
+        for pair in self.map.windows(2) {
+            if pair[1] < pair[0] {
+                issues.push(Issue::UnorderedMapItem {
+                    item_type: pair[1].item_type(),
+                    offset: pair[1].offset(),
+                });
+            }
+        }
+        // Only sections with fixed size items have a known end.
+        let mut sections: Vec<_> = self.map.iter().collect();
+        sections.sort_by_key(|item| item.offset());
+        for pair in sections.windows(2) {
+            if let Some(item_size) = fixed_item_size(pair[0].item_type()) {
+                let end = u64::from(pair[0].offset()) + u64::from(pair[0].size()) * item_size;
+                if u64::from(pair[1].offset()) < end {
+                    issues.push(Issue::OverlappingSections {
+                        first: pair[0].item_type(),
+                        second: pair[1].item_type(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Adds an issue to the list for each unknown index in the field and method IDs.
+    fn check_ids(&self, issues: &mut Vec<Issue>) {
+        for (index, field) in self.field_ids.iter().enumerate() {
+            self.check_type_index(issues, "field_id_item", index, field.class_index());
+            self.check_type_index(issues, "field_id_item", index, field.type_index());
+            self.check_string_index(issues, "field_id_item", index, field.name_index());
+        }
+        for (index, method) in self.method_ids.iter().enumerate() {
+            self.check_type_index(issues, "method_id_item", index, method.class_index());
+            if method.prototype_index() >= self.prototypes.len() {
+                issues.push(Issue::UnknownPrototypeIndex {
+                    method_index: index,
+                    prototype_index: method.prototype_index(),
+                });
+            }
+            self.check_string_index(issues, "method_id_item", index, method.name_index());
+        }
+    }
+
+    /// Adds an issue to the list for each malformed superclass or interface name.
+    fn check_class_names(&self, issues: &mut Vec<Issue>) {
+        for class in &self.types {
+            for name in class.superclass().into_iter().chain(class.interfaces()) {
+                if class_name(&format!("L{}", name)).is_err() {
+                    issues.push(Issue::InvalidClassName {
+                        class: class.name().to_owned(),
+                        name: name.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Adds an issue to the list for each class that is its own superclass, directly or not.
+    fn check_inheritance(&self, issues: &mut Vec<Issue>) {
+        // The runtime rejects classes that are their own superclass, but the parser keeps them.
+        let classes_by_name: HashMap<_, _> = self
+            .types
+            .iter()
+            .map(|class| (class.name(), class))
+            .collect();
+        for class in &self.types {
+            let mut visited = HashSet::new();
+            let mut current = class;
+            while let Some(superclass) = current
+                .superclass()
+                .and_then(|superclass| classes_by_name.get(superclass.as_str()))
+            {
+                if superclass.name() == class.name() {
+                    issues.push(Issue::CyclicInheritance {
+                        class: class.name().to_owned(),
+                    });
+                    break;
+                }
+                // The class could extend a cycle without being part of it.
+                if !visited.insert(superclass.name()) {
+                    break;
+                }
+                current = superclass;
+            }
+        }
+    }
+
+    /// Adds an issue to the list for each prototype that does not match its short form
+    /// descriptor.
+    fn check_prototypes(&self, issues: &mut Vec<Issue>) {
+        for (index, prototype) in self.prototypes.iter().enumerate() {
+            if !prototype.matches_shorty() {
+                issues.push(Issue::ShortyMismatch {
+                    prototype_index: index,
+                });
+            }
+        }
+    }
+
+    /// Adds an issue to the list for each code item outside the data section, or with handlers of
+    /// unknown types.
+    fn check_code_items(&self, issues: &mut Vec<Issue>) {
+        let data_start = u64::from(self.header.get_data_offset().unwrap_or(0));
+        let data_end = data_start + u64::from(self.header.get_data_size());
+        for (offset, code_item) in self.code_items() {
+            // The fixed part of a code item is 16 bytes long, followed by the instructions.
+            let end = u64::from(offset) + 16 + 2 * code_item.insns().len() as u64;
+            if u64::from(offset) < data_start || end > data_end {
+                issues.push(Issue::CodeItemOutsideData { offset });
+            }
+            // The handler is kept, so its type can still be resolved by index, to `None`.
+            for handler in code_item.handler_infos() {
+                if handler.type_index() as usize >= self.type_ids.len() {
+                    issues.push(Issue::UnknownHandlerType {
+                        offset,
+                        type_index: handler.type_index(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Adds an issue to the list if the given type index does not exist.
+    fn check_type_index(
+        &self,
+        issues: &mut Vec<Issue>,
+        item: &'static str,
+        index: usize,
+        type_index: usize,
+    ) {
+        if type_index >= self.type_ids.len() {
+            issues.push(Issue::UnknownTypeIndex {
+                item,
+                index,
+                type_index,
+            });
+        }
+    }
+
+    /// Adds an issue to the list if the given string index does not exist.
+    fn check_string_index(
+        &self,
+        issues: &mut Vec<Issue>,
+        item: &'static str,
+        index: usize,
+        string_index: usize,
+    ) {
+        if string_index >= self.strings.len() {
+            issues.push(Issue::UnknownStringIndex {
+                item,
+                index,
+                string_index,
+            });
+        }
+    }
+
+    /// Resolves the type and the element names and values of the given annotation.
+    ///
+    /// Values are rendered as they would appear in Java source code, so that the resolved
+    /// annotation can be printed as `@Foo(bar="baz")`.
+    pub fn resolve_annotation(&self, annotation: &EncodedAnnotation) -> ResolvedAnnotation {
+        let elements = annotation
+            .elements()
+            .iter()
+            .map(|element| {
+                (
+                    self.resolve_string(element.name_index() as usize),
+                    self.render_value(element),
+                )
+            })
+            .collect();
+
+        ResolvedAnnotation {
+            type_name: self.resolve_type_name(annotation.type_index() as usize),
+            elements,
+        }
+    }
+
+    /// Gets the string at the given index, or a placeholder if it does not exist.
+    fn resolve_string(&self, index: usize) -> String {
+        self.strings.get(index).map_or_else(
+            || format!("<unknown string {}>", index),
+            ToString::to_string,
+        )
+    }
+
+    /// Gets the name of the type at the given index, or a placeholder if it does not exist.
+    fn resolve_type_name(&self, index: usize) -> String {
+        self.type_ids
+            .get(index)
+            .map_or_else(|| format!("<unknown type {}>", index), Type::to_string)
+    }
+
+    /// Gets the `class.name` representation of a member, given its class and name indexes.
+    fn resolve_member(&self, class_index: usize, name_index: usize) -> String {
+        format!(
+            "{}.{}",
+            self.resolve_type_name(class_index),
+            self.resolve_string(name_index)
+        )
+    }
+
+    /// Gets the short form descriptor of the prototype at the given index, such as `VIL`.
+    ///
+    /// Shorties only tell apart primitive types from references, so they are a cheap way of
+    /// comparing signatures without resolving their types.
+    pub fn shorty_for_prototype(&self, index: u32) -> Option<&str> {
+        let prototype = self.prototypes.get(index as usize)?;
+        self.strings.get(prototype.shorty_index()? as usize)
+    }
+
+    /// Gets the method handles of the dex file, in the order of the `method_handles` section.
+    pub fn method_handles(&self) -> &[MethodHandleData] {
+        &self.method_handles
+    }
+
+    /// Gets the call site with the given index, as referenced by the `invoke-custom`
+    /// instructions, if it exists.
+    ///
+    /// Call sites are used to link lambdas and string concatenations in modern dex files.
+    pub fn call_site(&self, index: u32) -> Option<CallSite> {
+        self.call_sites.get(index as usize).cloned()
+    }
+
+    /// Gets the type a type reference points to, if it exists.
+    ///
+    /// Unlike `resolve_reference()`, this gives the parsed type, so that arrays and primitive
+    /// types can be told apart from classes.
+    pub fn resolve_type(&self, reference: TypeReference) -> Option<&Type> {
+        self.type_ids.get(reference as usize)
+    }
+
+    /// Resolves a field reference to its class, name and type, if it exists.
+    pub fn resolve_field(&self, reference: FieldReference) -> Option<ResolvedField> {
+        let field_id = self.field_ids.get(reference as usize)?;
+        Some(ResolvedField {
+            class: self.type_ids.get(field_id.class_index())?.clone().into(),
+            name: self.strings.get(field_id.name_index())?.to_string(),
+            field_type: self.type_ids.get(field_id.type_index())?.clone().into(),
+        })
+    }
+
+    /// Resolves a method reference to its class, name and prototype, if it exists.
+    pub fn resolve_method(&self, reference: MethodReference) -> Option<ResolvedMethod> {
+        let method_id = self.method_ids.get(reference as usize)?;
+        let prototype = self.prototypes.get(method_id.prototype_index())?;
+        Some(ResolvedMethod {
+            class: self.type_ids.get(method_id.class_index())?.clone().into(),
+            name: self.strings.get(method_id.name_index())?.to_string(),
+            parameters: prototype
+                .parameters()
+                .iter()
+                .cloned()
+                .map(ResolvedType::from)
+                .collect(),
+            return_type: prototype.return_type().clone().into(),
+        })
+    }
+
+    /// Checks that an `invoke-kind` or `invoke-kind/range` instruction passes as many argument
+    /// registers as the invoked method needs.
+    ///
+    /// Instance methods take `this` in their first register, and `long` and `double` arguments
+    /// take two registers each.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the instruction does not pass as many argument registers as the invoked
+    /// method needs, or if the invoked method does not exist.
+    pub fn verify_invoke_arity(&self, bytecode: &ByteCode) -> Result<()> {
+        #[allow(clippy::cast_possible_truncation)]
+        let (kind, found, method) = match bytecode {
+            ByteCode::Invoke(kind, registers, method) => (kind, registers.len() as u32, *method),
+            ByteCode::InvokeRange(kind, _, amount, method) => (kind, u32::from(*amount), *method),
+            _ => return Err(error::ArityError::NotAnInvoke.into()),
+        };
+        let prototype = self
+            .method_ids
+            .get(method as usize)
+            .and_then(|method_id| self.prototypes.get(method_id.prototype_index()))
+            .ok_or(error::ArityError::UnknownMethod { method })?;
+
+        let this = if let InvokeKind::Static = kind { 0 } else { 1 };
+        let expected = prototype
+            .parameters()
+            .iter()
+            .map(|parameter| u32::from(parameter.register_width()))
+            .sum::<u32>()
+            + this;
+        if expected == found {
+            Ok(())
+        } else {
+            Err(error::ArityError::Mismatch {
+                method: self.resolve_reference(Reference::Method(method)),
+                expected,
+                found,
+            }
+            .into())
+        }
+    }
+
+    /// Gets the textual representation of the item a reference points to.
+    ///
+    /// Types are represented by their descriptors, fields as `Lclass;.name:type` and methods
+    /// and prototypes using their signatures, such as `Lclass;.name:(II)V`, as `dexdump` does.
+    pub fn resolve_reference(&self, reference: Reference) -> String {
+        match reference {
+            Reference::String(i) => match self.strings.get(i as usize) {
+                Some(s) => format!("{:?}", s),
+                None => format!("<unknown string {}>", i),
+            },
+            Reference::Type(i) => self.resolve_type_descriptor(i as usize),
+            Reference::Field(i) => match self.field_ids.get(i as usize) {
+                Some(field) => format!(
+                    "{}.{}:{}",
+                    self.resolve_type_descriptor(field.class_index()),
+                    self.resolve_string(field.name_index()),
+                    self.resolve_type_descriptor(field.type_index())
+                ),
+                None => format!("<unknown field {}>", i),
+            },
+            Reference::Method(i) => match self.method_ids.get(i as usize) {
+                Some(method) => format!(
+                    "{}.{}:{}",
+                    self.resolve_type_descriptor(method.class_index()),
+                    self.resolve_string(method.name_index()),
+                    self.resolve_prototype(method.prototype_index())
+                ),
+                None => format!("<unknown method {}>", i),
+            },
+            Reference::Proto(i) => self.resolve_prototype(i as usize),
+            Reference::CallSite(i) => format!("call_site@{}", i),
+        }
+    }
+
+    /// Gets the signature of the prototype at the given index, or a placeholder if it does not
+    /// exist.
+    fn resolve_prototype(&self, index: usize) -> String {
+        match self.prototypes.get(index) {
+            Some(prototype) => format!(
+                "({}){}",
+                prototype
+                    .parameters()
+                    .iter()
+                    .map(Type::to_descriptor)
+                    .collect::<String>(),
+                prototype.return_type().to_descriptor()
+            ),
+            None => format!("<unknown prototype {}>", index),
+        }
+    }
+
+    /// Gets the class defining the item a reference points to, if it is defined in this dex file.
+    ///
+    /// For field and method references, this is the class declaring the member, and for type
+    /// references, the class itself. References to items defined elsewhere, such as framework
+    /// APIs, and references to other kinds of items return `None`.
+    pub fn defining_class(&self, reference: Reference) -> Option<&Class> {
+        let type_index = match reference {
+            Reference::Type(i) => i as usize,
+            Reference::Field(i) => self.field_ids.get(i as usize)?.class_index(),
+            Reference::Method(i) => self.method_ids.get(i as usize)?.class_index(),
+            Reference::String(_) | Reference::Proto(_) | Reference::CallSite(_) => return None,
+        };
+        match self.type_ids.get(type_index)? {
+            Type::FullyQualifiedName(name) => self.types.iter().find(|class| class.name() == name),
+            _ => None,
+        }
+    }
+
+    /// Gets the descriptor of the type at the given index, or a placeholder if it does not exist.
+    fn resolve_type_descriptor(&self, index: usize) -> String {
+        self.type_ids
+            .get(index)
+            .map_or_else(|| format!("<unknown type {}>", index), Type::to_descriptor)
+    }
+
+    /// Renders an encoded value as it would appear in Java source code.
+    #[allow(clippy::cast_possible_truncation)]
+    fn render_value(&self, value: &Value) -> String {
+        match value {
+            Value::Byte(v) => v.to_string(),
+            Value::Short(v) => v.to_string(),
+            Value::Char(v) => match std::char::from_u32(u32::from(*v)) {
+                Some(c) => format!("{:?}", c),
+                None => format!("'\\u{:04x}'", v),
+            },
+            Value::Int(v) => v.to_string(),
+            Value::Long(v) => format!("{}L", v),
+            Value::Float(v) => format!("{}f", v),
+            Value::Double(v) => v.to_string(),
+            Value::String(i) => match self.strings.get(*i as usize) {
+                Some(s) => format!("{:?}", s),
+                None => format!("<unknown string {}>", i),
+            },
+            Value::Type(i) => format!("{}.class", self.resolve_type_name(*i as usize)),
+            Value::Field(i) | Value::Enum(i) => match self.field_ids.get(*i as usize) {
+                Some(field) => self.resolve_member(field.class_index(), field.name_index()),
+                None => format!("<unknown field {}>", i),
+            },
+            Value::Method(i) => match self.method_ids.get(*i as usize) {
+                Some(method) => self.resolve_member(method.class_index(), method.name_index()),
+                None => format!("<unknown method {}>", i),
+            },
+            Value::MethodType(i) => self.resolve_prototype(*i as usize),
+            Value::MethodHandle(i) => match self.method_handles.get(*i as usize) {
+                Some(handle) => {
+                    // Method handles store 16-bit indexes.
+                    let index = handle.field_or_method_index() as u32;
+                    self.resolve_reference(if handle.handle_type().is_field_accessor() {
+                        Reference::Field(index)
+                    } else {
+                        Reference::Method(index)
+                    })
+                }
+                None => format!("<unknown method handle {}>", i),
+            },
+            Value::Array(array) => format!(
+                "{{{}}}",
+                array
+                    .iter()
+                    .map(|v| self.render_value(v))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Value::Annotation(annotation) => self.resolve_annotation(annotation).to_string(),
+            Value::Null => "null".to_owned(),
+            Value::Boolean(v) => v.to_string(),
+        }
+    }
+
+    // /// Ads the file in the given path to the current Dex data structure.
+    // pub fn add_file<P: AsRef<Path>>(path: P) -> Result<()> {
+    //     unimplemented!() // TODO
+    // }
+    //
+    // /// Verifies the file at the given path.
+    // pub fn verify_file<P: AsRef<Path>>(&self, path: P) -> bool {
+    //     self.header.verify_file(path) // TODO
+    // }
+    //
+    // /// Verifies the file in the given reader.
+    // ///
+    // /// The reader should be positioned at the start of the file.
+    // pub fn verify_reader<R: Read>(&self, reader: R) -> bool {
+    //     self.header.verify_reader(reader) // TODO
+    // }
+}
+
+impl ReferenceResolver for Dex {
+    fn string(&self, index: StringReference) -> String {
+        self.resolve_reference(Reference::String(index))
+    }
+
+    fn type_name(&self, index: TypeReference) -> String {
+        self.resolve_reference(Reference::Type(index))
+    }
+
+    fn field(&self, index: FieldReference) -> String {
+        self.resolve_reference(Reference::Field(index))
+    }
+
+    fn method(&self, index: MethodReference) -> String {
+        self.resolve_reference(Reference::Method(index))
+    }
+
+    fn proto(&self, index: PrototypeReference) -> String {
+        self.resolve_reference(Reference::Proto(index))
+    }
+}
+
+impl TryFrom<DexReader> for Dex {
+    type Error = error::Error;
+
+    fn try_from(reader: DexReader) -> Result<Self> {
+        let types = reader
+            .classes
+            .iter()
+            .enumerate()
+            .map(|(class_index, class)| Class::from_data(&reader, class_index, class))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            header: reader.header,
+            strings: reader.strings,
+            type_ids: reader.types,
+            prototypes: reader.prototypes,
+            field_ids: reader.field_ids,
+            method_ids: reader.method_ids,
+            method_handles: reader.method_handles,
+            call_sites: reader.call_sites,
+            hierarchy: ClassHierarchy::new(&types),
+            types,
+            code_items: reader.code_segments,
+            map: reader.map,
+            link_data: reader.link_data,
+            raw_bytes: if reader.options.retain_raw_bytes() {
+                Some(reader.file_cursor.into_inner())
+            } else {
+                None
+            },
+            layout: reader.layout,
+        })
+    }
+}
+
+/// Reverse index of the class hierarchy of the classes in a dex file.
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
+struct ClassHierarchy {
+    /// Indexes of the classes directly extending each class, by class name.
+    subclasses: HashMap<String, Vec<usize>>,
+    /// Indexes of the classes and interfaces directly implementing or extending each interface,
+    /// by interface name.
+    implementors: HashMap<String, Vec<usize>>,
+}
+
+impl ClassHierarchy {
+    /// Builds the index for the given classes.
+    fn new(classes: &[Class]) -> Self {
+        let mut hierarchy = Self::default();
+        for (index, class) in classes.iter().enumerate() {
+            if let Some(superclass) = class.superclass() {
+                hierarchy
+                    .subclasses
+                    .entry(superclass.clone())
+                    .or_insert_with(Vec::new)
+                    .push(index);
+            }
+            for interface in class.interfaces() {
+                hierarchy
+                    .implementors
+                    .entry(interface.clone())
+                    .or_insert_with(Vec::new)
+                    .push(index);
+            }
+        }
+        hierarchy
+    }
+}
+
+/// Java class representation.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
+pub struct Class {
+    name: Arc<str>,
+    access_flags: AccessFlags,
+    superclass: Option<String>,
+    interfaces: Box<[String]>,
+    source_file: Option<String>,
+    annotations: Box<[Annotation]>,
+    static_fields: Box<[Field]>,
+    instance_fields: Box<[Field]>,
+    direct_methods: Box<[Method]>,
+    virtual_methods: Box<[Method]>,
+    // static_values: Option<Box<[Value]>>,
+    parse_error: Option<String>,
+}
+
+impl Class {
+    /// Creates a builder for a class with the given descriptor, such as `Lcom/example/Foo;`.
+    pub fn builder<S>(descriptor: S) -> ClassBuilder
+    where
+        S: Into<String>,
+    {
+        ClassBuilder::new(descriptor)
+    }
+
+    /// Creates a class from its definition, resolving the names of the class and its members.
+    fn from_data(reader: &DexReader, class_index: usize, class: &types::Class) -> Result<Self> {
+        // Hidden API flags of the members of the class, in class data order.
+        let hidden_api_flags = reader
+            .hidden_api_flags
+            .get(class_index)
+            .and_then(Option::as_deref);
+        let hidden_api_flag = |index: usize| {
+            hidden_api_flags
+                .and_then(|flags| flags.get(index))
+                .copied()
+                .map(HiddenApiFlag::from_bits)
+        };
+        let (static_fields, instance_fields, direct_methods, virtual_methods) =
+            if let Some(data) = class.class_data() {
+                let static_count = data.static_fields().len();
+                let instance_count = data.instance_fields().len();
+                let direct_count = data.direct_methods().len();
+                let fields = |fields: &[types::read::Field], first: usize| {
+                    fields
+                        .iter()
+                        .enumerate()
+                        .map(|(i, field)| {
+                            Field::from_data(reader, class, *field, hidden_api_flag(first + i))
+                        })
+                        .collect::<Result<_>>()
+                };
+                let methods = |methods: &[types::read::Method], first: usize| {
+                    methods
+                        .iter()
+                        .enumerate()
+                        .map(|(i, method)| {
+                            Method::from_data(reader, class, method, hidden_api_flag(first + i))
+                        })
+                        .collect::<Result<_>>()
+                };
+                (
+                    fields(data.static_fields(), 0)?,
+                    fields(data.instance_fields(), static_count)?,
+                    methods(data.direct_methods(), static_count + instance_count)?,
+                    methods(
+                        data.virtual_methods(),
+                        static_count + instance_count + direct_count,
+                    )?,
+                )
+            } else {
+                Default::default()
+            };
+
+        Ok(Self {
+            name: Arc::from(reader.class_name(class.class_index())?),
+            access_flags: class.access_flags(),
+            superclass: class
+                .superclass_index()
+                .map(|index| reader.class_name(index).map(str::to_owned))
+                .transpose()?,
+            interfaces: class
+                .interfaces()
+                .iter()
+                .map(|interface| match interface {
+                    Type::FullyQualifiedName(name) => Ok(name.clone()),
+                    other => Err(error::Parse::NotAClass(other.to_descriptor()).into()),
+                })
+                .collect::<Result<_>>()?,
+            source_file: class
+                .source_file_index()
+                .map(|index| reader.string_at(index as usize).map(str::to_owned))
+                .transpose()?,
+            annotations: class
+                .annotations()
+                .map_or_else(Box::default, |annotations| {
+                    annotations.class_annotations().to_vec().into_boxed_slice()
+                }),
+            static_fields,
+            instance_fields,
+            direct_methods,
+            virtual_methods,
+            parse_error: reader.class_errors.get(class_index).cloned().flatten(),
+        })
+    }
+
+    /// Gets the name of the class.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Gets the access flags of the class.
+    pub fn access_flags(&self) -> AccessFlags {
+        self.access_flags
+    }
+
+    /// Gets the raw access flags of the class, as found in the dex file.
+    pub fn raw_access_flags(&self) -> u32 {
+        self.access_flags.bits()
+    }
+
+    /// Checks if the class is an interface, including annotation types.
+    pub fn is_interface(&self) -> bool {
+        self.access_flags.contains(AccessFlags::ACC_INTERFACE)
+    }
+
+    /// Checks if the class is an enum.
+    pub fn is_enum(&self) -> bool {
+        self.access_flags.contains(AccessFlags::ACC_ENUM)
+    }
+
+    /// Checks if the class is an annotation type.
+    pub fn is_annotation(&self) -> bool {
+        self.access_flags.contains(AccessFlags::ACC_ANNOTATION)
+    }
+
+    /// Checks if the class is abstract. Interfaces are always abstract.
+    pub fn is_abstract(&self) -> bool {
+        self.access_flags.contains(AccessFlags::ACC_ABSTRACT)
+    }
+
+    /// Gets the annotations of the class.
+    pub fn annotations(&self) -> &[Annotation] {
+        &self.annotations
+    }
+
+    /// Gets the superclass of the class, if any.
+    pub fn superclass(&self) -> Option<&String> {
+        self.superclass.as_ref()
+    }
+
+    /// Gets the list of interfaces implemented by the class.
+    pub fn interfaces(&self) -> &[String] {
+        &self.interfaces
+    }
+
+    /// Gets the name of the source file where the class was implemented.
+    pub fn source_file(&self) -> Option<&String> {
+        self.source_file.as_ref()
+    }
+
+    /// Gets the static fields of the class.
+    pub fn static_fields(&self) -> &[Field] {
+        &self.static_fields
+    }
+
+    /// Gets the instance fields of the class.
+    pub fn instance_fields(&self) -> &[Field] {
+        &self.instance_fields
+    }
+
+    /// Gets the direct methods of the class.
+    ///
+    /// Direct methods are static, private or constructor methods.
+    pub fn direct_methods(&self) -> &[Method] {
+        &self.direct_methods
+    }
+
+    /// Gets the virtual methods of the class.
+    pub fn virtual_methods(&self) -> &[Method] {
+        &self.virtual_methods
+    }
+
+    /// Gets all the methods of the class, direct methods first.
+    pub fn methods(&self) -> impl Iterator<Item = &Method> {
+        self.direct_methods
+            .iter()
+            .chain(self.virtual_methods.iter())
+    }
+
+    /// Gets the methods of the class that were declared in the source code.
+    ///
+    /// Synthetic and bridge methods, generated by the compiler for accessors, covariant returns
+    /// or generics, are skipped. This is usually what should be shown in a human-readable API
+    /// listing, while `methods()` should be used for analysis, since the generated methods are
+    /// still executed.
+    pub fn declared_methods(&self) -> impl Iterator<Item = &Method> {
+        self.methods()
+            .filter(|method| !method.is_synthetic_or_bridge())
+    }
+
+    /// Gets the string literals loaded by the code of the class, with `const-string` and
+    /// `const-string/jumbo` instructions.
+    ///
+    /// Each literal is listed once, in the order of its first use. String constants of static
+    /// fields and annotations are not included, since they are not loaded by code.
+    pub fn string_literals<'a>(&self, dex: &'a Dex) -> Vec<&'a str> {
+        let mut seen = HashSet::new();
+        self.methods()
+            .flat_map(|method| method.instructions(dex))
+            .filter_map(|(_, bytecode)| match bytecode {
+                ByteCode::ConstString(_, index) | ByteCode::ConstStringJumbo(_, index) => {
+                    dex.strings.get(index as usize)
+                }
+                _ => None,
+            })
+            .filter(|string| seen.insert(*string))
+            .collect()
+    }
+
+    /// Gets the error found while reading the data of the class, if any.
+    ///
+    /// If the class data could not be read, the class has no fields nor methods. If the code of
+    /// some of its methods could not be read, those methods have no code. The rest of the dex file
+    /// is still read, which allows analyzing partially corrupted files.
+    pub fn parse_error(&self) -> Option<&str> {
+        self.parse_error.as_deref()
+    }
+}
+
+/// Classes are compared by name, since class names are unique in a dex file.
+impl PartialEq for Class {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for Class {}
+
+impl PartialOrd for Class {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Class {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.name.cmp(&other.name)
+    }
+}
+
+/// Builder to assemble a `Class` without parsing a dex file.
+///
+/// Class names are given as type descriptors, such as `Lcom/example/Foo;`, and they are
+/// validated when building the class.
+#[derive(Debug, Clone)]
+pub struct ClassBuilder {
+    name: String,
+    access_flags: AccessFlags,
+    superclass: Option<String>,
+    interfaces: Vec<String>,
+    source_file: Option<String>,
+    static_fields: Vec<Field>,
+    instance_fields: Vec<Field>,
+    direct_methods: Vec<Method>,
+    virtual_methods: Vec<Method>,
+}
+
+impl ClassBuilder {
+    /// Creates a builder for a class with the given descriptor, such as `Lcom/example/Foo;`.
+    pub fn new<S>(descriptor: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            name: descriptor.into(),
+            access_flags: AccessFlags::empty(),
+            superclass: None,
+            interfaces: Vec::new(),
+            source_file: None,
+            static_fields: Vec::new(),
+            instance_fields: Vec::new(),
+            direct_methods: Vec::new(),
+            virtual_methods: Vec::new(),
+        }
+    }
+
+    /// Sets the access flags of the class.
+    #[must_use]
+    pub fn access_flags(mut self, access_flags: AccessFlags) -> Self {
+        self.access_flags = access_flags;
+        self
+    }
+
+    /// Sets the descriptor of the superclass of the class.
+    #[must_use]
+    pub fn superclass<S>(mut self, descriptor: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.superclass = Some(descriptor.into());
+        self
+    }
+
+    /// Adds the descriptor of an interface implemented by the class.
+    #[must_use]
+    pub fn interface<S>(mut self, descriptor: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.interfaces.push(descriptor.into());
+        self
+    }
+
+    /// Sets the name of the source file where the class was implemented.
+    #[must_use]
+    pub fn source_file<S>(mut self, source_file: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.source_file = Some(source_file.into());
+        self
+    }
+
+    /// Adds a field to the class.
+    ///
+    /// The field will be a static field if it has the `ACC_STATIC` flag, and an instance field
+    /// otherwise.
+    #[must_use]
+    pub fn field(mut self, field: Field) -> Self {
+        if field.access_flags.contains(AccessFlags::ACC_STATIC) {
+            self.static_fields.push(field);
+        } else {
+            self.instance_fields.push(field);
+        }
+        self
+    }
+
+    /// Adds a method to the class.
+    ///
+    /// The method will be a direct method if it has the `ACC_STATIC`, `ACC_PRIVATE` or
+    /// `ACC_CONSTRUCTOR` flags, and a virtual method otherwise.
+    #[must_use]
+    pub fn method(mut self, method: Method) -> Self {
+        if method.access_flags.intersects(
+            AccessFlags::ACC_STATIC | AccessFlags::ACC_PRIVATE | AccessFlags::ACC_CONSTRUCTOR,
+        ) {
+            self.direct_methods.push(method);
+        } else {
+            self.virtual_methods.push(method);
+        }
+        self
+    }
+
+    /// Builds the class, validating the class names.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the name of the class, its superclass or one of its interfaces is not a
+    /// valid class name.
+    pub fn build(self) -> Result<Class> {
+        let name = class_name(&self.name)?;
+        let superclass = self
+            .superclass
+            .as_ref()
+            .map(|descriptor| class_name(descriptor))
+            .transpose()?;
+        let interfaces = self
+            .interfaces
+            .iter()
+            .map(|descriptor| class_name(descriptor))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Class {
+            name: Arc::from(name),
+            access_flags: self.access_flags,
+            superclass,
+            interfaces,
+            source_file: self.source_file,
+            static_fields: self.static_fields.into_boxed_slice(),
+            instance_fields: self.instance_fields.into_boxed_slice(),
+            direct_methods: self.direct_methods.into_boxed_slice(),
+            virtual_methods: self.virtual_methods.into_boxed_slice(),
+            annotations: Box::default(),
+            parse_error: None,
+        })
+    }
+}
+
+/// Gets the size in bytes of the items of a section, if all of them have the same size.
+fn fixed_item_size(item_type: ItemType) -> Option<u64> {
+    let size = match item_type {
+        ItemType::Header => HEADER_SIZE,
+        ItemType::StringId => STRING_ID_ITEM_SIZE,
+        ItemType::TypeId => TYPE_ID_ITEM_SIZE,
+        ItemType::ProtoId => PROTO_ID_ITEM_SIZE,
+        ItemType::FieldId => FIELD_ID_ITEM_SIZE,
+        ItemType::MethodId => METHOD_ID_ITEM_SIZE,
+        ItemType::ClassDef => CLASS_DEF_ITEM_SIZE,
+        ItemType::CallSiteId => CALL_SITE_ID_ITEM_SIZE,
+        ItemType::MethodHandle => METHOD_HANDLE_ITEM_SIZE,
+        _ => return None,
+    };
+    Some(u64::from(size))
+}
+
+/// Computes the Shannon entropy of the given bytes, in bits per byte.
+#[allow(clippy::cast_precision_loss)]
+fn shannon_entropy(bytes: &[u8]) -> f64 {
+    let mut counts = [0_u64; 256];
+    for &byte in bytes {
+        counts[usize::from(byte)] += 1;
+    }
+    // Precision loss only happens with files far larger than the format allows.
+    let total = bytes.len() as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let probability = count as f64 / total;
+            -probability * probability.log2()
+        })
+        .sum()
+}
+
+/// Opens the dex file at the given path, checking that its size is plausible.
+fn open_file<P>(path: P) -> Result<(BufReader<fs::File>, usize)>
+where
+    P: AsRef<Path>,
+{
+    let file = fs::File::open(path)?;
+    let file_size = file.metadata()?.len();
+    if file_size < u64::from(HEADER_SIZE) || file_size > u64::from(u32::max_value()) {
+        return Err(error::InvalidFileSize { file_size }.into());
+    }
+    let size = usize::try_from(file_size).map_err(|_| error::InvalidFileSize { file_size })?;
+    Ok((BufReader::new(file), size))
+}
+
+/// Gets the class name, as stored in a `Class`, from a class descriptor.
+fn class_name(descriptor: &str) -> Result<String, error::Parse> {
+    match descriptor.parse::<Type>() {
+        Ok(Type::FullyQualifiedName(ref name))
+            if name.len() > 1 && name.ends_with(';') && !name.contains(&['.', '[', ' '][..]) =>
+        {
+            Ok(name.clone())
+        }
+        _ => Err(error::Parse::InvalidTypeDescriptor(descriptor.to_owned())),
+    }
+}
+
+/// Mangles a class name, method name or type descriptor for a JNI symbol name.
+///
+/// Package separators become `_`, and the characters that cannot appear in a C identifier are
+/// escaped, as in the JNI specification.
+fn jni_mangle(name: &str) -> String {
+    use std::fmt::Write as _;
+
+    let mut mangled = String::with_capacity(name.len());
+    for c in name.chars() {
+        match c {
+            '/' => mangled.push('_'),
+            '_' => mangled.push_str("_1"),
+            ';' => mangled.push_str("_2"),
+            '[' => mangled.push_str("_3"),
+            c if c.is_ascii_alphanumeric() => mangled.push(c),
+            c => {
+                let mut units = [0; 2];
+                for unit in c.encode_utf16(&mut units).iter() {
+                    let _ = write!(mangled, "_0{:04x}", unit);
+                }
+            }
+        }
+    }
+    mangled
+}
+
+/// Structural issue found when validating a dex file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Issue {
+    /// An item references a type that does not exist.
+    UnknownTypeIndex {
+        /// Kind of the item, as named in the dex format specification.
+        item: &'static str,
+        /// Index of the item in its section.
+        index: usize,
+        /// Index of the missing type.
+        type_index: usize,
+    },
+    /// An item references a string that does not exist.
+    UnknownStringIndex {
+        /// Kind of the item, as named in the dex format specification.
+        item: &'static str,
+        /// Index of the item in its section.
+        index: usize,
+        /// Index of the missing string.
+        string_index: usize,
+    },
+    /// A method ID references a prototype that does not exist.
+    UnknownPrototypeIndex {
+        /// Index of the method ID.
+        method_index: usize,
+        /// Index of the missing prototype.
+        prototype_index: usize,
+    },
+    /// A superclass or interface name is not a well-formed class name.
+    InvalidClassName {
+        /// Name of the class referencing the invalid name.
+        class: String,
+        /// Invalid name.
+        name: String,
+    },
+    /// A class is its own direct or indirect superclass.
+    CyclicInheritance {
+        /// Name of the class.
+        class: String,
+    },
+    /// A prototype does not match its short form descriptor.
+    ShortyMismatch {
+        /// Index of the prototype.
+        prototype_index: usize,
+    },
+    /// A code item is not fully contained in the data section.
+    CodeItemOutsideData {
+        /// Offset of the code item.
+        offset: u32,
+    },
+    /// A catch handler of a code item catches an exception type that does not exist.
+    UnknownHandlerType {
+        /// Offset of the code item.
+        offset: u32,
+        /// Index of the missing type.
+        type_index: u32,
+    },
+    /// The map contains more than one entry for the same item type.
+    DuplicateMapItem {
+        /// Duplicated item type.
+        item_type: ItemType,
+    },
+    /// A map entry has a lower offset than the previous one.
+    UnorderedMapItem {
+        /// Item type of the out of order entry.
+        item_type: ItemType,
+        /// Offset of the out of order entry.
+        offset: u32,
+    },
+    /// A section starts before the end of the section preceding it.
+    OverlappingSections {
+        /// Item type of the first section.
+        first: ItemType,
+        /// Item type of the section starting inside the first one.
+        second: ItemType,
+    },
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownTypeIndex {
+                item,
+                index,
+                type_index,
+            } => write!(
+                f,
+                "`{}` with index {} references unknown type {}",
+                item, index, type_index
+            ),
+            Self::UnknownStringIndex {
+                item,
+                index,
+                string_index,
+            } => write!(
+                f,
+                "`{}` with index {} references unknown string {}",
+                item, index, string_index
+            ),
+            Self::UnknownPrototypeIndex {
+                method_index,
+                prototype_index,
+            } => write!(
+                f,
+                "`method_id_item` with index {} references unknown prototype {}",
+                method_index, prototype_index
+            ),
+            Self::InvalidClassName { class, name } => write!(
+                f,
+                "class `{}` references the invalid class name `{}`",
+                class, name
+            ),
+            Self::CyclicInheritance { class } => {
+                write!(f, "class `{}` is its own superclass", class)
+            }
+            Self::ShortyMismatch { prototype_index } => write!(
+                f,
+                "prototype {} does not match its short form descriptor",
+                prototype_index
+            ),
+            Self::CodeItemOutsideData { offset } => write!(
+                f,
+                "the code item at offset {:#010x} is outside of the data section",
+                offset
+            ),
+            Self::UnknownHandlerType { offset, type_index } => write!(
+                f,
+                "a catch handler of the code item at offset {:#010x} references unknown type {}",
+                offset, type_index
+            ),
+            Self::DuplicateMapItem { item_type } => {
+                write!(f, "the map has more than one `{:?}` entry", item_type)
+            }
+            Self::UnorderedMapItem { item_type, offset } => write!(
+                f,
+                "the `{:?}` map entry at offset {:#010x} is not sorted by offset",
+                item_type, offset
+            ),
+            Self::OverlappingSections { first, second } => write!(
+                f,
+                "the `{:?}` section starts inside the `{:?}` section",
+                second, first
+            ),
+        }
+    }
+}
+
+/// Recoverable anomaly found when parsing a dex file with `Dex::from_file_lenient()` or
+/// `Dex::from_reader_lenient()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// The declared size of a string does not match its contents.
+    StringSizeMismatch {
+        /// Index of the string.
+        index: usize,
+        /// Declared string size, in UTF-16 code units.
+        expected_size: u32,
+        /// Actual string size, in UTF-16 code units.
+        actual_size: usize,
+    },
+    /// The number of strings declared in the header does not match the number of strings in the
+    /// string ID section of the map, so only the strings in both were read.
+    StringCountMismatch {
+        /// Number of strings declared in the header.
+        declared: usize,
+        /// Number of strings in the string ID section of the map.
+        actual: usize,
+    },
+    /// The map has a section of an item type not defined in the specification, which was
+    /// skipped.
+    UnknownItemType {
+        /// Value of the item type.
+        item_type: u16,
+        /// Offset of the section.
+        offset: u32,
+    },
+    /// Some bytes do not belong to any section of the file, such as a payload appended after its
+    /// end.
+    UnknownData {
+        /// Offset of the first unknown byte.
+        offset: u64,
+        /// Number of unknown bytes.
+        size: u64,
+    },
+    /// The file has a link section, which is unusual in dex files that are not statically linked.
+    LinkData {
+        /// Offset of the link section.
+        offset: u64,
+        /// Size of the link section, in bytes.
+        size: u64,
+    },
+    /// The data or the code of a class could not be read, so some of its members are missing.
+    UnreadableClass {
+        /// Name of the class.
+        class: String,
+        /// Error found when reading the class.
+        error: String,
+    },
+    /// A structural issue, as found by `Dex::validate()`.
+    Issue(Issue),
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::StringSizeMismatch {
+                index,
+                expected_size,
+                actual_size,
+            } => write!(
+                f,
+                "string {} has {} UTF-16 code units, but its declared size is {}",
+                index, actual_size, expected_size
+            ),
+            Self::StringCountMismatch { declared, actual } => write!(
+                f,
+                "the header declares {} strings, but the map declares {}",
+                declared, actual
+            ),
+            Self::UnknownItemType { item_type, offset } => write!(
+                f,
+                "the section at offset {:#010x} has an unknown item type, {:#06x}, so it was \
+                 skipped",
+                offset, item_type
+            ),
+            Self::UnknownData { offset, size } => write!(
+                f,
+                "{} bytes at offset {:#010x} do not belong to any section",
+                size, offset
+            ),
+            Self::LinkData { offset, size } => write!(
+                f,
+                "the file has a link section of {} bytes at offset {:#010x}",
+                size, offset
+            ),
+            Self::UnreadableClass { class, error } => {
+                write!(f, "class `{}` could not be fully read: {}", class, error)
+            }
+            Self::Issue(issue) => issue.fmt(f),
+        }
+    }
+}
+
+/// Annotation with its type and element names and values resolved.
+#[derive(Debug, Clone)]
+pub struct ResolvedAnnotation {
+    type_name: String,
+    elements: BTreeMap<String, String>,
+}
+
+impl ResolvedAnnotation {
+    /// Gets the name of the type of the annotation.
+    pub fn type_name(&self) -> &str {
+        self.type_name.as_str()
+    }
+
+    /// Gets the elements of the annotation, as a map from element names to rendered values.
+    pub fn elements(&self) -> &BTreeMap<String, String> {
+        &self.elements
+    }
+}
+
+impl fmt::Display for ResolvedAnnotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "@{}", self.type_name)?;
+        if !self.elements.is_empty() {
+            let elements = self
+                .elements
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect::<Vec<_>>();
+            write!(f, "({})", elements.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Type of a resolved reference.
+///
+/// It is displayed as in Java source code, with fully qualified class names, such as
+/// `com.example.Foo` or `int[][]`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ResolvedType {
+    resolved_type: Type,
+}
+
+impl ResolvedType {
+    /// Gets the resolved type.
+    pub fn get(&self) -> &Type {
+        &self.resolved_type
+    }
+}
+
+impl From<Type> for ResolvedType {
+    fn from(resolved_type: Type) -> Self {
+        Self { resolved_type }
+    }
+}
+
+impl fmt::Display for ResolvedType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.resolved_type {
+            Type::FullyQualifiedName(name) => {
+                write!(f, "{}", name.trim_end_matches(';').replace('/', "."))
+            }
+            Type::Array {
+                dimensions,
+                array_type,
+            } => {
+                write!(f, "{}", Self::from((**array_type).clone()))?;
+                for _ in 0..*dimensions {
+                    f.write_str("[]")?;
+                }
+                Ok(())
+            }
+            primitive => write!(f, "{}", primitive),
+        }
+    }
+}
+
+/// Field with its class, name and type resolved from a field reference.
+///
+/// It is displayed as `com.example.Foo.count:int`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ResolvedField {
+    class: ResolvedType,
+    name: String,
+    field_type: ResolvedType,
+}
+
+impl ResolvedField {
+    /// Gets the class defining the field.
+    pub fn class(&self) -> &ResolvedType {
+        &self.class
+    }
+
+    /// Gets the name of the field.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Gets the type of the field.
+    pub fn field_type(&self) -> &ResolvedType {
+        &self.field_type
+    }
+}
+
+impl fmt::Display for ResolvedField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}:{}", self.class, self.name, self.field_type)
+    }
+}
+
+/// Method with its class, name and prototype resolved from a method reference.
+///
+/// It is displayed as `com.example.Foo.update(int,java.lang.String):void`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ResolvedMethod {
+    class: ResolvedType,
+    name: String,
+    parameters: Vec<ResolvedType>,
+    return_type: ResolvedType,
+}
+
+impl ResolvedMethod {
+    /// Gets the class defining the method.
+    pub fn class(&self) -> &ResolvedType {
+        &self.class
+    }
+
+    /// Gets the name of the method.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Gets the types of the parameters of the method.
+    pub fn parameters(&self) -> &[ResolvedType] {
+        &self.parameters
+    }
+
+    /// Gets the return type of the method.
+    pub fn return_type(&self) -> &ResolvedType {
+        &self.return_type
+    }
+}
+
+impl fmt::Display for ResolvedMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}(", self.class, self.name)?;
+        for (i, parameter) in self.parameters.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}", parameter)?;
+        }
+        write!(f, "):{}", self.return_type)
+    }
+}
+
+/// Class field structure.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
+pub struct Field {
+    access_flags: AccessFlags,
+    field_type: String,
+    name: Arc<str>,
+    annotations: Box<[Annotation]>,
+    hidden_api_flag: Option<HiddenApiFlag>,
+}
+
+impl Field {
+    /// Creates a new field.
+    pub fn new<T, N>(access_flags: AccessFlags, field_type: T, name: N) -> Self
+    where
+        T: Into<String>,
+        N: Into<String>,
+    {
+        Self {
+            access_flags,
+            field_type: field_type.into(),
+            name: Arc::from(name.into()),
+            annotations: Box::default(),
+            hidden_api_flag: None,
+        }
+    }
+
+    /// Creates a field from its encoded data, resolving its type and name.
+    fn from_data(
+        reader: &DexReader,
+        class: &types::Class,
+        field: types::read::Field,
+        hidden_api_flag: Option<HiddenApiFlag>,
+    ) -> Result<Self> {
+        let field_id = reader
+            .field_ids
+            .get(field.field_index() as usize)
+            .ok_or_else(|| error::Parse::UnknownFieldIndex(field.field_index()))?;
+        Ok(Self {
+            access_flags: field.access_flags(),
+            field_type: reader.type_at(field_id.type_index())?.to_string(),
+            name: reader.name_at(field_id.name_index())?,
+            annotations: class
+                .annotations()
+                .and_then(|annotations| {
+                    annotations
+                        .field_annotations()
+                        .iter()
+                        .find(|annotated| annotated.field_index() == field.field_index())
+                })
+                .map_or_else(Box::default, |annotated| {
+                    annotated.annotations().to_vec().into_boxed_slice()
+                }),
+            hidden_api_flag,
+        })
+    }
+
+    /// Gets the access flags of the field.
+    pub fn access_flags(&self) -> AccessFlags {
+        self.access_flags
+    }
+
+    /// Gets the raw access flags of the field, as found in the dex file.
+    ///
+    /// In fields, the `0x40` bit means `volatile` and the `0x80` bit means `transient`, even if
+    /// the typed flags also report them as `ACC_BRIDGE` and `ACC_VARARGS`.
+    pub fn raw_access_flags(&self) -> u32 {
+        self.access_flags.bits()
+    }
+
+    /// Gets the type of the field.
+    pub fn field_type(&self) -> &str {
+        self.field_type.as_str()
+    }
+
+    /// Gets the name of the field.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Gets the hidden API flags of the field.
+    ///
+    /// They are only available in dex files with a `hiddenapi_class_data` section, which are
+    /// usually part of the Android framework, so this is `None` for most application dex files.
+    pub fn hidden_api_flag(&self) -> Option<HiddenApiFlag> {
+        self.hidden_api_flag
+    }
+
+    /// Gets the annotations of the field.
+    pub fn annotations(&self) -> &[Annotation] {
+        &self.annotations
+    }
+
+    /// Checks if the field was generated by the compiler instead of declared in the source code.
+    pub fn is_synthetic(&self) -> bool {
+        self.access_flags.contains(AccessFlags::ACC_SYNTHETIC)
+    }
+}
+
+/// Class method structure.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
+pub struct Method {
+    access_flags: AccessFlags,
+    name: Arc<str>,
+    return_type: Type,
+    parameters: Box<[Type]>,
+    annotations: Box<[Annotation]>,
+    parameter_annotations: Box<[Box<[Annotation]>]>,
+    code_offset: Option<u32>,
+    hidden_api_flag: Option<HiddenApiFlag>,
+}
+
+impl Method {
+    /// Creates a new method.
+    pub fn new<N>(
+        access_flags: AccessFlags,
+        name: N,
+        return_type: Type,
+        parameters: Vec<Type>,
+    ) -> Self
+    where
+        N: Into<String>,
+    {
+        Self {
+            access_flags,
+            name: Arc::from(name.into()),
+            return_type,
+            parameters: parameters.into_boxed_slice(),
+            annotations: Box::default(),
+            parameter_annotations: Box::default(),
+            code_offset: None,
+            hidden_api_flag: None,
+        }
+    }
+
+    /// Creates a method from its encoded data, resolving its name and prototype.
+    fn from_data(
+        reader: &DexReader,
+        class: &types::Class,
+        method: &types::read::Method,
+        hidden_api_flag: Option<HiddenApiFlag>,
+    ) -> Result<Self> {
+        let method_id = reader
+            .method_ids
+            .get(method.method_index() as usize)
+            .ok_or_else(|| error::Parse::UnknownMethodIndex(method.method_index()))?;
+        let prototype = reader.prototype_at(method_id.prototype_index())?;
+        Ok(Self {
+            access_flags: method.access_flags(),
+            name: reader.name_at(method_id.name_index())?,
+            return_type: prototype.return_type().clone(),
+            parameters: prototype.parameters().into(),
+            annotations: class
+                .annotations()
+                .and_then(|annotations| {
+                    annotations
+                        .method_annotations()
+                        .iter()
+                        .find(|annotated| annotated.method_index() == method.method_index())
+                })
+                .map_or_else(Box::default, |annotated| {
+                    annotated.annotations().to_vec().into_boxed_slice()
+                }),
+            parameter_annotations: class
+                .annotations()
+                .and_then(|annotations| {
+                    annotations
+                        .parameter_annotations()
+                        .iter()
+                        .find(|parameter| parameter.method_index() == method.method_index())
+                })
+                .map_or_else(Box::default, |parameter| {
+                    parameter.annotations().to_vec().into_boxed_slice()
+                }),
+            code_offset: method.code_offset(),
+            hidden_api_flag,
+        })
+    }
+
+    /// Gets the access flags of the method.
+    pub fn access_flags(&self) -> AccessFlags {
+        self.access_flags
+    }
+
+    /// Gets the raw access flags of the method, as found in the dex file.
+    ///
+    /// In methods, the `0x40` bit means `bridge` and the `0x80` bit means `varargs`, even if the
+    /// typed flags also report them as `ACC_VOLATILE` and `ACC_TRANSIENT`.
+    pub fn raw_access_flags(&self) -> u32 {
+        self.access_flags.bits()
+    }
+
+    /// Gets the name of the method.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Checks if the method is a constructor, either of instances, `<init>`, or of the class
+    /// itself, `<clinit>`.
+    pub fn is_constructor(&self) -> bool {
+        self.is_instance_constructor() || self.is_static_initializer()
+    }
+
+    /// Checks if the method is an instance constructor: a non-static `<init>` method with the
+    /// `ACC_CONSTRUCTOR` flag.
+    pub fn is_instance_constructor(&self) -> bool {
+        self.name() == "<init>"
+            && self.access_flags.contains(AccessFlags::ACC_CONSTRUCTOR)
+            && !self.access_flags.contains(AccessFlags::ACC_STATIC)
+    }
+
+    /// Checks if the method is the static initializer of its class: a static `<clinit>` method
+    /// with the `ACC_CONSTRUCTOR` flag.
+    ///
+    /// It runs when the class is first used, so it is where constants such as keys are usually
+    /// decrypted.
+    pub fn is_static_initializer(&self) -> bool {
+        self.name() == "<clinit>"
+            && self
+                .access_flags
+                .contains(AccessFlags::ACC_CONSTRUCTOR | AccessFlags::ACC_STATIC)
+    }
+
+    /// Gets the return type of the method.
+    pub fn return_type(&self) -> &Type {
+        &self.return_type
+    }
+
+    /// Gets the parameter types of the method.
+    pub fn parameters(&self) -> &[Type] {
+        &self.parameters
+    }
+
+    /// Gets the descriptor of the method, made of its name and the descriptor of its prototype,
+    /// such as `foo(ILjava/lang/String;)V`.
+    ///
+    /// This is the format used to identify methods in JVM tools and mapping files.
+    pub fn descriptor(&self) -> String {
+        format!(
+            "{}({}){}",
+            self.name,
+            self.parameters
+                .iter()
+                .map(Type::to_descriptor)
+                .collect::<String>(),
+            self.return_type.to_descriptor()
+        )
+    }
+
+    /// Gets the name of the JNI symbol implementing the native method, given the class declaring
+    /// it, such as `Java_com_example_Foo_bar`.
+    ///
+    /// This is the symbol the runtime looks up first in the native libraries of the application,
+    /// unless the method is registered with `RegisterNatives()`.
+    pub fn jni_short_name(&self, class: &Class) -> String {
+        format!(
+            "Java_{}_{}",
+            jni_mangle(class.name().trim_end_matches(';')),
+            jni_mangle(&self.name)
+        )
+    }
+
+    /// Gets the name of the JNI symbol implementing the method, including its parameter types,
+    /// such as `Java_com_example_Foo_bar__ILjava_lang_String_2`.
+    ///
+    /// This is the symbol used to tell apart overloaded native methods, and the runtime looks it
+    /// up if the short name is not found.
+    pub fn jni_long_name(&self, class: &Class) -> String {
+        let parameters = self
+            .parameters
+            .iter()
+            .map(Type::to_descriptor)
+            .collect::<String>();
+        format!(
+            "{}__{}",
+            self.jni_short_name(class),
+            jni_mangle(&parameters)
+        )
+    }
+
+    /// Gets the annotations of the method.
+    pub fn annotations(&self) -> &[Annotation] {
+        &self.annotations
+    }
+
+    /// Gets the annotations of each of the parameters of the method.
+    ///
+    /// The list is indexed by parameter position, and parameters without annotations have an
+    /// empty list of annotations.
+    pub fn parameter_annotations(&self) -> Vec<Vec<&Annotation>> {
+        let count = self.parameters.len().max(self.parameter_annotations.len());
+        (0..count)
+            .map(|position| {
+                self.parameter_annotations
+                    .get(position)
+                    .map_or_else(Vec::new, |annotations| annotations.iter().collect())
+            })
+            .collect()
+    }
+
+    /// Gets the offset of the code item of the method, if it has code.
+    ///
+    /// Abstract and native methods have no code, so this is `None` for them.
+    pub fn code_offset(&self) -> Option<u32> {
+        self.code_offset
+    }
+
+    /// Gets the hidden API flags of the method.
+    ///
+    /// They are only available in dex files with a `hiddenapi_class_data` section, so this is
+    /// `None` for most application dex files.
+    pub fn hidden_api_flag(&self) -> Option<HiddenApiFlag> {
+        self.hidden_api_flag
+    }
+
+    /// Gets the code item of the method from the dex file it belongs to, if it has code.
+    ///
+    /// Returns `None` for abstract and native methods, and for methods whose code item could not
+    /// be found in the given dex file.
+    pub fn code<'a>(&self, dex: &'a Dex) -> Option<&'a CodeItem> {
+        self.code_offset.and_then(|offset| dex.code_item(offset))
+    }
+
+    /// Gets the range of registers holding the incoming arguments of the method, if it has code.
+    ///
+    /// Arguments are passed in the last registers of the method, starting with `this` for
+    /// instance methods, and `long` and `double` arguments take two registers each. This allows
+    /// naming the registers of the method after its parameters.
+    ///
+    /// Returns `None` if the number of argument registers needed by the prototype does not match
+    /// the `ins_size` of the code item, or if there are not enough registers for them.
+    pub fn parameter_registers(&self, dex: &Dex) -> Option<Range<u16>> {
+        let code = self.code(dex)?;
+        let this = u16::from(!self.access_flags.contains(AccessFlags::ACC_STATIC));
+        let width = self
+            .parameters
+            .iter()
+            .map(|parameter| u16::from(parameter.register_width()))
+            .fold(this, u16::saturating_add);
+        if width != code.ins_size() {
+            return None;
+        }
+        let registers_size = code.registers_size();
+        Some(registers_size.checked_sub(width)?..registers_size)
+    }
+
+    /// Disassembles the code of the method.
+    ///
+    /// Each instruction is returned along with its offset in code units from the start of the
+    /// method, and references to other items of the dex file are resolved, similarly to the
+    /// output of `dexdump -d`. Decoding stops at the first instruction that cannot be decoded.
+    ///
+    /// Methods without code, such as abstract and native methods, yield an empty listing.
+    pub fn disassemble(&self, dex: &Dex) -> Vec<(u32, String)> {
+        self.instructions(dex)
+            .map(|(pc, bytecode)| (pc, bytecode.to_string_resolved(dex)))
+            .collect()
+    }
+
+    /// Decodes the instructions of the method, along with their offset in code units from the
+    /// start of the method.
+    ///
+    /// Methods without code, such as abstract and native methods, yield no instructions.
+    /// Decoding stops at the first instruction that cannot be decoded.
+    pub fn instructions<'a>(
+        &self,
+        dex: &'a Dex,
+    ) -> Instructions<CodeUnits<'a, LittleEndian>, LittleEndian> {
+        let insns = self.code(dex).map_or(&[][..], CodeItem::insns);
+        ByteCodeDecoder::from_code_units(insns).with_pcs()
+    }
+
+    /// Computes a fingerprint of the code of the method that does not depend on registers or
+    /// indexes, to find methods with the same code in different dex files.
+    ///
+    /// See `bytecode::content_hash()` for the normalization of the instructions. Methods without
+    /// code all have the same hash.
+    pub fn content_hash(&self, dex: &Dex) -> u64 {
+        bytecode::content_hash(self.instructions(dex).map(|(_, bytecode)| bytecode))
+    }
+
+    /// Checks if the method is a synthetic or a bridge method.
+    ///
+    /// These methods are generated by the compiler instead of declared in the source code. Note
+    /// that `ACC_BRIDGE` shares its bit with `ACC_VOLATILE`, so this check is only meaningful for
+    /// methods.
+    pub fn is_synthetic_or_bridge(&self) -> bool {
+        self.access_flags
+            .intersects(AccessFlags::ACC_SYNTHETIC | AccessFlags::ACC_BRIDGE)
+    }
 }
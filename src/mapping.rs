@@ -0,0 +1,299 @@
+//! ProGuard and R8 mapping files.
+
+use crate::{
+    error::{self, Result},
+    types::Type,
+    Class, Field, Method,
+};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+    sync::Arc,
+};
+
+/// Name mapping of an obfuscated application, as found in the `mapping.txt` file generated by
+/// ProGuard and R8.
+///
+/// Class and type names are stored as in `Class`, such as `com/example/Foo;`, and they are keyed
+/// by their obfuscated names.
+#[derive(Debug, Default, Clone)]
+pub struct ProguardMapping {
+    classes: HashMap<String, ClassMapping>,
+}
+
+/// Mapping of the members of a class.
+#[derive(Debug, Default, Clone)]
+struct ClassMapping {
+    /// Original name of the class.
+    name: String,
+    /// Original names of the fields, by obfuscated name.
+    fields: HashMap<String, String>,
+    /// Original names of the methods, by obfuscated name and original parameter types.
+    methods: HashMap<(String, Vec<String>), String>,
+}
+
+impl ProguardMapping {
+    /// Reads the mapping from the file at the given path.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, or if a line of the mapping is not valid.
+    pub fn from_file<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let file = File::open(path.as_ref())?;
+        Self::from_reader(BufReader::new(file))
+    }
+
+    /// Reads the mapping from the given reader.
+    ///
+    /// Comments and the original line numbers of the methods are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the reader fails, or if a line of the mapping is not valid.
+    pub fn from_reader<R>(reader: R) -> Result<Self>
+    where
+        R: BufRead,
+    {
+        let mut mapping = Self::default();
+        let mut current: Option<&mut ClassMapping> = None;
+        for (index, line) in reader.lines().enumerate() {
+            let line = line?;
+            let invalid_line = || error::Mapping::InvalidLine {
+                line: index + 1,
+                content: line.clone(),
+            };
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            if !line.starts_with(char::is_whitespace) {
+                let (original, obfuscated) = split_mapping(trimmed.trim_end_matches(':'))
+                    .filter(|_| trimmed.ends_with(':'))
+                    .ok_or_else(invalid_line)?;
+                let class = mapping
+                    .classes
+                    .entry(java_to_type(obfuscated))
+                    .or_insert_with(ClassMapping::default);
+                class.name = java_to_type(original);
+                current = Some(class);
+                continue;
+            }
+
+            let class = current.as_mut().ok_or_else(invalid_line)?;
+            let (original, obfuscated) = split_mapping(trimmed).ok_or_else(invalid_line)?;
+            // Methods may be prefixed by their obfuscated line range, as in `1:5:void foo()`.
+            let original = original.trim_start_matches(|c: char| c.is_ascii_digit() || c == ':');
+            let mut parts = original.splitn(2, ' ');
+            let _member_type = parts.next().ok_or_else(invalid_line)?;
+            let member = parts.next().ok_or_else(invalid_line)?;
+            match member.find('(') {
+                Some(open) => {
+                    let close = member.rfind(')').ok_or_else(invalid_line)?;
+                    let name = &member[..open];
+                    // The original class is included when the method was inlined or moved.
+                    let name = name.rsplit('.').next().unwrap_or(name);
+                    let parameters = member[open + 1..close]
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|parameter| !parameter.is_empty())
+                        .map(java_to_type)
+                        .collect();
+                    let _ = class
+                        .methods
+                        .entry((obfuscated.to_owned(), parameters))
+                        .or_insert_with(|| name.to_owned());
+                }
+                None => {
+                    let _ = class
+                        .fields
+                        .entry(obfuscated.to_owned())
+                        .or_insert_with(|| member.to_owned());
+                }
+            }
+        }
+        Ok(mapping)
+    }
+
+    /// Gets the original name of a class, given its obfuscated name, such as `a/b;`.
+    pub fn original_class_name(&self, name: &str) -> Option<&str> {
+        self.classes.get(name).map(|class| class.name.as_str())
+    }
+
+    /// Gets the original name of a type, as displayed by `Type`, including array types.
+    ///
+    /// Types not in the mapping, such as primitive and framework types, are returned unchanged.
+    fn original_type(&self, name: &str) -> String {
+        let (element, dimensions) = match (name.rfind('['), name.ends_with(']')) {
+            (Some(i), true) => (&name[..i], &name[i..]),
+            _ => (name, ""),
+        };
+        match self.original_class_name(element) {
+            Some(original) => format!("{}{}", original, dimensions),
+            None => name.to_owned(),
+        }
+    }
+
+    /// Gets the original type of a method parameter or return type, including array types.
+    ///
+    /// Types not in the mapping, such as primitive and framework types, are returned unchanged.
+    fn original_method_type(&self, method_type: &Type) -> Type {
+        match method_type {
+            Type::FullyQualifiedName(name) => self.original_class_name(name).map_or_else(
+                || method_type.clone(),
+                |original| Type::FullyQualifiedName(original.to_owned()),
+            ),
+            Type::Array {
+                dimensions,
+                array_type,
+            } => Type::Array {
+                dimensions: *dimensions,
+                array_type: Box::new(self.original_method_type(array_type)),
+            },
+            _ => method_type.clone(),
+        }
+    }
+
+    /// Renames the given classes, along with their members and the types they use.
+    pub(crate) fn apply(&self, classes: &mut [Class]) {
+        for class in classes {
+            let class_mapping = self.classes.get(&*class.name);
+            class.superclass = class
+                .superclass
+                .as_ref()
+                .map(|superclass| self.original_type(superclass));
+            for interface in &mut *class.interfaces {
+                *interface = self.original_type(interface);
+            }
+            for field in class
+                .static_fields
+                .iter_mut()
+                .chain(class.instance_fields.iter_mut())
+            {
+                self.apply_to_field(field, class_mapping);
+            }
+            for method in class
+                .direct_methods
+                .iter_mut()
+                .chain(class.virtual_methods.iter_mut())
+            {
+                self.apply_to_method(method, class_mapping);
+            }
+            if let Some(class_mapping) = class_mapping {
+                class.name = Arc::from(class_mapping.name.as_str());
+            }
+        }
+    }
+
+    /// Renames a field and its type.
+    fn apply_to_field(&self, field: &mut Field, class_mapping: Option<&ClassMapping>) {
+        if let Some(name) = class_mapping.and_then(|class| class.fields.get(&*field.name)) {
+            field.name = Arc::from(name.as_str());
+        }
+        field.field_type = self.original_type(&field.field_type);
+    }
+
+    /// Renames a method and the types in its prototype.
+    fn apply_to_method(&self, method: &mut Method, class_mapping: Option<&ClassMapping>) {
+        let parameters: Vec<_> = method
+            .parameters
+            .iter()
+            .map(|parameter| self.original_method_type(parameter))
+            .collect();
+        if let Some(class_mapping) = class_mapping {
+            let key = (
+                method.name.to_string(),
+                parameters.iter().map(Type::to_string).collect(),
+            );
+            if let Some(name) = class_mapping.methods.get(&key) {
+                method.name = Arc::from(name.as_str());
+            }
+        }
+        method.return_type = self.original_method_type(&method.return_type);
+        method.parameters = parameters.into_boxed_slice();
+    }
+}
+
+/// Splits a mapping line in its original and obfuscated parts.
+fn split_mapping(line: &str) -> Option<(&str, &str)> {
+    let arrow = line.find(" -> ")?;
+    Some((line[..arrow].trim(), line[arrow + 4..].trim()))
+}
+
+/// Converts a Java type name, such as `java.lang.String[]`, to the way `Type` displays it.
+fn java_to_type(name: &str) -> String {
+    let element = name.trim_end_matches("[]");
+    let dimensions = (name.len() - element.len()) / 2;
+    let element = match element {
+        "void" | "boolean" | "byte" | "short" | "char" | "int" | "long" | "float" | "double" => {
+            element.to_owned()
+        }
+        _ => format!("{};", element.replace('.', "/")),
+    };
+    if dimensions == 0 {
+        element
+    } else {
+        format!("{}[{}]", element, dimensions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{java_to_type, ProguardMapping};
+
+    #[test]
+    fn it_converts_java_types() {
+        assert_eq!("int", java_to_type("int"));
+        assert_eq!("java/lang/String;", java_to_type("java.lang.String"));
+        assert_eq!("byte[2]", java_to_type("byte[][]"));
+        assert_eq!(
+            "com/example/Foo$Bar;[1]",
+            java_to_type("com.example.Foo$Bar[]")
+        );
+    }
+
+    #[test]
+    fn it_parses_a_mapping() {
+        let mapping = "# compiler: R8\n\
+                       com.example.Foo -> a.a:\n\
+                       \x20   int count -> a\n\
+                       \x20   1:4:void update(int,com.example.Foo[]):10:13 -> a\n\
+                       \x20   java.lang.String com.example.Bar.name() -> b\n\
+                       com.example.Bar -> a.b:\n";
+        let mapping = ProguardMapping::from_reader(mapping.as_bytes()).unwrap();
+
+        assert_eq!(
+            Some("com/example/Foo;"),
+            mapping.original_class_name("a/a;")
+        );
+        assert_eq!(
+            Some("com/example/Bar;"),
+            mapping.original_class_name("a/b;")
+        );
+        assert_eq!(None, mapping.original_class_name("com/example/Foo;"));
+        let class = &mapping.classes["a/a;"];
+        assert_eq!("count", class.fields["a"]);
+        assert_eq!(
+            "update",
+            class.methods[&(
+                "a".to_owned(),
+                vec!["int".to_owned(), "com/example/Foo;[1]".to_owned()]
+            )]
+        );
+        assert_eq!("name", class.methods[&("b".to_owned(), Vec::new())]);
+        assert_eq!("com/example/Foo;[1]", mapping.original_type("a/a;[1]"));
+        assert_eq!("int", mapping.original_type("int"));
+    }
+
+    #[test]
+    fn it_rejects_invalid_mappings() {
+        assert!(ProguardMapping::from_reader(&b"    int count -> a\n"[..]).is_err());
+        assert!(ProguardMapping::from_reader(&b"com.example.Foo a.a:\n"[..]).is_err());
+        assert!(ProguardMapping::from_reader(&b"com.example.Foo -> a.a\n"[..]).is_err());
+    }
+}
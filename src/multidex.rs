@@ -0,0 +1,103 @@
+//! Multidex application support.
+
+use crate::{bytecode::MethodReference, error::Result, Class, Dex, Method, StringInterner};
+use std::collections::HashSet;
+
+/// Set of dex files that form a single application.
+///
+/// Applications with more methods than fit in a single dex file are split in several dex files,
+/// `classes.dex`, `classes2.dex` and so on. The runtime treats all of them as a single class
+/// space, so a reference in one dex file may point to a class defined in any other.
+#[derive(Debug)]
+pub struct MultiDex {
+    dex_files: Vec<Dex>,
+}
+
+impl MultiDex {
+    /// Creates a new multidex application from its dex files, in class path order.
+    pub fn new(dex_files: Vec<Dex>) -> Self {
+        Self { dex_files }
+    }
+
+    /// Parses the dex files of an application from their contents, in class path order.
+    ///
+    /// The dex files share the storage of their common strings. See `StringInterner` for more
+    /// information.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the dex files cannot be parsed.
+    pub fn from_bytes<B>(dex_files: &[B]) -> Result<Self>
+    where
+        B: AsRef<[u8]>,
+    {
+        let mut interner = StringInterner::new();
+        let dex_files = dex_files
+            .iter()
+            .map(|bytes| {
+                let bytes = bytes.as_ref();
+                Dex::from_reader_with_interner(bytes, bytes.len(), &mut interner)
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self::new(dex_files))
+    }
+
+    /// Gets the dex files of the application, in class path order.
+    pub fn dex_files(&self) -> &[Dex] {
+        &self.dex_files
+    }
+
+    /// Finds the class with the given descriptor, such as `Lcom/example/Foo;`, in any of the dex
+    /// files.
+    ///
+    /// Returns the class along with the dex file defining it. If more than one dex file defines
+    /// the class, the first one in class path order is returned, as the runtime does.
+    pub fn resolve_class(&self, descriptor: &str) -> Option<(&Dex, &Class)> {
+        self.dex_files
+            .iter()
+            .find_map(|dex| dex.find_class(descriptor).map(|class| (dex, class)))
+    }
+
+    /// Resolves a method reference of the dex file at the given position to the method it
+    /// points to, in any of the dex files.
+    ///
+    /// If the referenced class does not declare the method, it is looked up in its superclasses,
+    /// which may also be defined in other dex files. Returns the method along with the dex file
+    /// defining it, or `None` if the method is not defined in the application, as is the case of
+    /// framework methods.
+    pub fn resolve_method(
+        &self,
+        dex_index: usize,
+        method: MethodReference,
+    ) -> Option<(&Dex, &Method)> {
+        let dex = self.dex_files.get(dex_index)?;
+        let method_id = dex.method_ids.get(method as usize)?;
+        let name = dex.strings.get(method_id.name_index())?;
+        let prototype = dex.prototypes.get(method_id.prototype_index())?;
+
+        let mut descriptor = dex.type_ids.get(method_id.class_index())?.to_descriptor();
+        // Malformed applications could have cycles in their class hierarchy.
+        let mut visited = HashSet::new();
+        loop {
+            let (dex, class) = self.resolve_class(&descriptor)?;
+            if !visited.insert(class.name()) {
+                return None;
+            }
+            let found = class.methods().find(|candidate| {
+                candidate.name() == name
+                    && candidate.return_type() == prototype.return_type()
+                    && candidate.parameters() == prototype.parameters()
+            });
+            if let Some(found) = found {
+                return Some((dex, found));
+            }
+            descriptor = format!("L{}", class.superclass()?);
+        }
+    }
+}
+
+impl From<Vec<Dex>> for MultiDex {
+    fn from(dex_files: Vec<Dex>) -> Self {
+        Self::new(dex_files)
+    }
+}
@@ -0,0 +1,167 @@
+//! Network indicators found in the string table of a dex file.
+
+use regex::Regex;
+use std::{
+    collections::BTreeSet,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+/// URLs with a network scheme, up to the first whitespace or quote character.
+const URL_PATTERN: &str = r#"(?i)\b(?:https?|ftps?|wss?)://[^\s"'<>`]+"#;
+/// Hostnames ending in a common top level domain, such as `example.com`.
+///
+/// Only whole words are matched, so Java class names such as `com.example.Foo` are not taken
+/// as hostnames. Names starting with `JAVA_PACKAGE_ROOTS` are not either.
+const HOSTNAME_PATTERN: &str = r"^(?:[a-z0-9](?:[a-z0-9-]{0,61}[a-z0-9])?\.)+(?:com|net|org|info|biz|io|co|me|xyz|top|online|site|club|ru|cn|de|uk|br|in|jp|fr|it|nl|pl|tk|su|ir|kr|us|cc|ws)$";
+/// First labels of Java package and constant names, such as `android.intent.extra.cc`, that
+/// would otherwise be taken as hostnames.
+const JAVA_PACKAGE_ROOTS: [&str; 9] = [
+    "android", "androidx", "com", "dalvik", "java", "javax", "kotlin", "net", "org",
+];
+/// Candidate IPv4 addresses, four dotted decimal numbers, validated with `Ipv4Addr`.
+const IPV4_PATTERN: &str = r"\b\d{1,3}\.\d{1,3}\.\d{1,3}\.\d{1,3}\b";
+/// Candidate IPv6 addresses, hexadecimal groups separated by colons, validated with `Ipv6Addr`.
+///
+/// The address is in the first group, and it cannot be next to letters or digits, so that C++
+/// names such as `std::string` are not taken as addresses.
+const IPV6_PATTERN: &str =
+    r"(?i)(?:^|[^0-9a-z:])([0-9a-f]{0,4}(?::[0-9a-f]{0,4}){2,7})(?:$|[^0-9a-z:])";
+
+/// Network indicators found in the string table of a dex file, as extracted by
+/// `Dex::extract_network_indicators()`.
+///
+/// These are usually the first thing to look for when analyzing a malicious application, as
+/// they point to the servers it talks to. Each list is sorted and has no duplicates.
+///
+/// The patterns used are deliberately simple, so that they can be reviewed at a glance:
+///
+///  - URLs start with an `http`, `https`, `ftp`, `ftps`, `ws` or `wss` scheme, and end at the
+///    first whitespace, quote or angle bracket.
+///  - Hostnames are whole words, outside of URLs, made of DNS labels and ending in one of a
+///    list of common top level domains, such as `com`, `net`, `io` or `ru`. Words starting like
+///    a Java package, such as `android.` or `com.`, are skipped.
+///  - IPv4 addresses are four dotted decimal numbers, and IPv6 addresses are hexadecimal groups
+///    separated by colons. Both are only kept if the standard library parses them as valid
+///    addresses, and the unspecified IPv6 address, `::`, is ignored.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NetworkIndicators {
+    urls: Vec<String>,
+    hostnames: Vec<String>,
+    ipv4_addresses: Vec<Ipv4Addr>,
+    ipv6_addresses: Vec<Ipv6Addr>,
+}
+
+impl NetworkIndicators {
+    /// Extracts the network indicators found in the given strings.
+    pub(crate) fn from_strings<'s, I>(strings: I) -> Self
+    where
+        I: IntoIterator<Item = &'s str>,
+    {
+        let url_pattern = Regex::new(URL_PATTERN).expect("invalid URL pattern");
+        let hostname_pattern = Regex::new(HOSTNAME_PATTERN).expect("invalid hostname pattern");
+        let ipv4_pattern = Regex::new(IPV4_PATTERN).expect("invalid IPv4 pattern");
+        let ipv6_pattern = Regex::new(IPV6_PATTERN).expect("invalid IPv6 pattern");
+
+        let mut urls = BTreeSet::new();
+        let mut hostnames = BTreeSet::new();
+        let mut ipv4_addresses = BTreeSet::new();
+        let mut ipv6_addresses = BTreeSet::new();
+        for string in strings {
+            urls.extend(url_pattern.find_iter(string).map(|url| url.as_str()));
+            ipv4_addresses.extend(
+                ipv4_pattern
+                    .find_iter(string)
+                    .filter_map(|address| address.as_str().parse::<Ipv4Addr>().ok()),
+            );
+            ipv6_addresses.extend(
+                ipv6_pattern
+                    .captures_iter(string)
+                    .filter_map(|captures| captures[1].parse::<Ipv6Addr>().ok())
+                    .filter(|address| !address.is_unspecified()),
+            );
+
+            // Hosts of URLs are already reported as part of them.
+            let without_urls = url_pattern.replace_all(string, " ");
+            hostnames.extend(
+                without_urls
+                    .split(|c: char| !(c.is_ascii_alphanumeric() || c == '.' || c == '-'))
+                    .map(|word| word.trim_matches('.').to_ascii_lowercase())
+                    .filter(|word| {
+                        hostname_pattern.is_match(word)
+                            && !JAVA_PACKAGE_ROOTS
+                                .iter()
+                                .any(|root| word.split('.').next() == Some(root))
+                    }),
+            );
+        }
+
+        Self {
+            urls: urls.into_iter().map(str::to_owned).collect(),
+            hostnames: hostnames.into_iter().collect(),
+            ipv4_addresses: ipv4_addresses.into_iter().collect(),
+            ipv6_addresses: ipv6_addresses.into_iter().collect(),
+        }
+    }
+
+    /// Gets the URLs.
+    pub fn urls(&self) -> &[String] {
+        &self.urls
+    }
+
+    /// Gets the hostnames that are not part of a URL, in lowercase.
+    pub fn hostnames(&self) -> &[String] {
+        &self.hostnames
+    }
+
+    /// Gets the IPv4 addresses.
+    pub fn ipv4_addresses(&self) -> &[Ipv4Addr] {
+        &self.ipv4_addresses
+    }
+
+    /// Gets the IPv6 addresses.
+    pub fn ipv6_addresses(&self) -> &[Ipv6Addr] {
+        &self.ipv6_addresses
+    }
+
+    /// Checks if no network indicators were found.
+    pub fn is_empty(&self) -> bool {
+        self.urls.is_empty()
+            && self.hostnames.is_empty()
+            && self.ipv4_addresses.is_empty()
+            && self.ipv6_addresses.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NetworkIndicators;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    #[test]
+    fn it_extracts_network_indicators() {
+        let indicators = NetworkIndicators::from_strings(vec![
+            "Register at http://payatu.com/register to get your PIN",
+            "connecting to 192.168.1.20:8080",
+            "fallback 2001:db8::1, then c2.evil.ru",
+            "Lcom/example/net/Client;",
+            "com.example.Foo",
+            "android.intent.extra.cc",
+            "version 1.2.3.4567 and 999.1.1.1",
+            "std::string",
+            "http://payatu.com/register",
+        ]);
+
+        assert_eq!(vec!["http://payatu.com/register"], indicators.urls());
+        assert_eq!(vec!["c2.evil.ru"], indicators.hostnames());
+        assert_eq!(
+            vec![Ipv4Addr::new(192, 168, 1, 20)],
+            indicators.ipv4_addresses()
+        );
+        assert_eq!(
+            vec![Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)],
+            indicators.ipv6_addresses()
+        );
+        assert!(!indicators.is_empty());
+        assert!(NetworkIndicators::from_strings(vec!["Ljava/lang/Object;"]).is_empty());
+    }
+}
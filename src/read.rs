@@ -1,20 +1,31 @@
 //! Dex file reader module.
 
 use crate::{
-    error,
+    error::{self, Error, Result},
     header::Header,
+    intern::{StringInterner, StringTable},
+    sizes::{
+        CALL_SITE_ID_ITEM_SIZE, CLASS_DEF_ITEM_SIZE, CODE_ITEM_HEADER_SIZE, FIELD_ID_ITEM_SIZE,
+        HEADER_SIZE, METHOD_HANDLE_ITEM_SIZE, METHOD_ID_ITEM_SIZE, PROTO_ID_ITEM_SIZE,
+        STRING_ID_ITEM_SIZE, TYPE_ID_ITEM_SIZE,
+    },
     types::{
         read::{
-            AnnotationsDirectoryOffsets, ClassData, ClassDefData, CodeItem, DebugInfo, FieldIdData,
-            MethodIdData, PrototypeIdData,
+            AnnotationsDirectoryOffsets, CallSite, ClassData, ClassDefData, CodeItem, DebugInfo,
+            DexString, FieldIdData, MapItem, MethodHandleData, MethodIdData, PrototypeIdData,
         },
-        Annotation, AnnotationsDirectory, Array, Class, FieldAnnotations, MethodAnnotations,
-        ParameterAnnotations, Prototype, ShortyDescriptor, Type,
+        Annotation, AnnotationsDirectory, Array, Class, FieldAnnotations, ItemType,
+        MethodAnnotations, ParameterAnnotations, Prototype, ShortyDescriptor, Type,
     },
+    Layout, LayoutItem, ParseOptions, ParseTimings, Warning,
 };
-use anyhow::{Context, Result};
 use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
-use std::io::{BufRead, Cursor, Read};
+use std::{
+    convert::TryFrom,
+    io::{Cursor, Read, Seek, SeekFrom},
+    sync::Arc,
+    time::Instant,
+};
 
 /// Structure for reading a Dex file in a fast way.
 #[derive(Debug)]
@@ -26,7 +37,9 @@ pub struct DexReader {
     /// Header of the dex file.
     pub(crate) header: Header,
     /// String list.
-    pub(crate) strings: Vec<String>,
+    pub(crate) strings: StringTable,
+    /// Interner sharing the strings read with other dex files, if any.
+    interner: Option<StringInterner>,
     /// Type list.
     pub(crate) types: Vec<Type>,
     /// Prototype ID list.
@@ -38,28 +51,50 @@ pub struct DexReader {
     pub(crate) field_ids: Vec<FieldIdData>,
     /// Method ID list.
     pub(crate) method_ids: Vec<MethodIdData>,
+    /// Method handle list.
+    pub(crate) method_handles: Vec<MethodHandleData>,
+    /// Call site list.
+    pub(crate) call_sites: Vec<CallSite>,
     /// List of classes.
     pub(crate) classes: Vec<Class>,
+    /// Map of the dex file.
+    pub(crate) map: Vec<MapItem>,
 
-    /// List of lists of references to annotation set offsets.
-    pub(crate) annotation_set_ref_list: Vec<Box<[u32]>>,
-    /// Set of annotations.
-    pub(crate) annotation_sets: Vec<Box<[u32]>>,
     /// Code segment list.
     pub(crate) code_segments: Vec<(u32, CodeItem)>,
     /// Debug information list.
     pub(crate) debug_info: Vec<(u32, DebugInfo)>,
-    // /// Annotation list.
-    // annotations: Vec<(u32, AnnotationItem)>,
     /// Array list.
     pub(crate) arrays: Vec<(u32, Array)>,
     /// Annotations directories.
     pub(crate) annotations_directories: Vec<(u32, AnnotationsDirectory)>,
+    /// Hidden API flags of the members of each class, if the dex file has hidden API data.
+    ///
+    /// The flags of each class are in the same order as the members in its class data.
+    pub(crate) hidden_api_flags: Vec<Option<Box<[u32]>>>,
+    /// Errors found while reading the data of each class, if any.
+    ///
+    /// Classes whose data or code could not be read are kept, but with their members missing.
+    pub(crate) class_errors: Vec<Option<String>>,
+    /// Position of each read class in the class definition list.
+    ///
+    /// Classes skipped by the class filter are not read, so this can have gaps.
+    class_def_indexes: Vec<usize>,
+    /// Options used when parsing the file.
+    pub(crate) options: ParseOptions,
+    /// Recoverable anomalies found when parsing in lenient mode.
+    pub(crate) warnings: Vec<Warning>,
+    /// Contents of the link section, if the file has one.
+    pub(crate) link_data: Option<Box<[u8]>>,
+    /// Location of the parsed items, if enabled in the options.
+    pub(crate) layout: Option<Layout>,
+    /// Time spent in each phase of the parsing.
+    pub(crate) timings: ParseTimings,
 }
 
 impl DexReader {
     /// Creates a new reader with the information from the header of the file.
-    pub fn from_read<R, S>(mut file: R, size: S) -> Result<Self>
+    pub fn from_read<R, S>(mut file: R, size: S, options: ParseOptions) -> Result<Self>
     where
         R: Read + ReadBytesExt,
         S: Into<Option<usize>>,
@@ -69,79 +104,516 @@ impl DexReader {
         } else {
             Vec::new()
         };
-        let _ = file
-            .read_to_end(&mut file_contents)
-            .context("could not read dex file contents")?;
+        let _ = file.read_to_end(&mut file_contents)?;
         let mut file_cursor = Cursor::new(file_contents.into_boxed_slice());
-        let header =
-            Header::from_reader(&mut file_cursor).context("could not read dex file header")?;
-        let strings = Vec::with_capacity(header.get_string_ids_size() as usize);
-        let types = Vec::with_capacity(header.get_type_ids_size() as usize);
-        let prototypes = Vec::with_capacity(header.get_prototype_ids_size() as usize);
-        let field_ids = Vec::with_capacity(header.get_field_ids_size() as usize);
-        let method_ids = Vec::with_capacity(header.get_method_ids_size() as usize);
+        let header = Header::from_reader(&mut file_cursor).section("header_item", 0)?;
+        // The sizes in the header are not checked against the actual size of the file.
+        let file_size = file_cursor.get_ref().len() as u64;
+        let strings = checked_with_capacity(
+            "string_ids_size",
+            header.get_string_ids_size(),
+            u64::from(STRING_ID_ITEM_SIZE),
+            file_size,
+        )?;
+        let types = checked_with_capacity(
+            "type_ids_size",
+            header.get_type_ids_size(),
+            u64::from(TYPE_ID_ITEM_SIZE),
+            file_size,
+        )?;
+        let prototypes = checked_with_capacity(
+            "proto_ids_size",
+            header.get_prototype_ids_size(),
+            u64::from(PROTO_ID_ITEM_SIZE),
+            file_size,
+        )?;
+        let field_ids = checked_with_capacity(
+            "field_ids_size",
+            header.get_field_ids_size(),
+            u64::from(FIELD_ID_ITEM_SIZE),
+            file_size,
+        )?;
+        let method_ids = checked_with_capacity(
+            "method_ids_size",
+            header.get_method_ids_size(),
+            u64::from(METHOD_ID_ITEM_SIZE),
+            file_size,
+        )?;
+        let classes = checked_with_capacity(
+            "class_defs_size",
+            header.get_class_defs_size(),
+            u64::from(CLASS_DEF_ITEM_SIZE),
+            file_size,
+        )?;
         Ok(Self {
             file_cursor,
             header,
-            strings,
+            strings: StringTable::Owned(strings),
+            interner: None,
             types,
             prototypes,
             field_ids,
             method_ids,
-            classes: Vec::new(),
-            annotation_set_ref_list: Vec::new(),
-            annotation_sets: Vec::new(),
+            method_handles: Vec::new(),
+            call_sites: Vec::new(),
+            classes,
+            map: Vec::new(),
             code_segments: Vec::new(),
             debug_info: Vec::new(),
-            // annotations: Vec::new(),
             arrays: Vec::new(),
             annotations_directories: Vec::new(),
+            hidden_api_flags: Vec::new(),
+            class_errors: Vec::new(),
+            class_def_indexes: Vec::new(),
+            options,
+            warnings: Vec::new(),
+            link_data: None,
+            layout: if options.record_layout() {
+                Some(Layout::new())
+            } else {
+                None
+            },
+            timings: ParseTimings::default(),
         })
     }
 
+    /// Shares the strings read with the other dex files read with the given interner.
+    ///
+    /// This must be called before reading the data, and the interner is given back by
+    /// `take_interner()`.
+    pub(crate) fn share_strings(&mut self, interner: StringInterner) {
+        // The number of strings was checked against the size of the file when creating the
+        // reader.
+        self.strings = StringTable::Shared(Vec::with_capacity(
+            self.header.get_string_ids_size() as usize
+        ));
+        self.interner = Some(interner);
+    }
+
+    /// Takes back the interner given to `share_strings()`, if any.
+    pub(crate) fn take_interner(&mut self) -> Option<StringInterner> {
+        self.interner.take()
+    }
+
     /// Reads data from a whole file and stores its information.
-    pub fn read_data(&mut self) -> Result<()> {
+    ///
+    /// Only the classes whose descriptor passes the given filter are read. The ID lists are
+    /// always read in full.
+    ///
+    /// Bytes that do not belong to any section, which could hide a payload, are recorded as
+    /// warnings.
+    pub fn read_data(&mut self, class_filter: &dyn Fn(&str) -> bool) -> Result<()> {
+        if self.header.is_little_endian() {
+            self.read_endian_data::<LittleEndian>(class_filter)?;
+        } else {
+            self.read_endian_data::<BigEndian>(class_filter)?;
+        }
+        let start = Instant::now();
+        self.read_link_data()?;
+        self.check_unknown_data();
+        self.timings.finish = start.elapsed();
+
+        Ok(())
+    }
+
+    /// Reads the contents of the link section, if the file has one.
+    ///
+    /// The link section is only used by statically linked files, and its format is not
+    /// specified, so its presence is recorded as a warning.
+    fn read_link_data(&mut self) -> Result<()> {
+        let offset = match self.header.get_link_offset() {
+            Some(offset) => offset,
+            None => return Ok(()),
+        };
+        let size = self.header.get_link_size();
+        let end = u64::from(offset) + u64::from(size);
+        #[allow(clippy::cast_possible_truncation)]
+        let link_data: Box<[u8]> = self
+            .file_cursor
+            .get_ref()
+            .get(offset as usize..end as usize)
+            .ok_or_else(|| error::Parse::InvalidOffset {
+                desc: format!(
+                    "the link section ends at offset {:#010x}, after the end of the file",
+                    end
+                ),
+            })
+            .section("link_data", u64::from(offset))?
+            .into();
+        self.link_data = Some(link_data);
+        self.warnings.push(Warning::LinkData {
+            offset: u64::from(offset),
+            size: u64::from(size),
+        });
+
+        Ok(())
+    }
+
+    /// Records the bytes between the ID lists and the data section, unless they are zero padding,
+    /// and the bytes after the end of the file as declared in the header.
+    ///
+    /// The call site ID and method handle lists are only in the map, so they are taken from it.
+    /// The header already checks that the data and link sections are contiguous.
+    fn check_unknown_data(&mut self) {
+        let header = &self.header;
+        let map_lists = [
+            (ItemType::CallSiteId, CALL_SITE_ID_ITEM_SIZE),
+            (ItemType::MethodHandle, METHOD_HANDLE_ITEM_SIZE),
+        ]
+        .iter()
+        .filter_map(|&(item_type, item_size)| {
+            self.map_item(item_type)
+                .map(|item| (Some(item.offset()), item.size(), item_size))
+        })
+        .collect::<Vec<_>>();
+        let ids_end = [
+            (
+                header.get_string_ids_offset(),
+                header.get_string_ids_size(),
+                STRING_ID_ITEM_SIZE,
+            ),
+            (
+                header.get_type_ids_offset(),
+                header.get_type_ids_size(),
+                TYPE_ID_ITEM_SIZE,
+            ),
+            (
+                header.get_prototype_ids_offset(),
+                header.get_prototype_ids_size(),
+                PROTO_ID_ITEM_SIZE,
+            ),
+            (
+                header.get_field_ids_offset(),
+                header.get_field_ids_size(),
+                FIELD_ID_ITEM_SIZE,
+            ),
+            (
+                header.get_method_ids_offset(),
+                header.get_method_ids_size(),
+                METHOD_ID_ITEM_SIZE,
+            ),
+            (
+                header.get_class_defs_offset(),
+                header.get_class_defs_size(),
+                CLASS_DEF_ITEM_SIZE,
+            ),
+        ]
+        .iter()
+        .chain(&map_lists)
+        .filter_map(|&(offset, size, item_size)| {
+            offset.map(|offset| u64::from(offset) + size as u64 * u64::from(item_size))
+        })
+        .fold(u64::from(HEADER_SIZE), u64::max);
+        if let Some(data_offset) = header.get_data_offset() {
+            #[allow(clippy::cast_possible_truncation)]
+            let gap = self
+                .file_cursor
+                .get_ref()
+                .get(ids_end as usize..data_offset as usize)
+                .unwrap_or_default();
+            if gap.iter().any(|&byte| byte != 0) {
+                self.warnings.push(Warning::UnknownData {
+                    offset: ids_end,
+                    size: u64::from(data_offset) - ids_end,
+                });
+            }
+        }
+
+        let declared_size = u64::from(header.get_file_size());
+        let input_size = self.file_cursor.get_ref().len() as u64;
+        if input_size > declared_size {
+            self.warnings.push(Warning::UnknownData {
+                offset: declared_size,
+                size: input_size - declared_size,
+            });
+        }
+    }
+
+    /// Reads the descriptor of the class at the given position of the class definition list,
+    /// without reading any other section of the file.
+    ///
+    /// Only the `class_idx` field of the class definition, its type ID and its string are read.
+    pub(crate) fn read_class_descriptor(&mut self, class_def_index: usize) -> Result<String> {
         if self.header.is_little_endian() {
-            self.read_endian_data::<LittleEndian>()
+            self.read_endian_class_descriptor::<LittleEndian>(class_def_index)
         } else {
-            self.read_endian_data::<BigEndian>()
+            self.read_endian_class_descriptor::<BigEndian>(class_def_index)
+        }
+    }
+
+    /// Reads the descriptor of a class in the correct endianness.
+    fn read_endian_class_descriptor<B>(&mut self, class_def_index: usize) -> Result<String>
+    where
+        B: ByteOrder,
+    {
+        let class_def_offset = u64::from(self.header.get_class_defs_offset().unwrap_or(0))
+            + class_def_index as u64 * u64::from(CLASS_DEF_ITEM_SIZE);
+        self.file_cursor.set_position(class_def_offset);
+        let type_index = self
+            .file_cursor
+            .read_u32::<B>()
+            .section("class_def_item", class_def_offset)?;
+        if type_index >= self.header.get_type_ids_size() {
+            return Err(error::Parse::UnknownTypeIndex(type_index))
+                .section("class_def_item", class_def_offset);
+        }
+
+        let type_id_offset = u64::from(self.header.get_type_ids_offset().unwrap_or(0))
+            + u64::from(type_index) * u64::from(TYPE_ID_ITEM_SIZE);
+        self.file_cursor.set_position(type_id_offset);
+        let string_index = self
+            .file_cursor
+            .read_u32::<B>()
+            .section("type_id_item", type_id_offset)?;
+        if string_index >= self.header.get_string_ids_size() {
+            return Err(error::Parse::UnknownStringIndex(string_index))
+                .section("type_id_item", type_id_offset);
+        }
+
+        self.read_endian_string_at::<B>(string_index as usize)
+    }
+
+    /// Reads the string at the given index of the string ID list, without reading the rest of
+    /// the strings.
+    pub(crate) fn read_string_at(&mut self, index: usize) -> Result<String> {
+        if self.header.is_little_endian() {
+            self.read_endian_string_at::<LittleEndian>(index)
+        } else {
+            self.read_endian_string_at::<BigEndian>(index)
+        }
+    }
+
+    /// Reads the string at the given index of the string ID list in the correct endianness.
+    fn read_endian_string_at<B>(&mut self, index: usize) -> Result<String>
+    where
+        B: ByteOrder,
+    {
+        let string_id_offset = u64::from(self.header.get_string_ids_offset().unwrap_or(0))
+            + index as u64 * u64::from(STRING_ID_ITEM_SIZE);
+        self.file_cursor.set_position(string_id_offset);
+        let string_data_offset = self
+            .file_cursor
+            .read_u32::<B>()
+            .section("string_id_item", string_id_offset)?;
+        self.file_cursor.set_position(u64::from(string_data_offset));
+        self.read_string(index)
+            .section("string_data_item", u64::from(string_data_offset))
+    }
+
+    /// Records the location of the item just read, given its offset, if the layout is recorded.
+    ///
+    /// The item is considered to end at the current position of the reader.
+    fn record_layout(&mut self, item: LayoutItem, offset: u64) {
+        let end = self.file_cursor.position();
+        if let Some(layout) = &mut self.layout {
+            layout.record(item, offset, end);
         }
     }
 
     /// Reads the data in the correct endianness.
-    fn read_endian_data<B>(&mut self) -> Result<()>
+    fn read_endian_data<B>(&mut self, class_filter: &dyn Fn(&str) -> bool) -> Result<()>
     where
         B: ByteOrder,
     {
+        let start = Instant::now();
+        let map_offset = self.header.get_map_offset();
+        self.file_cursor.set_position(u64::from(map_offset));
+        self.read_map::<B>()
+            .section("map_list", u64::from(map_offset))?;
+
         if let Some(offset) = self.header.get_string_ids_offset() {
             self.file_cursor.set_position(u64::from(offset));
-            self.read_string_list::<B>()
-                .context("could not read string list")?;
+            self.read_string_list::<B>()?;
         }
+        let strings_end = Instant::now();
+        self.timings.strings = strings_end - start;
+
         if let Some(offset) = self.header.get_type_ids_offset() {
             self.file_cursor.set_position(u64::from(offset));
-            self.read_all_types::<B>()
-                .context("could not read type list")?;
+            self.read_all_types::<B>()?;
         }
         if let Some(offset) = self.header.get_prototype_ids_offset() {
             self.file_cursor.set_position(u64::from(offset));
-            self.read_prototype_list::<B>()
-                .context("could not read prototype list")?;
+            self.read_prototype_list::<B>()?;
         }
         if let Some(offset) = self.header.get_field_ids_offset() {
             self.file_cursor.set_position(u64::from(offset));
-            self.read_field_id_list::<B>()
-                .context("could not read field ID list")?;
+            self.read_field_id_list::<B>()?;
         }
         if let Some(offset) = self.header.get_method_ids_offset() {
             self.file_cursor.set_position(u64::from(offset));
-            self.read_method_id_list::<B>()
-                .context("could not read method ID list")?;
+            self.read_method_id_list::<B>()?;
         }
+        if let Some(method_handles) = self.map_item(ItemType::MethodHandle) {
+            self.file_cursor
+                .set_position(u64::from(method_handles.offset()));
+            self.read_method_handle_list::<B>(method_handles.size())?;
+        }
+        if let Some(call_site_ids) = self.map_item(ItemType::CallSiteId) {
+            self.file_cursor
+                .set_position(u64::from(call_site_ids.offset()));
+            self.read_call_site_list::<B>(call_site_ids.size())?;
+        }
+        let id_tables_end = Instant::now();
+        self.timings.id_tables = id_tables_end - strings_end;
+
         if let Some(offset) = self.header.get_class_defs_offset() {
             self.file_cursor.set_position(u64::from(offset));
-            self.read_class_list::<B>()
-                .context("could not read class list")?;
+            self.read_class_list::<B>(class_filter)?;
+        }
+        let class_data_end = Instant::now();
+        self.timings.class_data = class_data_end - id_tables_end;
+        let code_item_count = self
+            .map
+            .iter()
+            .find(|item| item.item_type() == ItemType::Code)
+            .map_or(0, |item| item.size());
+        self.read_code_items::<B>(code_item_count);
+        if let Some(hidden_api) = self
+            .map
+            .iter()
+            .find(|item| item.item_type() == ItemType::HiddenapiClassData)
+            .copied()
+        {
+            self.file_cursor
+                .set_position(u64::from(hidden_api.offset()));
+            self.read_hidden_api_class_data::<B>()
+                .section("hiddenapi_class_data_item", u64::from(hidden_api.offset()))?;
+        }
+        self.timings.code_items = class_data_end.elapsed();
+
+        Ok(())
+    }
+
+    /// Reads the map of the dex file.
+    fn read_map<B>(&mut self) -> Result<()>
+    where
+        B: ByteOrder,
+    {
+        let size = self.file_cursor.read_u32::<B>()?;
+        // Each map item takes 12 bytes.
+        self.map =
+            checked_with_capacity("size", size, 12, remaining_bytes(&mut self.file_cursor)?)?;
+        for _ in 0..size {
+            let current_offset = self.file_cursor.position();
+            let item = MapItem::from_reader::<_, B>(&mut self.file_cursor)
+                .section("map_item", current_offset)?;
+            // Vendor sections are kept in the map, but nothing reads them.
+            if let ItemType::Unknown(item_type) = item.item_type() {
+                if !self.options.lenient() {
+                    return Err(error::Parse::InvalidItemType(item_type))
+                        .section("map_item", current_offset);
+                }
+                self.warnings.push(Warning::UnknownItemType {
+                    item_type,
+                    offset: item.offset(),
+                });
+            }
+            self.map.push(item);
+        }
+
+        Ok(())
+    }
+
+    /// Reads the code items of the methods of all the classes, sorted by offset.
+    ///
+    /// A code item that cannot be read does not abort the parsing: the error is recorded for the
+    /// class of its method, and the method is left without code.
+    fn read_code_items<B>(&mut self, count: u32)
+    where
+        B: ByteOrder,
+    {
+        // The count comes from the map, so it is only used as a hint if it is plausible.
+        let mut offsets = checked_with_capacity(
+            "size",
+            count,
+            u64::from(CODE_ITEM_HEADER_SIZE),
+            self.file_cursor.get_ref().len() as u64,
+        )
+        .unwrap_or_default();
+        for (class_index, class) in self.classes.iter().enumerate() {
+            if let Some(data) = class.class_data() {
+                let methods = data.direct_methods().iter().chain(data.virtual_methods());
+                for method in methods {
+                    if let Some(offset) = method.code_offset() {
+                        offsets.push((offset, class_index));
+                    }
+                }
+            }
+        }
+        offsets.sort_unstable();
+        offsets.dedup_by_key(|(offset, _)| *offset);
+
+        self.code_segments.reserve_exact(offsets.len());
+        let file_size = self.file_cursor.get_ref().len() as u64;
+        for (offset, class_index) in offsets {
+            // Each code unit takes two bytes after the header of the code item.
+            let remaining_units =
+                file_size.saturating_sub(u64::from(offset) + u64::from(CODE_ITEM_HEADER_SIZE)) / 2;
+            let max_insns_size = self
+                .options
+                .max_insns_per_method()
+                .map_or(remaining_units, |max| remaining_units.min(u64::from(max)));
+            let max_insns_size = u32::try_from(max_insns_size).unwrap_or(u32::max_value());
+            self.file_cursor.set_position(u64::from(offset));
+            match CodeItem::from_reader_limited::<_, B>(&mut self.file_cursor, max_insns_size)
+                .section("code_item", u64::from(offset))
+            {
+                Ok(code_item) => {
+                    self.record_layout(LayoutItem::CodeItem(offset), u64::from(offset));
+                    self.code_segments.push((offset, code_item));
+                }
+                Err(error) => {
+                    let class_error = &mut self.class_errors[class_index];
+                    if class_error.is_none() {
+                        *class_error = Some(error.chain_message());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reads the hidden API flags of the members of all the classes.
+    ///
+    /// The section starts with its size and one offset per class definition, relative to the
+    /// start of the section, followed by the flags of each class with a non-zero offset.
+    fn read_hidden_api_class_data<B>(&mut self) -> Result<()>
+    where
+        B: ByteOrder,
+    {
+        let section_offset = self.file_cursor.position();
+        let _size = self.file_cursor.read_u32::<B>()?;
+        let mut offsets = checked_with_capacity(
+            "class_defs_size",
+            self.header.get_class_defs_size(),
+            4,
+            remaining_bytes(&mut self.file_cursor)?,
+        )?;
+        for _ in 0..self.header.get_class_defs_size() {
+            offsets.push(self.file_cursor.read_u32::<B>()?);
+        }
+
+        self.hidden_api_flags.reserve_exact(self.classes.len());
+        for (class, &def_index) in self.classes.iter().zip(&self.class_def_indexes) {
+            let offset = offsets[def_index];
+            if offset == 0 {
+                self.hidden_api_flags.push(None);
+                continue;
+            }
+            let member_count = class.class_data().map_or(0, |data| {
+                data.static_fields().len()
+                    + data.instance_fields().len()
+                    + data.direct_methods().len()
+                    + data.virtual_methods().len()
+            });
+            self.file_cursor
+                .set_position(section_offset + u64::from(offset));
+            let mut flags = Vec::with_capacity(member_count);
+            for _ in 0..member_count {
+                let (flag, _) = uleb128(&mut self.file_cursor)?;
+                flags.push(flag);
+            }
+            self.hidden_api_flags.push(Some(flags.into_boxed_slice()));
         }
 
         Ok(())
@@ -152,46 +624,75 @@ impl DexReader {
     where
         B: ByteOrder,
     {
-        for _ in 0..self.header.get_string_ids_size() {
+        // Packers sometimes inflate the number of strings in the header.
+        let declared = self.header.get_string_ids_size() as usize;
+        let actual = self
+            .map_item(ItemType::StringId)
+            .map_or(0, |item| item.size() as usize);
+        let count = if declared == actual {
+            declared
+        } else if self.options.lenient() {
+            self.warnings
+                .push(Warning::StringCountMismatch { declared, actual });
+            declared.min(actual)
+        } else {
+            return Err(error::Parse::StringCountMismatch { declared, actual }.into());
+        };
+
+        let data_offset = self.header.get_data_offset().unwrap_or(0);
+        let file_size = self.header.get_file_size();
+        for index in 0..count {
             let current_offset = self.file_cursor.position();
-            let offset = self.file_cursor.read_u32::<B>().with_context(|| {
-                format!(
-                    "could not read string offset from string ID at offset {:#010x}",
-                    current_offset
-                )
-            })?;
+            let offset = self
+                .file_cursor
+                .read_u32::<B>()
+                .section("string_id_item", current_offset)?;
+            if offset < data_offset || offset >= file_size {
+                return Err(error::Parse::StringOffsetOutOfRange { index, offset })
+                    .section("string_id_item", current_offset);
+            }
             let current_offset = self.file_cursor.position();
             self.file_cursor.set_position(u64::from(offset));
-            let str_data = self.read_string()?;
-            self.strings.push(str_data);
+            let str_data = self
+                .read_string(index)
+                .section("string_data_item", u64::from(offset))?;
+            self.record_layout(LayoutItem::StringData(index), u64::from(offset));
+            self.strings.push(str_data, self.interner.as_mut());
             self.file_cursor.set_position(current_offset);
         }
 
         Ok(())
     }
 
-    /// Reads an actual string.
-    fn read_string(&mut self) -> Result<String> {
-        let (size, _) = uleb128(&mut self.file_cursor).context("could not read string size")?;
-        let mut data = Vec::with_capacity(size as usize);
-        if size > 0 {
-            let _ = self.file_cursor.read_until(0, &mut data)?;
-            let _ = data.pop();
-        }
-
-        let string = String::from_utf8(data).context("error decoding UTF-8 from string data")?;
-        let char_count = string.chars().count();
-        if char_count == size as usize {
-            Ok(string)
+    /// Reads an actual string, checking its declared size.
+    ///
+    /// In lenient mode, a size mismatch is recorded as a warning instead.
+    fn read_string(&mut self, index: usize) -> Result<String> {
+        let string = self.read_string_full()?;
+        let actual_size = string.computed_utf16_units();
+        if actual_size == string.utf16_units() as usize {
+            Ok(string.into_text())
+        } else if self.options.lenient() {
+            self.warnings.push(Warning::StringSizeMismatch {
+                index,
+                expected_size: string.utf16_units(),
+                actual_size,
+            });
+            Ok(string.into_text())
         } else {
             Err(error::Parse::StringSizeMismatch {
-                expected_size: size,
-                actual_size: char_count,
+                expected_size: string.utf16_units(),
+                actual_size,
             }
             .into())
         }
     }
 
+    /// Reads an actual string, along with its declared size and its encoded length.
+    fn read_string_full(&mut self) -> Result<DexString> {
+        DexString::from_reader(&mut self.file_cursor)
+    }
+
     /// Reads the list of types.
     fn read_all_types<B>(&mut self) -> Result<()>
     where
@@ -199,19 +700,20 @@ impl DexReader {
     {
         for _ in 0..self.header.get_type_ids_size() {
             let current_offset = self.file_cursor.position();
-            let index = self.file_cursor.read_u32::<B>().with_context(|| {
-                format!("could not read type ID at offset {:#010x}", current_offset)
-            })?;
+            let index = self
+                .file_cursor
+                .read_u32::<B>()
+                .section("type_id_item", current_offset)?;
             let type_str = self
                 .strings
                 .get(index as usize)
-                .ok_or_else(|| error::Parse::UnknownStringIndex(index))?;
-            self.types.push(type_str.parse::<Type>().with_context(|| {
-                format!(
-                    "could not read type descriptor from string at index {} (`{}`)",
-                    index, type_str
-                )
-            })?);
+                .ok_or_else(|| error::Parse::UnknownStringIndex(index))
+                .section("type_id_item", current_offset)?;
+            self.types.push(
+                type_str
+                    .parse::<Type>()
+                    .section("type_id_item", current_offset)?,
+            );
         }
 
         Ok(())
@@ -224,76 +726,68 @@ impl DexReader {
     {
         for _ in 0..self.header.get_prototype_ids_size() {
             let current_offset = self.file_cursor.position();
-            let prototype_id = PrototypeIdData::from_reader::<_, B>(&mut self.file_cursor)
-                .with_context(|| {
-                    format!(
-                        "could not read prototype ID at offset {:#010x}",
-                        current_offset
-                    )
-                })?;
-
-            let parameters = if let Some(off) = prototype_id.parameters_offset() {
-                let current_offset = self.file_cursor.position();
-                self.file_cursor.set_position(u64::from(off));
-                let parameters = self
-                    .read_type_list::<B>()
-                    .context("could not read parameter list")?;
-                self.file_cursor.set_position(current_offset);
-                Some(parameters)
-            } else {
-                None
-            };
-            let shorty_str = self
-                .strings
-                .get(prototype_id.shorty_index() as usize)
-                .ok_or_else(|| error::Parse::UnknownStringIndex(prototype_id.shorty_index()))?;
-            let shorty_descriptor = shorty_str.parse::<ShortyDescriptor>().with_context(|| {
-                format!(
-                    "could not read shorty descriptor from string at index {} (`{}`)",
-                    prototype_id.shorty_index(),
-                    shorty_str
-                )
-            })?;
-            let return_type = self
-                .types
-                .get(prototype_id.return_type_index() as usize)
-                .cloned()
-                .ok_or_else(|| error::Parse::UnknownTypeIndex(prototype_id.return_type_index()))?;
-
-            self.prototypes
-                .push(Prototype::new(shorty_descriptor, return_type, parameters));
+            let prototype = self
+                .read_prototype::<B>()
+                .section("proto_id_item", current_offset)?;
+            self.prototypes.push(prototype);
         }
         Ok(())
     }
 
+    /// Reads a prototype ID, resolving its shorty descriptor, return type and parameters.
+    fn read_prototype<B>(&mut self) -> Result<Prototype>
+    where
+        B: ByteOrder,
+    {
+        let prototype_id = PrototypeIdData::from_reader::<_, B>(&mut self.file_cursor)?;
+
+        let parameters = if let Some(off) = prototype_id.parameters_offset() {
+            let current_offset = self.file_cursor.position();
+            self.file_cursor.set_position(u64::from(off));
+            let parameters = self
+                .read_type_list::<B>()
+                .section("type_list", u64::from(off))?;
+            self.file_cursor.set_position(current_offset);
+            Some(parameters)
+        } else {
+            None
+        };
+        let shorty_str = self
+            .strings
+            .get(prototype_id.shorty_index() as usize)
+            .ok_or_else(|| error::Parse::UnknownStringIndex(prototype_id.shorty_index()))?;
+        let shorty_descriptor = shorty_str.parse::<ShortyDescriptor>()?;
+        let return_type = self
+            .types
+            .get(prototype_id.return_type_index() as usize)
+            .cloned()
+            .ok_or_else(|| error::Parse::UnknownTypeIndex(prototype_id.return_type_index()))?;
+
+        Ok(Prototype::new(shorty_descriptor, return_type, parameters)
+            .with_shorty_index(prototype_id.shorty_index()))
+    }
+
     /// Reads a list of types.
     fn read_type_list<B>(&mut self) -> Result<Box<[Type]>>
     where
         B: ByteOrder,
     {
-        let current_offset = self.file_cursor.position();
-        let size = self.file_cursor.read_u32::<B>().with_context(|| {
-            format!(
-                "error reading the size of the type list at offset {:#010x}",
-                current_offset
-            )
-        })?;
+        let size = self.file_cursor.read_u32::<B>()?;
 
-        let mut type_list = Vec::with_capacity(size as usize);
+        let mut type_list =
+            checked_with_capacity("size", size, 2, remaining_bytes(&mut self.file_cursor)?)?;
         for _ in 0..size {
-            let current_offset = self.file_cursor.position();
-            let index = self.file_cursor.read_u16::<B>().with_context(|| {
-                format!(
-                    "error reading type index for type list item at offset {:#010x}",
-                    current_offset
-                )
-            })?;
-            type_list.push(
-                self.types
-                    .get(index as usize)
-                    .cloned()
-                    .ok_or_else(|| error::Parse::UnknownTypeIndex(index.into()))?,
-            );
+            let index = self.file_cursor.read_u16::<B>()?;
+            let list_type = self
+                .types
+                .get(index as usize)
+                .cloned()
+                .ok_or_else(|| error::Parse::UnknownTypeIndex(index.into()))?;
+            // Type lists hold parameter types and interfaces, which can never be `void`.
+            if list_type == Type::Void {
+                return Err(error::Parse::UnexpectedVoid("type_list").into());
+            }
+            type_list.push(list_type);
         }
 
         Ok(type_list.into_boxed_slice())
@@ -306,11 +800,13 @@ impl DexReader {
     {
         for _ in 0..self.header.get_field_ids_size() {
             let current_offset = self.file_cursor.position();
-            self.field_ids.push(
-                FieldIdData::from_reader::<_, B>(&mut self.file_cursor).with_context(|| {
-                    format!("could not read field ID at offset {:#010x}", current_offset)
-                })?,
-            );
+            let field_id = FieldIdData::from_reader::<_, B>(&mut self.file_cursor)
+                .section("field_id_item", current_offset)?;
+            if self.types.get(field_id.type_index()) == Some(&Type::Void) {
+                return Err(error::Parse::UnexpectedVoid("field_id_item"))
+                    .section("field_id_item", current_offset);
+            }
+            self.field_ids.push(field_id);
         }
 
         Ok(())
@@ -321,112 +817,228 @@ impl DexReader {
     where
         B: ByteOrder,
     {
-        for _ in 0..self.header.get_method_ids_size() {
+        for index in 0..self.header.get_method_ids_size() {
             let current_offset = self.file_cursor.position();
             self.method_ids.push(
-                MethodIdData::from_reader::<_, B>(&mut self.file_cursor).with_context(|| {
-                    format!(
-                        "could not read method ID at offset {:#010x}",
-                        current_offset
-                    )
-                })?,
+                MethodIdData::from_reader::<_, B>(&mut self.file_cursor)
+                    .section("method_id_item", current_offset)?,
             );
+            self.record_layout(LayoutItem::MethodId(index as usize), current_offset);
         }
 
         Ok(())
     }
 
-    /// Reads the list of classes.
-    fn read_class_list<B>(&mut self) -> Result<()>
+    /// Gets the map item of the given type, if the dex file has items of that type.
+    fn map_item(&self, item_type: ItemType) -> Option<MapItem> {
+        self.map
+            .iter()
+            .find(|item| item.item_type() == item_type)
+            .copied()
+    }
+
+    /// Reads the list of method handles, with the given size.
+    fn read_method_handle_list<B>(&mut self, size: u32) -> Result<()>
     where
         B: ByteOrder,
     {
-        for _ in 0..self.header.get_class_defs_size() {
+        self.method_handles = checked_with_capacity(
+            "size",
+            size,
+            u64::from(METHOD_HANDLE_ITEM_SIZE),
+            remaining_bytes(&mut self.file_cursor)?,
+        )?;
+        for _ in 0..size {
+            let current_offset = self.file_cursor.position();
+            self.method_handles.push(
+                MethodHandleData::from_reader::<_, B>(&mut self.file_cursor)
+                    .section("method_handle_item", current_offset)?,
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Reads the list of call sites, with the given size.
+    fn read_call_site_list<B>(&mut self, size: u32) -> Result<()>
+    where
+        B: ByteOrder,
+    {
+        self.call_sites = checked_with_capacity(
+            "size",
+            size,
+            u64::from(CALL_SITE_ID_ITEM_SIZE),
+            remaining_bytes(&mut self.file_cursor)?,
+        )?;
+        for _ in 0..size {
+            let current_offset = self.file_cursor.position();
+            let offset = self
+                .file_cursor
+                .read_u32::<B>()
+                .section("call_site_id_item", current_offset)?;
+            self.file_cursor.set_position(u64::from(offset));
+            self.call_sites.push(
+                CallSite::from_reader(&mut self.file_cursor, &self.method_handles)
+                    .section("call_site_item", u64::from(offset))?,
+            );
+            self.file_cursor
+                .set_position(current_offset + u64::from(CALL_SITE_ID_ITEM_SIZE));
+        }
+
+        Ok(())
+    }
+
+    /// Reads the list of classes, skipping those whose descriptor does not pass the filter.
+    fn read_class_list<B>(&mut self, class_filter: &dyn Fn(&str) -> bool) -> Result<()>
+    where
+        B: ByteOrder,
+    {
+        for def_index in 0..self.header.get_class_defs_size() {
             let class_offset = self.file_cursor.position();
-            let class_def =
-                ClassDefData::from_reader::<_, B>(&mut self.file_cursor).with_context(|| {
-                    format!(
-                        "could not read class definition data at offset {:#010x}",
-                        class_offset
-                    )
-                })?;
-
-            let new_offset = self.file_cursor.position();
-            let interfaces = if let Some(offset) = class_def.interfaces_offset() {
-                self.file_cursor.set_position(u64::from(offset));
-                self.read_type_list::<B>().with_context(||format!(
-                    "could not read interfaces list at offset {:#010x} for class at offset {:#010x}",
-                    offset,
-                    class_offset
-                ))?
-            // TODO check that all are classes (Fully Qualified Names) and no duplicates.
-            } else {
-                Vec::new().into_boxed_slice()
-            };
-            let annotations = if let Some(offset) = class_def.annotations_offset() {
-                self.file_cursor.set_position(u64::from(offset));
-                Some(self.read_annotations_directory::<B>().with_context(||format!(
-                    "could not read annotation list at offset {:#010x} for class at offset {:#010x}",
-                    offset,
-                    class_offset
-                ))?)
-            } else {
-                None
-            };
-            let class_data = if let Some(offset) = class_def.class_data_offset() {
-                self.file_cursor.set_position(u64::from(offset));
-                Some(
-                    ClassData::from_reader(&mut self.file_cursor).with_context(|| {
-                        format!(
-                        "could not read class data at offset {:#010x} for class at offset {:#010x}",
-                        offset,
-                        class_offset
-                    )
-                    })?,
-                )
-            } else {
-                None
-            };
-            let static_values = if let Some(offset) = class_def.static_values_offset() {
-                self.file_cursor.set_position(u64::from(offset));
-                Some(Array::from_reader(&mut self.file_cursor).with_context(|| {
-                    format!("could not read encoded array at offset {:#010x}", offset)
-                })?)
-            } else {
-                None
-            };
-            self.file_cursor.set_position(new_offset);
-
-            self.classes.push(Class::new(
-                class_def.class_index(),
-                class_def.access_flags(),
-                class_def.superclass_index(),
-                interfaces,
-                class_def.source_file_index(),
-                annotations,
-                class_data,
-                static_values,
-            ));
+            let class_def = ClassDefData::from_reader::<_, B>(&mut self.file_cursor)
+                .section("class_def_item", class_offset)?;
+            let class_end = self.file_cursor.position();
+            let class_name = self
+                .class_name(class_def.class_index())
+                .section("class_def_item", class_offset)?;
+            if !class_filter(&format!("L{};", class_name)) {
+                continue;
+            }
+            let class = self
+                .read_class::<B>(&class_def)
+                .section("class_def_item", class_offset)?;
+            if let Some(layout) = &mut self.layout {
+                layout.record(
+                    LayoutItem::ClassDef(self.classes.len()),
+                    class_offset,
+                    class_end,
+                );
+            }
+            self.classes.push(class);
+            self.class_def_indexes.push(def_index as usize);
         }
 
         Ok(())
     }
 
+    /// Gets the name of the class with the given type index, without the leading `L` and the
+    /// trailing `;`.
+    pub(crate) fn class_name(&self, type_index: u32) -> Result<&str> {
+        match self.types.get(type_index as usize) {
+            Some(Type::FullyQualifiedName(name)) => Ok(name),
+            Some(other) => Err(error::Parse::NotAClass(other.to_descriptor()).into()),
+            None => Err(error::Parse::UnknownTypeIndex(type_index).into()),
+        }
+    }
+
+    /// Gets the type with the given index.
+    // Indexes in the dex file are at most 32 bits long.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn type_at(&self, index: usize) -> Result<&Type> {
+        self.types
+            .get(index)
+            .ok_or_else(|| error::Parse::UnknownTypeIndex(index as u32).into())
+    }
+
+    /// Gets the prototype with the given index.
+    // Indexes in the dex file are at most 32 bits long.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn prototype_at(&self, index: usize) -> Result<&Prototype> {
+        self.prototypes
+            .get(index)
+            .ok_or_else(|| error::Parse::UnknownPrototypeIndex(index as u32).into())
+    }
+
+    /// Gets the string with the given index.
+    // Indexes in the dex file are at most 32 bits long.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn string_at(&self, index: usize) -> Result<&str> {
+        self.strings
+            .get(index)
+            .ok_or_else(|| error::Parse::UnknownStringIndex(index as u32).into())
+    }
+
+    /// Gets the string with the given index as a name, sharing it if the strings are shared.
+    // Indexes in the dex file are at most 32 bits long.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn name_at(&self, index: usize) -> Result<Arc<str>> {
+        self.strings
+            .get_name(index)
+            .ok_or_else(|| error::Parse::UnknownStringIndex(index as u32).into())
+    }
+
+    /// Reads the data a class definition points to.
+    fn read_class<B>(&mut self, class_def: &ClassDefData) -> Result<Class>
+    where
+        B: ByteOrder,
+    {
+        let new_offset = self.file_cursor.position();
+        let interfaces = if let Some(offset) = class_def.interfaces_offset() {
+            self.file_cursor.set_position(u64::from(offset));
+            self.read_type_list::<B>()
+                .section("type_list", u64::from(offset))?
+        // TODO check that all are classes (Fully Qualified Names) and no duplicates.
+        } else {
+            Vec::new().into_boxed_slice()
+        };
+        let annotations = if let Some(offset) = class_def.annotations_offset() {
+            self.file_cursor.set_position(u64::from(offset));
+            Some(
+                self.read_annotations_directory::<B>()
+                    .section("annotations_directory_item", u64::from(offset))?,
+            )
+        } else {
+            None
+        };
+        // Class data that cannot be read does not abort the parsing: the class is kept without
+        // members, and the error is recorded.
+        let mut class_error = None;
+        let class_data = if let Some(offset) = class_def.class_data_offset() {
+            self.file_cursor.set_position(u64::from(offset));
+            match ClassData::from_reader(&mut self.file_cursor)
+                .section("class_data_item", u64::from(offset))
+            {
+                Ok(class_data) => Some(class_data),
+                Err(error) => {
+                    class_error = Some(error.chain_message());
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        self.class_errors.push(class_error);
+        let static_values = if let Some(offset) = class_def.static_values_offset() {
+            self.file_cursor.set_position(u64::from(offset));
+            Some(
+                Array::from_reader(&mut self.file_cursor)
+                    .section("encoded_array_item", u64::from(offset))?,
+            )
+        } else {
+            None
+        };
+        self.file_cursor.set_position(new_offset);
+
+        Ok(Class::new(
+            class_def.class_index(),
+            class_def.access_flags(),
+            class_def.superclass_index(),
+            interfaces,
+            class_def.source_file_index(),
+            annotations,
+            class_data,
+            static_values,
+        ))
+    }
+
     /// Reads an annotations directory.
     fn read_annotations_directory<B: ByteOrder>(&mut self) -> Result<AnnotationsDirectory> {
-        let current_offset = self.file_cursor.position();
-        let read = AnnotationsDirectoryOffsets::from_reader::<_, B>(&mut self.file_cursor)
-            .with_context(|| {
-                format!(
-                    "could not read annotation directory at offset {:#010x}",
-                    current_offset
-                )
-            })?;
+        let read = AnnotationsDirectoryOffsets::from_reader::<_, B>(&mut self.file_cursor)?;
 
         let class_annotations = if let Some(off) = read.class_annotations_offset() {
             self.file_cursor.set_position(u64::from(off));
             self.read_annotation_set::<B>()
-                .context("could not read class annotations set")?
+                .section("annotation_set_item", u64::from(off))?
         } else {
             Vec::new().into_boxed_slice()
         };
@@ -436,7 +1048,7 @@ impl DexReader {
             field_annotations.push(FieldAnnotations::new(
                 fa_off.field_index(),
                 self.read_annotation_set::<B>()
-                    .context("could not read field annotations set")?,
+                    .section("annotation_set_item", u64::from(fa_off.offset()))?,
             ));
         }
         let mut method_annotations = Vec::with_capacity(read.method_annotations().len());
@@ -445,7 +1057,7 @@ impl DexReader {
             method_annotations.push(MethodAnnotations::new(
                 ma_off.method_index(),
                 self.read_annotation_set::<B>()
-                    .context("could not read method annotations set")?,
+                    .section("annotation_set_item", u64::from(ma_off.offset()))?,
             ));
         }
         let mut parameter_annotations = Vec::with_capacity(read.parameter_annotations().len());
@@ -453,8 +1065,8 @@ impl DexReader {
             self.file_cursor.set_position(u64::from(pa_off.offset()));
             parameter_annotations.push(ParameterAnnotations::new(
                 pa_off.method_index(),
-                self.read_annotation_set::<B>()
-                    .context("could not read parameter annotations set")?,
+                self.read_annotation_set_ref_list::<B>()
+                    .section("annotation_set_ref_list", u64::from(pa_off.offset()))?,
             ));
         }
 
@@ -466,31 +1078,50 @@ impl DexReader {
         ))
     }
 
+    /// Reads an annotation set reference list, with the annotation sets it references.
+    fn read_annotation_set_ref_list<B>(&mut self) -> Result<Vec<Box<[Annotation]>>>
+    where
+        B: ByteOrder,
+    {
+        let size = self.file_cursor.read_u32::<B>()?;
+        let mut offsets =
+            checked_with_capacity("size", size, 4, remaining_bytes(&mut self.file_cursor)?)?;
+        for _ in 0..size {
+            offsets.push(self.file_cursor.read_u32::<B>()?);
+        }
+
+        let mut annotation_sets = Vec::with_capacity(offsets.len());
+        for &offset in &offsets {
+            if offset == 0 {
+                annotation_sets.push(Vec::new().into_boxed_slice());
+            } else {
+                self.file_cursor.set_position(u64::from(offset));
+                annotation_sets.push(
+                    self.read_annotation_set::<B>()
+                        .section("annotation_set_item", u64::from(offset))?,
+                );
+            }
+        }
+        Ok(annotation_sets)
+    }
+
     /// Reads an annotation set.
     fn read_annotation_set<B>(&mut self) -> Result<Box<[Annotation]>>
     where
         B: ByteOrder,
     {
-        let current_offset = self.file_cursor.position();
-        let size = self.file_cursor.read_u32::<B>().with_context(|| {
-            format!(
-                "error reading annotation set size at offset {:#010x}",
-                current_offset
-            )
-        })?;
-        let mut annotation_set = Vec::with_capacity(size as usize);
+        let size = self.file_cursor.read_u32::<B>()?;
+        let mut annotation_set =
+            checked_with_capacity("size", size, 4, remaining_bytes(&mut self.file_cursor)?)?;
 
         for _ in 0..size {
-            let current_offset = self.file_cursor.position();
-            let annotation_offset = self.file_cursor.read_u32::<B>().with_context(|| {
-                format!(
-                    "error reading annotation offset at offset {:#010x}",
-                    current_offset
-                )
-            })?;
+            let annotation_offset = self.file_cursor.read_u32::<B>()?;
             let current_offset = self.file_cursor.position();
             self.file_cursor.set_position(u64::from(annotation_offset));
-            annotation_set.push(self.read_annotation()?);
+            annotation_set.push(
+                self.read_annotation()
+                    .section("annotation_item", u64::from(annotation_offset))?,
+            );
             self.file_cursor.set_position(current_offset);
         }
 
@@ -498,100 +1129,15 @@ impl DexReader {
     }
 
     /// Reads an annotation.
+    #[allow(clippy::cast_possible_truncation)]
     fn read_annotation(&mut self) -> Result<Annotation> {
-        let current_offset = self.file_cursor.position();
-        let annotation = Annotation::from_reader(&mut self.file_cursor).with_context(|| {
-            format!(
-                "could not read annotation at offset {:#010x}",
-                current_offset
-            )
-        })?;
-
-        Ok(annotation)
-    }
-
-    // /// Reads the map of the dex file.
-    // fn read_map<B>(&mut self) -> Result<()>
-    // where
-    //     B: ByteOrder,
-    // {
-    //     let map = Map::from_reader::<_, E>(&mut self.reader, &mut self.offset_map).context(
-    //         format_err!("error reading map at offset {:#010x}", self.current_offset),
-    //     )?;
-    //     if let Some(count) = map.get_num_items_for(ItemType::TypeList) {
-    //         self.type_lists.reserve_exact(count);
-    //     }
-    //     if let Some(count) = map.get_num_items_for(ItemType::AnnotationSetList) {
-    //         self.annotation_set_ref_list.reserve_exact(count);
-    //     }
-    //     if let Some(count) = map.get_num_items_for(ItemType::AnnotationSet) {
-    //         self.annotation_sets.reserve_exact(count);
-    //     }
-    //     if let Some(count) = map.get_num_items_for(ItemType::Annotation) {
-    //         self.annotations.reserve_exact(count);
-    //     }
-    //     if let Some(count) = map.get_num_items_for(ItemType::EncodedArray) {
-    //         self.arrays.reserve_exact(count);
-    //     }
-    //     if let Some(count) = map.get_num_items_for(ItemType::AnnotationsDirectory) {
-    //         self.annotations_directories.reserve_exact(count);
-    //     }
-    //     self.current_offset += 4 + MAP_ITEM_SIZE * map.get_item_list().len() as u32;
-
-    //     Ok(())
-    // }
-
-    // /// Reads a list of annotation sets.
-    // fn read_annotation_set_list<B>(&mut self) -> Result<()>
-    // where
-    //     B: ByteOrder,
-    // {
-    //     let size = self.reader.read_u32::<E>().with_context(|| {
-    //         format!(
-    //             "error reading annotation set list size at offset {:#010x}",
-    //             self.current_offset
-    //         )
-    //     })?;
-    //     let mut annotation_set_list = Vec::with_capacity(size as usize);
-
-    //     for _ in 0..size {
-    //         let annotation_set_offset = self.reader.read_u32::<E>().with_context(|| {
-    //             format!(
-    //                 "could not read annotation set offset for an annotation set in the annotation \
-    //              set list at offset {:#010x}",
-    //                 self.current_offset
-    //             )
-    //         })?;
-    //         self.offset_map
-    //             .insert(annotation_set_offset, OffsetType::AnnotationSet);
-    //         annotation_set_list.push(annotation_set_offset);
-    //     }
-    //     self.annotation_set_ref_list
-    //         .push(annotation_set_list.into_boxed_slice());
-    //     self.current_offset += 4 + ANNOTATION_SET_REF_SIZE * size;
-
-    //     Ok(())
-    // }
-
-    // /// Reads code information.
-    // fn read_code_item<B>(&mut self) -> Result<()>
-    // where
-    //     B: ByteOrder,
-    // {
-    //     let (code_item, read) =
-    //         CodeItem::from_reader::<_, E>(&mut self.reader, &mut self.offset_map).with_context(
-    //             || {
-    //                 format!(
-    //                     "could not read code item at offset {:#010x}",
-    //                     self.current_offset
-    //                 )
-    //             },
-    //         )?;
-    //     self.code_segments.push((self.current_offset, code_item));
-    //     self.current_offset += read;
-
-    //     Ok(())
-    // }
+        let start = self.file_cursor.position();
+        let annotation = Annotation::from_reader(&mut self.file_cursor)?;
+        // The annotation was read from the file contents, so its offsets fit in memory.
+        let raw_bytes =
+            self.file_cursor.get_ref()[start as usize..self.file_cursor.position() as usize].into();
+        Ok(annotation.with_raw_bytes(raw_bytes))
+    }
 
     // /// Reads debug information.
     // fn read_debug_info(&mut self) -> Result<()> {
@@ -608,29 +1154,63 @@ impl DexReader {
     // }
 }
 
+/// Extension trait to attach the section and offset of the item being read to an error.
+trait SectionContext<T> {
+    /// Wraps the error in an `error::Parse::SectionRead` error for the given section and offset.
+    ///
+    /// Errors that already are an `error::Parse::SectionRead` error are returned unchanged, so
+    /// that nested items report the innermost section and offset.
+    fn section(self, section: &'static str, offset: u64) -> Result<T>;
+}
+
+impl<T, E> SectionContext<T> for std::result::Result<T, E>
+where
+    E: Into<Error>,
+{
+    #[allow(clippy::cast_possible_truncation)]
+    fn section(self, section: &'static str, offset: u64) -> Result<T> {
+        // Dex files can't be bigger than `u32::max_value()` bytes.
+        self.map_err(|e| match e.into() {
+            located @ Error::Parse(error::Parse::SectionRead { .. }) => located,
+            source => error::Parse::SectionRead {
+                section,
+                offset: offset as u32,
+                source: Box::new(source),
+            }
+            .into(),
+        })
+    }
+}
+
 /// Reads a `uleb128` from a reader.
 ///
 /// Returns the `u32` represented by the `uleb128` and the number of bytes read.
+///
+/// # Errors
+///
+/// Returns an error if the input ends before the last byte of the number, if it does not fit
+/// in a `u32`, or if it takes more than 5 bytes.
 pub fn uleb128<R>(reader: &mut R) -> Result<(u32, u32)>
 where
     R: Read,
 {
     let mut result = 0;
-    let mut read = 0;
-    for (i, byte) in reader.bytes().enumerate() {
-        let byte = byte.with_context(|| format!("could not read byte {}", i))?;
+    let mut buffer = [0_u8; 1];
+    for i in 0..5 {
+        reader.read_exact(&mut buffer)?;
+        let byte = buffer[0];
         let payload = u32::from(byte & 0b0111_1111);
-        match i {
-            0..=4 => result |= payload << (i * 7),
-            _ => return Err(error::Parse::InvalidLeb128.into()),
+        // Only the lowest 4 bits of the fifth byte fit in a `u32`.
+        if i == 4 && payload > 0b1111 {
+            return Err(error::Parse::Leb128OutOfRange(byte).into());
         }
+        result |= payload << (i * 7);
 
         if byte & 0b1000_0000 == 0x00 {
-            read = i + 1;
-            break;
+            return Ok((result, i + 1));
         }
     }
-    Ok((result, read as u32))
+    Err(error::Parse::InvalidLeb128.into())
 }
 
 /// `U32p1` definition.
@@ -669,25 +1249,76 @@ where
 /// Reads a `sleb128` from a reader.
 ///
 /// Returns the `i32` represented by the `sleb128` and the number of bytes read.
+///
+/// # Errors
+///
+/// Returns an error if the input ends before the last byte of the number, or if it takes more
+/// than 5 bytes.
 pub fn sleb128<R>(reader: &mut R) -> Result<(i32, u32)>
 where
     R: Read,
 {
-    let (uleb128, read) = uleb128(reader)?;
-    let s_bits = read * 7;
-    let mut signed = uleb128 as i32;
+    let mut result = 0_u32;
+    let mut buffer = [0_u8; 1];
+    for i in 0..5 {
+        reader.read_exact(&mut buffer)?;
+        let byte = buffer[0];
+        // The bits of the fifth byte above the 32nd bit only hold the sign extension.
+        result |= u32::from(byte & 0b0111_1111) << (i * 7);
 
-    if (signed & (1 << (s_bits - 1))) != 0 {
-        signed |= -1 << s_bits; // Sign extension
+        if byte & 0b1000_0000 == 0x00 {
+            let read = i + 1;
+            let s_bits = read * 7;
+            #[allow(clippy::cast_possible_wrap)]
+            let mut signed = result as i32;
+            if s_bits < 32 && byte & 0b0100_0000 != 0 {
+                signed |= -1 << s_bits; // Sign extension
+            }
+            return Ok((signed, read));
+        }
     }
+    Err(error::Parse::InvalidLeb128.into())
+}
 
-    Ok((signed, read))
+/// Gets the number of bytes left to read in a reader.
+#[allow(clippy::seek_from_current)]
+pub(crate) fn remaining_bytes<R>(reader: &mut R) -> Result<u64>
+where
+    R: Seek,
+{
+    let position = reader.seek(SeekFrom::Current(0))?;
+    let end = reader.seek(SeekFrom::End(0))?;
+    let _ = reader.seek(SeekFrom::Start(position))?;
+    Ok(end.saturating_sub(position))
+}
+
+/// Creates a vector for `count` items read from the input, checking that they fit in the
+/// remaining bytes of the input.
+///
+/// Each item takes at least `element_size` bytes, so larger counts can only come from corrupt or
+/// crafted files, and allocating memory for them upfront could exhaust the available memory.
+pub(crate) fn checked_with_capacity<T>(
+    size_name: &'static str,
+    count: u32,
+    element_size: u64,
+    remaining_bytes: u64,
+) -> Result<Vec<T>, error::Parse> {
+    let limit = remaining_bytes / element_size;
+    if u64::from(count) > limit {
+        return Err(error::Parse::ImplausibleSize {
+            size_name,
+            size: u64::from(count),
+            limit,
+        });
+    }
+    Ok(Vec::with_capacity(count as usize))
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{sleb128, uleb128, uleb128p1, U32p1};
-    use std::io::Cursor;
+    use super::{checked_with_capacity, remaining_bytes, sleb128, uleb128, uleb128p1, U32p1};
+    use crate::error;
+    use std::io::{self, Cursor};
 
     #[test]
     fn ut_sleb128() {
@@ -698,6 +1329,18 @@ mod tests {
             sleb128(&mut Cursor::new(&[0x80_u8, 0x7f_u8])).unwrap().0,
             -128
         );
+        assert_eq!(
+            sleb128(&mut Cursor::new(&[0x80_u8, 0x80, 0x80, 0x80, 0x78]))
+                .unwrap()
+                .0,
+            i32::min_value()
+        );
+        assert_eq!(
+            sleb128(&mut Cursor::new(&[0xff_u8, 0xff, 0xff, 0xff, 0x07]))
+                .unwrap()
+                .0,
+            i32::max_value()
+        );
     }
 
     #[test]
@@ -711,6 +1354,46 @@ mod tests {
         );
     }
 
+    #[test]
+    fn ut_uleb128_range() {
+        assert_eq!(
+            uleb128(&mut Cursor::new(&[0xff_u8, 0xff, 0xff, 0xff, 0x0f]))
+                .unwrap()
+                .0,
+            u32::max_value()
+        );
+        let error = uleb128(&mut Cursor::new(&[0xff_u8, 0xff, 0xff, 0xff, 0x1f])).unwrap_err();
+        assert!(matches!(
+            error,
+            error::Error::Parse(error::Parse::Leb128OutOfRange(0x1f))
+        ));
+    }
+
+    #[test]
+    fn ut_leb128_truncated() {
+        for bytes in &[&[][..], &[0x80_u8], &[0xff_u8, 0xff]] {
+            assert!(matches!(
+                uleb128(&mut Cursor::new(bytes)),
+                Err(error::Error::Io(ref error)) if error.kind() == io::ErrorKind::UnexpectedEof
+            ));
+            assert!(matches!(
+                sleb128(&mut Cursor::new(bytes)),
+                Err(error::Error::Io(ref error)) if error.kind() == io::ErrorKind::UnexpectedEof
+            ));
+        }
+
+        // The fifth byte cannot have the continuation bit set, even if more bytes follow.
+        let bytes = [0x80_u8, 0x80, 0x80, 0x80, 0x80, 0x00];
+        assert!(matches!(
+            uleb128(&mut Cursor::new(&bytes)),
+            Err(error::Error::Parse(error::Parse::InvalidLeb128))
+        ));
+        assert!(matches!(
+            sleb128(&mut Cursor::new(&bytes)),
+            Err(error::Error::Parse(error::Parse::InvalidLeb128))
+        ));
+    }
+
     #[test]
     fn ut_uleb128p1() {
         assert_eq!(
@@ -730,4 +1413,23 @@ mod tests {
             U32p1::U32(16255)
         );
     }
+
+    #[test]
+    fn it_checks_capacities_against_the_remaining_input() {
+        let mut reader = Cursor::new([0_u8; 10]);
+        reader.set_position(2);
+        assert_eq!(8, remaining_bytes(&mut reader).unwrap());
+        assert_eq!(2, reader.position());
+
+        let vec = checked_with_capacity::<u32>("size", 2, 4, 8).unwrap();
+        assert!(vec.capacity() >= 2);
+        assert!(matches!(
+            checked_with_capacity::<u32>("size", 3, 4, 8),
+            Err(error::Parse::ImplausibleSize {
+                size_name: "size",
+                size: 3,
+                limit: 2,
+            })
+        ));
+    }
 }
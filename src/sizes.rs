@@ -5,6 +5,9 @@ pub(crate) const PROTO_ID_ITEM_SIZE: u32 = 0x0c;
 pub(crate) const FIELD_ID_ITEM_SIZE: u32 = 0x08;
 pub(crate) const METHOD_ID_ITEM_SIZE: u32 = 0x08;
 pub(crate) const CLASS_DEF_ITEM_SIZE: u32 = 0x20;
+pub(crate) const CALL_SITE_ID_ITEM_SIZE: u32 = 0x04;
+pub(crate) const METHOD_HANDLE_ITEM_SIZE: u32 = 0x08;
+pub(crate) const CODE_ITEM_HEADER_SIZE: u32 = 0x10;
 // pub(crate) const MAP_ITEM_SIZE: u32 = 12;
 // pub(crate) const TYPE_ITEM_SIZE: u32 = 2;
 // pub(crate) const ANNOTATION_SET_REF_SIZE: u32 = 4;
@@ -0,0 +1,109 @@
+//! Strings of a dex file decoded on demand.
+
+use crate::{
+    error::{self, Result},
+    read::DexReader,
+    ParseOptions,
+};
+use std::{
+    collections::HashMap,
+    fmt,
+    io::BufRead,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+/// String table of a dex file whose strings are decoded only when they are requested.
+///
+/// Only the header of the file is parsed when it is created. Each string is decoded the first
+/// time it is requested, by reading its offset from the string ID list and its data from the
+/// data section, and it is cached, so later requests for the same index do not decode it again.
+///
+/// This is meant for tools that only need a few strings of big files, such as the ones referenced
+/// by a handful of instructions, since `Dex::string_at()` requires the full file to be parsed,
+/// with all of its strings decoded. The contents of the file are still read into memory.
+pub struct LazyStrings {
+    reader: Mutex<DexReader>,
+    cache: Mutex<HashMap<u32, Arc<str>>>,
+}
+
+impl LazyStrings {
+    /// Creates the lazy string table of the dex file in the given reader.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the dex file cannot be read or its header is not valid.
+    pub fn from_reader<R, S>(reader: R, size: S) -> Result<Self>
+    where
+        R: BufRead,
+        S: Into<Option<usize>>,
+    {
+        let reader = DexReader::from_read(reader, size.into(), ParseOptions::default())?;
+        Ok(Self {
+            reader: Mutex::new(reader),
+            cache: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Gets the number of strings declared in the header of the dex file.
+    pub fn len(&self) -> usize {
+        self.reader
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .header
+            .get_string_ids_size() as usize
+    }
+
+    /// Checks if the dex file declares no strings.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets the number of strings decoded so far.
+    pub fn decoded_count(&self) -> usize {
+        self.cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    /// Gets the string at the given index of the string table, decoding it if it was not
+    /// requested before.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there is no string at the given index, or if its string ID or its data
+    /// is not valid.
+    pub fn string_at(&self, index: u32) -> Result<Arc<str>> {
+        if let Some(string) = self
+            .cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&index)
+        {
+            return Ok(Arc::clone(string));
+        }
+
+        let string: Arc<str> = {
+            let mut reader = self.reader.lock().unwrap_or_else(PoisonError::into_inner);
+            if index >= reader.header.get_string_ids_size() {
+                return Err(error::Parse::UnknownStringIndex(index).into());
+            }
+            reader.read_string_at(index as usize)?.into()
+        };
+        let _ = self
+            .cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(index, Arc::clone(&string));
+        Ok(string)
+    }
+}
+
+impl fmt::Debug for LazyStrings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LazyStrings")
+            .field("len", &self.len())
+            .field("decoded", &self.decoded_count())
+            .finish()
+    }
+}
@@ -0,0 +1,79 @@
+//! Time spent in each phase of the parsing of a dex file.
+
+use std::{fmt, time::Duration};
+
+/// Time spent in each phase of the parsing of a dex file, as measured by
+/// `Dex::parse_bytes_measured()`.
+///
+/// This helps finding which phase to optimize when parsing is slow for a given file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ParseTimings {
+    pub(crate) header: Duration,
+    pub(crate) strings: Duration,
+    pub(crate) id_tables: Duration,
+    pub(crate) class_data: Duration,
+    pub(crate) code_items: Duration,
+    pub(crate) finish: Duration,
+}
+
+impl ParseTimings {
+    /// Gets the time spent reading the file contents and its header.
+    pub fn header(&self) -> Duration {
+        self.header
+    }
+
+    /// Gets the time spent reading the map and decoding the strings of the string ID list.
+    pub fn strings(&self) -> Duration {
+        self.strings
+    }
+
+    /// Gets the time spent reading the type, prototype, field, method, method handle and call
+    /// site ID lists.
+    pub fn id_tables(&self) -> Duration {
+        self.id_tables
+    }
+
+    /// Gets the time spent reading the class definitions and their class data, annotations and
+    /// static values.
+    pub fn class_data(&self) -> Duration {
+        self.class_data
+    }
+
+    /// Gets the time spent reading the code items, along with the hidden API data.
+    pub fn code_items(&self) -> Duration {
+        self.code_items
+    }
+
+    /// Gets the time spent checking the rest of the file and building the final structure.
+    pub fn finish(&self) -> Duration {
+        self.finish
+    }
+
+    /// Gets the total time spent parsing the file.
+    pub fn total(&self) -> Duration {
+        self.header
+            + self.strings
+            + self.id_tables
+            + self.class_data
+            + self.code_items
+            + self.finish
+    }
+}
+
+impl fmt::Display for ParseTimings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let phases = [
+            ("header", self.header),
+            ("strings", self.strings),
+            ("id tables", self.id_tables),
+            ("class data", self.class_data),
+            ("code items", self.code_items),
+            ("finish", self.finish),
+            ("total", self.total()),
+        ];
+        for (name, duration) in &phases {
+            writeln!(f, "{:>10}: {:?}", name, duration)?;
+        }
+        Ok(())
+    }
+}
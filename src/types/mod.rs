@@ -4,10 +4,13 @@ pub mod read;
 use self::read::ClassData;
 use crate::error;
 use bitflags::bitflags;
-use std::{fmt, ops::Deref, str::FromStr};
+#[cfg(feature = "cache")]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{convert::TryFrom, fmt, ops::Deref, str::FromStr};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 /// Basic built-in types.
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
 pub enum Type {
     /// Void type.
     Void,
@@ -43,39 +46,145 @@ pub enum Type {
 impl FromStr for Type {
     type Err = error::Parse;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || error::Parse::InvalidTypeDescriptor(s.to_owned());
         let mut chars = s.chars();
-        match chars.next() {
-            Some('V') => Ok(Self::Void),
-            Some('Z') => Ok(Self::Boolean),
-            Some('B') => Ok(Self::Byte),
-            Some('S') => Ok(Self::Short),
-            Some('C') => Ok(Self::Char),
-            Some('I') => Ok(Self::Int),
-            Some('J') => Ok(Self::Long),
-            Some('F') => Ok(Self::Float),
-            Some('D') => Ok(Self::Double),
-            Some('L') => Ok(Self::FullyQualifiedName(chars.collect())),
+        let primitive = match chars.next() {
+            Some('V') => Self::Void,
+            Some('Z') => Self::Boolean,
+            Some('B') => Self::Byte,
+            Some('S') => Self::Short,
+            Some('C') => Self::Char,
+            Some('I') => Self::Int,
+            Some('J') => Self::Long,
+            Some('F') => Self::Float,
+            Some('D') => Self::Double,
+            Some('L') => {
+                // The name must be non-empty and end with the only `;` of the descriptor.
+                let name = chars.as_str();
+                return if name.len() > 1 && name.find(';') == Some(name.len() - 1) {
+                    Ok(Self::FullyQualifiedName(name.to_owned()))
+                } else {
+                    Err(invalid())
+                };
+            }
             Some('[') => {
-                let mut dimensions = 1;
-                loop {
-                    match chars.next() {
-                        Some('[') => dimensions += 1,
-                        Some(t) => {
-                            let mut type_str = String::with_capacity(s.len() - dimensions as usize);
-                            type_str.push(t);
-                            type_str.push_str(chars.as_str());
-                            return Ok(Self::Array {
-                                dimensions,
-                                array_type: Box::new(type_str.parse()?),
-                            });
-                        }
-                        None => {
-                            return Err(error::Parse::InvalidTypeDescriptor(s.to_owned()));
-                        }
-                    }
+                let element = s.trim_start_matches('[');
+                let dimensions = u8::try_from(s.len() - element.len()).map_err(|_| invalid())?;
+                return match element.parse() {
+                    Ok(Self::Void) | Err(_) => Err(invalid()),
+                    Ok(array_type) => Ok(Self::Array {
+                        dimensions,
+                        array_type: Box::new(array_type),
+                    }),
+                };
+            }
+            _ => return Err(invalid()),
+        };
+        // Primitive types are a single character.
+        if chars.as_str().is_empty() {
+            Ok(primitive)
+        } else {
+            Err(invalid())
+        }
+    }
+}
+
+impl Type {
+    /// Checks if the type is a primitive type.
+    ///
+    /// `void` is not considered a primitive type, since it's only valid as a return type.
+    pub fn is_primitive(&self) -> bool {
+        match self {
+            Self::Boolean
+            | Self::Byte
+            | Self::Short
+            | Self::Char
+            | Self::Int
+            | Self::Long
+            | Self::Float
+            | Self::Double => true,
+            Self::Void | Self::FullyQualifiedName(_) | Self::Array { .. } => false,
+        }
+    }
+
+    /// Checks if the type is a reference type, that is, an object or an array.
+    pub fn is_reference(&self) -> bool {
+        matches!(self, Self::FullyQualifiedName(_) | Self::Array { .. })
+    }
+
+    /// Checks if the type is an array.
+    pub fn is_array(&self) -> bool {
+        matches!(self, Self::Array { .. })
+    }
+
+    /// Gets the type descriptor of the type, as it appears in the dex file.
+    pub fn to_descriptor(&self) -> String {
+        match self {
+            Self::Void => "V".to_owned(),
+            Self::Boolean => "Z".to_owned(),
+            Self::Byte => "B".to_owned(),
+            Self::Short => "S".to_owned(),
+            Self::Char => "C".to_owned(),
+            Self::Int => "I".to_owned(),
+            Self::Long => "J".to_owned(),
+            Self::Float => "F".to_owned(),
+            Self::Double => "D".to_owned(),
+            Self::FullyQualifiedName(name) => format!("L{}", name),
+            Self::Array {
+                dimensions,
+                array_type,
+            } => format!(
+                "{}{}",
+                "[".repeat(*dimensions as usize),
+                array_type.to_descriptor()
+            ),
+        }
+    }
+
+    /// Gets the name of the type as it is written in Java, such as `java.lang.String[]`.
+    ///
+    /// The separator between nested classes and the classes containing them is chosen with the
+    /// given style. Note that anonymous and local classes, such as `Outer$1`, have no Java name,
+    /// so they are separated in the same way.
+    pub fn to_java_name(&self, nest_style: NestStyle) -> String {
+        match self {
+            Self::FullyQualifiedName(name) => {
+                let name = name.trim_end_matches(';').replace('/', ".");
+                match nest_style {
+                    NestStyle::Binary => name,
+                    NestStyle::Canonical => name.replace('$', "."),
                 }
             }
-            _ => Err(error::Parse::InvalidTypeDescriptor(s.to_owned())),
+            Self::Array {
+                dimensions,
+                array_type,
+            } => format!(
+                "{}{}",
+                array_type.to_java_name(nest_style),
+                "[]".repeat(*dimensions as usize)
+            ),
+            _ => self.to_string(),
+        }
+    }
+
+    /// Gets the number of registers needed to hold a value of this type.
+    ///
+    /// `long` and `double` values are wide, and need two registers. Any other value needs one
+    /// register, including references to arrays of wide values.
+    pub fn register_width(&self) -> u8 {
+        match self {
+            Self::Long | Self::Double => 2,
+            _ => 1,
+        }
+    }
+
+    /// Gets the type of the elements of an array, without any array dimension.
+    ///
+    /// For non-array types, the type itself is returned.
+    pub fn element_type(&self) -> &Self {
+        match self {
+            Self::Array { array_type, .. } => array_type,
+            _ => self,
         }
     }
 }
@@ -101,7 +210,19 @@ impl fmt::Display for Type {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Style of the names of nested classes, used by `Type::to_java_name()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NestStyle {
+    /// Keeps the `$` separator of the binary name, as in `com.foo.Outer$Inner`, as
+    /// `Class.getName()` does.
+    Binary,
+    /// Separates nested classes with a dot, as in `com.foo.Outer.Inner`, as they are written in
+    /// Java source code.
+    Canonical,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
 enum ShortyReturnType {
     Void,
     Boolean,
@@ -166,7 +287,8 @@ impl From<ShortyFieldType> for ShortyReturnType {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
 enum ShortyFieldType {
     Boolean,
     Byte,
@@ -198,6 +320,7 @@ impl ShortyFieldType {
 
 /// Short form of type descriptor.
 #[derive(Debug)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
 pub struct ShortyDescriptor {
     return_type: ShortyReturnType,
     field_types: Box<[ShortyFieldType]>,
@@ -225,8 +348,10 @@ impl FromStr for ShortyDescriptor {
 
 /// Prototype implementation.
 #[derive(Debug)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
 pub struct Prototype {
     descriptor: ShortyDescriptor,
+    shorty_index: Option<u32>,
     return_type: Type,
     parameters: Option<Box<[Type]>>,
 }
@@ -240,14 +365,54 @@ impl Prototype {
     ) -> Self {
         Self {
             descriptor,
+            shorty_index: None,
             return_type,
             parameters: parameters.into(),
         }
     }
+
+    /// Sets the index of the short form descriptor in the string IDs list.
+    pub(crate) fn with_shorty_index(mut self, shorty_index: u32) -> Self {
+        self.shorty_index = Some(shorty_index);
+        self
+    }
+
+    /// Gets the index of the short form descriptor of the prototype in the string IDs list, if
+    /// the prototype was read from a dex file.
+    pub fn shorty_index(&self) -> Option<u32> {
+        self.shorty_index
+    }
+
+    /// Gets the return type of the prototype.
+    pub fn return_type(&self) -> &Type {
+        &self.return_type
+    }
+
+    /// Gets the parameter types of the prototype.
+    pub fn parameters(&self) -> &[Type] {
+        self.parameters
+            .as_ref()
+            .map_or(&[], |parameters| parameters)
+    }
+
+    /// Checks if the short form descriptor of the prototype matches its return and parameter
+    /// types.
+    pub fn matches_shorty(&self) -> bool {
+        let parameters = self.parameters();
+        ShortyReturnType::from(self.return_type.clone()) == self.descriptor.return_type
+            && parameters.len() == self.descriptor.field_types.len()
+            && parameters
+                .iter()
+                .zip(self.descriptor.field_types.iter())
+                .all(|(parameter, &field_type)| {
+                    ShortyReturnType::from(parameter.clone()) == ShortyReturnType::from(field_type)
+                })
+    }
 }
 
 /// Annotation visibility.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
 pub enum Visibility {
     /// Build time visibility.
     Build,
@@ -257,8 +422,116 @@ pub enum Visibility {
     System,
 }
 
+/// Kind of a method handle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
+pub enum MethodHandleType {
+    /// Static field setter.
+    StaticPut,
+    /// Static field getter.
+    StaticGet,
+    /// Instance field setter.
+    InstancePut,
+    /// Instance field getter.
+    InstanceGet,
+    /// Static method invoker.
+    InvokeStatic,
+    /// Instance method invoker.
+    InvokeInstance,
+    /// Constructor invoker.
+    InvokeConstructor,
+    /// Direct method invoker.
+    InvokeDirect,
+    /// Interface method invoker.
+    InvokeInterface,
+}
+
+impl MethodHandleType {
+    /// Checks if the method handle accesses a field, in which case it references a field ID
+    /// instead of a method ID.
+    pub fn is_field_accessor(self) -> bool {
+        matches!(
+            self,
+            Self::StaticPut | Self::StaticGet | Self::InstancePut | Self::InstanceGet
+        )
+    }
+}
+
+impl TryFrom<u16> for MethodHandleType {
+    type Error = error::Parse;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(Self::StaticPut),
+            0x01 => Ok(Self::StaticGet),
+            0x02 => Ok(Self::InstancePut),
+            0x03 => Ok(Self::InstanceGet),
+            0x04 => Ok(Self::InvokeStatic),
+            0x05 => Ok(Self::InvokeInstance),
+            0x06 => Ok(Self::InvokeConstructor),
+            0x07 => Ok(Self::InvokeDirect),
+            0x08 => Ok(Self::InvokeInterface),
+            v => Err(error::Parse::InvalidMethodHandleType(v)),
+        }
+    }
+}
+
+/// Type of an item in the map of the dex file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
+pub enum ItemType {
+    /// `header_item`.
+    Header,
+    /// `string_id_item`.
+    StringId,
+    /// `type_id_item`.
+    TypeId,
+    /// `proto_id_item`.
+    ProtoId,
+    /// `field_id_item`.
+    FieldId,
+    /// `method_id_item`.
+    MethodId,
+    /// `class_def_item`.
+    ClassDef,
+    /// `call_site_id_item`.
+    CallSiteId,
+    /// `method_handle_item`.
+    MethodHandle,
+    /// `map_list`.
+    MapList,
+    /// `type_list`.
+    TypeList,
+    /// `annotation_set_ref_list`.
+    AnnotationSetRefList,
+    /// `annotation_set_item`.
+    AnnotationSet,
+    /// `class_data_item`.
+    ClassData,
+    /// `code_item`.
+    Code,
+    /// `string_data_item`.
+    StringData,
+    /// `debug_info_item`.
+    DebugInfo,
+    /// `annotation_item`.
+    Annotation,
+    /// `encoded_array_item`.
+    EncodedArray,
+    /// `annotations_directory_item`.
+    AnnotationsDirectory,
+    /// `hiddenapi_class_data_item`.
+    HiddenapiClassData,
+    /// Item type not defined in the dex format specification, with its value.
+    ///
+    /// Some vendors add their own sections to dex files. They are only kept in the map when
+    /// parsing leniently, and their items are skipped.
+    Unknown(u16),
+}
+
 /// Value of a variable.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
 pub enum Value {
     /// Byte.
     Byte(i8),
@@ -282,6 +555,10 @@ pub enum Value {
     Field(u32),
     /// Method with the index into the prototype IDs list.
     Method(u32),
+    /// Method type, with the index into the prototype IDs list.
+    MethodType(u32),
+    /// Method handle, with the index into the method handles list.
+    MethodHandle(u32),
     /// Enum with the index into the fields IDs list.
     Enum(u32),
     /// An array of values.
@@ -296,15 +573,40 @@ pub enum Value {
 
 /// Array.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
 pub struct Array {
     inner: Box<[Value]>,
+    encoded_widths: Box<[Option<u8>]>,
+}
+
+impl Array {
+    /// Gets the number of bytes the value at the given index was encoded with in the dex file.
+    ///
+    /// Numeric values and indexes can be encoded with fewer bytes than their type has, and the
+    /// parsed `Value` only keeps the decoded number, so this is the only record of the width
+    /// found in the file. The crate does not encode values, so this is not used when writing. It
+    /// is `None` for values without a sized payload, such as arrays, annotations, `null` and
+    /// booleans, or if the index is out of bounds.
+    pub fn encoded_width(&self, index: usize) -> Option<u8> {
+        self.encoded_widths.get(index).copied().flatten()
+    }
+}
+
+impl Deref for Array {
+    type Target = [Value];
+
+    fn deref(&self) -> &[Value] {
+        &self.inner
+    }
 }
 
 /// Annotation element.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
 pub struct AnnotationElement {
     name: u32,
     value: Value,
+    encoded_width: Option<u8>,
 }
 
 impl AnnotationElement {
@@ -312,6 +614,13 @@ impl AnnotationElement {
     pub fn name_index(&self) -> u32 {
         self.name
     }
+
+    /// Gets the number of bytes the value was encoded with in the dex file.
+    ///
+    /// See `Array::encoded_width()` for more information.
+    pub fn encoded_width(&self) -> Option<u8> {
+        self.encoded_width
+    }
 }
 
 impl Deref for AnnotationElement {
@@ -324,6 +633,7 @@ impl Deref for AnnotationElement {
 
 /// Annotation.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
 pub struct EncodedAnnotation {
     type_id: u32,
     elements: Box<[AnnotationElement]>,
@@ -343,9 +653,11 @@ impl EncodedAnnotation {
 
 /// Annotation item
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
 pub struct Annotation {
     visibility: Visibility,
     annotation: EncodedAnnotation,
+    raw_bytes: Box<[u8]>,
 }
 
 impl Annotation {
@@ -353,6 +665,20 @@ impl Annotation {
     pub fn visibility(&self) -> Visibility {
         self.visibility
     }
+
+    /// Gets the encoded bytes of the annotation item, as they are in the dex file.
+    ///
+    /// This allows reproducing or hashing the annotation without encoding it again. It is empty
+    /// if the annotation was not read from a dex file.
+    pub fn raw_bytes(&self) -> &[u8] {
+        &self.raw_bytes
+    }
+
+    /// Sets the encoded bytes of the annotation item.
+    pub(crate) fn with_raw_bytes(mut self, raw_bytes: Box<[u8]>) -> Self {
+        self.raw_bytes = raw_bytes;
+        self
+    }
 }
 
 impl Deref for Annotation {
@@ -413,6 +739,55 @@ impl AnnotationsDirectory {
     pub fn parameter_annotations(&self) -> &[ParameterAnnotations] {
         &self.parameter_annotations
     }
+
+    /// Gets a copy of the directory with only the annotations visible at runtime.
+    ///
+    /// Build time annotations are not available at runtime, and system annotations are used by
+    /// the runtime itself, so these are the annotations that the application can read through
+    /// reflection. Fields and methods left without annotations are removed, but parameter
+    /// annotation sets are kept, so that they still match the parameters of the method.
+    #[must_use]
+    pub fn runtime_annotations(&self) -> Self {
+        let runtime = |annotations: &[Annotation]| -> Box<[Annotation]> {
+            annotations
+                .iter()
+                .filter(|annotation| annotation.visibility() == Visibility::Runtime)
+                .cloned()
+                .collect()
+        };
+        Self {
+            class_annotations: runtime(&self.class_annotations),
+            field_annotations: self
+                .field_annotations
+                .iter()
+                .map(|field| FieldAnnotations::new(field.field_id, runtime(&field.annotations)))
+                .filter(|field| !field.annotations.is_empty())
+                .collect(),
+            method_annotations: self
+                .method_annotations
+                .iter()
+                .map(|method| {
+                    MethodAnnotations::new(method.method_id, runtime(&method.annotations))
+                })
+                .filter(|method| !method.annotations.is_empty())
+                .collect(),
+            parameter_annotations: self
+                .parameter_annotations
+                .iter()
+                .map(|method| {
+                    ParameterAnnotations::new(
+                        method.method_id,
+                        method
+                            .annotations
+                            .iter()
+                            .map(|parameter| runtime(parameter))
+                            .collect::<Box<[_]>>(),
+                    )
+                })
+                .filter(|method| method.annotations.iter().any(|set| !set.is_empty()))
+                .collect(),
+        }
+    }
 }
 
 /// Field annotations.
@@ -473,15 +848,18 @@ impl MethodAnnotations {
 #[derive(Debug, Clone)]
 pub struct ParameterAnnotations {
     method_id: u32,
-    annotations: Box<[Annotation]>,
+    annotations: Box<[Box<[Annotation]>]>,
 }
 
 impl ParameterAnnotations {
-    /// Creates a new list of method annotations.
-    pub fn new(method_id: u32, annotations: Box<[Annotation]>) -> Self {
+    /// Creates a new list of parameter annotations.
+    pub fn new<A>(method_id: u32, annotations: A) -> Self
+    where
+        A: Into<Box<[Box<[Annotation]>]>>,
+    {
         Self {
             method_id,
-            annotations,
+            annotations: annotations.into(),
         }
     }
 
@@ -490,8 +868,10 @@ impl ParameterAnnotations {
         self.method_id
     }
 
-    /// Gets the list of annotations.
-    pub fn annotations(&self) -> &[Annotation] {
+    /// Gets the list of annotation sets, one for each parameter of the method.
+    ///
+    /// Parameters without annotations have an empty set.
+    pub fn annotations(&self) -> &[Box<[Annotation]>] {
         &self.annotations
     }
 }
@@ -624,6 +1004,100 @@ impl fmt::Display for AccessFlags {
     }
 }
 
+#[cfg(feature = "cache")]
+impl Serialize for AccessFlags {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.bits().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "cache")]
+impl<'de> Deserialize<'de> for AccessFlags {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let bits = u32::deserialize(deserializer)?;
+        Self::from_bits(bits)
+            .ok_or_else(|| de::Error::custom(error::Parse::InvalidAccessFlags(bits)))
+    }
+}
+
+/// Hidden API restriction of a class member, from the `hiddenapi_class_data` section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HiddenApiRestriction {
+    /// The member can be used by any application (whitelist).
+    Whitelist,
+    /// The member can be used, but it is not supported (greylist).
+    Greylist,
+    /// The member cannot be used by applications (blacklist).
+    Blacklist,
+    /// The member can only be used by applications targeting Android O or lower.
+    GreylistMaxO,
+    /// The member can only be used by applications targeting Android P or lower.
+    GreylistMaxP,
+    /// The member can only be used by applications targeting Android Q or lower.
+    GreylistMaxQ,
+    /// The member can only be used by applications targeting Android R or lower.
+    GreylistMaxR,
+    /// The member can only be used by applications targeting Android S or lower.
+    GreylistMaxS,
+}
+
+/// Hidden API flags of a class member.
+///
+/// The lowest 3 bits contain the restriction of the member, and the next bits mark the domains
+/// the member belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
+pub struct HiddenApiFlag(u32);
+
+impl HiddenApiFlag {
+    /// Mask of the bits containing the restriction.
+    const RESTRICTION_MASK: u32 = 0x7;
+    /// Bit marking the member as part of the core platform API.
+    const CORE_PLATFORM_API: u32 = 0x8;
+    /// Bit marking the member as part of the test API.
+    const TEST_API: u32 = 0x10;
+
+    /// Creates the hidden API flags from their raw value.
+    pub fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    /// Gets the raw value of the flags.
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Gets the restriction of the member.
+    pub fn restriction(self) -> HiddenApiRestriction {
+        match self.0 & Self::RESTRICTION_MASK {
+            0 => HiddenApiRestriction::Whitelist,
+            1 => HiddenApiRestriction::Greylist,
+            2 => HiddenApiRestriction::Blacklist,
+            3 => HiddenApiRestriction::GreylistMaxO,
+            4 => HiddenApiRestriction::GreylistMaxP,
+            5 => HiddenApiRestriction::GreylistMaxQ,
+            6 => HiddenApiRestriction::GreylistMaxR,
+            _ => HiddenApiRestriction::GreylistMaxS,
+        }
+    }
+
+    /// Checks if the member is part of the core platform API.
+    pub fn is_core_platform_api(self) -> bool {
+        self.0 & Self::CORE_PLATFORM_API != 0
+    }
+
+    /// Checks if the member is part of the test API.
+    pub fn is_test_api(self) -> bool {
+        self.0 & Self::TEST_API != 0
+    }
+}
+
 /// Structure representing a class.
 #[derive(Debug)]
 pub struct Class {
@@ -710,7 +1184,235 @@ impl Class {
 
 #[cfg(test)]
 mod test {
-    use super::AccessFlags;
+    use super::{
+        AccessFlags, Annotation, AnnotationsDirectory, EncodedAnnotation, FieldAnnotations,
+        HiddenApiFlag, HiddenApiRestriction, MethodAnnotations, NestStyle, ParameterAnnotations,
+        Prototype, Type, Visibility,
+    };
+    use crate::error;
+
+    fn annotation(type_id: u32, visibility: Visibility) -> Annotation {
+        Annotation {
+            visibility,
+            annotation: EncodedAnnotation {
+                type_id,
+                elements: Box::default(),
+            },
+            raw_bytes: Box::default(),
+        }
+    }
+
+    #[test]
+    fn it_filters_runtime_annotations() {
+        let directory = AnnotationsDirectory::new(
+            vec![
+                annotation(1, Visibility::Build),
+                annotation(2, Visibility::Runtime),
+                annotation(3, Visibility::System),
+            ],
+            vec![
+                FieldAnnotations::new(10, vec![annotation(4, Visibility::Build)].into()),
+                FieldAnnotations::new(11, vec![annotation(5, Visibility::Runtime)].into()),
+            ],
+            vec![MethodAnnotations::new(
+                20,
+                vec![annotation(6, Visibility::Build)].into(),
+            )],
+            vec![
+                ParameterAnnotations::new(
+                    21,
+                    vec![
+                        vec![annotation(7, Visibility::Build)].into_boxed_slice(),
+                        vec![annotation(8, Visibility::Runtime)].into_boxed_slice(),
+                    ],
+                ),
+                ParameterAnnotations::new(
+                    22,
+                    vec![vec![annotation(9, Visibility::Build)].into_boxed_slice()],
+                ),
+            ],
+        );
+
+        let runtime = directory.runtime_annotations();
+        assert_eq!(3, directory.class_annotations().len());
+        assert_eq!(1, runtime.class_annotations().len());
+        assert_eq!(2, runtime.class_annotations()[0].type_index());
+        assert_eq!(1, runtime.field_annotations().len());
+        assert_eq!(11, runtime.field_annotations()[0].field_index());
+        assert_eq!(
+            5,
+            runtime.field_annotations()[0].annotations()[0].type_index()
+        );
+        assert!(runtime.method_annotations().is_empty());
+        assert_eq!(1, runtime.parameter_annotations().len());
+        let parameters = &runtime.parameter_annotations()[0];
+        assert_eq!(21, parameters.method_index());
+        assert_eq!(2, parameters.annotations().len());
+        assert!(parameters.annotations()[0].is_empty());
+        assert_eq!(
+            Visibility::Runtime,
+            parameters.annotations()[1][0].visibility()
+        );
+    }
+
+    #[test]
+    fn it_gets_the_java_name_of_nested_classes() {
+        let inner = "Lcom/foo/Outer$Inner;".parse::<Type>().unwrap();
+        assert_eq!("com.foo.Outer$Inner", inner.to_java_name(NestStyle::Binary));
+        assert_eq!(
+            "com.foo.Outer.Inner",
+            inner.to_java_name(NestStyle::Canonical)
+        );
+
+        let array = "[[Lcom/foo/Outer$Inner;".parse::<Type>().unwrap();
+        assert_eq!(
+            "com.foo.Outer$Inner[][]",
+            array.to_java_name(NestStyle::Binary)
+        );
+        assert_eq!(
+            "com.foo.Outer.Inner[][]",
+            array.to_java_name(NestStyle::Canonical)
+        );
+
+        assert_eq!(
+            "int[]",
+            "[I".parse::<Type>()
+                .unwrap()
+                .to_java_name(NestStyle::Binary)
+        );
+        assert_eq!(
+            "java.lang.String",
+            "Ljava/lang/String;"
+                .parse::<Type>()
+                .unwrap()
+                .to_java_name(NestStyle::Canonical)
+        );
+    }
+
+    #[test]
+    fn it_can_get_the_register_width_of_wide_types() {
+        assert_eq!(2, "J".parse::<Type>().unwrap().register_width());
+        assert_eq!(2, "D".parse::<Type>().unwrap().register_width());
+    }
+
+    #[test]
+    fn it_can_get_the_register_width_of_narrow_types() {
+        assert_eq!(1, "I".parse::<Type>().unwrap().register_width());
+        assert_eq!(
+            1,
+            "Ljava/lang/Long;".parse::<Type>().unwrap().register_width()
+        );
+        assert_eq!(1, "[J".parse::<Type>().unwrap().register_width());
+    }
+
+    #[test]
+    fn it_can_check_a_prototype_against_its_shorty() {
+        let parameters = vec![Type::Int, "[J".parse().unwrap()].into_boxed_slice();
+        let prototype = Prototype::new("VIL".parse().unwrap(), Type::Void, parameters.clone());
+        assert!(prototype.matches_shorty());
+
+        let prototype = Prototype::new("VII".parse().unwrap(), Type::Void, parameters.clone());
+        assert!(!prototype.matches_shorty());
+
+        let prototype = Prototype::new("IIL".parse().unwrap(), Type::Void, parameters);
+        assert!(!prototype.matches_shorty());
+
+        let prototype = Prototype::new("VI".parse().unwrap(), Type::Void, None);
+        assert!(!prototype.matches_shorty());
+    }
+
+    #[test]
+    fn it_can_decode_hidden_api_flags() {
+        let flag = HiddenApiFlag::from_bits(0x0a);
+        assert_eq!(HiddenApiRestriction::Blacklist, flag.restriction());
+        assert!(flag.is_core_platform_api());
+        assert!(!flag.is_test_api());
+
+        let flag = HiddenApiFlag::from_bits(0x14);
+        assert_eq!(HiddenApiRestriction::GreylistMaxP, flag.restriction());
+        assert!(!flag.is_core_platform_api());
+        assert!(flag.is_test_api());
+        assert_eq!(0x14, flag.bits());
+    }
+
+    #[test]
+    fn it_can_get_the_descriptor_of_a_type() {
+        for descriptor in &[
+            "V",
+            "I",
+            "J",
+            "Ljava/lang/String;",
+            "[[Ljava/lang/String;",
+            "[Z",
+        ] {
+            assert_eq!(
+                *descriptor,
+                descriptor.parse::<Type>().unwrap().to_descriptor()
+            );
+        }
+    }
+
+    #[test]
+    fn it_can_classify_a_primitive_type() {
+        let int = "I".parse::<Type>().unwrap();
+
+        assert!(int.is_primitive());
+        assert!(!int.is_reference());
+        assert!(!int.is_array());
+        assert_eq!("int", int.element_type().to_string());
+        assert!(!Type::Void.is_primitive());
+    }
+
+    #[test]
+    fn it_can_classify_an_array_type() {
+        let array = "[[Ljava/lang/String;".parse::<Type>().unwrap();
+
+        assert!(!array.is_primitive());
+        assert!(array.is_reference());
+        assert!(array.is_array());
+        let element_type = array.element_type();
+        assert!(element_type.is_reference());
+        assert!(!element_type.is_array());
+        assert_eq!("java/lang/String;", element_type.to_string());
+    }
+
+    #[test]
+    fn it_rejects_malformed_type_descriptors() {
+        for descriptor in &[
+            "",
+            "L;",
+            "Ljava/lang/Object",
+            "Lfoo;bar;",
+            "II",
+            "[",
+            "[V",
+            "[[X",
+        ] {
+            match descriptor.parse::<Type>() {
+                Err(error::Parse::InvalidTypeDescriptor(invalid)) => {
+                    assert_eq!(descriptor, &invalid)
+                }
+                other => panic!("{:?} was parsed as {:?}", descriptor, other),
+            }
+        }
+        assert_eq!(
+            Type::Array {
+                dimensions: 2,
+                array_type: Box::new(Type::Int),
+            },
+            "[[I".parse::<Type>().unwrap()
+        );
+    }
+
+    #[test]
+    fn it_can_classify_an_object_type() {
+        let object = "Ljava/lang/Object;".parse::<Type>().unwrap();
+
+        assert!(!object.is_primitive());
+        assert!(object.is_reference());
+        assert!(!object.is_array());
+        assert_eq!("java/lang/Object;", object.element_type().to_string());
+    }
 
     #[test]
     fn it_can_display_access() {
@@ -1,15 +1,128 @@
 //! Types used for reading Dex files.
 
 use super::{
-    AccessFlags, Annotation, AnnotationElement, Array, EncodedAnnotation, Value, Visibility,
+    AccessFlags, Annotation, AnnotationElement, Array, EncodedAnnotation, ItemType,
+    MethodHandleType, Value, Visibility,
 };
+#[cfg(feature = "extract")]
+use crate::write::{write_sleb128, write_uleb128};
 use crate::{
-    error,
-    read::{sleb128, uleb128, uleb128p1},
+    error::{self, Result},
+    read::{checked_with_capacity, remaining_bytes, sleb128, uleb128, uleb128p1},
 };
-use anyhow::{Context, Result};
 use byteorder::{ByteOrder, LittleEndian, ReadBytesExt};
-use std::io::Read;
+#[cfg(feature = "cache")]
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    convert::TryFrom,
+    fmt,
+    io::{BufRead, Read, Seek},
+};
+
+/// Structure representing the `string_data_item` type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DexString {
+    text: String,
+    utf16_units: u32,
+    byte_len: u32,
+}
+
+impl DexString {
+    /// Creates a new `DexString` from a reader.
+    ///
+    /// The declared size of the string is not checked against its contents, so that the caller
+    /// can decide how to handle mismatches.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the string cannot be read, if its declared size is larger than the
+    /// rest of the input, or if its data is not valid MUTF-8.
+    pub fn from_reader<R>(reader: &mut R) -> Result<Self>
+    where
+        R: BufRead + Seek,
+    {
+        let (utf16_units, _) = uleb128(reader)?;
+        // Each UTF-16 code unit takes, at least, one byte.
+        let mut data =
+            checked_with_capacity("utf16_size", utf16_units, 1, remaining_bytes(reader)?)?;
+        if utf16_units > 0 {
+            let _ = reader.read_until(0, &mut data)?;
+            let _ = data.pop();
+        }
+        let byte_len = data.len() as u32;
+
+        // Most strings are also valid UTF-8, only null characters and characters outside of the
+        // basic multilingual plane are encoded differently.
+        let text = match String::from_utf8(data) {
+            Ok(text) => text,
+            Err(e) => String::from_utf16(&mutf8_to_utf16(e.as_bytes())?)
+                .map_err(|_| error::Parse::InvalidMutf8)?,
+        };
+        Ok(Self {
+            text,
+            utf16_units,
+            byte_len,
+        })
+    }
+
+    /// Gets the decoded text of the string.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Gets the size of the string in UTF-16 code units, as declared in the dex file.
+    pub fn utf16_units(&self) -> u32 {
+        self.utf16_units
+    }
+
+    /// Gets the length of the encoded string data in bytes, without the terminating null byte.
+    pub fn byte_len(&self) -> u32 {
+        self.byte_len
+    }
+
+    /// Gets the actual size of the decoded string in UTF-16 code units.
+    pub fn computed_utf16_units(&self) -> usize {
+        self.text.encode_utf16().count()
+    }
+
+    /// Converts the structure into the decoded text of the string.
+    pub fn into_text(self) -> String {
+        self.text
+    }
+}
+
+/// Decodes MUTF-8 data into UTF-16 code units.
+///
+/// Four byte UTF-8 sequences are not valid MUTF-8, but they are accepted too.
+fn mutf8_to_utf16(data: &[u8]) -> Result<Vec<u16>> {
+    let invalid = || error::Parse::InvalidMutf8;
+    let mut units = Vec::with_capacity(data.len());
+    let mut bytes = data.iter().map(|&byte| u32::from(byte));
+    while let Some(byte) = bytes.next() {
+        let mut continuation = || match bytes.next() {
+            Some(byte) if byte & 0xC0 == 0x80 => Ok(byte & 0x3F),
+            _ => Err(invalid()),
+        };
+        let code_point = match byte {
+            0x00..=0x7F => byte,
+            0xC0..=0xDF => (byte & 0x1F) << 6 | continuation()?,
+            0xE0..=0xEF => (byte & 0x0F) << 12 | continuation()? << 6 | continuation()?,
+            0xF0..=0xF7 => {
+                (byte & 0x07) << 18 | continuation()? << 12 | continuation()? << 6 | continuation()?
+            }
+            _ => return Err(invalid().into()),
+        };
+        if code_point > 0xFFFF {
+            let code_point = code_point - 0x1_0000;
+            units.push(0xD800 | (code_point >> 10) as u16);
+            units.push(0xDC00 | (code_point & 0x3FF) as u16);
+        } else {
+            units.push(code_point as u16);
+        }
+    }
+    Ok(units)
+}
 
 /// Data structure representing the `proto_id_item` type.
 #[derive(Debug, Copy, Clone)]
@@ -21,20 +134,18 @@ pub struct PrototypeIdData {
 
 impl PrototypeIdData {
     /// Creates a new `PrototypeIdData` from a reader.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the reader ends before the end of the item.
     pub fn from_reader<R, B>(reader: &mut R) -> Result<Self>
     where
         R: ReadBytesExt,
         B: ByteOrder,
     {
-        let shorty_index = reader
-            .read_u32::<B>()
-            .context("could not read the shorty_index field")?;
-        let return_type_index = reader
-            .read_u32::<B>()
-            .context("could not read the return_type_index field")?;
-        let parameters_offset = reader
-            .read_u32::<B>()
-            .context("could not read the parameters_offset field")?;
+        let shorty_index = reader.read_u32::<B>()?;
+        let return_type_index = reader.read_u32::<B>()?;
+        let parameters_offset = reader.read_u32::<B>()?;
         Ok(Self {
             shorty_index,
             return_type_index,
@@ -75,6 +186,7 @@ impl PrototypeIdData {
 
 /// Structure representing the `field_id_item` type.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
 pub struct FieldIdData {
     class_index: u16,
     type_index: u16,
@@ -83,20 +195,18 @@ pub struct FieldIdData {
 
 impl FieldIdData {
     /// Creates a new `FieldIdData` from a reader.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the reader ends before the end of the item.
     pub fn from_reader<R, B>(reader: &mut R) -> Result<Self>
     where
         R: ReadBytesExt,
         B: ByteOrder,
     {
-        let class_index = reader
-            .read_u16::<B>()
-            .context("could not read the class_index field")?;
-        let type_index = reader
-            .read_u16::<B>()
-            .context("could not read the type_index field")?;
-        let name_index = reader
-            .read_u32::<B>()
-            .context("could not read the name_index field")?;
+        let class_index = reader.read_u16::<B>()?;
+        let type_index = reader.read_u16::<B>()?;
+        let name_index = reader.read_u32::<B>()?;
         Ok(Self {
             class_index,
             type_index,
@@ -128,8 +238,147 @@ impl FieldIdData {
     }
 }
 
+/// Structure representing the `method_handle_item` type.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
+pub struct MethodHandleData {
+    handle_type: MethodHandleType,
+    field_or_method_index: u16,
+}
+
+impl MethodHandleData {
+    /// Creates a new `MethodHandleData` from a reader.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the reader ends before the end of the item, or if the method handle
+    /// type is not valid.
+    pub fn from_reader<R, B>(reader: &mut R) -> Result<Self>
+    where
+        R: ReadBytesExt,
+        B: ByteOrder,
+    {
+        let handle_type = reader.read_u16::<B>()?;
+        let handle_type = MethodHandleType::try_from(handle_type)?;
+        let _unused = reader.read_u16::<B>()?;
+        let field_or_method_index = reader.read_u16::<B>()?;
+        let _unused = reader.read_u16::<B>()?;
+        Ok(Self {
+            handle_type,
+            field_or_method_index,
+        })
+    }
+
+    /// Gets the kind of the method handle.
+    pub fn handle_type(self) -> MethodHandleType {
+        self.handle_type
+    }
+
+    /// Gets the index of the field or method accessed by the method handle.
+    ///
+    /// It is an index into the `field_ids` list if the handle is a field accessor, and into the
+    /// `method_ids` list otherwise.
+    pub fn field_or_method_index(self) -> usize {
+        self.field_or_method_index as usize
+    }
+
+    /// Gets the index into the `field_ids` list of the field accessed by the method handle, if
+    /// it is a field accessor.
+    pub fn field_index(self) -> Option<usize> {
+        if self.handle_type.is_field_accessor() {
+            Some(self.field_or_method_index())
+        } else {
+            None
+        }
+    }
+
+    /// Gets the index into the `method_ids` list of the method invoked by the method handle, if
+    /// it is a method invoker.
+    pub fn method_index(self) -> Option<usize> {
+        if self.handle_type.is_field_accessor() {
+            None
+        } else {
+            Some(self.field_or_method_index())
+        }
+    }
+}
+
+/// Call site of an `invoke-custom` instruction, from its `call_site_item`.
+///
+/// The call site is linked by invoking its bootstrap method with the name and the type of the
+/// method to link, followed by its extra arguments.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
+pub struct CallSite {
+    bootstrap_method: MethodHandleData,
+    method_name_index: u32,
+    method_type_index: u32,
+    extra_arguments: Box<[Value]>,
+}
+
+impl CallSite {
+    /// Creates a new `CallSite` from a reader, resolving its bootstrap method in the given list
+    /// of method handles.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the call site cannot be read, or if its bootstrap method handle does not
+    /// exist.
+    pub fn from_reader<R>(reader: &mut R, method_handles: &[MethodHandleData]) -> Result<Self>
+    where
+        R: Read + Seek,
+    {
+        let array = Array::from_reader(reader)?;
+        let invalid = |error: &str| error::Parse::InvalidCallSite {
+            error: error.to_owned(),
+        };
+        let (bootstrap_method, method_name_index, method_type_index) = match &*array {
+            [Value::MethodHandle(handle), Value::String(name), Value::MethodType(prototype), ..] => {
+                (*handle, *name, *prototype)
+            }
+            _ => {
+                return Err(invalid(
+                    "it does not start with a method handle, a string and a method type",
+                )
+                .into())
+            }
+        };
+        let bootstrap_method = method_handles
+            .get(bootstrap_method as usize)
+            .copied()
+            .ok_or_else(|| invalid("its bootstrap method handle does not exist"))?;
+        Ok(Self {
+            bootstrap_method,
+            method_name_index,
+            method_type_index,
+            extra_arguments: array[3..].to_vec().into_boxed_slice(),
+        })
+    }
+
+    /// Gets the method handle of the bootstrap method that links the call site.
+    pub fn bootstrap_method(&self) -> MethodHandleData {
+        self.bootstrap_method
+    }
+
+    /// Gets the index into the `string_ids` list of the name of the method to link.
+    pub fn method_name_index(&self) -> u32 {
+        self.method_name_index
+    }
+
+    /// Gets the index into the `proto_ids` list of the type of the method to link.
+    pub fn method_type_index(&self) -> u32 {
+        self.method_type_index
+    }
+
+    /// Gets the extra arguments of the bootstrap method.
+    pub fn extra_arguments(&self) -> &[Value] {
+        &self.extra_arguments
+    }
+}
+
 /// Structure representing the `method_id_item` type.
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
 pub struct MethodIdData {
     class_index: u16,
     prototype_index: u16,
@@ -138,20 +387,18 @@ pub struct MethodIdData {
 
 impl MethodIdData {
     /// Creates a new `MethodIdData` from a reader.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the reader ends before the end of the item.
     pub fn from_reader<R, B>(reader: &mut R) -> Result<Self>
     where
         R: ReadBytesExt,
         B: ByteOrder,
     {
-        let class_index = reader
-            .read_u16::<B>()
-            .context("could not read the class_index field")?;
-        let prototype_index = reader
-            .read_u16::<B>()
-            .context("could not read the prototype_index field")?;
-        let name_index = reader
-            .read_u32::<B>()
-            .context("could not read the name_index field")?;
+        let class_index = reader.read_u16::<B>()?;
+        let prototype_index = reader.read_u16::<B>()?;
+        let name_index = reader.read_u32::<B>()?;
         Ok(Self {
             class_index,
             prototype_index,
@@ -183,7 +430,7 @@ impl MethodIdData {
     }
 }
 
-const NO_INDEX: u32 = 0xFFFF_FFFF;
+pub(crate) const NO_INDEX: u32 = 0xFFFF_FFFF;
 
 /// Data of a class definition.
 #[derive(Debug, Copy, Clone)]
@@ -200,6 +447,11 @@ pub struct ClassDefData {
 
 impl ClassDefData {
     /// Creates a new `ClassDefData` from a reader.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the reader ends before the end of the item, or if the access flags of
+    /// the class are not valid.
     pub fn from_reader<R, B>(reader: &mut R) -> Result<Self>
     where
         R: ReadBytesExt,
@@ -215,30 +467,14 @@ impl ClassDefData {
             }
         }
 
-        let class_index = reader
-            .read_u32::<B>()
-            .context("could not read the class_index field")?;
-        let access_flags = reader
-            .read_u32::<B>()
-            .context("could not read the access_flags field")?;
-        let superclass_index = reader
-            .read_u32::<B>()
-            .context("could not read the superclass_id field")?;
-        let interfaces_offset = reader
-            .read_u32::<B>()
-            .context("could not read the interfaces_offset field")?;
-        let source_file_index = reader
-            .read_u32::<B>()
-            .context("could not read the source_file_id field")?;
-        let annotations_offset = reader
-            .read_u32::<B>()
-            .context("could not read the annotations_offset field")?;
-        let class_data_offset = reader
-            .read_u32::<B>()
-            .context("could not read the class_data_offset field")?;
-        let static_values_offset = reader
-            .read_u32::<B>()
-            .context("could not read the static_values_offset field")?;
+        let class_index = reader.read_u32::<B>()?;
+        let access_flags = reader.read_u32::<B>()?;
+        let superclass_index = reader.read_u32::<B>()?;
+        let interfaces_offset = reader.read_u32::<B>()?;
+        let source_file_index = reader.read_u32::<B>()?;
+        let annotations_offset = reader.read_u32::<B>()?;
+        let class_data_offset = reader.read_u32::<B>()?;
+        let static_values_offset = reader.read_u32::<B>()?;
 
         Ok(Self {
             class_index,
@@ -314,6 +550,125 @@ impl Visibility {
     }
 }
 
+impl ItemType {
+    /// Gets the item type with the given value, as stored in the map of the dex file.
+    ///
+    /// Values not defined in the specification give an `ItemType::Unknown`.
+    pub(crate) fn from_u16(value: u16) -> Self {
+        match value {
+            0x0000 => Self::Header,
+            0x0001 => Self::StringId,
+            0x0002 => Self::TypeId,
+            0x0003 => Self::ProtoId,
+            0x0004 => Self::FieldId,
+            0x0005 => Self::MethodId,
+            0x0006 => Self::ClassDef,
+            0x0007 => Self::CallSiteId,
+            0x0008 => Self::MethodHandle,
+            0x1000 => Self::MapList,
+            0x1001 => Self::TypeList,
+            0x1002 => Self::AnnotationSetRefList,
+            0x1003 => Self::AnnotationSet,
+            0x2000 => Self::ClassData,
+            0x2001 => Self::Code,
+            0x2002 => Self::StringData,
+            0x2003 => Self::DebugInfo,
+            0x2004 => Self::Annotation,
+            0x2005 => Self::EncodedArray,
+            0x2006 => Self::AnnotationsDirectory,
+            0xF000 => Self::HiddenapiClassData,
+            v => Self::Unknown(v),
+        }
+    }
+
+    /// Gets the value of the item type, as stored in the map of the dex file.
+    #[cfg(feature = "extract")]
+    pub(crate) fn to_u16(self) -> u16 {
+        match self {
+            Self::Header => 0x0000,
+            Self::StringId => 0x0001,
+            Self::TypeId => 0x0002,
+            Self::ProtoId => 0x0003,
+            Self::FieldId => 0x0004,
+            Self::MethodId => 0x0005,
+            Self::ClassDef => 0x0006,
+            Self::CallSiteId => 0x0007,
+            Self::MethodHandle => 0x0008,
+            Self::MapList => 0x1000,
+            Self::TypeList => 0x1001,
+            Self::AnnotationSetRefList => 0x1002,
+            Self::AnnotationSet => 0x1003,
+            Self::ClassData => 0x2000,
+            Self::Code => 0x2001,
+            Self::StringData => 0x2002,
+            Self::DebugInfo => 0x2003,
+            Self::Annotation => 0x2004,
+            Self::EncodedArray => 0x2005,
+            Self::AnnotationsDirectory => 0x2006,
+            Self::HiddenapiClassData => 0xF000,
+            Self::Unknown(v) => v,
+        }
+    }
+}
+
+/// Structure representing the `map_item` type.
+///
+/// Map items are ordered by offset, as they must appear in the map list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
+pub struct MapItem {
+    item_type: ItemType,
+    size: u32,
+    offset: u32,
+}
+
+impl MapItem {
+    /// Creates a new map item from a reader.
+    pub(crate) fn from_reader<R, B>(reader: &mut R) -> Result<Self>
+    where
+        R: ReadBytesExt,
+        B: ByteOrder,
+    {
+        let item_type = reader.read_u16::<B>()?;
+        let item_type = ItemType::from_u16(item_type);
+        let _unused = reader.read_u16::<B>()?;
+        let size = reader.read_u32::<B>()?;
+        let offset = reader.read_u32::<B>()?;
+        Ok(Self {
+            item_type,
+            size,
+            offset,
+        })
+    }
+
+    /// Gets the type of the items.
+    pub fn item_type(self) -> ItemType {
+        self.item_type
+    }
+
+    /// Gets the number of items of this type.
+    pub fn size(self) -> u32 {
+        self.size
+    }
+
+    /// Gets the offset of the first item of this type.
+    pub fn offset(self) -> u32 {
+        self.offset
+    }
+}
+
+impl PartialOrd for MapItem {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for MapItem {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.offset, self.item_type, self.size).cmp(&(other.offset, other.item_type, other.size))
+    }
+}
+
 const VALUE_BYTE: u8 = 0x00;
 const VALUE_SHORT: u8 = 0x02;
 const VALUE_CHAR: u8 = 0x03;
@@ -334,224 +689,104 @@ const VALUE_NULL: u8 = 0x1e;
 const VALUE_BOOLEAN: u8 = 0x1f;
 
 impl Value {
-    fn from_reader<R>(reader: &mut R) -> Result<Self>
+    /// Reads a value, along with the number of bytes its payload was encoded with, if it has a
+    /// sized payload.
+    fn from_reader_with_width<R>(reader: &mut R) -> Result<(Self, Option<u8>)>
     where
-        R: Read + ReadBytesExt,
+        R: Read + Seek + ReadBytesExt,
     {
         let mut value_type = [0_u8];
-        reader
-            .read_exact(&mut value_type)
-            .context("could not read the value_type field")?;
+        reader.read_exact(&mut value_type)?;
         let arg = value_type[0] >> 5;
         let value_type = value_type[0] & 0b0001_1111;
 
+        let encoded_width = match value_type {
+            VALUE_ARRAY | VALUE_ANNOTATION | VALUE_NULL | VALUE_BOOLEAN => None,
+            _ => Some(arg + 1),
+        };
+        Ok((Self::read_payload(reader, value_type, arg)?, encoded_width))
+    }
+
+    /// Reads a value.
+    #[cfg(test)]
+    fn from_reader<R>(reader: &mut R) -> Result<Self>
+    where
+        R: Read + Seek + ReadBytesExt,
+    {
+        Self::from_reader_with_width(reader).map(|(value, _)| value)
+    }
+
+    /// Reads the payload of a value, given its type and the argument in its header.
+    fn read_payload<R>(reader: &mut R, value_type: u8, arg: u8) -> Result<Self>
+    where
+        R: Read + Seek + ReadBytesExt,
+    {
         match value_type {
-            VALUE_BYTE => {
-                if arg == 0 {
-                    Ok(Self::Byte(reader.read_i8().context("could not read Byte")?))
-                } else {
-                    Err(error::Parse::InvalidValue {
-                        error: format!("invalid arg ({}) for Byte value", arg),
-                    }
-                    .into())
-                }
-            }
+            VALUE_BYTE => match arg {
+                0 => Ok(Self::Byte(reader.read_i8()?)),
+                a => Err(Self::invalid_arg(a, "Byte")),
+            },
+            // The arguments are checked, so the integers read fit in the type of the value.
+            #[allow(clippy::cast_possible_truncation)]
             VALUE_SHORT => match arg {
-                0 => Ok(Self::Short(i16::from(
-                    reader.read_i8().context("could not read Short")?,
-                ))),
-                1 => Ok(Self::Short(
-                    reader
-                        .read_i16::<LittleEndian>()
-                        .context("could not read Short")?,
-                )),
-                a => Err(error::Parse::InvalidValue {
-                    error: format!("invalid arg ({}) for Short value", a),
-                }
-                .into()),
+                0..=1 => Ok(Self::Short(Self::read_signed(reader, arg)? as i16)),
+                a => Err(Self::invalid_arg(a, "Short")),
             },
+            #[allow(clippy::cast_possible_truncation)]
             VALUE_CHAR => match arg {
-                0 => Ok(Self::Char(u16::from(
-                    reader.read_u8().context("could not read Char")?,
-                ))),
-                1 => Ok(Self::Char(
-                    reader
-                        .read_u16::<LittleEndian>()
-                        .context("could not read Char")?,
-                )),
-                a => Err(error::Parse::InvalidValue {
-                    error: format!("invalid arg ({}) for Char value", a),
-                }
-                .into()),
+                0..=1 => Ok(Self::Char(Self::read_unsigned(reader, arg)? as u16)),
+                a => Err(Self::invalid_arg(a, "Char")),
             },
-            VALUE_INT => {
-                match arg {
-                    0 => Ok(Self::Int(i32::from(
-                        reader.read_i8().context("could not read Int")?,
-                    ))),
-                    1 => Ok(Self::Int(i32::from(
-                        reader
-                            .read_i16::<LittleEndian>()
-                            .context("could not read Int")?,
-                    ))),
-                    2 => {
-                        let mut bytes = [0_u8; 3];
-                        reader
-                            .read_exact(&mut bytes)
-                            .context("could not read Int")?;
-                        // Reading in little endian
-                        Ok(Self::Int(
-                            i32::from(bytes[0])
-                                | i32::from(bytes[1]) << 8
-                                | i32::from(bytes[2] as i8) << 16,
-                        ))
-                    }
-                    3 => Ok(Self::Int(
-                        reader
-                            .read_i32::<LittleEndian>()
-                            .context("could not read Int")?,
-                    )),
-                    a => Err(error::Parse::InvalidValue {
-                        error: format!("invalid arg ({}) for Int value", a),
-                    }
-                    .into()),
-                }
-            }
-            VALUE_LONG => {
-                match arg {
-                    0 => Ok(Self::Long(i64::from(reader.read_i8()?))),
-                    1 => Ok(Self::Long(i64::from(
-                        reader
-                            .read_i16::<LittleEndian>()
-                            .context("could not read Long")?,
-                    ))),
-                    2 => {
-                        let mut bytes = [0_u8; 3];
-                        reader
-                            .read_exact(&mut bytes)
-                            .context("could not read Long")?;
-                        // Reading in little endian
-                        Ok(Self::Long(
-                            i64::from(bytes[0])
-                                | i64::from(bytes[1]) << 8
-                                | i64::from(bytes[2] as i8) << 16,
-                        ))
-                    }
-                    3 => Ok(Self::Long(i64::from(
-                        reader
-                            .read_i32::<LittleEndian>()
-                            .context("could not read Long")?,
-                    ))),
-                    4 => {
-                        let mut bytes = [0_u8; 5];
-                        reader
-                            .read_exact(&mut bytes)
-                            .context("could not read Long")?;
-                        // Reading in little endian
-                        Ok(Self::Long(
-                            i64::from(bytes[0])
-                                | i64::from(bytes[1]) << 8
-                                | i64::from(bytes[2]) << 16
-                                | i64::from(bytes[3]) << 24
-                                | i64::from(bytes[4] as i8) << 32,
-                        ))
-                    }
-                    5 => {
-                        let mut bytes = [0_u8; 6];
-                        reader
-                            .read_exact(&mut bytes)
-                            .context("could not read Long")?;
-                        // Reading in little endian
-                        Ok(Self::Long(
-                            i64::from(bytes[0])
-                                | i64::from(bytes[1]) << 8
-                                | i64::from(bytes[2]) << 16
-                                | i64::from(bytes[3]) << 24
-                                | i64::from(bytes[4]) << 32
-                                | i64::from(bytes[5] as i8) << 40,
-                        ))
-                    }
-                    6 => {
-                        let mut bytes = [0_u8; 7];
-                        reader
-                            .read_exact(&mut bytes)
-                            .context("could not read Long")?;
-                        // Reading in little endian
-                        Ok(Self::Long(
-                            i64::from(bytes[0])
-                                | i64::from(bytes[1]) << 8
-                                | i64::from(bytes[2]) << 16
-                                | i64::from(bytes[3]) << 24
-                                | i64::from(bytes[4]) << 32
-                                | i64::from(bytes[5]) << 40
-                                | i64::from(bytes[6] as i8) << 48,
-                        ))
-                    }
-                    7 => Ok(Self::Long(
-                        reader
-                            .read_i64::<LittleEndian>()
-                            .context("could not read Long")?,
-                    )),
-                    _ => unreachable!(),
-                }
-            }
-            VALUE_FLOAT => match arg {
-                c @ 0..=3 => {
-                    let mut bytes = [0_u8; 4];
-                    reader
-                        .read_exact(&mut bytes[..=c as usize])
-                        .context("could not read Float")?;
-                    Ok(Self::Float(LittleEndian::read_f32(&bytes)))
-                }
-                a => Err(error::Parse::InvalidValue {
-                    error: format!("invalid arg ({}) for Float value", a),
-                }
-                .into()),
+            #[allow(clippy::cast_possible_truncation)]
+            VALUE_INT => match arg {
+                0..=3 => Ok(Self::Int(Self::read_signed(reader, arg)? as i32)),
+                a => Err(Self::invalid_arg(a, "Int")),
             },
-            VALUE_DOUBLE => match arg {
-                c @ 0..=7 => {
-                    let mut bytes = [0_u8; 8];
-                    reader
-                        .read_exact(&mut bytes[..=c as usize])
-                        .context("could not read Double")?;
-                    Ok(Self::Double(LittleEndian::read_f64(&bytes)))
-                }
-                _ => unreachable!(),
+            // The argument is 3 bits long, so all 8 sizes are valid.
+            VALUE_LONG => Ok(Self::Long(Self::read_signed(reader, arg)?)),
+            VALUE_FLOAT => match arg {
+                0..=3 => Ok(Self::Float(LittleEndian::read_f32(&Self::read_bytes(
+                    reader, arg,
+                )?))),
+                a => Err(Self::invalid_arg(a, "Float")),
             },
-            VALUE_METHOD_TYPE => unimplemented!(),
-            VALUE_METHOD_HANDLE => unimplemented!(),
+            VALUE_DOUBLE => Ok(Self::Double(LittleEndian::read_f64(&Self::read_bytes(
+                reader, arg,
+            )?))),
+            VALUE_METHOD_TYPE => {
+                let prototype_index = Self::read_u32(reader, arg)?;
+                Ok(Self::MethodType(prototype_index))
+            }
+            VALUE_METHOD_HANDLE => {
+                let method_handle_index = Self::read_u32(reader, arg)?;
+                Ok(Self::MethodHandle(method_handle_index))
+            }
             VALUE_STRING => {
-                let string_index =
-                    Self::read_u32(reader, arg).context("could not read String index")?;
+                let string_index = Self::read_u32(reader, arg)?;
                 Ok(Self::String(string_index))
             }
             VALUE_TYPE => {
-                let type_index =
-                    Self::read_u32(reader, arg).context("could not read Type index")?;
+                let type_index = Self::read_u32(reader, arg)?;
                 Ok(Self::Type(type_index))
             }
             VALUE_FIELD => {
-                let field_index =
-                    Self::read_u32(reader, arg).context("could not read Field index")?;
+                let field_index = Self::read_u32(reader, arg)?;
                 Ok(Self::Field(field_index))
             }
             VALUE_METHOD => {
-                let method_index =
-                    Self::read_u32(reader, arg).context("could not read Method index")?;
+                let method_index = Self::read_u32(reader, arg)?;
                 Ok(Self::Method(method_index))
             }
             VALUE_ENUM => {
-                let enum_index =
-                    Self::read_u32(reader, arg).context("could not read Enum index")?;
+                let enum_index = Self::read_u32(reader, arg)?;
                 Ok(Self::Enum(enum_index))
             }
             VALUE_ARRAY => {
-                let array = Array::from_reader(reader).context("could not read Array")?;
+                let array = Array::from_reader(reader)?;
                 Ok(Self::Array(array))
             }
             VALUE_ANNOTATION => {
-                let annotation = EncodedAnnotation::from_reader(reader)
-                    .context("could not read Annotation value")?;
+                let annotation = EncodedAnnotation::from_reader(reader)?;
                 Ok(Self::Annotation(annotation))
             }
             VALUE_NULL => Ok(Self::Null),
@@ -570,6 +805,42 @@ impl Value {
         }
     }
 
+    /// Reads the `arg + 1` bytes of a value, into the start of an 8 byte buffer.
+    fn read_bytes<R>(reader: &mut R, arg: u8) -> Result<[u8; 8]>
+    where
+        R: Read,
+    {
+        let mut bytes = [0_u8; 8];
+        reader.read_exact(&mut bytes[..=usize::from(arg)])?;
+        Ok(bytes)
+    }
+
+    /// Reads a sign-extended little endian integer of `arg + 1` bytes.
+    fn read_signed<R>(reader: &mut R, arg: u8) -> Result<i64>
+    where
+        R: Read,
+    {
+        let bytes = Self::read_bytes(reader, arg)?;
+        Ok(LittleEndian::read_int(&bytes, usize::from(arg) + 1))
+    }
+
+    /// Reads a zero-extended little endian integer of `arg + 1` bytes.
+    fn read_unsigned<R>(reader: &mut R, arg: u8) -> Result<u64>
+    where
+        R: Read,
+    {
+        let bytes = Self::read_bytes(reader, arg)?;
+        Ok(LittleEndian::read_uint(&bytes, usize::from(arg) + 1))
+    }
+
+    /// Creates the error for an argument that is not valid for the given value type.
+    fn invalid_arg(arg: u8, value_name: &str) -> error::Error {
+        error::Parse::InvalidValue {
+            error: format!("invalid arg ({}) for {} value", arg, value_name),
+        }
+        .into()
+    }
+
     fn read_u32<R>(reader: &mut R, arg: u8) -> Result<u32>
     where
         R: Read + ReadBytesExt,
@@ -594,18 +865,26 @@ impl Value {
 
 impl Array {
     /// Creates an array from a reader.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the values of the array cannot be read, or if the array
+    /// declares more values than could fit in the rest of the input.
     pub fn from_reader<R>(reader: &mut R) -> Result<Self>
     where
-        R: Read,
+        R: Read + Seek,
     {
-        let (size, _) = uleb128(reader).context("could not read array size")?;
-        let mut array = Vec::with_capacity(size as usize);
+        let (size, _) = uleb128(reader)?;
+        let mut array = checked_with_capacity("size", size, 1, remaining_bytes(reader)?)?;
+        let mut encoded_widths = Vec::with_capacity(array.capacity());
         for _ in 0..size {
-            let value = Value::from_reader(reader).context("could not read value")?;
+            let (value, encoded_width) = Value::from_reader_with_width(reader)?;
             array.push(value);
+            encoded_widths.push(encoded_width);
         }
         Ok(Self {
             inner: array.into_boxed_slice(),
+            encoded_widths: encoded_widths.into_boxed_slice(),
         })
     }
 }
@@ -615,15 +894,20 @@ impl EncodedAnnotation {
     #[doc(hidden)]
     pub fn from_reader<R>(reader: &mut R) -> Result<Self>
     where
-        R: Read,
+        R: Read + Seek,
     {
-        let (type_id, _) = uleb128(reader).context("could not read type ID")?;
-        let (size, _) = uleb128(reader).context("could not read size")?;
-        let mut elements = Vec::with_capacity(size as usize);
+        let (type_id, _) = uleb128(reader)?;
+        let (size, _) = uleb128(reader)?;
+        // Each element has, at least, a one byte name index and a one byte value.
+        let mut elements = checked_with_capacity("size", size, 2, remaining_bytes(reader)?)?;
         for _ in 0..size {
-            let (name, _) = uleb128(reader).context("could not read element's name_id")?;
-            let value = Value::from_reader(reader).context("could not read element's value")?;
-            elements.push(AnnotationElement { name, value });
+            let (name, _) = uleb128(reader)?;
+            let (value, encoded_width) = Value::from_reader_with_width(reader)?;
+            elements.push(AnnotationElement {
+                name,
+                value,
+                encoded_width,
+            });
         }
         Ok(Self {
             type_id,
@@ -637,18 +921,16 @@ impl Annotation {
     #[doc(hidden)]
     pub fn from_reader<R>(reader: &mut R) -> Result<Self>
     where
-        R: Read,
+        R: Read + Seek,
     {
         let mut visibility = [0_u8];
-        reader
-            .read_exact(&mut visibility)
-            .context("could not read visibility")?;
+        reader.read_exact(&mut visibility)?;
         let visibility = Visibility::from_u8(visibility[0])?;
-        let annotation =
-            EncodedAnnotation::from_reader(reader).context("could not read annotation")?;
+        let annotation = EncodedAnnotation::from_reader(reader)?;
         Ok(Self {
             visibility,
             annotation,
+            raw_bytes: Box::default(),
         })
     }
 }
@@ -721,55 +1003,53 @@ pub struct AnnotationsDirectoryOffsets {
 
 impl AnnotationsDirectoryOffsets {
     /// Creates a new annotations directory from a reader.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be read, or if it declares more annotations than
+    /// could fit in the rest of the input.
     pub fn from_reader<R, B>(reader: &mut R) -> Result<Self>
     where
-        R: Read + ReadBytesExt,
+        R: Read + Seek + ReadBytesExt,
         B: ByteOrder,
     {
-        let class_annotations_offset = reader
-            .read_u32::<B>()
-            .context("could not read class annotations offset")?;
-        let field_annotations_size = reader
-            .read_u32::<B>()
-            .context("could not read field annotations size")?
-            as usize;
-        let method_annotations_size = reader
-            .read_u32::<B>()
-            .context("could not read method annotations size")?
-            as usize;
-        let parameter_annotations_size = reader
-            .read_u32::<B>()
-            .context("could not read parameter annotations size")?
-            as usize;
-
-        let mut field_annotations = Vec::with_capacity(field_annotations_size);
+        let class_annotations_offset = reader.read_u32::<B>()?;
+        let field_annotations_size = reader.read_u32::<B>()?;
+        let method_annotations_size = reader.read_u32::<B>()?;
+        let parameter_annotations_size = reader.read_u32::<B>()?;
+
+        // Each annotation is made of a member index and an offset, of 4 bytes each.
+        let mut remaining = remaining_bytes(reader)?;
+        let mut field_annotations =
+            checked_with_capacity("fields_size", field_annotations_size, 8, remaining)?;
+        remaining -= u64::from(field_annotations_size) * 8;
+        let mut method_annotations = checked_with_capacity(
+            "annotated_methods_size",
+            method_annotations_size,
+            8,
+            remaining,
+        )?;
+        remaining -= u64::from(method_annotations_size) * 8;
+        let mut parameter_annotations = checked_with_capacity(
+            "annotated_parameters_size",
+            parameter_annotations_size,
+            8,
+            remaining,
+        )?;
+
         for _ in 0..field_annotations_size {
-            let field_id = reader
-                .read_u32::<B>()
-                .context("could not read field ID for field annotation")?;
-            let offset = reader
-                .read_u32::<B>()
-                .context("could not read field annotation offset")?;
+            let field_id = reader.read_u32::<B>()?;
+            let offset = reader.read_u32::<B>()?;
             field_annotations.push(FieldAnnotationsOffset { field_id, offset });
         }
-        let mut method_annotations = Vec::with_capacity(method_annotations_size);
         for _ in 0..method_annotations_size {
-            let method_id = reader
-                .read_u32::<B>()
-                .context("could not read method ID for method annotation")?;
-            let offset = reader
-                .read_u32::<B>()
-                .context("could not read method annotation offset")?;
+            let method_id = reader.read_u32::<B>()?;
+            let offset = reader.read_u32::<B>()?;
             method_annotations.push(MethodAnnotationsOffset { method_id, offset });
         }
-        let mut parameter_annotations = Vec::with_capacity(parameter_annotations_size);
         for _ in 0..parameter_annotations_size {
-            let method_id = reader
-                .read_u32::<B>()
-                .context("could not read method ID for parameter annotation")?;
-            let offset = reader
-                .read_u32::<B>()
-                .context("could not read annotation offset")?;
+            let method_id = reader.read_u32::<B>()?;
+            let offset = reader.read_u32::<B>()?;
             parameter_annotations.push(ParameterAnnotationsOffset { method_id, offset });
         }
         Ok(Self {
@@ -805,19 +1085,52 @@ impl AnnotationsDirectoryOffsets {
     }
 }
 
+/// Structure representing the `encoded_field` type.
 #[derive(Debug, Clone, Copy)]
-struct Field {
+pub struct Field {
     field_id: u32,
     access_flags: AccessFlags,
 }
 
+impl Field {
+    /// Gets the index of the field in the field IDs list.
+    pub fn field_index(self) -> u32 {
+        self.field_id
+    }
+
+    /// Gets the access flags of the field.
+    pub fn access_flags(self) -> AccessFlags {
+        self.access_flags
+    }
+}
+
+/// Structure representing the `encoded_method` type.
 #[derive(Debug, Clone, Copy)]
-struct Method {
+pub struct Method {
     method_id: u32,
     access_flags: AccessFlags,
     code_offset: Option<u32>,
 }
 
+impl Method {
+    /// Gets the index of the method in the method IDs list.
+    pub fn method_index(&self) -> u32 {
+        self.method_id
+    }
+
+    /// Gets the access flags of the method.
+    pub fn access_flags(&self) -> AccessFlags {
+        self.access_flags
+    }
+
+    /// Gets the offset of the code of the method, if it has code.
+    ///
+    /// Abstract and native methods have no code.
+    pub fn code_offset(&self) -> Option<u32> {
+        self.code_offset
+    }
+}
+
 /// Class data structure.
 #[derive(Debug)]
 pub struct ClassData {
@@ -829,34 +1142,41 @@ pub struct ClassData {
 
 impl ClassData {
     /// Creates a new class data structure from a reader.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the class data cannot be read, or if it declares more fields or
+    /// methods than could fit in the rest of the input.
     pub fn from_reader<R>(reader: &mut R) -> Result<Self>
     where
-        R: Read,
+        R: Read + Seek,
     {
-        let (static_fields_size, _) =
-            uleb128(reader).context("could not read static_fields_size field")?;
-        let (instance_fields_size, _) =
-            uleb128(reader).context("could not read instance_fields_size field")?;
-        let (direct_methods_size, _) =
-            uleb128(reader).context("could not read direct_methods_size field")?;
-        let (virtual_methods_size, _) =
-            uleb128(reader).context("could not read virtual_methods_size field")?;
-
-        let mut static_fields = Vec::with_capacity(static_fields_size as usize);
-        Self::read_fields(reader, static_fields_size, &mut static_fields)
-            .context("could not read class static fields")?;
-
-        let mut instance_fields = Vec::with_capacity(instance_fields_size as usize);
-        Self::read_fields(reader, instance_fields_size, &mut instance_fields)
-            .context("could not read class instance fields")?;
-
-        let mut direct_methods = Vec::with_capacity(direct_methods_size as usize);
-        Self::read_methods(reader, direct_methods_size, &mut direct_methods)
-            .context("could not read class direct methods")?;
-
-        let mut virtual_methods = Vec::with_capacity(virtual_methods_size as usize);
-        Self::read_methods(reader, virtual_methods_size, &mut virtual_methods)
-            .context("could not read class virtual methods")?;
+        let (static_fields_size, _) = uleb128(reader)?;
+        let (instance_fields_size, _) = uleb128(reader)?;
+        let (direct_methods_size, _) = uleb128(reader)?;
+        let (virtual_methods_size, _) = uleb128(reader)?;
+
+        // Encoded fields take at least 2 bytes, and encoded methods at least 3.
+        let mut remaining = remaining_bytes(reader)?;
+        let mut static_fields =
+            checked_with_capacity("static_fields_size", static_fields_size, 2, remaining)?;
+        remaining -= u64::from(static_fields_size) * 2;
+        let mut instance_fields =
+            checked_with_capacity("instance_fields_size", instance_fields_size, 2, remaining)?;
+        remaining -= u64::from(instance_fields_size) * 2;
+        let mut direct_methods =
+            checked_with_capacity("direct_methods_size", direct_methods_size, 3, remaining)?;
+        remaining -= u64::from(direct_methods_size) * 3;
+        let mut virtual_methods =
+            checked_with_capacity("virtual_methods_size", virtual_methods_size, 3, remaining)?;
+
+        Self::read_fields(reader, static_fields_size, &mut static_fields)?;
+
+        Self::read_fields(reader, instance_fields_size, &mut instance_fields)?;
+
+        Self::read_methods(reader, direct_methods_size, &mut direct_methods)?;
+
+        Self::read_methods(reader, virtual_methods_size, &mut virtual_methods)?;
 
         Ok(Self {
             static_fields,
@@ -866,14 +1186,38 @@ impl ClassData {
         })
     }
 
+    /// Gets the static fields of the class.
+    pub fn static_fields(&self) -> &[Field] {
+        &self.static_fields
+    }
+
+    /// Gets the instance fields of the class.
+    pub fn instance_fields(&self) -> &[Field] {
+        &self.instance_fields
+    }
+
+    /// Gets the direct methods of the class.
+    ///
+    /// Direct methods are static, private or constructor methods.
+    pub fn direct_methods(&self) -> &[Method] {
+        &self.direct_methods
+    }
+
+    /// Gets the virtual methods of the class.
+    ///
+    /// Virtual methods are all the non-direct methods of the class.
+    pub fn virtual_methods(&self) -> &[Method] {
+        &self.virtual_methods
+    }
+
     fn read_fields<R>(reader: &mut R, field_count: u32, field_vec: &mut Vec<Field>) -> Result<()>
     where
         R: Read,
     {
         if field_count > 0 {
             // First field's ID is given directly.
-            let (field_id, _) = uleb128(reader).context("could not read field ID")?;
-            let (access_flags, _) = uleb128(reader).context("could not read field access flags")?;
+            let (field_id, _) = uleb128(reader)?;
+            let (access_flags, _) = uleb128(reader)?;
 
             field_vec.push(Field {
                 field_id,
@@ -883,11 +1227,10 @@ impl ClassData {
 
             let mut last_field_id = field_id;
             for _ in 1..field_count {
-                let (field_id_diff, _) = uleb128(reader).context("could not read field ID")?;
-                let (access_flags, _) =
-                    uleb128(reader).context("could not read field access flags")?;
+                let (field_id_diff, _) = uleb128(reader)?;
+                let (access_flags, _) = uleb128(reader)?;
 
-                // Field IDs other than the first one are given by difference.
+                // Field IDs other than the first one are given by difference, which can be zero.
                 last_field_id += field_id_diff;
 
                 field_vec.push(Field {
@@ -910,10 +1253,9 @@ impl ClassData {
     {
         if method_count > 0 {
             // First method's ID is given directly.
-            let (method_id, _) = uleb128(reader).context("could not read method ID")?;
-            let (access_flags, _) =
-                uleb128(reader).context("could not read method access flags")?;
-            let (code_offset, _) = uleb128(reader).context("could not read method code offset")?;
+            let (method_id, _) = uleb128(reader)?;
+            let (access_flags, _) = uleb128(reader)?;
+            let (code_offset, _) = uleb128(reader)?;
 
             let code_offset = if code_offset == 0 {
                 None
@@ -930,11 +1272,9 @@ impl ClassData {
 
             let mut last_method_id = method_id;
             for _ in 1..method_count {
-                let (method_id_diff, _) = uleb128(reader).context("could not read method ID")?;
-                let (access_flags, _) =
-                    uleb128(reader).context("could not read method access flags")?;
-                let (code_offset, _) =
-                    uleb128(reader).context("could not read method code offset")?;
+                let (method_id_diff, _) = uleb128(reader)?;
+                let (access_flags, _) = uleb128(reader)?;
+                let (code_offset, _) = uleb128(reader)?;
 
                 let code_offset = if code_offset == 0 {
                     None
@@ -942,7 +1282,7 @@ impl ClassData {
                     Some(code_offset)
                 };
 
-                // Method IDs other than the first one are given by difference.
+                // Method IDs other than the first one are given by difference, which can be zero.
                 last_method_id += method_id_diff;
 
                 method_vec.push(Method {
@@ -967,25 +1307,31 @@ pub struct DebugInfo {
 
 impl DebugInfo {
     /// Creates a new debug information structure from a reader.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the debug information cannot be read.
     pub fn from_reader<R>(reader: &mut R) -> Result<(Self, u32)>
     where
-        R: Read,
+        R: Read + Seek,
     {
-        let (line_start, mut read) = uleb128(reader).context("could not read line_start field")?;
-        let (parameters_size, read_p) =
-            uleb128(reader).context("could not read parameters_size field")?;
+        let (line_start, mut read) = uleb128(reader)?;
+        let (parameters_size, read_p) = uleb128(reader)?;
         read += read_p;
 
-        let mut parameter_names = Vec::with_capacity(parameters_size as usize);
+        let mut parameter_names = checked_with_capacity(
+            "parameters_size",
+            parameters_size,
+            1,
+            remaining_bytes(reader)?,
+        )?;
         for _ in 0..parameters_size {
-            let (name_index, read_i) =
-                uleb128p1(reader).context("could not read parameter name index")?;
+            let (name_index, read_i) = uleb128p1(reader)?;
             read += read_i;
             parameter_names.push(name_index.into());
         }
 
-        let (bytecode, read_b) =
-            DebugBytecode::from_reader(reader).context("could not read debug bytecode")?;
+        let (bytecode, read_b) = DebugBytecode::from_reader(reader)?;
         read += read_b;
 
         Ok((
@@ -1008,6 +1354,11 @@ impl DebugInfo {
     pub fn parameter_names(&self) -> &[Option<u32>] {
         &self.parameter_names
     }
+
+    /// Gets the instructions of the debug state machine, ending with `DBG_END_SEQUENCE`.
+    pub fn bytecode(&self) -> &[DebugInstruction] {
+        &self.bytecode.bytecode
+    }
 }
 
 /// Debug bytecode.
@@ -1025,8 +1376,7 @@ impl DebugBytecode {
         let mut bytecode = Vec::new();
         let mut read = 0;
         loop {
-            let (instruction, read_i) =
-                DebugInstruction::from_reader(reader).context("could not read instruction")?;
+            let (instruction, read_i) = DebugInstruction::from_reader(reader)?;
             read += read_i;
             bytecode.push(instruction);
 
@@ -1039,75 +1389,153 @@ impl DebugBytecode {
 }
 
 /// Debug state machine instruction.
+///
+/// String and type IDs are indexes in the string and type lists of the dex file.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
-enum DebugInstruction {
+pub enum DebugInstruction {
+    /// Ends the debug information sequence (`DBG_END_SEQUENCE`).
     EndSequence,
+    /// Advances the address register (`DBG_ADVANCE_PC`).
     AdvancePc {
+        /// Amount to add to the address register, in code units.
         addr_diff: u32,
     },
+    /// Advances the line register (`DBG_ADVANCE_LINE`).
     AdvanceLine {
+        /// Amount to add to the line register.
         line_diff: i32,
     },
+    /// Introduces a local variable at the current address (`DBG_START_LOCAL`).
     StartLocal {
+        /// Register that will contain the variable.
         register_num: u32,
+        /// String ID of the name of the variable.
         name_id: Option<u32>,
+        /// Type ID of the type of the variable.
         type_id: Option<u32>,
     },
+    /// Introduces a local variable with a type signature at the current address
+    /// (`DBG_START_LOCAL_EXTENDED`).
     StartLocalExtended {
+        /// Register that will contain the variable.
         register_num: u32,
+        /// String ID of the name of the variable.
         name_id: Option<u32>,
+        /// Type ID of the type of the variable.
         type_id: Option<u32>,
+        /// String ID of the type signature of the variable.
         sig_id: Option<u32>,
     },
+    /// Marks a local variable as out of scope at the current address (`DBG_END_LOCAL`).
     EndLocal {
+        /// Register that contains the variable.
         register_num: u32,
     },
+    /// Re-introduces a local variable at the current address (`DBG_RESTART_LOCAL`).
     RestartLocal {
+        /// Register that contains the variable.
         register_num: u32,
     },
+    /// Marks the end of the method prologue (`DBG_SET_PROLOGUE_END`).
     SetPrologueEnd,
+    /// Marks the start of the method epilogue (`DBG_SET_EPILOGUE_BEGIN`).
     SetEpilogueBegin,
+    /// Sets the source file of the following line entries (`DBG_SET_FILE`).
     SetFile {
+        /// String ID of the name of the source file.
         name_id: u32,
     },
+    /// Advances both the line and address registers and emits a position entry.
     SpecialOpcode {
+        /// Opcode, from `0x0a` to `0xff`.
         opcode: u8,
     },
 }
 
+impl fmt::Display for DebugInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        /// Formats an optional index, as `NO_INDEX` if it is missing.
+        fn index(prefix: &str, index: Option<u32>) -> String {
+            match index {
+                Some(index) => format!("{}@{}", prefix, index),
+                None => format!("{}@NO_INDEX", prefix),
+            }
+        }
+
+        match *self {
+            Self::EndSequence => write!(f, "DBG_END_SEQUENCE"),
+            Self::AdvancePc { addr_diff } => write!(f, "DBG_ADVANCE_PC {}", addr_diff),
+            Self::AdvanceLine { line_diff } => write!(f, "DBG_ADVANCE_LINE {}", line_diff),
+            Self::StartLocal {
+                register_num,
+                name_id,
+                type_id,
+            } => write!(
+                f,
+                "DBG_START_LOCAL v{}, {}, {}",
+                register_num,
+                index("name", name_id),
+                index("type", type_id)
+            ),
+            Self::StartLocalExtended {
+                register_num,
+                name_id,
+                type_id,
+                sig_id,
+            } => write!(
+                f,
+                "DBG_START_LOCAL_EXTENDED v{}, {}, {}, {}",
+                register_num,
+                index("name", name_id),
+                index("type", type_id),
+                index("sig", sig_id)
+            ),
+            Self::EndLocal { register_num } => write!(f, "DBG_END_LOCAL v{}", register_num),
+            Self::RestartLocal { register_num } => {
+                write!(f, "DBG_RESTART_LOCAL v{}", register_num)
+            }
+            Self::SetPrologueEnd => write!(f, "DBG_SET_PROLOGUE_END"),
+            Self::SetEpilogueBegin => write!(f, "DBG_SET_EPILOGUE_BEGIN"),
+            Self::SetFile { name_id } => write!(f, "DBG_SET_FILE name@{}", name_id),
+            Self::SpecialOpcode { opcode } => {
+                // The adjusted opcode encodes both the line and the address increments.
+                let adjusted = i32::from(opcode) - 0x0a;
+                write!(
+                    f,
+                    "DBG_SPECIAL {:#04x} (line += {}, address += {})",
+                    opcode,
+                    adjusted % 15 - 4,
+                    adjusted / 15
+                )
+            }
+        }
+    }
+}
+
 impl DebugInstruction {
     fn from_reader<R>(reader: &mut R) -> Result<(Self, u32)>
     where
         R: Read,
     {
         let mut opcode = [0_u8];
-        reader
-            .read_exact(&mut opcode)
-            .context("could not read opcode")?;
+        reader.read_exact(&mut opcode)?;
         let mut read = 1;
         let instruction = match opcode[0] {
             0x00_u8 => Self::EndSequence,
             0x01_u8 => {
-                let (addr_diff, read_ad) = uleb128(reader)
-                    .context("could not read `addr_diff` for the DBG_ADVANCE_PC instruction")?;
+                let (addr_diff, read_ad) = uleb128(reader)?;
                 read += read_ad;
                 Self::AdvancePc { addr_diff }
             }
             0x02_u8 => {
-                let (line_diff, read_ld) = sleb128(reader).context({
-                    "could not read `line_diff` for the DBG_ADVANCE_LINE instruction"
-                })?;
+                let (line_diff, read_ld) = sleb128(reader)?;
                 read += read_ld;
                 Self::AdvanceLine { line_diff }
             }
             0x03_u8 => {
-                let (register_num, read_rn) = uleb128(reader).context({
-                    "could not read `register_num` for the DBG_START_LOCAL instruction"
-                })?;
-                let (name_id, read_ni) = uleb128p1(reader)
-                    .context("could not read `name_id` for the DBG_START_LOCAL instruction")?;
-                let (type_id, read_ti) = uleb128p1(reader)
-                    .context("could not read `type_id` for the DBG_START_LOCAL instruction")?;
+                let (register_num, read_rn) = uleb128(reader)?;
+                let (name_id, read_ni) = uleb128p1(reader)?;
+                let (type_id, read_ti) = uleb128p1(reader)?;
                 read += read_rn + read_ni + read_ti;
 
                 Self::StartLocal {
@@ -1117,18 +1545,10 @@ impl DebugInstruction {
                 }
             }
             0x04_u8 => {
-                let (register_num, read_rn) = uleb128(reader).context({
-                    "could not read `register_num` for the DBG_START_LOCAL_EXTENDED instruction"
-                })?;
-                let (name_id, read_ni) = uleb128p1(reader).context({
-                    "could not read `name_id` for the DBG_START_LOCAL_EXTENDED instruction"
-                })?;
-                let (type_id, read_ti) = uleb128p1(reader).context({
-                    "could not read `type_id` for the DBG_START_LOCAL_EXTENDED instruction"
-                })?;
-                let (sig_id, read_si) = uleb128p1(reader).context({
-                    "could not read `sig_id` for the DBG_START_LOCAL_EXTENDED instruction"
-                })?;
+                let (register_num, read_rn) = uleb128(reader)?;
+                let (name_id, read_ni) = uleb128p1(reader)?;
+                let (type_id, read_ti) = uleb128p1(reader)?;
+                let (sig_id, read_si) = uleb128p1(reader)?;
                 read += read_rn + read_ni + read_ti + read_si;
 
                 Self::StartLocalExtended {
@@ -1139,23 +1559,19 @@ impl DebugInstruction {
                 }
             }
             0x05_u8 => {
-                let (register_num, read_rn) = uleb128(reader)
-                    .context("could not read `register_num` for the DBG_END_LOCAL instruction")?;
+                let (register_num, read_rn) = uleb128(reader)?;
                 read += read_rn;
                 Self::EndLocal { register_num }
             }
             0x06_u8 => {
-                let (register_num, read_rn) = uleb128(reader).context(
-                    "could not read `register_num` for the DBG_RESTART_LOCAL instruction",
-                )?;
+                let (register_num, read_rn) = uleb128(reader)?;
                 read += read_rn;
                 Self::RestartLocal { register_num }
             }
             0x07_u8 => Self::SetPrologueEnd,
             0x08_u8 => Self::SetEpilogueBegin,
             0x09_u8 => {
-                let (name_id, read_ni) = uleb128(reader)
-                    .context("could not read `name_id` for the DBG_SET_FILE instruction")?;
+                let (name_id, read_ni) = uleb128(reader)?;
                 read += read_ni;
                 Self::SetFile { name_id }
             }
@@ -1168,69 +1584,86 @@ impl DebugInstruction {
 
 /// Code Item structure
 #[derive(Debug)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
 pub struct CodeItem {
     registers_size: u16,
     ins_size: u16,
     outs_size: u16,
     debug_info_offset: u32,
     insns: Vec<u16>,
+    raw_insns: Box<[u8]>,
     tries: Vec<TryItem>,
     handlers: Vec<CatchHandler>,
 }
 
 impl CodeItem {
     /// Reads a code item from the given reader.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the code item cannot be read or is not valid.
     pub fn from_reader<R, B>(reader: &mut R) -> Result<Self>
     where
-        R: Read + ReadBytesExt,
+        R: Read + Seek + ReadBytesExt,
+        B: ByteOrder,
+    {
+        Self::from_reader_limited::<_, B>(reader, u32::max_value())
+    }
+
+    /// Reads a code item from the given reader, rejecting code items with more than the given
+    /// number of code units.
+    ///
+    /// The bytecode is allocated before being read, so the limit should not be larger than what
+    /// the remaining input could hold, to avoid huge allocations for crafted sizes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the code item cannot be read, or if it has more code units than the
+    /// limit.
+    pub fn from_reader_limited<R, B>(reader: &mut R, max_insns_size: u32) -> Result<Self>
+    where
+        R: Read + Seek + ReadBytesExt,
         B: ByteOrder,
     {
-        let registers_size = reader
-            .read_u16::<B>()
-            .context("could not read registers size")?;
-        let ins_size = reader
-            .read_u16::<B>()
-            .context("could not read incoming words size")?;
-        let outs_size = reader
-            .read_u16::<B>()
-            .context("could not read outgoing words size")?;
-        let tries_size = reader
-            .read_u16::<B>()
-            .context("could not read tries size")?;
-        let debug_info_offset = reader
-            .read_u32::<B>()
-            .context("could not read debug information offset")?;
-        let insns_size = reader
-            .read_u32::<B>()
-            .context("could not read the size of the bytecode array")?;
-
-        let mut insns = Vec::with_capacity(insns_size as usize);
-        for _ in 0..insns_size {
-            insns.push(reader.read_u16::<B>().context("could not read bytecode")?);
+        let registers_size = reader.read_u16::<B>()?;
+        let ins_size = reader.read_u16::<B>()?;
+        let outs_size = reader.read_u16::<B>()?;
+        let tries_size = reader.read_u16::<B>()?;
+        let debug_info_offset = reader.read_u32::<B>()?;
+        let insns_size = reader.read_u32::<B>()?;
+        if insns_size > max_insns_size {
+            return Err(error::Parse::ImplausibleSize {
+                size_name: "insns_size",
+                size: u64::from(insns_size),
+                limit: u64::from(max_insns_size),
+            }
+            .into());
         }
 
+        let mut raw_insns = vec![0_u8; insns_size as usize * 2].into_boxed_slice();
+        reader.read_exact(&mut raw_insns)?;
+        let insns = raw_insns.chunks_exact(2).map(B::read_u16).collect();
+
         if tries_size != 0 && (insns_size & 0b1 != 0) {
             let mut padding = [0_u8; 2];
-            reader
-                .read_exact(&mut padding)
-                .context("could not read padding before tries")?;
+            reader.read_exact(&mut padding)?;
         }
 
         let mut tries = Vec::with_capacity(tries_size as usize);
         for _ in 0..tries_size {
-            tries.push(TryItem::from_reader::<_, B>(reader).context("could not read try item")?);
+            tries.push(TryItem::from_reader::<_, B>(reader)?);
         }
 
         let mut handlers = Vec::new();
         if tries_size > 0 {
-            let (handlers_size, _) =
-                uleb128(reader).context("could not read catch handlers size")?;
+            let (handlers_size, mut handler_offset) = uleb128(reader)?;
 
-            handlers.reserve_exact(handlers_size as usize);
+            handlers =
+                checked_with_capacity("handlers_size", handlers_size, 1, remaining_bytes(reader)?)?;
             for _ in 0..handlers_size {
-                let (handler, _) =
-                    CatchHandler::from_reader(reader).context("could not read catch handler")?;
+                let (handler, read) = CatchHandler::from_reader(reader, handler_offset)?;
                 handlers.push(handler);
+                handler_offset += read;
             }
         }
 
@@ -1240,14 +1673,123 @@ impl CodeItem {
             outs_size,
             debug_info_offset,
             insns,
+            raw_insns,
             tries,
             handlers,
         })
     }
+
+    /// Gets the number of registers used by the method.
+    pub fn registers_size(&self) -> u16 {
+        self.registers_size
+    }
+
+    /// Gets the number of words of the incoming arguments of the method.
+    pub fn ins_size(&self) -> u16 {
+        self.ins_size
+    }
+
+    /// Gets the number of words of the outgoing arguments needed by the method for invocations.
+    pub fn outs_size(&self) -> u16 {
+        self.outs_size
+    }
+
+    /// Gets the bytecode of the method, as code units.
+    pub fn insns(&self) -> &[u16] {
+        &self.insns
+    }
+
+    /// Gets the handlers of all the catch handlers of the method, including the ones not used by
+    /// any try item.
+    pub(crate) fn handler_infos(&self) -> impl Iterator<Item = &HandlerInfo> {
+        self.handlers
+            .iter()
+            .flat_map(|handler| handler.handlers.iter())
+    }
+
+    /// Writes the code item in little endian byte order, without debug information.
+    ///
+    /// Try items reference their catch handlers by byte offset, so the handlers are written
+    /// with the shortest LEB128 encodings, as compilers do, and the offsets are checked.
+    #[cfg(feature = "extract")]
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn write(&self, out: &mut Vec<u8>) -> Result<()> {
+        out.extend_from_slice(&self.registers_size.to_le_bytes());
+        out.extend_from_slice(&self.ins_size.to_le_bytes());
+        out.extend_from_slice(&self.outs_size.to_le_bytes());
+        out.extend_from_slice(&(self.tries.len() as u16).to_le_bytes());
+        out.extend_from_slice(&0_u32.to_le_bytes());
+        out.extend_from_slice(&(self.insns.len() as u32).to_le_bytes());
+        for unit in &self.insns {
+            out.extend_from_slice(&unit.to_le_bytes());
+        }
+        if self.tries.is_empty() {
+            return Ok(());
+        }
+        if self.insns.len() & 0b1 != 0 {
+            out.extend_from_slice(&[0, 0]);
+        }
+
+        let mut handlers = Vec::new();
+        write_uleb128(&mut handlers, self.handlers.len() as u32);
+        let mut handler_offsets = Vec::with_capacity(self.handlers.len());
+        for handler in &self.handlers {
+            handler_offsets.push(handlers.len());
+            handler.write(&mut handlers);
+        }
+        for try_item in &self.tries {
+            if !handler_offsets.contains(&usize::from(try_item.handler_offset)) {
+                return Err(error::Write::NonCanonicalHandlers {
+                    handler_offset: try_item.handler_offset,
+                }
+                .into());
+            }
+            out.extend_from_slice(&try_item.start_address.to_le_bytes());
+            out.extend_from_slice(&try_item.insn_count.to_le_bytes());
+            out.extend_from_slice(&try_item.handler_offset.to_le_bytes());
+        }
+        out.extend_from_slice(&handlers);
+
+        Ok(())
+    }
+
+    /// Gets the instruction ranges covered by the try blocks of the method, along with their
+    /// exception handlers.
+    ///
+    /// Try items whose handler offset does not point to a catch handler, which can only happen
+    /// in malformed files, are given no handlers.
+    pub fn try_ranges(&self) -> Vec<TryRange> {
+        self.tries
+            .iter()
+            .map(|try_item| {
+                let handler = self
+                    .handlers
+                    .iter()
+                    .find(|handler| handler.offset == u32::from(try_item.handler_offset));
+                TryRange {
+                    start_address: try_item.start_address,
+                    end_address: try_item
+                        .start_address
+                        .saturating_add(u32::from(try_item.insn_count)),
+                    handlers: handler.map_or_else(Vec::new, |handler| handler.handlers.clone()),
+                    catch_all_address: handler.and_then(|handler| handler.catch_all_addr),
+                }
+            })
+            .collect()
+    }
+
+    /// Gets the bytecode of the method, exactly as it was stored in the dex file.
+    ///
+    /// This is useful to hash or compare method bodies without decoding them. Its length is
+    /// always twice the number of code units in the bytecode.
+    pub fn raw_insns_bytes(&self) -> &[u8] {
+        &self.raw_insns
+    }
 }
 
 /// Try item structure.
 #[derive(Debug)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
 struct TryItem {
     start_address: u32,
     insn_count: u16,
@@ -1261,15 +1803,9 @@ impl TryItem {
         R: Read + ReadBytesExt,
         B: ByteOrder,
     {
-        let start_address = reader
-            .read_u32::<B>()
-            .context("could not read start address")?;
-        let insn_count = reader
-            .read_u16::<B>()
-            .context("could not read instruction count")?;
-        let handler_offset = reader
-            .read_u16::<B>()
-            .context("could not read catch handler offset")?;
+        let start_address = reader.read_u32::<B>()?;
+        let insn_count = reader.read_u16::<B>()?;
+        let handler_offset = reader.read_u16::<B>()?;
 
         Ok(Self {
             start_address,
@@ -1279,34 +1815,71 @@ impl TryItem {
     }
 }
 
+/// Instruction range covered by a try block, with the handlers of the exceptions it catches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TryRange {
+    start_address: u32,
+    end_address: u32,
+    handlers: Vec<HandlerInfo>,
+    catch_all_address: Option<u32>,
+}
+
+impl TryRange {
+    /// Gets the address, in code units, of the first instruction covered by the try block.
+    pub fn start_address(&self) -> u32 {
+        self.start_address
+    }
+
+    /// Gets the address, in code units, right after the last instruction covered by the try
+    /// block.
+    pub fn end_address(&self) -> u32 {
+        self.end_address
+    }
+
+    /// Gets the handlers of the exception types caught by the try block, in the order in which
+    /// they are checked.
+    pub fn handlers(&self) -> &[HandlerInfo] {
+        &self.handlers
+    }
+
+    /// Gets the address of the handler of any other exception thrown in the try block, if any.
+    pub fn catch_all_address(&self) -> Option<u32> {
+        self.catch_all_address
+    }
+}
+
 /// Struct representing a catch handler.
 #[derive(Debug)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
 struct CatchHandler {
+    /// Offset of the handler in bytes, from the start of the encoded catch handler list.
+    offset: u32,
     handlers: Vec<HandlerInfo>,
     catch_all_addr: Option<u32>,
 }
 
 impl CatchHandler {
-    /// Reads a catch handler from a reader.
+    /// Reads a catch handler, found at the given offset of the catch handler list, from a
+    /// reader.
     #[allow(clippy::cast_sign_loss)]
-    fn from_reader<R>(reader: &mut R) -> Result<(Self, u32)>
+    fn from_reader<R>(reader: &mut R, offset: u32) -> Result<(Self, u32)>
     where
-        R: Read,
+        R: Read + Seek,
     {
-        let (size, mut read) = sleb128(reader).context("could not read the catch handler size")?;
+        let (size, mut read) = sleb128(reader)?;
 
-        let abs_size = size.abs() as usize;
-        let mut handlers = Vec::with_capacity(abs_size);
+        // `i32::min_value()` has no positive counterpart, but it fits in a `u32`.
+        let abs_size = size.wrapping_abs() as u32;
+        // Each handler has, at least, a one byte type index and a one byte address.
+        let mut handlers = checked_with_capacity("size", abs_size, 2, remaining_bytes(reader)?)?;
         for _ in 0..abs_size {
-            let (handler_info, read_hi) =
-                HandlerInfo::from_reader(reader).context("could not read handler information")?;
+            let (handler_info, read_hi) = HandlerInfo::from_reader(reader)?;
             handlers.push(handler_info);
             read += read_hi;
         }
 
         let catch_all_addr = if size < 1 {
-            let (addr, read_ca) =
-                uleb128(reader).context("could not read the catch all address")?;
+            let (addr, read_ca) = uleb128(reader)?;
             read += read_ca;
             Some(addr)
         } else {
@@ -1315,16 +1888,41 @@ impl CatchHandler {
 
         Ok((
             Self {
+                offset,
                 handlers,
                 catch_all_addr,
             },
             read,
         ))
     }
+
+    /// Writes the catch handler, with the shortest LEB128 encodings.
+    #[cfg(feature = "extract")]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    fn write(&self, out: &mut Vec<u8>) {
+        let size = self.handlers.len() as i32;
+        write_sleb128(
+            out,
+            if self.catch_all_addr.is_some() {
+                -size
+            } else {
+                size
+            },
+        );
+        for handler in &self.handlers {
+            write_uleb128(out, handler.type_id);
+            write_uleb128(out, handler.addr);
+        }
+        if let Some(addr) = self.catch_all_addr {
+            write_uleb128(out, addr);
+        }
+    }
 }
 
-#[derive(Debug)]
-struct HandlerInfo {
+/// Handler of an exception type in a catch handler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cache", derive(Serialize, Deserialize))]
+pub struct HandlerInfo {
     type_id: u32,
     addr: u32,
 }
@@ -1335,11 +1933,21 @@ impl HandlerInfo {
     where
         R: Read,
     {
-        let (type_id, read_t) = uleb128(reader).context("could not read type ID")?;
-        let (addr, read_a) = uleb128(reader).context("could not read address")?;
+        let (type_id, read_t) = uleb128(reader)?;
+        let (addr, read_a) = uleb128(reader)?;
 
         Ok((Self { type_id, addr }, read_t + read_a))
     }
+
+    /// Gets the type index of the exception caught by the handler.
+    pub fn type_index(&self) -> u32 {
+        self.type_id
+    }
+
+    /// Gets the address, in code units, of the handler.
+    pub fn address(&self) -> u32 {
+        self.addr
+    }
 }
 
 #[cfg(test)]
@@ -1347,9 +1955,117 @@ mod tests {
     use std::{f32, f64, io::Cursor};
 
     use super::{
-        VALUE_BYTE, VALUE_CHAR, VALUE_DOUBLE, VALUE_FLOAT, VALUE_INT, VALUE_LONG, VALUE_SHORT,
+        CallSite, ClassData, CodeItem, DebugInfo, DexString, MethodHandleData, VALUE_BYTE,
+        VALUE_CHAR, VALUE_DOUBLE, VALUE_FLOAT, VALUE_INT, VALUE_LONG, VALUE_SHORT,
+    };
+    use crate::{
+        error,
+        types::{Array, EncodedAnnotation, MethodHandleType, Value},
     };
-    use crate::types::Value;
+    use byteorder::LittleEndian;
+
+    #[test]
+    fn it_reads_supplementary_characters_from_string_data() {
+        // "a😀\0", with the emoji encoded as a surrogate pair and the null character in two
+        // bytes, as MUTF-8 requires.
+        let raw = [
+            0x04, 0x61, 0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80, 0xC0, 0x80, 0x00,
+        ];
+        let string = DexString::from_reader(&mut Cursor::new(&raw[..])).unwrap();
+
+        assert_eq!("a\u{1F600}\0", string.text());
+        assert_eq!(4, string.utf16_units());
+        assert_eq!(4, string.computed_utf16_units());
+        assert_eq!(3, string.text().chars().count());
+        assert_eq!(9, string.byte_len());
+    }
+
+    #[test]
+    fn it_rejects_string_sizes_larger_than_the_data() {
+        // A declared size of 0x0fff_ffff code units, followed by a single character.
+        let raw = [0xFF, 0xFF, 0xFF, 0x7F, 0x61, 0x00];
+        let error = DexString::from_reader(&mut Cursor::new(&raw[..])).unwrap_err();
+        assert!(matches!(
+            error,
+            error::Error::Parse(error::Parse::ImplausibleSize {
+                size_name: "utf16_size",
+                size: 0x0FFF_FFFF,
+                limit: 2,
+            })
+        ));
+    }
+
+    #[test]
+    fn it_reads_a_method_handle() {
+        // `invoke-static` handle of method 0x0102.
+        let raw = [0x04, 0x00, 0x00, 0x00, 0x02, 0x01, 0x00, 0x00];
+        let handle =
+            MethodHandleData::from_reader::<_, LittleEndian>(&mut Cursor::new(&raw[..])).unwrap();
+
+        assert_eq!(MethodHandleType::InvokeStatic, handle.handle_type());
+        assert_eq!(0x0102, handle.field_or_method_index());
+        assert_eq!(Some(0x0102), handle.method_index());
+        assert_eq!(None, handle.field_index());
+
+        let raw = [0x09, 0x00, 0x00, 0x00, 0x02, 0x01, 0x00, 0x00];
+        let error = MethodHandleData::from_reader::<_, LittleEndian>(&mut Cursor::new(&raw[..]))
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            error::Error::Parse(error::Parse::InvalidMethodHandleType(0x09))
+        ));
+    }
+
+    #[test]
+    fn it_reads_a_call_site() {
+        let method_handles = [
+            MethodHandleData::from_reader::<_, LittleEndian>(&mut Cursor::new(
+                &[0x04, 0x00, 0x00, 0x00, 0x02, 0x01, 0x00, 0x00][..],
+            ))
+            .unwrap(),
+        ];
+        // Method handle 0, string 5, method type 3 and an extra integer argument 7.
+        let raw = [0x04, 0x16, 0x00, 0x17, 0x05, 0x15, 0x03, 0x04, 0x07];
+        let call_site = CallSite::from_reader(&mut Cursor::new(&raw[..]), &method_handles).unwrap();
+
+        assert_eq!(method_handles[0], call_site.bootstrap_method());
+        assert_eq!(5, call_site.method_name_index());
+        assert_eq!(3, call_site.method_type_index());
+        assert_eq!(1, call_site.extra_arguments().len());
+        assert!(matches!(call_site.extra_arguments()[0], Value::Int(7)));
+
+        // The method type and the method name are swapped.
+        let raw = [0x03, 0x16, 0x00, 0x15, 0x03, 0x17, 0x05];
+        let error = CallSite::from_reader(&mut Cursor::new(&raw[..]), &method_handles).unwrap_err();
+        assert!(matches!(
+            error,
+            error::Error::Parse(error::Parse::InvalidCallSite { .. })
+        ));
+
+        // The bootstrap method handle does not exist.
+        let raw = [0x03, 0x16, 0x01, 0x17, 0x05, 0x15, 0x03];
+        assert!(CallSite::from_reader(&mut Cursor::new(&raw[..]), &method_handles).is_err());
+    }
+
+    #[test]
+    fn it_keeps_the_encoded_width_of_values() {
+        // An int encoded in 2 bytes, even if it fits in 1, the same int encoded in 1 byte and a
+        // `null`.
+        let raw = [0x03, 0x24, 0x05, 0x00, 0x04, 0x05, 0x1e];
+        let array = Array::from_reader(&mut Cursor::new(&raw[..])).unwrap();
+
+        assert!(matches!(array[0], Value::Int(5)));
+        assert!(matches!(array[1], Value::Int(5)));
+        assert_eq!(Some(2), array.encoded_width(0));
+        assert_eq!(Some(1), array.encoded_width(1));
+        assert_eq!(None, array.encoded_width(2));
+        assert_eq!(None, array.encoded_width(3));
+
+        // Annotation of type 1 with an element named by string 2, with the same 2 byte int.
+        let raw = [0x01, 0x01, 0x02, 0x24, 0x05, 0x00];
+        let annotation = EncodedAnnotation::from_reader(&mut Cursor::new(&raw[..])).unwrap();
+        assert_eq!(Some(2), annotation.elements()[0].encoded_width());
+    }
 
     #[test]
     fn it_returns_error_if_value_type_is_not_valid() {
@@ -1613,4 +2329,166 @@ mod tests {
             _ => unreachable!(),
         }
     }
+
+    #[test]
+    fn it_accepts_zero_delta_field_ids() {
+        // 1 static field, 3 instance fields, no methods. The instance field IDs are encoded as
+        // 5, +0, +0, so the three of them refer to the same field ID.
+        let raw = [
+            0x01, 0x03, 0x00, 0x00, 0x02, 0x08, 0x05, 0x01, 0x00, 0x02, 0x00, 0x10,
+        ];
+        let class_data = ClassData::from_reader(&mut Cursor::new(raw)).unwrap();
+
+        let static_ids: Vec<_> = class_data
+            .static_fields
+            .iter()
+            .map(|f| f.field_id)
+            .collect();
+        let instance_ids: Vec<_> = class_data
+            .instance_fields
+            .iter()
+            .map(|f| f.field_id)
+            .collect();
+        assert_eq!(static_ids, [2]);
+        assert_eq!(instance_ids, [5, 5, 5]);
+    }
+
+    #[test]
+    fn it_accepts_zero_delta_method_ids() {
+        // 2 direct methods and 3 virtual methods, with zero deltas mixed with regular ones.
+        let raw = [
+            0x00, 0x00, 0x02, 0x03, // sizes
+            0x07, 0x01, 0x00, // direct: ID 7
+            0x00, 0x02, 0x00, // direct: ID 7 + 0
+            0x03, 0x01, 0x00, // virtual: ID 3
+            0x00, 0x01, 0x00, // virtual: ID 3 + 0
+            0x04, 0x01, 0x00, // virtual: ID 3 + 4
+        ];
+        let class_data = ClassData::from_reader(&mut Cursor::new(raw)).unwrap();
+
+        let direct_ids: Vec<_> = class_data
+            .direct_methods
+            .iter()
+            .map(|m| m.method_id)
+            .collect();
+        let virtual_ids: Vec<_> = class_data
+            .virtual_methods
+            .iter()
+            .map(|m| m.method_id)
+            .collect();
+        assert_eq!(direct_ids, [7, 7]);
+        assert_eq!(virtual_ids, [3, 3, 7]);
+    }
+
+    #[test]
+    fn it_exposes_raw_instruction_bytes() {
+        let raw = [
+            0x01, 0x00, // registers_size
+            0x01, 0x00, // ins_size
+            0x00, 0x00, // outs_size
+            0x00, 0x00, // tries_size
+            0x00, 0x00, 0x00, 0x00, // debug_info_off
+            0x03, 0x00, 0x00, 0x00, // insns_size
+            0x12, 0x10, 0x0f, 0x00, 0x00, 0x00, // insns
+        ];
+        let code_item = CodeItem::from_reader::<_, LittleEndian>(&mut Cursor::new(raw)).unwrap();
+
+        assert_eq!(code_item.insns(), &[0x1012, 0x000f, 0x0000]);
+        assert_eq!(
+            code_item.raw_insns_bytes().len(),
+            code_item.insns().len() * 2
+        );
+        assert_eq!(code_item.raw_insns_bytes(), &raw[16..]);
+    }
+
+    #[test]
+    fn it_rejects_implausible_insns_sizes() {
+        let raw = [
+            0x01, 0x00, // registers_size
+            0x01, 0x00, // ins_size
+            0x00, 0x00, // outs_size
+            0x00, 0x00, // tries_size
+            0x00, 0x00, 0x00, 0x00, // debug_info_off
+            0xff, 0xff, 0xff, 0x7f, // insns_size
+            0x0e, 0x00, // insns
+        ];
+        let error =
+            CodeItem::from_reader_limited::<_, LittleEndian>(&mut Cursor::new(raw), 1).unwrap_err();
+
+        match &error {
+            error::Error::Parse(error::Parse::ImplausibleSize {
+                size_name: "insns_size",
+                size: 0x7fff_ffff,
+                limit: 1,
+            }) => {}
+            _ => panic!("unexpected error: {:?}", error),
+        }
+    }
+
+    #[test]
+    fn it_rejects_implausible_class_data_sizes() {
+        // 0x0fffffff static fields, and no other members.
+        let raw = [0xff, 0xff, 0xff, 0x7f, 0x00, 0x00, 0x00, 0x00, 0x01];
+        let error = ClassData::from_reader(&mut Cursor::new(raw)).unwrap_err();
+
+        match &error {
+            error::Error::Parse(error::Parse::ImplausibleSize {
+                size_name: "static_fields_size",
+                size: 0x0fff_ffff,
+                limit: 1,
+            }) => {}
+            _ => panic!("unexpected error: {:?}", error),
+        }
+    }
+
+    #[test]
+    fn it_rejects_implausible_array_sizes() {
+        // An array of 0xffffffff values, with a nested annotation with 0x0fffffff elements.
+        let raw = [0xff, 0xff, 0xff, 0xff, 0x0f, 0x00];
+        assert!(Array::from_reader(&mut Cursor::new(raw)).is_err());
+        let raw = [0x01, 0xff, 0xff, 0xff, 0x7f, 0x00];
+        assert!(EncodedAnnotation::from_reader(&mut Cursor::new(raw)).is_err());
+
+        let raw = [0x02, 0x1e, 0x1f];
+        let array = Array::from_reader(&mut Cursor::new(raw)).unwrap();
+        assert_eq!(2, array.len());
+    }
+
+    #[test]
+    fn it_renders_debug_instructions() {
+        let raw = [
+            0x0a, 0x01, 0x06, // Line 10, one parameter named string@5.
+            0x07, // DBG_SET_PROLOGUE_END
+            0x03, 0x01, 0x04, 0x00, // DBG_START_LOCAL
+            0x01, 0x02, // DBG_ADVANCE_PC
+            0x02, 0x7f, // DBG_ADVANCE_LINE
+            0x09, 0x07, // DBG_SET_FILE
+            0x05, 0x01, // DBG_END_LOCAL
+            0x1f, // Special opcode.
+            0x00, // DBG_END_SEQUENCE
+        ];
+        let (debug_info, read) = DebugInfo::from_reader(&mut Cursor::new(raw)).unwrap();
+        assert_eq!(raw.len() as u32, read);
+        assert_eq!(10, debug_info.line_start());
+        assert_eq!(&[Some(5)], debug_info.parameter_names());
+
+        let rendered: Vec<_> = debug_info
+            .bytecode()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            vec![
+                "DBG_SET_PROLOGUE_END",
+                "DBG_START_LOCAL v1, name@3, type@NO_INDEX",
+                "DBG_ADVANCE_PC 2",
+                "DBG_ADVANCE_LINE -1",
+                "DBG_SET_FILE name@7",
+                "DBG_END_LOCAL v1",
+                "DBG_SPECIAL 0x1f (line += 2, address += 1)",
+                "DBG_END_SEQUENCE",
+            ],
+            rendered
+        );
+    }
 }
@@ -0,0 +1,414 @@
+//! Dex file writing.
+
+use crate::{
+    error::{self, Result},
+    header::ENDIAN_CONSTANT,
+    sizes::{
+        CLASS_DEF_ITEM_SIZE, FIELD_ID_ITEM_SIZE, HEADER_SIZE, METHOD_ID_ITEM_SIZE,
+        PROTO_ID_ITEM_SIZE, STRING_ID_ITEM_SIZE, TYPE_ID_ITEM_SIZE,
+    },
+    types::{read::NO_INDEX, ItemType, Type},
+    Dex, Field, Method,
+};
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+
+/// Writes an unsigned LEB128 number, with its shortest encoding.
+#[allow(clippy::cast_possible_truncation)]
+pub(crate) fn write_uleb128(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Writes a signed LEB128 number, with its shortest encoding.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub(crate) fn write_sleb128(out: &mut Vec<u8>, mut value: i32) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Writes a `string_data_item`, encoding the string in MUTF-8.
+#[allow(clippy::cast_possible_truncation)]
+fn write_string_data(out: &mut Vec<u8>, string: &str) {
+    let units: Vec<u16> = string.encode_utf16().collect();
+    write_uleb128(out, units.len() as u32);
+    for unit in units {
+        match unit {
+            0x0001..=0x007F => out.push(unit as u8),
+            0x0000 | 0x0080..=0x07FF => {
+                out.push(0xC0 | (unit >> 6) as u8);
+                out.push(0x80 | (unit & 0x3F) as u8);
+            }
+            _ => {
+                out.push(0xE0 | (unit >> 12) as u8);
+                out.push(0x80 | (unit >> 6 & 0x3F) as u8);
+                out.push(0x80 | (unit & 0x3F) as u8);
+            }
+        }
+    }
+    out.push(0);
+}
+
+/// Pads the data with zeros until its length is a multiple of 4.
+fn align(data: &mut Vec<u8>) {
+    data.resize((data.len() + 0b11) & !0b11, 0);
+}
+
+/// Gets the short form descriptor character of a type.
+fn shorty_char(value_type: &Type) -> char {
+    match value_type {
+        Type::FullyQualifiedName(_) | Type::Array { .. } => 'L',
+        primitive => primitive.to_descriptor().chars().next().unwrap_or('V'),
+    }
+}
+
+/// Writes a dex file containing only the class with the given descriptor.
+///
+/// All the ID lists are kept, in the same order, so that the indexes in the bytecode of the
+/// class stay valid without having to rewrite it. Only the class definition, its class data,
+/// the code of its methods and the data these need are written. Annotations, static values
+/// and debug information are left out.
+#[allow(clippy::cast_possible_truncation, clippy::too_many_lines)]
+pub(crate) fn extract_class(dex: &Dex, descriptor: &str) -> Result<Vec<u8>> {
+    let class = dex
+        .types
+        .iter()
+        .find(|class| descriptor.starts_with('L') && class.name() == &descriptor[1..])
+        .ok_or_else(|| error::Write::UnknownClass(descriptor.to_owned()))?;
+
+    let string_indexes: HashMap<&str, u32> = dex
+        .strings
+        .iter()
+        .enumerate()
+        .map(|(index, string)| (string, index as u32))
+        .collect();
+    let string_index = |string: &str| {
+        string_indexes
+            .get(string)
+            .copied()
+            .ok_or_else(|| error::Write::MissingString(string.to_owned()))
+    };
+    let type_descriptors: Vec<String> = dex.type_ids.iter().map(Type::to_descriptor).collect();
+    let type_indexes: HashMap<&str, u32> = type_descriptors
+        .iter()
+        .enumerate()
+        .map(|(index, descriptor)| (descriptor.as_str(), index as u32))
+        .collect();
+    let type_index = |descriptor: &str| {
+        type_indexes
+            .get(descriptor)
+            .copied()
+            .ok_or_else(|| error::Write::MissingString(descriptor.to_owned()))
+    };
+    let class_index = type_index(descriptor)?;
+
+    let string_ids_offset = HEADER_SIZE;
+    let type_ids_offset = string_ids_offset + STRING_ID_ITEM_SIZE * dex.strings.len() as u32;
+    let proto_ids_offset = type_ids_offset + TYPE_ID_ITEM_SIZE * dex.type_ids.len() as u32;
+    let field_ids_offset = proto_ids_offset + PROTO_ID_ITEM_SIZE * dex.prototypes.len() as u32;
+    let method_ids_offset = field_ids_offset + FIELD_ID_ITEM_SIZE * dex.field_ids.len() as u32;
+    let class_defs_offset = method_ids_offset + METHOD_ID_ITEM_SIZE * dex.method_ids.len() as u32;
+    let data_offset = class_defs_offset + CLASS_DEF_ITEM_SIZE;
+
+    // Data section, with the map entries of its sections.
+    let mut data = Vec::new();
+    let mut data_map = Vec::new();
+    let position = |data: &Vec<u8>| data_offset + data.len() as u32;
+
+    let mut type_lists = Vec::new();
+    let mut write_type_list = |data: &mut Vec<u8>, types: &[String]| -> Result<u32> {
+        align(data);
+        let offset = position(data);
+        type_lists.push(offset);
+        data.extend_from_slice(&(types.len() as u32).to_le_bytes());
+        for descriptor in types {
+            data.extend_from_slice(&(type_index(descriptor)? as u16).to_le_bytes());
+        }
+        Ok(offset)
+    };
+    let mut parameters_offsets = Vec::with_capacity(dex.prototypes.len());
+    for prototype in &dex.prototypes {
+        parameters_offsets.push(if prototype.parameters().is_empty() {
+            0
+        } else {
+            let parameters: Vec<_> = prototype
+                .parameters()
+                .iter()
+                .map(Type::to_descriptor)
+                .collect();
+            write_type_list(&mut data, &parameters)?
+        });
+    }
+    let interfaces_offset = if class.interfaces().is_empty() {
+        0
+    } else {
+        let interfaces: Vec<_> = class
+            .interfaces()
+            .iter()
+            .map(|name| format!("L{}", name))
+            .collect();
+        write_type_list(&mut data, &interfaces)?
+    };
+    if let Some(&offset) = type_lists.first() {
+        data_map.push((ItemType::TypeList, type_lists.len() as u32, offset));
+    }
+
+    let mut string_data_offsets = Vec::with_capacity(dex.strings.len());
+    for string in dex.strings.iter() {
+        string_data_offsets.push(position(&data));
+        write_string_data(&mut data, string);
+    }
+    if let Some(&offset) = string_data_offsets.first() {
+        data_map.push((ItemType::StringData, dex.strings.len() as u32, offset));
+    }
+
+    let mut code_items = Vec::new();
+    let mut write_code = |data: &mut Vec<u8>, method: &Method| -> Result<u32> {
+        match method
+            .code_offset()
+            .and_then(|offset| dex.code_item(offset))
+        {
+            Some(code_item) => {
+                align(data);
+                let offset = position(data);
+                code_items.push(offset);
+                code_item.write(data)?;
+                Ok(offset)
+            }
+            None => Ok(0),
+        }
+    };
+    let mut direct_methods = Vec::with_capacity(class.direct_methods().len());
+    for method in class.direct_methods() {
+        direct_methods.push((method, write_code(&mut data, method)?));
+    }
+    let mut virtual_methods = Vec::with_capacity(class.virtual_methods().len());
+    for method in class.virtual_methods() {
+        virtual_methods.push((method, write_code(&mut data, method)?));
+    }
+    if let Some(&offset) = code_items.first() {
+        data_map.push((ItemType::Code, code_items.len() as u32, offset));
+    }
+
+    let unknown_member = |name: &str| error::Write::UnknownMember {
+        class: descriptor.to_owned(),
+        name: name.to_owned(),
+    };
+    let field_index = |field: &Field| {
+        dex.field_ids
+            .iter()
+            .position(|id| {
+                id.class_index() == class_index as usize
+                    && dex.strings.get(id.name_index()) == Some(field.name())
+                    && dex
+                        .type_ids
+                        .get(id.type_index())
+                        .map(Type::to_string)
+                        .as_deref()
+                        == Some(field.field_type())
+            })
+            .ok_or_else(|| unknown_member(field.name()))
+    };
+    let method_index = |method: &Method| {
+        dex.method_ids
+            .iter()
+            .position(|id| {
+                let prototype = match dex.prototypes.get(id.prototype_index()) {
+                    Some(prototype) => prototype,
+                    None => return false,
+                };
+                id.class_index() == class_index as usize
+                    && dex.strings.get(id.name_index()) == Some(method.name())
+                    && prototype.return_type() == method.return_type()
+                    && prototype.parameters() == method.parameters()
+            })
+            .ok_or_else(|| unknown_member(method.name()))
+    };
+
+    let class_data_offset = position(&data);
+    data_map.push((ItemType::ClassData, 1, class_data_offset));
+    write_uleb128(&mut data, class.static_fields().len() as u32);
+    write_uleb128(&mut data, class.instance_fields().len() as u32);
+    write_uleb128(&mut data, direct_methods.len() as u32);
+    write_uleb128(&mut data, virtual_methods.len() as u32);
+    for fields in &[class.static_fields(), class.instance_fields()] {
+        let mut encoded = Vec::with_capacity(fields.len());
+        for field in *fields {
+            encoded.push((field_index(field)? as u32, field.raw_access_flags()));
+        }
+        encoded.sort_unstable();
+        let mut previous = 0;
+        for (index, access_flags) in encoded {
+            write_uleb128(&mut data, index - previous);
+            write_uleb128(&mut data, access_flags);
+            previous = index;
+        }
+    }
+    for methods in &[direct_methods, virtual_methods] {
+        let mut encoded = Vec::with_capacity(methods.len());
+        for (method, code_offset) in methods {
+            encoded.push((
+                method_index(method)? as u32,
+                method.raw_access_flags(),
+                *code_offset,
+            ));
+        }
+        encoded.sort_unstable();
+        let mut previous = 0;
+        for (index, access_flags, code_offset) in encoded {
+            write_uleb128(&mut data, index - previous);
+            write_uleb128(&mut data, access_flags);
+            write_uleb128(&mut data, code_offset);
+            previous = index;
+        }
+    }
+
+    align(&mut data);
+    let map_offset = position(&data);
+    data_map.push((ItemType::MapList, 1, map_offset));
+    let mut map = vec![(ItemType::Header, 1, 0)];
+    let id_sections = [
+        (ItemType::StringId, dex.strings.len(), string_ids_offset),
+        (ItemType::TypeId, dex.type_ids.len(), type_ids_offset),
+        (ItemType::ProtoId, dex.prototypes.len(), proto_ids_offset),
+        (ItemType::FieldId, dex.field_ids.len(), field_ids_offset),
+        (ItemType::MethodId, dex.method_ids.len(), method_ids_offset),
+        (ItemType::ClassDef, 1, class_defs_offset),
+    ];
+    for &(item_type, size, offset) in &id_sections {
+        if size > 0 {
+            map.push((item_type, size as u32, offset));
+        }
+    }
+    map.extend(data_map);
+    data.extend_from_slice(&(map.len() as u32).to_le_bytes());
+    for (item_type, size, offset) in map {
+        data.extend_from_slice(&item_type.to_u16().to_le_bytes());
+        data.extend_from_slice(&0_u16.to_le_bytes());
+        data.extend_from_slice(&size.to_le_bytes());
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+
+    let file_size = data_offset + data.len() as u32;
+    let mut file = Vec::with_capacity(file_size as usize);
+    file.extend_from_slice(dex.header.get_magic());
+    // Checksum and signature, computed at the end.
+    file.extend_from_slice(&[0; 24]);
+    for value in &[file_size, HEADER_SIZE, ENDIAN_CONSTANT, 0, 0, map_offset] {
+        file.extend_from_slice(&value.to_le_bytes());
+    }
+    for &(_, size, offset) in &id_sections {
+        let offset = if size > 0 { offset } else { 0 };
+        file.extend_from_slice(&(size as u32).to_le_bytes());
+        file.extend_from_slice(&offset.to_le_bytes());
+    }
+    file.extend_from_slice(&(file_size - data_offset).to_le_bytes());
+    file.extend_from_slice(&data_offset.to_le_bytes());
+
+    for offset in string_data_offsets {
+        file.extend_from_slice(&offset.to_le_bytes());
+    }
+    for descriptor in &type_descriptors {
+        file.extend_from_slice(&string_index(descriptor)?.to_le_bytes());
+    }
+    for (prototype, parameters_offset) in dex.prototypes.iter().zip(parameters_offsets) {
+        let shorty: String = Some(prototype.return_type())
+            .into_iter()
+            .chain(prototype.parameters())
+            .map(shorty_char)
+            .collect();
+        file.extend_from_slice(&string_index(&shorty)?.to_le_bytes());
+        file.extend_from_slice(
+            &type_index(&prototype.return_type().to_descriptor())?.to_le_bytes(),
+        );
+        file.extend_from_slice(&parameters_offset.to_le_bytes());
+    }
+    for field in &dex.field_ids {
+        file.extend_from_slice(&(field.class_index() as u16).to_le_bytes());
+        file.extend_from_slice(&(field.type_index() as u16).to_le_bytes());
+        file.extend_from_slice(&(field.name_index() as u32).to_le_bytes());
+    }
+    for method in &dex.method_ids {
+        file.extend_from_slice(&(method.class_index() as u16).to_le_bytes());
+        file.extend_from_slice(&(method.prototype_index() as u16).to_le_bytes());
+        file.extend_from_slice(&(method.name_index() as u32).to_le_bytes());
+    }
+
+    let superclass_index = match class.superclass() {
+        Some(superclass) => type_index(&format!("L{}", superclass))?,
+        None => NO_INDEX,
+    };
+    let source_file_index = match class.source_file() {
+        Some(source_file) => string_index(source_file)?,
+        None => NO_INDEX,
+    };
+    for value in &[
+        class_index,
+        class.raw_access_flags(),
+        superclass_index,
+        interfaces_offset,
+        source_file_index,
+        0,
+        class_data_offset,
+        0,
+    ] {
+        file.extend_from_slice(&value.to_le_bytes());
+    }
+    file.extend_from_slice(&data);
+
+    let signature = Sha1::digest(&file[32..]);
+    file[12..32].copy_from_slice(&signature);
+    let checksum = adler::adler32_slice(&file[12..]);
+    file[8..12].copy_from_slice(&checksum.to_le_bytes());
+
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_sleb128, write_string_data, write_uleb128};
+    use crate::read::{sleb128, uleb128};
+    use std::io::Cursor;
+
+    #[test]
+    fn it_writes_leb128() {
+        for &value in &[0, 1, 127, 128, 16256, 0xFFFF_FFFF] {
+            let mut out = Vec::new();
+            write_uleb128(&mut out, value);
+            assert_eq!(value, uleb128(&mut Cursor::new(&out)).unwrap().0);
+        }
+        for &value in &[0, 1, -1, 63, 64, -64, -65, -128, 0x7FFF_FFFF, -0x8000_0000] {
+            let mut out = Vec::new();
+            write_sleb128(&mut out, value);
+            assert_eq!(value, sleb128(&mut Cursor::new(&out)).unwrap().0);
+        }
+
+        let mut out = Vec::new();
+        write_uleb128(&mut out, 16256);
+        assert_eq!(vec![0x80, 0x7F], out);
+    }
+
+    #[test]
+    fn it_writes_mutf8_string_data() {
+        let mut out = Vec::new();
+        write_string_data(&mut out, "a\u{1F600}\0");
+        assert_eq!(
+            vec![0x04, 0x61, 0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80, 0xC0, 0x80, 0x00],
+            out
+        );
+    }
+}
@@ -2,8 +2,16 @@
 
 extern crate dalvik;
 
-use dalvik::types::AccessFlags;
-use std::{fs, path::Path};
+use dalvik::{
+    bytecode::{ArrayOperation, InstructionFilter, Reference},
+    types::{AccessFlags, EncodedAnnotation, HiddenApiRestriction, ItemType, Type},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::Path,
+    sync::Arc,
+};
 
 #[test]
 fn it_header_read() {
@@ -41,7 +49,52 @@ fn it_header_read() {
     assert_eq!(1791, header.get_class_defs_size());
     assert_eq!(0x59420, header.get_class_defs_offset().unwrap());
     assert_eq!(2_420_664, header.get_data_size());
-    assert_eq!(0x79ff8, header.get_data_offset());
+    assert_eq!(Some(0x79ff8), header.get_data_offset());
+    assert_eq!((0x79ff8, 0x79ff8 + 2_420_664), header.data_range());
+    assert_eq!(file_size, u64::from(header.data_range().1));
+}
+
+#[test]
+fn it_gets_the_map_range() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    assert_eq!(18, dex.section_sizes().len());
+    // The map has a 4-byte size followed by 12 bytes per item.
+    assert_eq!((0x79ff8, 0x79ff8 + 4 + 18 * 12), dex.map_range());
+    let (data_start, data_end) = dex.header().data_range();
+    let (map_start, map_end) = dex.map_range();
+    assert!(data_start <= map_start && map_end <= data_end);
+}
+
+#[test]
+fn it_reads_absent_header_offsets_as_none() {
+    // Remove the class definitions from the header.
+    let mut bytes = fs::read("test.dex").unwrap();
+    write_u32(&mut bytes, 0x60, 0);
+    write_u32(&mut bytes, 0x64, 0);
+
+    let header = dalvik::Header::from_reader(&bytes[..]).unwrap();
+    assert_eq!(0, header.get_class_defs_size());
+    assert!(header.get_class_defs_offset().is_none());
+    assert!(header.get_link_offset().is_none());
+    assert!(header.get_data_offset().is_some());
+}
+
+#[test]
+fn it_detects_compact_dex() {
+    let mut bytes = fs::read("test.dex").unwrap();
+    bytes[0..8].copy_from_slice(b"cdex001\0");
+
+    let error = dalvik::Header::from_reader(&bytes[..]).unwrap_err();
+    assert!(matches!(
+        error,
+        dalvik::error::Error::Parse(dalvik::error::Parse::CompactDexUnsupported)
+    ));
+
+    let error = dalvik::Dex::from_reader(&bytes[..], bytes.len()).unwrap_err();
+    assert!(matches!(
+        error.root_cause(),
+        dalvik::error::Error::Parse(dalvik::error::Parse::CompactDexUnsupported)
+    ));
 }
 
 // #[test]
@@ -55,8 +108,2366 @@ fn it_file_read() {
     let dex = dalvik::Dex::from_file("test.dex").unwrap();
 }
 
+#[test]
+fn it_reports_failing_section_and_offset() {
+    let mut bytes = fs::read("test.dex").unwrap();
+    // Point the first string ID to the first code item, which is not valid string data.
+    bytes[0x70..0x74].copy_from_slice(&0xaabe8_u32.to_le_bytes());
+
+    let error = dalvik::Dex::from_reader(&bytes[..], bytes.len()).unwrap_err();
+    let message = error.to_string();
+    assert!(message.contains("string_data_item"));
+    assert!(message.contains("0x000aabe8"));
+}
+
+#[test]
+fn it_reports_the_innermost_failing_section() {
+    let mut bytes = fs::read("test.dex").unwrap();
+    // Make the interface list of the first class point to a type that does not exist.
+    bytes[0x7_eeec..0x7_eeee].copy_from_slice(&0xffff_u16.to_le_bytes());
+
+    let error = dalvik::Dex::from_reader(&bytes[..], bytes.len()).unwrap_err();
+    let message = error.to_string();
+    assert!(message.contains("type_list"));
+    assert!(message.contains("0x0007eee8"));
+    assert!(!message.contains("class_def_item"));
+}
+
+#[test]
+fn it_rejects_string_offsets_outside_data_section() {
+    let mut bytes = fs::read("test.dex").unwrap();
+    // Point the second string ID to the checksum in the header.
+    bytes[0x74..0x78].copy_from_slice(&0x08_u32.to_le_bytes());
+
+    let error = dalvik::Dex::from_reader(&bytes[..], bytes.len()).unwrap_err();
+    assert!(error.to_string().contains("string_id_item"));
+    assert!(matches!(
+        error.root_cause(),
+        dalvik::error::Error::Parse(dalvik::error::Parse::StringOffsetOutOfRange {
+            index: 1,
+            offset: 0x08
+        })
+    ));
+}
+
+#[test]
+fn it_resolves_annotation_elements() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    // `Ldalvik/annotation/InnerClass;` (type 2188) with `accessFlags` (string 7964) set to 9 and
+    // `name` (string 15436) set to the string `value` (string 19638).
+    let raw = [
+        0x8c, 0x11, 0x02, 0x9c, 0x3e, 0x04, 0x09, 0xcc, 0x78, 0x37, 0xb6, 0x4c,
+    ];
+    let annotation = EncodedAnnotation::from_reader(&mut io::Cursor::new(raw)).unwrap();
+
+    let resolved = dex.resolve_annotation(&annotation);
+    assert_eq!("dalvik/annotation/InnerClass;", resolved.type_name());
+    assert_eq!(2, resolved.elements().len());
+    assert_eq!("9", resolved.elements()["accessFlags"]);
+    assert_eq!("\"value\"", resolved.elements()["name"]);
+    assert_eq!(
+        "@dalvik/annotation/InnerClass;(accessFlags=9, name=\"value\")",
+        resolved.to_string()
+    );
+}
+
+#[test]
+fn it_reads_dex_at_base_offset() {
+    let dex_bytes = fs::read("test.dex").unwrap();
+    let mut bytes = vec![0xaa_u8; 0x1234];
+    bytes.extend_from_slice(&dex_bytes);
+    bytes.extend_from_slice(&[0xbb; 0x10]);
+
+    let dex = dalvik::Dex::from_reader_at(&bytes[..], 0x1234, dex_bytes.len()).unwrap();
+    let reference = dalvik::Dex::from_file("test.dex").unwrap();
+    assert_eq!(reference.types().len(), dex.types().len());
+    assert_eq!(reference.types()[0].name(), dex.types()[0].name());
+
+    assert!(dalvik::Dex::from_reader_at(&bytes[..0x1000], 0x1234, None).is_err());
+}
+
+#[test]
+fn it_reads_class_members() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let class = &dex.types()[57];
+    assert_eq!(
+        "android/support/design/internal/ParcelableSparseArray;",
+        class.name()
+    );
+
+    assert_eq!(1, class.static_fields().len());
+    assert!(class.instance_fields().is_empty());
+    let creator = &class.static_fields()[0];
+    assert_eq!("CREATOR", creator.name());
+    assert_eq!("android/os/Parcelable$Creator;", creator.field_type());
+    assert_eq!(
+        AccessFlags::ACC_PUBLIC | AccessFlags::ACC_STATIC | AccessFlags::ACC_FINAL,
+        creator.access_flags()
+    );
+
+    assert_eq!(3, class.direct_methods().len());
+    assert_eq!(2, class.virtual_methods().len());
+    let clinit = &class.direct_methods()[0];
+    assert_eq!("<clinit>", clinit.name());
+    assert_eq!(&Type::Void, clinit.return_type());
+    assert!(clinit.parameters().is_empty());
+    assert_eq!("describeContents", class.virtual_methods()[0].name());
+    assert_eq!(&Type::Int, class.virtual_methods()[0].return_type());
+}
+
+#[test]
+fn it_detects_constructors() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let class = dex.find_class("Ljakhar/aseem/diva/NotesProvider;").unwrap();
+    let method = |name| {
+        class
+            .methods()
+            .find(|method| method.name() == name)
+            .unwrap()
+    };
+
+    let init = method("<init>");
+    assert!(init.is_constructor());
+    assert!(init.is_instance_constructor());
+    assert!(!init.is_static_initializer());
+
+    let clinit = method("<clinit>");
+    assert!(clinit.is_constructor());
+    assert!(!clinit.is_instance_constructor());
+    assert!(clinit.is_static_initializer());
+
+    let query = method("query");
+    assert!(!query.is_constructor());
+    assert!(!query.is_instance_constructor());
+    assert!(!query.is_static_initializer());
+}
+
+#[test]
+fn it_builds_a_class() {
+    let class = dalvik::Class::builder("Lcom/example/Foo;")
+        .access_flags(AccessFlags::ACC_PUBLIC | AccessFlags::ACC_FINAL)
+        .superclass("Ljava/lang/Object;")
+        .interface("Ljava/lang/Runnable;")
+        .source_file("Foo.java")
+        .field(dalvik::Field::new(
+            AccessFlags::ACC_PRIVATE | AccessFlags::ACC_STATIC,
+            "int",
+            "counter",
+        ))
+        .field(dalvik::Field::new(
+            AccessFlags::ACC_PRIVATE,
+            "java/lang/String;",
+            "name",
+        ))
+        .method(dalvik::Method::new(
+            AccessFlags::ACC_PUBLIC | AccessFlags::ACC_CONSTRUCTOR,
+            "<init>",
+            Type::Void,
+            Vec::new(),
+        ))
+        .method(dalvik::Method::new(
+            AccessFlags::ACC_PUBLIC,
+            "run",
+            Type::Void,
+            Vec::new(),
+        ))
+        .build()
+        .unwrap();
+
+    assert_eq!("com/example/Foo;", class.name());
+    assert_eq!("public final", class.access_flags().to_string());
+    assert_eq!(
+        Some("java/lang/Object;"),
+        class.superclass().map(String::as_str)
+    );
+    assert_eq!(&["java/lang/Runnable;".to_owned()], class.interfaces());
+    assert_eq!(Some("Foo.java"), class.source_file().map(String::as_str));
+    assert_eq!("counter", class.static_fields()[0].name());
+    assert_eq!("name", class.instance_fields()[0].name());
+    assert_eq!("<init>", class.direct_methods()[0].name());
+    assert_eq!("run", class.virtual_methods()[0].name());
+}
+
+#[test]
+fn it_rejects_invalid_class_names() {
+    assert!(dalvik::Class::builder("com.example.Foo").build().is_err());
+    assert!(dalvik::Class::builder("Lcom/example/Foo").build().is_err());
+    assert!(dalvik::Class::builder("I").build().is_err());
+    assert!(dalvik::Class::builder("Lcom/example/Foo;")
+        .superclass("java.lang.Object")
+        .build()
+        .is_err());
+}
+
+#[test]
+fn it_skips_synthetic_and_bridge_methods() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+
+    // `compare(Object, Object)` is a synthetic bridge to `compare(View, View)`.
+    let class = &dex.types()[71];
+    assert_eq!(
+        "android/support/design/widget/CoordinatorLayout$1;",
+        class.name()
+    );
+    assert_eq!(3, class.methods().count());
+    let bridge = &class.virtual_methods()[1];
+    assert_eq!("compare", bridge.name());
+    assert!(bridge.is_synthetic_or_bridge());
+    let declared: Vec<_> = class.declared_methods().map(dalvik::Method::name).collect();
+    assert_eq!(vec!["<init>", "compare"], declared);
+    assert!(!class.virtual_methods()[0].is_synthetic_or_bridge());
+
+    // Synthetic constructor.
+    let class = &dex.types()[82];
+    assert_eq!(5, class.methods().count());
+    assert_eq!(4, class.declared_methods().count());
+
+    let all = dex.methods().count();
+    let declared = dex
+        .methods()
+        .filter(|method| !method.is_synthetic_or_bridge())
+        .count();
+    assert!(declared < all);
+}
+
+#[test]
+fn it_reads_parameter_annotations() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let class = &dex.types()[103];
+    assert_eq!("android/support/design/widget/Snackbar;", class.name());
+
+    let make = &class.direct_methods()[14];
+    assert_eq!("make", make.name());
+    let annotations = make.parameter_annotations();
+    assert_eq!(3, annotations.len());
+    assert_eq!(1, annotations[0].len());
+    assert_eq!(
+        "android/support/annotation/NonNull;",
+        dex.resolve_annotation(annotations[0][0]).type_name()
+    );
+    assert_eq!(1, annotations[1].len());
+    assert_eq!(
+        "android/support/annotation/StringRes;",
+        dex.resolve_annotation(annotations[1][0]).type_name()
+    );
+    assert!(annotations[2].is_empty());
+
+    let no_annotations = &class.direct_methods()[0];
+    assert!(no_annotations
+        .parameter_annotations()
+        .iter()
+        .all(Vec::is_empty));
+}
+
+#[test]
+fn it_sorts_classes_by_name() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let sorted = dex.classes_sorted();
+    assert_eq!(dex.types().len(), sorted.len());
+
+    let names: Vec<_> = sorted.iter().take(4).map(|class| class.name()).collect();
+    assert_eq!(
+        vec![
+            "android/support/annotation/AnimRes;",
+            "android/support/annotation/AnimatorRes;",
+            "android/support/annotation/AnyRes;",
+            "android/support/annotation/ArrayRes;",
+        ],
+        names
+    );
+    assert!(sorted.windows(2).all(|pair| pair[0] < pair[1]));
+
+    // The original order is kept.
+    assert!(dex.types().windows(2).any(|pair| pair[0] > pair[1]));
+}
+
+#[test]
+fn it_reads_all_code_items() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+
+    // The map of `test.dex` declares 13342 code items starting at offset 0xaabe8.
+    assert_eq!(13342, dex.code_items().count());
+    let (first_offset, first) = dex.code_items().next().unwrap();
+    assert_eq!(0xaabe8, first_offset);
+    assert!(!first.insns().is_empty());
+    assert!(dex.code_items().all(|(offset, _)| offset % 4 == 0));
+}
+
 // #[test]
 // fn it_file_verify() {
 //     let file = dalvik::Dex::from_file("test.dex").unwrap();
 //     assert!(file.verify_file("test.dex"));
 // }
+
+#[test]
+fn it_disassembles_a_method() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let clinit = &dex.types()[57].direct_methods()[0];
+    assert_eq!(Some(0xab790), clinit.code_offset());
+
+    let listing = clinit.disassemble(&dex);
+    let expected = vec![
+        (
+            0,
+            "new-instance v0, Landroid/support/design/internal/ParcelableSparseArray$1; \
+             // type@313",
+        ),
+        (
+            2,
+            "invoke-direct {v0}, \
+             Landroid/support/design/internal/ParcelableSparseArray$1;.<init>:()V // method@1383",
+        ),
+        (
+            5,
+            "invoke-static {v0}, Landroid/support/v4/os/ParcelableCompat;.newCreator:\
+             (Landroid/support/v4/os/ParcelableCompatCreatorCallbacks;)\
+             Landroid/os/Parcelable$Creator; // method@6566",
+        ),
+        (8, "move-result-object v0"),
+        (
+            9,
+            "sput-object v0, Landroid/support/design/internal/ParcelableSparseArray;.CREATOR:\
+             Landroid/os/Parcelable$Creator; // field@1638",
+        ),
+        (11, "return-void"),
+    ];
+    assert_eq!(expected.len(), listing.len());
+    for ((pc, instruction), (expected_pc, expected_instruction)) in listing.iter().zip(expected) {
+        assert_eq!(expected_pc, *pc);
+        assert_eq!(expected_instruction, instruction);
+    }
+}
+
+#[test]
+fn it_skips_methods_without_code() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+
+    let suggest = &dex.types()[8].virtual_methods()[0];
+    assert_eq!("suggest", suggest.name());
+    assert!(suggest.access_flags().contains(AccessFlags::ACC_ABSTRACT));
+    assert_eq!(None, suggest.code_offset());
+    assert!(suggest.code(&dex).is_none());
+    assert!(suggest.disassemble(&dex).is_empty());
+
+    let native = dex.types()[1176]
+        .methods()
+        .find(|method| method.access_flags().contains(AccessFlags::ACC_NATIVE))
+        .unwrap();
+    assert_eq!(None, native.code_offset());
+    assert!(native.code(&dex).is_none());
+    assert!(native.disassemble(&dex).is_empty());
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn it_reads_dex_from_async_reader() {
+    let bytes = std::fs::read("test.dex").unwrap();
+    let size = bytes.len();
+
+    let dex = dalvik::Dex::from_async_read(&bytes[..], size)
+        .await
+        .unwrap();
+    let expected = dalvik::Dex::from_file("test.dex").unwrap();
+    assert_eq!(expected.types().len(), dex.types().len());
+    assert_eq!(expected.code_items().count(), dex.code_items().count());
+}
+
+#[test]
+fn it_keeps_raw_access_flags() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let class = &dex.types()[1308];
+    assert_eq!("android/support/v4/content/AsyncTaskLoader;", class.name());
+    assert_eq!(class.access_flags().bits(), class.raw_access_flags());
+
+    let field = &class.instance_fields()[0];
+    assert_eq!("mCancellingTask", field.name());
+    assert_eq!(0x40, field.raw_access_flags());
+    assert!(field.access_flags().contains(AccessFlags::ACC_VOLATILE));
+}
+
+/// Reads a little endian `u32` at the given offset.
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut buf = [0; 4];
+    buf.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_le_bytes(buf)
+}
+
+/// Writes a little endian `u32` at the given offset.
+fn write_u32(bytes: &mut [u8], offset: usize, value: u32) {
+    bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+#[test]
+fn it_reads_hidden_api_flags() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    assert!(dex
+        .methods()
+        .all(|method| method.hidden_api_flag().is_none()));
+
+    // Append a `hiddenapi_class_data` section with flags for the members of class 57, followed
+    // by a copy of the map with an entry for the new section.
+    let mut bytes = fs::read("test.dex").unwrap();
+    let class_count = read_u32(&bytes, 0x60) as usize;
+    let section_offset = bytes.len();
+    let section_size = 4 + 4 * class_count + 8;
+    bytes.extend_from_slice(&(section_size as u32).to_le_bytes());
+    for class in 0..class_count {
+        let offset = if class == 57 { 4 + 4 * class_count } else { 0 };
+        bytes.extend_from_slice(&(offset as u32).to_le_bytes());
+    }
+    // One static field, three direct methods and two virtual methods.
+    bytes.extend_from_slice(&[0x02, 0x00, 0x09, 0x01, 0x14, 0x03, 0x00, 0x00]);
+
+    let old_map_offset = read_u32(&bytes, 0x34) as usize;
+    let map_size = read_u32(&bytes, old_map_offset) as usize;
+    let map_offset = bytes.len();
+    let mut map = bytes[old_map_offset..old_map_offset + 4 + 12 * map_size].to_vec();
+    write_u32(&mut map, 0, map_size as u32 + 1);
+    for item in 0..map_size {
+        let item_offset = 4 + 12 * item;
+        // Update the offset of the map list itself.
+        if map[item_offset..item_offset + 2] == [0x00, 0x10] {
+            write_u32(&mut map, item_offset + 8, map_offset as u32);
+        }
+    }
+    map.extend_from_slice(&[0x00, 0xf0, 0x00, 0x00]);
+    map.extend_from_slice(&1_u32.to_le_bytes());
+    map.extend_from_slice(&(section_offset as u32).to_le_bytes());
+    bytes.extend_from_slice(&map);
+
+    let file_size = bytes.len() as u32;
+    let data_offset = read_u32(&bytes, 0x6c);
+    write_u32(&mut bytes, 0x20, file_size);
+    write_u32(&mut bytes, 0x34, map_offset as u32);
+    write_u32(&mut bytes, 0x68, file_size - data_offset);
+
+    let dex = dalvik::Dex::from_reader(&bytes[..], bytes.len()).unwrap();
+    let class = &dex.types()[57];
+    let creator = class.static_fields()[0].hidden_api_flag().unwrap();
+    assert_eq!(HiddenApiRestriction::Blacklist, creator.restriction());
+    let flags: Vec<_> = class
+        .methods()
+        .map(|method| method.hidden_api_flag().unwrap().bits())
+        .collect();
+    assert_eq!(vec![0x00, 0x09, 0x01, 0x14, 0x03], flags);
+    let clinit = class.direct_methods()[0].hidden_api_flag().unwrap();
+    assert_eq!(HiddenApiRestriction::Whitelist, clinit.restriction());
+    let virtual_method = class.virtual_methods()[0].hidden_api_flag().unwrap();
+    assert_eq!(
+        HiddenApiRestriction::GreylistMaxP,
+        virtual_method.restriction()
+    );
+    assert!(virtual_method.is_test_api());
+
+    assert!(dex.types()[56]
+        .methods()
+        .all(|method| method.hidden_api_flag().is_none()));
+}
+
+#[test]
+fn it_validates_a_dex_file() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    assert!(dex.validate().is_empty());
+
+    let mut bytes = fs::read("test.dex").unwrap();
+    // Point the class of `Ljava/lang/Object;.<init>` (method 19035) to a type that does not
+    // exist.
+    bytes[0x58c68..0x58c6a].copy_from_slice(&0xffff_u16.to_le_bytes());
+    let dex = dalvik::Dex::from_reader(&bytes[..], bytes.len()).unwrap();
+    let issues = dex.validate();
+    assert_eq!(
+        vec![dalvik::Issue::UnknownTypeIndex {
+            item: "method_id_item",
+            index: 19035,
+            type_index: 0xffff,
+        }],
+        issues
+    );
+    assert_eq!(
+        "`method_id_item` with index 19035 references unknown type 65535",
+        issues[0].to_string()
+    );
+}
+
+#[test]
+fn it_detects_duplicate_map_items() {
+    // Append a copy of the map with a second `string_data_item` entry, keeping the entry of the
+    // relocated map list last.
+    let mut bytes = fs::read("test.dex").unwrap();
+    let old_map_offset = read_u32(&bytes, 0x34) as usize;
+    let map_size = read_u32(&bytes, old_map_offset) as usize;
+    let map_offset = bytes.len();
+    let mut map = (map_size as u32 + 1).to_le_bytes().to_vec();
+    let mut map_list = None;
+    let mut string_data = None;
+    for item in 0..map_size {
+        let item_offset = old_map_offset + 4 + 12 * item;
+        let entry = &bytes[item_offset..item_offset + 12];
+        match entry[..2] {
+            [0x00, 0x10] => map_list = Some(entry.to_vec()),
+            [0x02, 0x20] => {
+                string_data = Some(entry.to_vec());
+                map.extend_from_slice(entry);
+            }
+            _ => map.extend_from_slice(entry),
+        }
+    }
+    let string_data = string_data.unwrap();
+    let string_data_offset = read_u32(&string_data, 8);
+    map.extend_from_slice(&string_data);
+    let mut map_list = map_list.unwrap();
+    write_u32(&mut map_list, 8, map_offset as u32);
+    map.extend_from_slice(&map_list);
+    bytes.extend_from_slice(&map);
+
+    let file_size = bytes.len() as u32;
+    let data_offset = read_u32(&bytes, 0x6c);
+    write_u32(&mut bytes, 0x20, file_size);
+    write_u32(&mut bytes, 0x34, map_offset as u32);
+    write_u32(&mut bytes, 0x68, file_size - data_offset);
+
+    let dex = dalvik::Dex::from_reader(&bytes[..], bytes.len()).unwrap();
+    let issues = dex.validate();
+    assert_eq!(
+        vec![
+            dalvik::Issue::DuplicateMapItem {
+                item_type: ItemType::StringData,
+            },
+            dalvik::Issue::UnorderedMapItem {
+                item_type: ItemType::StringData,
+                offset: string_data_offset,
+            },
+        ],
+        issues
+    );
+    assert_eq!(
+        "the map has more than one `StringData` entry",
+        issues[0].to_string()
+    );
+}
+
+#[test]
+fn it_checks_if_a_string_is_present() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    assert!(dex.contains_string("http://payatu.com"));
+    assert!(dex.contains_string("ACCESS_FINE_LOCATION"));
+    assert!(!dex.contains_string("http://example.com"));
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn it_finds_strings_matching_a_pattern() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let pattern = regex::Regex::new(r"^android\.permission\.\w+$").unwrap();
+    assert_eq!(
+        vec![
+            "android.permission.ACCESS_COARSE_LOCATION",
+            "android.permission.ACCESS_FINE_LOCATION",
+        ],
+        dex.find_strings(&pattern)
+    );
+    let pattern = regex::Regex::new("example\\.com").unwrap();
+    assert!(dex.find_strings(&pattern).is_empty());
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn it_extracts_network_indicators() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let indicators = dex.extract_network_indicators();
+    assert_eq!(vec!["http://payatu.com"], indicators.urls());
+    // From the `super@gmail.com` email address.
+    assert_eq!(vec!["gmail.com"], indicators.hostnames());
+    assert!(indicators.ipv4_addresses().is_empty());
+    assert!(indicators.ipv6_addresses().is_empty());
+}
+
+#[test]
+fn it_lists_external_types() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let external = dex.external_types();
+    let object = Type::FullyQualifiedName("java/lang/Object;".to_owned());
+    assert!(external.contains(&&object));
+
+    let local = Type::FullyQualifiedName(dex.types()[57].name().to_owned());
+    assert!(!external.contains(&&local));
+    assert!(external
+        .iter()
+        .all(|external_type| matches!(external_type, Type::FullyQualifiedName(_))));
+}
+
+#[cfg(feature = "extract")]
+#[test]
+fn it_extracts_a_class_as_a_dex_file() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let class = &dex.types()[57];
+    let descriptor = format!("L{}", class.name());
+    let bytes = dex.extract_class(&descriptor).unwrap();
+    assert!(bytes.len() < fs::metadata("test.dex").unwrap().len() as usize);
+
+    let extracted = dalvik::Dex::from_reader(&bytes[..], bytes.len()).unwrap();
+    assert!(extracted.validate().is_empty());
+    assert_eq!(1, extracted.types().len());
+    let extracted_class = &extracted.types()[0];
+    assert_eq!(class.name(), extracted_class.name());
+    assert_eq!(class.raw_access_flags(), extracted_class.raw_access_flags());
+    assert_eq!(class.superclass(), extracted_class.superclass());
+    assert_eq!(class.interfaces(), extracted_class.interfaces());
+    assert_eq!(
+        class.static_fields().len(),
+        extracted_class.static_fields().len()
+    );
+    for (method, extracted_method) in class.methods().zip(extracted_class.methods()) {
+        assert_eq!(method.name(), extracted_method.name());
+        assert_eq!(
+            method.disassemble(&dex),
+            extracted_method.disassemble(&extracted)
+        );
+    }
+    assert_eq!(class.methods().count(), extracted_class.methods().count());
+
+    assert!(dex.extract_class("Lcom/example/Missing;").is_err());
+}
+
+#[test]
+fn it_filters_instructions_by_family() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let class = &dex.types()[144];
+    let method = &class.direct_methods()[2];
+    assert_eq!("capabilityToString", method.name());
+
+    let pcs: Vec<u32> = method
+        .instructions(&dex)
+        .filter_const_strings()
+        .map(|(pc, _)| pc)
+        .collect();
+    assert_eq!(vec![3, 6, 9, 12, 15], pcs);
+    assert_eq!(0, method.instructions(&dex).filter_invokes().count());
+    assert_eq!(0, method.instructions(&dex).filter_field_ops().count());
+}
+
+#[cfg(feature = "cache")]
+#[test]
+fn it_round_trips_through_the_cache() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let mut cache = Vec::new();
+    dex.to_cache(&mut cache).unwrap();
+
+    let cached = dalvik::Dex::from_cache(&cache[..]).unwrap();
+    assert_eq!(dex.types().len(), cached.types().len());
+    assert_eq!(dex.code_items().count(), cached.code_items().count());
+    let names: Vec<_> = dex.methods().map(dalvik::Method::name).collect();
+    let cached_names: Vec<_> = cached.methods().map(dalvik::Method::name).collect();
+    assert_eq!(names, cached_names);
+    let clinit = &cached.types()[57].direct_methods()[0];
+    assert_eq!(
+        dex.types()[57].direct_methods()[0].disassemble(&dex),
+        clinit.disassemble(&cached)
+    );
+
+    cache[8] = cache[8].wrapping_add(1);
+    let error = dalvik::Dex::from_cache(&cache[..]).unwrap_err();
+    assert!(error.to_string().contains("cache version"));
+}
+
+#[test]
+fn it_gets_the_defining_class_of_a_reference() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+
+    // `Landroid/support/design/internal/ParcelableSparseArray$1;.<init>:()V`
+    let class = dex.defining_class(Reference::Method(1383)).unwrap();
+    assert_eq!(
+        "android/support/design/internal/ParcelableSparseArray$1;",
+        class.name()
+    );
+    // `Landroid/support/design/internal/ParcelableSparseArray;.CREATOR`
+    let class = dex.defining_class(Reference::Field(1638)).unwrap();
+    assert_eq!(
+        "android/support/design/internal/ParcelableSparseArray;",
+        class.name()
+    );
+
+    // `Ljava/lang/Object;.<init>:()V`
+    assert!(dex.defining_class(Reference::Method(19035)).is_none());
+    assert!(dex.defining_class(Reference::String(0)).is_none());
+}
+
+#[test]
+fn it_recovers_from_corrupt_classes() {
+    let mut bytes = fs::read("test.dex").unwrap();
+    // Make the class data of class 57 start with an out of range uleb128.
+    bytes[0x88373..0x88378].copy_from_slice(&[0xff, 0xff, 0xff, 0xff, 0x7f]);
+    // Make the bytecode of the first constructor of class 58 longer than the file.
+    bytes[0xab898..0xab89c].copy_from_slice(&0x0020_0000_u32.to_le_bytes());
+
+    let dex = dalvik::Dex::from_reader(&bytes[..], bytes.len()).unwrap();
+
+    let class = &dex.types()[57];
+    assert!(class.parse_error().unwrap().contains("class_data_item"));
+    assert!(class.static_fields().is_empty());
+    assert_eq!(0, class.methods().count());
+
+    let class = &dex.types()[58];
+    assert!(class.parse_error().unwrap().contains("code_item"));
+    assert_eq!(9, class.methods().count());
+    assert!(class.direct_methods()[0].code(&dex).is_none());
+    assert!(class.direct_methods()[1].code(&dex).is_some());
+
+    assert!(dex.types()[56].parse_error().is_none());
+    assert_eq!(13342 - 6, dex.code_items().count());
+}
+
+#[test]
+fn it_reads_class_and_member_annotations() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let class = &dex.types()[120];
+    assert_eq!("android/support/design/widget/TabLayout;", class.name());
+
+    assert_eq!(1, class.annotations().len());
+    assert_eq!(
+        "dalvik/annotation/MemberClasses;",
+        dex.resolve_annotation(&class.annotations()[0]).type_name()
+    );
+
+    let tabs = class
+        .instance_fields()
+        .iter()
+        .find(|field| field.name() == "mTabs")
+        .unwrap();
+    assert_eq!(1, tabs.annotations().len());
+    assert_eq!(
+        "dalvik/annotation/Signature;",
+        dex.resolve_annotation(&tabs.annotations()[0]).type_name()
+    );
+
+    let get_tab_at = class
+        .methods()
+        .find(|method| method.name() == "getTabAt")
+        .unwrap();
+    assert_eq!(1, get_tab_at.annotations().len());
+    assert_eq!(
+        "@android/support/annotation/Nullable;",
+        dex.resolve_annotation(&get_tab_at.annotations()[0])
+            .to_string()
+    );
+    assert!(class
+        .methods()
+        .find(|method| method.name() == "addTab")
+        .unwrap()
+        .annotations()
+        .is_empty());
+}
+
+#[test]
+fn it_gets_section_sizes_from_the_map() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let sizes = dex.section_sizes();
+    assert!(sizes
+        .iter()
+        .zip(dex.map_items())
+        .all(|(&(item_type, size), item)| item_type == item.item_type()
+            && size == item.size() as usize));
+
+    let sizes: HashMap<_, _> = sizes.into_iter().collect();
+    assert_eq!(18, sizes.len());
+    assert_eq!(Some(&1), sizes.get(&ItemType::Header));
+    assert_eq!(Some(&dex.strings().len()), sizes.get(&ItemType::StringData));
+    assert_eq!(Some(&dex.strings().len()), sizes.get(&ItemType::StringId));
+    assert_eq!(Some(&dex.types().len()), sizes.get(&ItemType::ClassDef));
+    assert_eq!(Some(&dex.code_items().count()), sizes.get(&ItemType::Code));
+    assert!(!sizes.contains_key(&ItemType::HiddenapiClassData));
+}
+
+#[test]
+fn it_generates_java_skeletons() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let skeletons = dex.to_java_skeletons();
+    assert_eq!(dex.types().len(), skeletons.len());
+
+    let (path, source) = &skeletons[57];
+    assert_eq!(
+        "android/support/design/internal/ParcelableSparseArray.java",
+        path
+    );
+    assert_eq!(
+        "package android.support.design.internal;
+
+import android.os.Parcelable;
+import android.util.SparseArray;
+
+public class ParcelableSparseArray extends SparseArray implements Parcelable {
+
+    public ParcelableSparseArray() {
+        throw new RuntimeException(\"Stub!\");
+    }
+
+    public ParcelableSparseArray(android.os.Parcel p0, ClassLoader p1) {
+        throw new RuntimeException(\"Stub!\");
+    }
+
+    public int describeContents() {
+        throw new RuntimeException(\"Stub!\");
+    }
+
+    public void writeToParcel(android.os.Parcel p0, int p1) {
+        throw new RuntimeException(\"Stub!\");
+    }
+}
+",
+        source
+    );
+}
+
+#[cfg(feature = "extract")]
+#[test]
+fn it_resolves_references_across_dex_files() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let extract = |descriptor| {
+        let bytes = dex.extract_class(descriptor).unwrap();
+        dalvik::Dex::from_reader(&bytes[..], bytes.len()).unwrap()
+    };
+    // `ActivityManagerCompat.isLowRamDevice()` delegates to `ActivityManagerCompatKitKat`.
+    let caller = extract("Landroid/support/v4/app/ActivityManagerCompat;");
+    let callee = extract("Landroid/support/v4/app/ActivityManagerCompatKitKat;");
+
+    let method = caller.types()[0]
+        .methods()
+        .find(|method| method.name() == "isLowRamDevice")
+        .unwrap();
+    let (_, invoke) = method
+        .instructions(&caller)
+        .find(|(_, bytecode)| bytecode.is_invoke())
+        .unwrap();
+    let method_index = match invoke.reference() {
+        Some(Reference::Method(i)) => i,
+        reference => panic!("unexpected reference {:?}", reference),
+    };
+    assert!(caller
+        .defining_class(Reference::Method(method_index))
+        .is_none());
+
+    let multidex = dalvik::MultiDex::new(vec![caller, callee]);
+    assert_eq!(2, multidex.dex_files().len());
+    let (class_dex, class) = multidex
+        .resolve_class("Landroid/support/v4/app/ActivityManagerCompatKitKat;")
+        .unwrap();
+    assert_eq!(
+        "android/support/v4/app/ActivityManagerCompatKitKat;",
+        class.name()
+    );
+    assert!(std::ptr::eq(class_dex, &multidex.dex_files()[1]));
+    assert!(multidex.resolve_class("Lcom/example/Missing;").is_none());
+
+    let (method_dex, resolved) = multidex.resolve_method(0, method_index).unwrap();
+    assert!(std::ptr::eq(method_dex, &multidex.dex_files()[1]));
+    assert_eq!("isLowRamDevice", resolved.name());
+    assert_eq!(
+        &[Type::FullyQualifiedName(
+            "android/app/ActivityManager;".to_owned()
+        )],
+        resolved.parameters()
+    );
+    assert_eq!(&Type::Boolean, resolved.return_type());
+    assert!(!resolved.disassemble(method_dex).is_empty());
+
+    // Framework methods, such as the `Object` constructor, are not defined in the application.
+    let constructor = multidex.dex_files()[0].types()[0]
+        .methods()
+        .find(|method| method.name() == "<init>")
+        .unwrap();
+    let (_, super_invoke) = constructor
+        .instructions(&multidex.dex_files()[0])
+        .find(|(_, bytecode)| bytecode.is_invoke())
+        .unwrap();
+    match super_invoke.reference() {
+        Some(Reference::Method(i)) => assert!(multidex.resolve_method(0, i).is_none()),
+        reference => panic!("unexpected reference {:?}", reference),
+    }
+    assert!(multidex.resolve_method(2, method_index).is_none());
+}
+
+#[test]
+fn it_gets_method_descriptors() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let descriptors: Vec<_> = dex.types()[57]
+        .methods()
+        .map(dalvik::Method::descriptor)
+        .collect();
+    assert_eq!(
+        vec![
+            "<clinit>()V",
+            "<init>()V",
+            "<init>(Landroid/os/Parcel;Ljava/lang/ClassLoader;)V",
+            "describeContents()I",
+            "writeToParcel(Landroid/os/Parcel;I)V",
+        ],
+        descriptors
+    );
+
+    let method = dalvik::Method::new(
+        AccessFlags::ACC_PUBLIC,
+        "foo",
+        "[Ljava/lang/String;".parse().unwrap(),
+        vec![Type::Int, "[[B".parse().unwrap(), Type::Long],
+    );
+    assert_eq!("foo(I[[BJ)[Ljava/lang/String;", method.descriptor());
+}
+
+#[test]
+fn it_applies_a_proguard_mapping() {
+    let mapping =
+        "com.example.SparseArray -> android.support.design.internal.ParcelableSparseArray:\n\
+                   \x20   int contents() -> describeContents\n\
+                   \x20   3:7:void write(android.os.Parcel,int):42:46 -> writeToParcel\n";
+    let mapping = dalvik::ProguardMapping::from_reader(mapping.as_bytes()).unwrap();
+    assert_eq!(
+        Some("com/example/SparseArray;"),
+        mapping.original_class_name("android/support/design/internal/ParcelableSparseArray;")
+    );
+
+    let mut dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let disassembly = dex.types()[57].direct_methods()[0].disassemble(&dex);
+    dex.apply_mapping(&mapping);
+
+    let class = &dex.types()[57];
+    assert_eq!("com/example/SparseArray;", class.name());
+    assert_eq!(
+        Some("android/util/SparseArray;"),
+        class.superclass().map(String::as_str)
+    );
+    let descriptors: Vec<_> = class.methods().map(dalvik::Method::descriptor).collect();
+    assert_eq!(
+        vec![
+            "<clinit>()V",
+            "<init>()V",
+            "<init>(Landroid/os/Parcel;Ljava/lang/ClassLoader;)V",
+            "contents()I",
+            "write(Landroid/os/Parcel;I)V",
+        ],
+        descriptors
+    );
+    // The ID lists are not renamed.
+    assert_eq!(
+        disassembly,
+        dex.types()[57].direct_methods()[0].disassemble(&dex)
+    );
+}
+
+#[test]
+fn it_rejects_implausible_bytecode_sizes() {
+    let mut bytes = fs::read("test.dex").unwrap();
+    // Code item of `ParcelableSparseArray.<clinit>()`, whose size is at offset 12.
+    let insns_size_offset = 0xab790 + 12;
+    bytes[insns_size_offset..insns_size_offset + 4].copy_from_slice(&[0xff; 4]);
+    let dex = dalvik::Dex::from_reader(&bytes[..], bytes.len()).unwrap();
+    let class = &dex.types()[57];
+    assert!(class
+        .parse_error()
+        .unwrap()
+        .contains("implausible `insns_size`"));
+    assert!(class.direct_methods()[0].code(&dex).is_none());
+
+    let options = dalvik::ParseOptions::new().with_max_insns_per_method(12);
+    assert_eq!(Some(12), options.max_insns_per_method());
+    let dex = dalvik::Dex::from_reader_with_options(
+        fs::File::open("test.dex").map(io::BufReader::new).unwrap(),
+        None,
+        options,
+    )
+    .unwrap();
+    assert!(dex
+        .code_items()
+        .all(|(_, code_item)| code_item.insns().len() <= 12));
+    let class = &dex.types()[57];
+    assert!(class.parse_error().is_some());
+    assert!(class.direct_methods()[0].code(&dex).is_some());
+}
+
+#[test]
+fn it_checks_the_kind_of_classes() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+
+    let annotation = &dex.types()[0];
+    assert_eq!("android/support/annotation/AnimRes;", annotation.name());
+    assert!(annotation.is_annotation());
+    assert!(annotation.is_interface());
+    assert!(annotation.is_abstract());
+    assert!(!annotation.is_enum());
+
+    let interface = &dex.types()[56];
+    assert!(interface.is_interface());
+    assert!(interface.is_abstract());
+    assert!(!interface.is_annotation());
+    assert!(!interface.is_enum());
+
+    let abstract_class = &dex.types()[61];
+    assert!(abstract_class.is_abstract());
+    assert!(!abstract_class.is_interface());
+
+    let enum_class = &dex.types()[326];
+    assert_eq!(
+        "android/support/v4/content/ModernAsyncTask$Status;",
+        enum_class.name()
+    );
+    assert!(enum_class.is_enum());
+    assert!(!enum_class.is_interface());
+    assert!(!enum_class.is_abstract());
+    assert!(!enum_class.is_annotation());
+
+    let class = &dex.types()[41];
+    assert!(!class.is_interface() && !class.is_enum() && !class.is_abstract());
+}
+
+#[test]
+fn it_resolves_type_references() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+
+    let int_array = dex.resolve_type(2350).unwrap();
+    match int_array {
+        Type::Array {
+            dimensions: 1,
+            array_type,
+        } => assert_eq!(&Type::Int, array_type.as_ref()),
+        t => panic!("unexpected type {:?}", t),
+    }
+    assert_eq!("[I", int_array.to_descriptor());
+    assert_eq!("[I", dex.resolve_reference(Reference::Type(2350)));
+
+    match dex.resolve_type(2418).unwrap() {
+        Type::Array { dimensions: 2, .. } => {}
+        t => panic!("unexpected type {:?}", t),
+    }
+    assert_eq!(Some(&Type::Int), dex.resolve_type(4));
+    assert_eq!(
+        Some(&Type::FullyQualifiedName("java/lang/String;".to_owned())),
+        dex.resolve_type(2277)
+    );
+    assert_eq!(None, dex.resolve_type(2419));
+
+    // Descriptors round-trip through parsing.
+    for index in 0..2419 {
+        let resolved = dex.resolve_type(index).unwrap();
+        assert_eq!(
+            resolved,
+            &resolved.to_descriptor().parse::<Type>().unwrap(),
+            "type {}",
+            index
+        );
+    }
+}
+
+#[test]
+fn it_gets_the_superclass_chain() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let class = dex
+        .find_class("Landroid/support/design/widget/FloatingActionButtonHoneycombMr1;")
+        .unwrap();
+    assert!(std::ptr::eq(class, &dex.types()[1602]));
+    assert!(dex.find_class("Ljava/lang/Object;").is_none());
+    assert!(dex.find_class("I").is_none());
+
+    let chain: Vec<_> = dex
+        .superclass_chain(class)
+        .into_iter()
+        .map(dalvik::Class::name)
+        .collect();
+    assert_eq!(
+        vec![
+            "android/support/design/widget/FloatingActionButtonEclairMr1;",
+            "android/support/design/widget/FloatingActionButtonImpl;",
+        ],
+        chain
+    );
+    assert!(dex.superclass_chain(&dex.types()[57]).is_empty());
+}
+
+#[test]
+fn it_finds_subclasses_and_implementors() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let names = |classes: Vec<&dalvik::Class>| {
+        let mut names: Vec<_> = classes
+            .into_iter()
+            .map(|class| class.name().to_owned())
+            .collect();
+        names.sort();
+        names
+    };
+
+    // `FloatingActionButtonHoneycombMr1` extends `FloatingActionButtonEclairMr1`.
+    assert_eq!(
+        vec![
+            "android/support/design/widget/FloatingActionButtonEclairMr1;",
+            "android/support/design/widget/FloatingActionButtonHoneycombMr1;",
+            "android/support/design/widget/FloatingActionButtonLollipop;",
+        ],
+        names(dex.subclasses_of("Landroid/support/design/widget/FloatingActionButtonImpl;"))
+    );
+    assert_eq!(
+        5,
+        dex.subclasses_of("Landroid/support/v7/widget/RecyclerView$ViewHolder;")
+            .len()
+    );
+    assert!(dex.subclasses_of("Lcom/example/Missing;").is_empty());
+
+    // `SupportMenu` and `SupportSubMenu` are interfaces extending `Menu`, and the builders and
+    // navigation menus extend `MenuBuilder`.
+    assert_eq!(
+        vec![
+            "android/support/design/internal/NavigationMenu;",
+            "android/support/design/internal/NavigationSubMenu;",
+            "android/support/v7/internal/view/menu/MenuBuilder;",
+            "android/support/v7/internal/view/menu/MenuWrapperICS;",
+            "android/support/v7/internal/view/menu/SubMenuBuilder;",
+            "android/support/v7/internal/view/menu/SubMenuWrapperICS;",
+        ],
+        names(dex.implementors_of("Landroid/view/Menu;"))
+    );
+    assert_eq!(68, dex.implementors_of("Ljava/lang/Runnable;").len());
+    assert!(dex.implementors_of("Lcom/example/Missing;").is_empty());
+}
+
+#[test]
+fn it_verifies_the_arity_of_invokes() {
+    use dalvik::{
+        bytecode::ByteCode,
+        error::{ArityError, Error},
+    };
+
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    // `writeToParcel(Landroid/os/Parcel;I)V` needs `this` and its two arguments.
+    let (method, (kind, registers, reference)) = dex
+        .methods()
+        .find_map(|method| {
+            method
+                .instructions(&dex)
+                .filter_invokes()
+                .find_map(|(_, bytecode)| match bytecode {
+                    ByteCode::Invoke(kind, registers, reference)
+                        if dex
+                            .resolve_reference(Reference::Method(reference))
+                            .ends_with(".writeToParcel:(Landroid/os/Parcel;I)V") =>
+                    {
+                        Some((kind, registers, reference))
+                    }
+                    _ => None,
+                })
+                .map(|invoke| (method, invoke))
+        })
+        .unwrap();
+    for (_, bytecode) in method.instructions(&dex).filter_invokes() {
+        dex.verify_invoke_arity(&bytecode).unwrap();
+    }
+
+    assert_eq!(3, registers.len());
+    let off_by_one = ByteCode::Invoke(kind, registers[..2].to_vec(), reference);
+    assert!(matches!(
+        dex.verify_invoke_arity(&off_by_one),
+        Err(Error::Arity(ArityError::Mismatch {
+            expected: 3,
+            found: 2,
+            ..
+        }))
+    ));
+    assert!(matches!(
+        dex.verify_invoke_arity(&ByteCode::ReturnVoid),
+        Err(Error::Arity(ArityError::NotAnInvoke))
+    ));
+}
+
+#[test]
+fn it_rejects_class_definitions_with_invalid_types() {
+    // Point the first class definition to a type that does not exist.
+    let mut bytes = fs::read("test.dex").unwrap();
+    write_u32(&mut bytes, 0x5_9420, 0xffff);
+    let errors = vec![
+        dalvik::Dex::from_reader(&bytes[..], bytes.len()).unwrap_err(),
+        dalvik::Dex::from_reader_filtered(&bytes[..], bytes.len(), |_: &str| true).unwrap_err(),
+        dalvik::Dex::from_reader_lenient(&bytes[..], bytes.len()).unwrap_err(),
+    ];
+    for error in errors {
+        assert!(matches!(
+            error.root_cause(),
+            dalvik::error::Error::Parse(dalvik::error::Parse::UnknownTypeIndex(0xffff))
+        ));
+    }
+
+    // Make `int` the superclass of the first class.
+    let mut bytes = fs::read("test.dex").unwrap();
+    write_u32(&mut bytes, 0x5_9428, 4);
+    let error = dalvik::Dex::from_reader(&bytes[..], bytes.len()).unwrap_err();
+    assert!(matches!(
+        error.root_cause(),
+        dalvik::error::Error::Parse(dalvik::error::Parse::NotAClass(descriptor)) if descriptor == "I"
+    ));
+}
+
+#[test]
+fn it_parses_only_the_filtered_classes() {
+    let package = "Landroid/support/design/";
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let filtered = dalvik::Dex::from_reader_filtered(
+        io::BufReader::new(fs::File::open("test.dex").unwrap()),
+        None,
+        |descriptor: &str| descriptor.starts_with(package),
+    )
+    .unwrap();
+
+    let expected: Vec<_> = dex
+        .types()
+        .iter()
+        .map(dalvik::Class::name)
+        .filter(|name| format!("L{}", name).starts_with(package))
+        .collect();
+    let names: Vec<_> = filtered.types().iter().map(dalvik::Class::name).collect();
+    assert!(!names.is_empty());
+    assert_eq!(expected, names);
+    assert!(filtered
+        .find_class("Landroid/support/v4/view/ViewCompat;")
+        .is_none());
+
+    // References to skipped classes still resolve to their names.
+    let references: Vec<_> = filtered
+        .methods()
+        .flat_map(|method| method.instructions(&filtered))
+        .filter_map(|(_, bytecode)| bytecode.reference())
+        .collect();
+    assert!(references.iter().any(|&reference| filtered
+        .resolve_reference(reference)
+        .starts_with("Landroid/support/v4/")));
+    for reference in references {
+        assert_eq!(
+            dex.resolve_reference(reference),
+            filtered.resolve_reference(reference)
+        );
+    }
+}
+
+#[test]
+fn it_retains_the_raw_bytes_for_verification() {
+    let file_size = fs::metadata("test.dex").unwrap().len() as usize;
+    let dex = dalvik::Dex::from_reader_with_options(
+        io::BufReader::new(fs::File::open("test.dex").unwrap()),
+        file_size,
+        dalvik::ParseOptions::new().with_retain_raw_bytes(true),
+    )
+    .unwrap();
+
+    let header = dex.header();
+    assert_eq!((12, file_size as u32), header.checksum_range());
+    assert_eq!((32, file_size as u32), header.signature_range());
+
+    let raw_bytes = dex.raw_bytes().unwrap();
+    assert_eq!(file_size, raw_bytes.len());
+    let (start, end) = header.checksum_range();
+    let (a, b) =
+        raw_bytes[start as usize..end as usize]
+            .iter()
+            .fold((1_u32, 0_u32), |(a, b), &byte| {
+                let a = (a + u32::from(byte)) % 65521;
+                (a, (b + a) % 65521)
+            });
+    assert_eq!(header.get_checksum(), b << 16 | a);
+
+    assert!(dalvik::Dex::from_file("test.dex")
+        .unwrap()
+        .raw_bytes()
+        .is_none());
+}
+
+#[test]
+fn it_gets_strings_by_index() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let index = dex.strings().len() as u32 / 2;
+    assert_eq!(
+        dex.strings().nth(index as usize),
+        Some(dex.string_at(index).unwrap())
+    );
+
+    let error = dex.string_at(dex.strings().len() as u32).unwrap_err();
+    assert!(matches!(
+        error,
+        dalvik::error::Error::Parse(dalvik::error::Parse::UnknownStringIndex(_))
+    ));
+}
+
+#[test]
+fn it_finds_reflection_call_sites() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    assert!(dex.uses_reflection());
+
+    let call_sites = dex.reflection_call_sites();
+    assert!(call_sites.iter().any(|(method, _, signature)| {
+        method.name() == "setActionBarDescription"
+            && signature
+                == "Ljava/lang/reflect/Method;.invoke:(Ljava/lang/Object;[Ljava/lang/Object;)\
+                    Ljava/lang/Object;"
+    }));
+    for (method, pc, _) in &call_sites {
+        assert!(method
+            .instructions(&dex)
+            .filter_invokes()
+            .any(|(invoke_pc, _)| invoke_pc == *pc));
+    }
+
+    let for_name = dex.call_sites_of(&["Ljava/lang/Class;.forName"]);
+    assert!(!for_name.is_empty());
+    assert!(for_name
+        .iter()
+        .all(|(_, _, signature)| signature.starts_with("Ljava/lang/Class;.forName:(")));
+    assert!(dex.call_sites_of(&["Lcom/example/Missing;.run"]).is_empty());
+
+    #[cfg(feature = "extract")]
+    {
+        let extracted = dex
+            .extract_class("Landroid/support/design/widget/FloatingActionButtonImpl;")
+            .unwrap();
+        let extracted = dalvik::Dex::from_reader(&extracted[..], extracted.len()).unwrap();
+        assert!(!extracted.uses_reflection());
+    }
+}
+
+#[cfg(feature = "extract")]
+#[test]
+fn it_hashes_method_contents_across_dex_files() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let descriptor = "Landroid/support/v4/app/ActivityManagerCompat;";
+    let bytes = dex.extract_class(descriptor).unwrap();
+    let extracted = dalvik::Dex::from_reader(&bytes[..], bytes.len()).unwrap();
+
+    let class = dex.find_class(descriptor).unwrap();
+    let extracted_class = extracted.find_class(descriptor).unwrap();
+    let hashes: Vec<_> = class
+        .methods()
+        .map(|method| method.content_hash(&dex))
+        .collect();
+    let extracted_hashes: Vec<_> = extracted_class
+        .methods()
+        .map(|method| method.content_hash(&extracted))
+        .collect();
+    assert_eq!(hashes, extracted_hashes);
+    // The constructor and `isLowRamDevice()` have different code.
+    assert_ne!(hashes[0], hashes[1]);
+}
+
+#[test]
+fn it_rejects_implausible_id_list_sizes() {
+    let mut bytes = fs::read("test.dex").unwrap();
+    // `type_ids_size` is at offset 0x40 of the header.
+    bytes[0x40..0x44].copy_from_slice(&[0xff; 4]);
+    let error = dalvik::Dex::from_reader(&bytes[..], bytes.len()).unwrap_err();
+    assert!(matches!(
+        error.root_cause(),
+        dalvik::error::Error::Parse(dalvik::error::Parse::ImplausibleSize {
+            size_name: "type_ids_size",
+            size: 0xffff_ffff,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn it_gets_the_try_ranges_of_a_method() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let class = dex
+        .find_class("Landroid/support/v4/app/BundleCompatDonut;")
+        .unwrap();
+    let method = class
+        .methods()
+        .find(|method| method.name() == "getBinder")
+        .unwrap();
+    let try_ranges = method.code(&dex).unwrap().try_ranges();
+
+    assert_eq!(2, try_ranges.len());
+    assert_eq!(6, try_ranges[0].start_address());
+    assert_eq!(30, try_ranges[0].end_address());
+    assert_eq!(36, try_ranges[1].start_address());
+    assert_eq!(50, try_ranges[1].end_address());
+    assert!(try_ranges[0].end_address() <= try_ranges[1].start_address());
+
+    let caught = |index: usize| {
+        try_ranges[index]
+            .handlers()
+            .iter()
+            .map(|handler| {
+                let exception = dex.resolve_type(handler.type_index()).unwrap();
+                (exception.to_string(), handler.address())
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        vec![("java/lang/NoSuchMethodException;".to_owned(), 51)],
+        caught(0)
+    );
+    assert_eq!(
+        vec![
+            (
+                "java/lang/reflect/InvocationTargetException;".to_owned(),
+                72
+            ),
+            ("java/lang/IllegalAccessException;".to_owned(), 74),
+            ("java/lang/IllegalArgumentException;".to_owned(), 60),
+        ],
+        caught(1)
+    );
+    assert!(try_ranges
+        .iter()
+        .all(|try_range| try_range.catch_all_address().is_none()));
+}
+
+#[test]
+fn it_reads_anomalous_dex_files_leniently() {
+    let (_, warnings) = dalvik::Dex::from_file_lenient("test.dex").unwrap();
+    assert!(warnings.is_empty());
+
+    let mut bytes = fs::read("test.dex").unwrap();
+    // Declare a size of 1 for the first string, which is empty.
+    bytes[0x1a_6fcc] = 1;
+    // Make the type ID section in the map one item longer, so that it overlaps the prototype IDs.
+    write_u32(&mut bytes, 0x7_a018, 0x974);
+
+    assert!(dalvik::Dex::from_reader(&bytes[..], bytes.len()).is_err());
+    let (dex, warnings) = dalvik::Dex::from_reader_lenient(&bytes[..], bytes.len()).unwrap();
+    assert_eq!(Some(""), dex.strings().next());
+    assert_eq!(
+        vec![
+            dalvik::Warning::StringSizeMismatch {
+                index: 0,
+                expected_size: 1,
+                actual_size: 0,
+            },
+            dalvik::Warning::Issue(dalvik::Issue::OverlappingSections {
+                first: ItemType::TypeId,
+                second: ItemType::ProtoId,
+            }),
+        ],
+        warnings
+    );
+    assert_eq!(
+        "string 0 has 0 UTF-16 code units, but its declared size is 1",
+        warnings[0].to_string()
+    );
+    assert_eq!(
+        "the `ProtoId` section starts inside the `TypeId` section",
+        warnings[1].to_string()
+    );
+}
+
+#[test]
+fn it_displays_resolved_references() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+
+    let method = dex.resolve_method(3085).unwrap();
+    assert_eq!("getBinder", method.name());
+    assert_eq!(
+        "android.support.v4.app.BundleCompatDonut.getBinder(android.os.Bundle,java.lang.String):\
+         android.os.IBinder",
+        method.to_string()
+    );
+    assert_eq!(
+        "android.support.v4.app.BundleCompatDonut.putBinder(android.os.Bundle,java.lang.String,\
+         android.os.IBinder):void",
+        dex.resolve_method(3086).unwrap().to_string()
+    );
+
+    let field = dex.resolve_field(2307).unwrap();
+    assert_eq!(&Type::Boolean, field.field_type().get());
+    assert_eq!(
+        "android.support.v4.app.BundleCompatDonut.sGetIBinderMethodFetched:boolean",
+        field.to_string()
+    );
+
+    let array = dalvik::ResolvedType::from(dex.resolve_type(2418).unwrap().clone());
+    assert_eq!("int[][]", array.to_string());
+    assert_eq!(
+        "android.support.v4.app.BundleCompatDonut",
+        method.class().to_string()
+    );
+    assert!(dex.resolve_method(u32::max_value()).is_none());
+    assert!(dex.resolve_field(u32::max_value()).is_none());
+}
+
+#[test]
+fn it_shares_strings_between_dex_files() {
+    let bytes = fs::read("test.dex").unwrap();
+    let mut interner = dalvik::StringInterner::new();
+    let first =
+        dalvik::Dex::from_reader_with_interner(&bytes[..], bytes.len(), &mut interner).unwrap();
+    let second =
+        dalvik::Dex::from_reader_with_interner(&bytes[..], bytes.len(), &mut interner).unwrap();
+
+    assert_eq!(first.strings().len() + first.types().len(), interner.len());
+    assert!(first
+        .strings()
+        .zip(second.strings())
+        .all(|(a, b)| a.as_ptr() == b.as_ptr()));
+
+    // Class and member names point to the shared strings.
+    let (first_class, second_class) = (&first.types()[0], &second.types()[0]);
+    assert_eq!(first_class.name().as_ptr(), second_class.name().as_ptr());
+    let method = first.methods().next().unwrap();
+    assert_eq!(
+        method.name().as_ptr(),
+        second.methods().next().unwrap().name().as_ptr()
+    );
+    assert!(first
+        .strings()
+        .any(|string| string.as_ptr() == method.name().as_ptr()));
+
+    // Files parsed without an interner have the same strings, in their own storage.
+    let unshared = dalvik::Dex::from_reader(&bytes[..], bytes.len()).unwrap();
+    assert_eq!(first.strings().len(), unshared.strings().len());
+    assert!(first.strings().eq(unshared.strings()));
+    assert_ne!(
+        method.name().as_ptr(),
+        unshared.methods().next().unwrap().name().as_ptr()
+    );
+
+    let multidex = dalvik::MultiDex::from_bytes(&[&bytes[..], &bytes[..]]).unwrap();
+    let [first, second] = [&multidex.dex_files()[0], &multidex.dex_files()[1]];
+    assert_eq!(
+        first.strings().nth(1).unwrap().as_ptr(),
+        second.strings().nth(1).unwrap().as_ptr()
+    );
+}
+
+#[test]
+fn it_warns_about_data_outside_of_the_sections() {
+    let mut bytes = fs::read("test.dex").unwrap();
+    let file_size = bytes.len() as u64;
+    // The class definitions are followed by zero padding before the data section.
+    bytes[0x6_7404] = 0xca;
+    bytes.extend_from_slice(b"appended payload");
+
+    let dex = dalvik::Dex::from_reader(&bytes[..], bytes.len()).unwrap();
+    assert_eq!(file_size, u64::from(dex.header().get_file_size()));
+    let (_, warnings) = dalvik::Dex::from_reader_lenient(&bytes[..], bytes.len()).unwrap();
+    assert_eq!(
+        vec![
+            dalvik::Warning::UnknownData {
+                offset: 0x6_7400,
+                size: 0x1_2bf8,
+            },
+            dalvik::Warning::UnknownData {
+                offset: file_size,
+                size: 16,
+            },
+        ],
+        warnings
+    );
+    assert_eq!(
+        "16 bytes at offset 0x002c8fb0 do not belong to any section",
+        warnings[1].to_string()
+    );
+}
+
+#[test]
+fn it_does_not_warn_about_call_sites_before_the_data_section() {
+    let mut bytes = fs::read("test.dex").unwrap();
+    // A method handle to invoke the first method statically, followed by a call site ID, in the
+    // padding after the class definitions.
+    let (handle_offset, call_site_id_offset) = (0x6_7400_u32, 0x6_7408_u32);
+    bytes[handle_offset as usize..call_site_id_offset as usize]
+        .copy_from_slice(&[0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+
+    // The map has no room for the new lists, so a copy of it is appended to the data section,
+    // followed by the call site item. Map items are sorted by offset.
+    let old_map_offset = 0x7_9ff8;
+    let old_map_size = read_u32(&bytes, old_map_offset) as usize;
+    let map_offset = bytes.len() as u32;
+    let call_site_offset = map_offset + 4 + 12 * (old_map_size as u32 + 2);
+    let mut map = Vec::new();
+    map.extend_from_slice(&(old_map_size as u32 + 2).to_le_bytes());
+    for i in 0..old_map_size {
+        let entry = &bytes[old_map_offset + 4 + 12 * i..old_map_offset + 16 + 12 * i];
+        if entry[..2] != [0x00, 0x10] {
+            map.extend_from_slice(entry);
+        }
+        if entry[..2] == [0x06, 0x00] {
+            map.extend_from_slice(&[0x08, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00]);
+            map.extend_from_slice(&handle_offset.to_le_bytes());
+            map.extend_from_slice(&[0x07, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00]);
+            map.extend_from_slice(&call_site_id_offset.to_le_bytes());
+        }
+    }
+    map.extend_from_slice(&[0x00, 0x10, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00]);
+    map.extend_from_slice(&map_offset.to_le_bytes());
+    write_u32(&mut bytes, call_site_id_offset as usize, call_site_offset);
+    bytes.extend_from_slice(&map);
+    // Method handle 0, string 0 and prototype 0, padded to 4 bytes.
+    bytes.extend_from_slice(&[0x03, 0x16, 0x00, 0x17, 0x00, 0x15, 0x00, 0x00]);
+    let file_size = bytes.len() as u32;
+    write_u32(&mut bytes, 0x20, file_size);
+    write_u32(&mut bytes, 0x34, map_offset);
+    write_u32(&mut bytes, 0x68, file_size - 0x7_9ff8);
+
+    let (dex, warnings) = dalvik::Dex::from_reader_lenient(&bytes[..], bytes.len()).unwrap();
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!(1, dex.method_handles().len());
+    assert_eq!(0, dex.call_site(0).unwrap().method_name_index());
+}
+
+#[test]
+fn it_reads_the_link_section() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    assert_eq!(None, dex.link_data());
+
+    let mut bytes = fs::read("test.dex").unwrap();
+    let link_offset = bytes.len() as u32;
+    let link_data = b"\x01\x02linked\x00\x00";
+    bytes.extend_from_slice(link_data);
+    let file_size = bytes.len() as u32;
+    write_u32(&mut bytes, 0x20, file_size);
+    write_u32(&mut bytes, 0x2c, link_data.len() as u32);
+    write_u32(&mut bytes, 0x30, link_offset);
+
+    let dex = dalvik::Dex::from_reader(&bytes[..], bytes.len()).unwrap();
+    assert_eq!(Some(&link_data[..]), dex.link_data());
+    let (_, warnings) = dalvik::Dex::from_reader_lenient(&bytes[..], bytes.len()).unwrap();
+    assert_eq!(
+        vec![dalvik::Warning::LinkData {
+            offset: u64::from(link_offset),
+            size: 10,
+        }],
+        warnings
+    );
+    assert_eq!(
+        "the file has a link section of 10 bytes at offset 0x002c8fb0",
+        warnings[0].to_string()
+    );
+
+    // The link section must be in the file.
+    let truncated = &bytes[..bytes.len() - 1];
+    assert!(dalvik::Dex::from_reader(truncated, truncated.len()).is_err());
+}
+
+#[test]
+fn it_lists_the_class_descriptors() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let bytes = fs::read("test.dex").unwrap();
+    let descriptors = dalvik::Dex::iter_class_descriptors(&bytes[..], bytes.len())
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    assert_eq!(dex.types().len(), descriptors.len());
+    let names: HashSet<_> = dex
+        .types()
+        .iter()
+        .map(|class| format!("L{}", class.name()))
+        .collect();
+    assert_eq!(names, descriptors.into_iter().collect());
+}
+
+#[test]
+fn it_finds_native_methods() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let native_methods = dex.native_methods();
+    let names: Vec<_> = native_methods
+        .iter()
+        .map(|(class, method)| (class.name(), method.name()))
+        .collect();
+    assert_eq!(
+        vec![
+            ("jakhar/aseem/diva/DivaJni;", "access"),
+            ("jakhar/aseem/diva/DivaJni;", "initiateLaunchSequence"),
+        ],
+        names
+    );
+    assert!(native_methods
+        .iter()
+        .all(|(_, method)| method.code_offset().is_none()));
+
+    let (class, method) = native_methods[0];
+    assert_eq!(
+        "Java_jakhar_aseem_diva_DivaJni_access",
+        method.jni_short_name(class)
+    );
+    assert_eq!(
+        "Java_jakhar_aseem_diva_DivaJni_access__Ljava_lang_String_2",
+        method.jni_long_name(class)
+    );
+
+    let method = dalvik::Method::new(
+        AccessFlags::ACC_NATIVE,
+        "set_$",
+        Type::Void,
+        vec![
+            "[B".parse().unwrap(),
+            Type::FullyQualifiedName("java/lang/String;".to_owned()),
+        ],
+    );
+    assert_eq!(
+        "Java_jakhar_aseem_diva_DivaJni_set_1_00024___3BLjava_lang_String_2",
+        method.jni_long_name(class)
+    );
+}
+
+#[test]
+fn it_records_the_layout_of_the_parsed_items() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    assert!(dex.layout().is_none());
+
+    let bytes = fs::read("test.dex").unwrap();
+    let options = dalvik::ParseOptions::new().with_record_layout(true);
+    let dex = dalvik::Dex::from_reader_with_options(&bytes[..], bytes.len(), options).unwrap();
+    let layout = dex.layout().unwrap();
+
+    // The string data offset is in its string ID item, and it is made of its size, its
+    // characters and a null terminator.
+    let string_ids_offset = dex.header().get_string_ids_offset().unwrap() as usize;
+    let string_id = &bytes[string_ids_offset + 100 * 4..string_ids_offset + 101 * 4];
+    let string_offset =
+        u32::from_le_bytes([string_id[0], string_id[1], string_id[2], string_id[3]]);
+    assert_eq!(Some(" id="), dex.strings().nth(100));
+    assert_eq!(
+        Some((string_offset, 6)),
+        layout.get(dalvik::LayoutItem::StringData(100))
+    );
+
+    let method_ids_offset = dex.header().get_method_ids_offset().unwrap();
+    assert_eq!(
+        Some((method_ids_offset + 3085 * 8, 8)),
+        layout.get(dalvik::LayoutItem::MethodId(3085))
+    );
+    let class_defs_offset = dex.header().get_class_defs_offset().unwrap();
+    assert_eq!(
+        Some((class_defs_offset + 32, 32)),
+        layout.get(dalvik::LayoutItem::ClassDef(1))
+    );
+
+    let (code_offset, code_item) = dex.code_items().next().unwrap();
+    let (offset, len) = layout
+        .get(dalvik::LayoutItem::CodeItem(code_offset))
+        .unwrap();
+    assert_eq!(code_offset, offset);
+    assert!(len >= 16 + code_item.insns().len() as u32 * 2);
+
+    assert_eq!(
+        dex.strings().len()
+            + dex.header().get_method_ids_size() as usize
+            + dex.types().len()
+            + dex.code_items().count(),
+        layout.len()
+    );
+}
+
+/// Counts the classes of a dex file, using the single error type of the crate.
+fn count_classes<P: AsRef<Path>>(path: P) -> Result<usize, dalvik::error::Error> {
+    let bytes = fs::read(path)?;
+    let dex = dalvik::Dex::from_reader(&bytes[..], bytes.len())?;
+    Ok(dex.types().len())
+}
+
+#[test]
+fn it_returns_the_crate_error() {
+    assert_eq!(1_791, count_classes("test.dex").unwrap());
+
+    let error = count_classes("does-not-exist.dex").unwrap_err();
+    assert!(matches!(
+        error,
+        dalvik::error::Error::Io(ref error) if error.kind() == io::ErrorKind::NotFound
+    ));
+
+    let mut bytes = fs::read("test.dex").unwrap();
+    bytes[0] = b'x';
+    let error = dalvik::Dex::from_reader(&bytes[..], bytes.len()).unwrap_err();
+    assert!(matches!(
+        error,
+        dalvik::error::Error::Parse(dalvik::error::Parse::SectionRead {
+            section: "header_item",
+            ..
+        })
+    ));
+    assert!(matches!(
+        error.root_cause(),
+        dalvik::error::Error::Header(dalvik::error::Header::IncorrectMagic { .. })
+    ));
+    // The wrapped error is also the source of the error, as in any other error chain.
+    let source = std::error::Error::source(&error).unwrap();
+    assert_eq!(error.root_cause().to_string(), source.to_string());
+    assert!(source.downcast_ref::<dalvik::error::Error>().is_some());
+
+    let error = dalvik::ProguardMapping::from_reader(&b"    int count -> a\n"[..]).unwrap_err();
+    assert!(matches!(
+        error,
+        dalvik::error::Error::Mapping(dalvik::error::Mapping::InvalidLine { line: 1, .. })
+    ));
+}
+
+#[test]
+fn it_gets_the_string_literals_of_a_class() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let class = dex
+        .find_class("Ljakhar/aseem/diva/HardcodeActivity;")
+        .unwrap();
+    assert_eq!(
+        vec![
+            "MD5",
+            "vendorsecretkey",
+            "Access granted! See you on the other side :)",
+            "Access denied! See you in hell :D",
+        ],
+        class.string_literals(&dex)
+    );
+
+    let class = dex.find_class("Ljakhar/aseem/diva/R$string;").unwrap();
+    assert!(class.string_literals(&dex).is_empty());
+}
+
+/// Builds the smallest valid dex file, with no IDs nor classes, just the header and the map.
+fn empty_dex() -> Vec<u8> {
+    let map = [2, 0x0000, 1, 0, 0x1000, 1, 0x70];
+    let mut bytes = vec![0; 0x70];
+    bytes[..8].copy_from_slice(b"dex\n035\0");
+    write_u32(&mut bytes, 0x20, 0x70 + map.len() as u32 * 4);
+    write_u32(&mut bytes, 0x24, 0x70);
+    write_u32(&mut bytes, 0x28, 0x1234_5678);
+    write_u32(&mut bytes, 0x34, 0x70);
+    write_u32(&mut bytes, 0x68, map.len() as u32 * 4);
+    write_u32(&mut bytes, 0x6c, 0x70);
+    for value in &map {
+        bytes.extend_from_slice(&u32::to_le_bytes(*value));
+    }
+    bytes
+}
+
+#[test]
+fn it_reads_a_dex_file_without_classes() {
+    let bytes = empty_dex();
+    let dex = dalvik::Dex::from_reader(&bytes[..], bytes.len()).unwrap();
+    assert_eq!(0, dex.header().get_class_defs_size());
+    assert!(dex.types().is_empty());
+    assert_eq!(0, dex.strings().len());
+    assert!(dex.external_types().is_empty());
+    assert!(dex.classes_sorted().is_empty());
+    assert!(dex.to_java_skeletons().is_empty());
+    assert_eq!(0, dex.code_items().count());
+    assert_eq!(0, dex.methods().count());
+    assert!(dex.native_methods().is_empty());
+    assert!(!dex.uses_reflection());
+    assert!(dex.find_class("Ljava/lang/Object;").is_none());
+    assert!(dex.subclasses_of("Ljava/lang/Object;").is_empty());
+    assert!(dex.validate().is_empty());
+    #[cfg(feature = "extract")]
+    assert!(dex.extract_class("Ljava/lang/Object;").is_err());
+
+    let (_, warnings) = dalvik::Dex::from_reader_lenient(&bytes[..], bytes.len()).unwrap();
+    assert!(warnings.is_empty());
+    assert_eq!(
+        0,
+        dalvik::Dex::iter_class_descriptors(&bytes[..], bytes.len())
+            .unwrap()
+            .count()
+    );
+}
+
+#[test]
+fn it_gets_the_shorty_of_a_prototype() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    // `BundleCompatDonut.getBinder(Bundle, String): IBinder` and `putBinder(Bundle, String,
+    // IBinder): void`.
+    assert_eq!(Some("LLL"), dex.shorty_for_prototype(583));
+    assert_eq!(Some("VLLL"), dex.shorty_for_prototype(2073));
+    assert_eq!(
+        None,
+        dex.shorty_for_prototype(dex.header().get_prototype_ids_size())
+    );
+}
+
+#[test]
+fn it_gets_the_parameter_registers_of_a_method() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let class = dex
+        .find_class("Landroid/support/v4/graphics/ColorUtils;")
+        .unwrap();
+    let method = class
+        .methods()
+        .find(|method| method.name() == "compositeAlpha")
+        .unwrap();
+    assert!(method.access_flags().contains(AccessFlags::ACC_STATIC));
+    assert_eq!(&[Type::Int, Type::Int], method.parameters());
+
+    let code = method.code(&dex).unwrap();
+    assert_eq!(4, code.registers_size());
+    assert_eq!(2, code.ins_size());
+    assert_eq!(Some(2..4), method.parameter_registers(&dex));
+
+    // Instance methods also take `this`.
+    let method = dex
+        .methods()
+        .find(|method| {
+            method.name() == "writeToParcel"
+                && !method.access_flags().contains(AccessFlags::ACC_STATIC)
+                && method.code(&dex).is_some()
+        })
+        .unwrap();
+    let code = method.code(&dex).unwrap();
+    let registers = method.parameter_registers(&dex).unwrap();
+    assert_eq!(code.ins_size(), registers.end - registers.start);
+    assert_eq!(code.registers_size(), registers.end);
+
+    let abstract_method = dex
+        .methods()
+        .find(|method| method.access_flags().contains(AccessFlags::ACC_ABSTRACT))
+        .unwrap();
+    assert!(abstract_method.parameter_registers(&dex).is_none());
+
+    // The code item of `compositeAlpha()` declares a third incoming register.
+    let code_offset = dex
+        .find_class("Landroid/support/v4/graphics/ColorUtils;")
+        .unwrap()
+        .methods()
+        .find(|method| method.name() == "compositeAlpha")
+        .and_then(dalvik::Method::code_offset)
+        .unwrap() as usize;
+    let mut bytes = fs::read("test.dex").unwrap();
+    bytes[code_offset + 2] = 3;
+    let dex = dalvik::Dex::from_reader(&bytes[..], bytes.len()).unwrap();
+    let method = dex
+        .find_class("Landroid/support/v4/graphics/ColorUtils;")
+        .unwrap()
+        .methods()
+        .find(|method| method.name() == "compositeAlpha")
+        .unwrap();
+    assert_eq!(3, method.code(&dex).unwrap().ins_size());
+    assert!(method.parameter_registers(&dex).is_none());
+}
+
+#[test]
+fn it_measures_the_parsing_phases() {
+    let bytes = fs::read("test.dex").unwrap();
+    let (dex, timings) = dalvik::Dex::parse_bytes_measured(&bytes).unwrap();
+    assert_eq!(1791, dex.types().len());
+
+    let phases = timings.header()
+        + timings.strings()
+        + timings.id_tables()
+        + timings.class_data()
+        + timings.code_items()
+        + timings.finish();
+    assert_eq!(phases, timings.total());
+    assert!(timings.class_data() > std::time::Duration::default());
+    assert!(timings.to_string().contains("class data"));
+}
+
+#[test]
+fn it_stops_at_cyclic_superclasses() {
+    let mut bytes = fs::read("test.dex").unwrap();
+    let dex = dalvik::Dex::from_reader(&bytes[..], bytes.len()).unwrap();
+    // Make the first two classes extend each other.
+    let class_defs = dex.header().get_class_defs_offset().unwrap() as usize;
+    let first = read_u32(&bytes, class_defs);
+    let second = read_u32(&bytes, class_defs + 32);
+    write_u32(&mut bytes, class_defs + 8, second);
+    write_u32(&mut bytes, class_defs + 32 + 8, first);
+
+    let (dex, warnings) = dalvik::Dex::from_reader_lenient(&bytes[..], bytes.len()).unwrap();
+    let (first, second) = (&dex.types()[0], &dex.types()[1]);
+    assert_eq!(vec![second], dex.superclass_chain(first));
+    assert_eq!(vec![first], dex.superclass_chain(second));
+    assert_eq!(
+        vec![second],
+        dex.subclasses_of(&format!("L{}", first.name()))
+    );
+
+    let issues = vec![
+        dalvik::Issue::CyclicInheritance {
+            class: first.name().to_owned(),
+        },
+        dalvik::Issue::CyclicInheritance {
+            class: second.name().to_owned(),
+        },
+    ];
+    assert_eq!(issues, dex.validate());
+    assert_eq!(
+        issues
+            .into_iter()
+            .map(dalvik::Warning::Issue)
+            .collect::<Vec<_>>(),
+        warnings
+    );
+    assert_eq!(
+        format!("class `{}` is its own superclass", first.name()),
+        dex.validate()[0].to_string()
+    );
+}
+
+#[test]
+fn it_keeps_the_raw_bytes_of_annotations() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let class = &dex.types()[120];
+    let annotation = &class.annotations()[0];
+    // System visibility, type 2189 and a single element, followed by the element.
+    assert_eq!(&[0x02, 0x8d, 0x11, 0x01], &annotation.raw_bytes()[..4]);
+    assert_eq!(2189, annotation.type_index());
+
+    let decoded =
+        dalvik::types::Annotation::from_reader(&mut io::Cursor::new(annotation.raw_bytes()))
+            .unwrap();
+    assert_eq!(annotation.visibility(), decoded.visibility());
+    assert_eq!(annotation.type_index(), decoded.type_index());
+    assert_eq!(
+        format!("{:?}", annotation.elements()),
+        format!("{:?}", decoded.elements())
+    );
+    assert_eq!(
+        dex.resolve_annotation(annotation).to_string(),
+        dex.resolve_annotation(&decoded).to_string()
+    );
+}
+
+#[test]
+fn it_filters_the_application_classes() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let classes = dex.application_classes("jakhar.aseem.diva");
+    assert_eq!(20, classes.len());
+    assert!(classes
+        .iter()
+        .any(|class| class.name() == "jakhar/aseem/diva/MainActivity;"));
+    assert!(classes
+        .iter()
+        .any(|class| class.name() == "jakhar/aseem/diva/NotesProvider$DBHelper;"));
+    assert!(dex.find_class("Ljakhar/aseem/diva/R$layout;").is_some());
+    assert!(classes.iter().all(|class| {
+        class.name() != "jakhar/aseem/diva/R$layout;"
+            && class.name() != "jakhar/aseem/diva/R;"
+            && class.name() != "jakhar/aseem/diva/BuildConfig;"
+    }));
+
+    // Only whole package names match.
+    assert!(dex.application_classes("jakhar.aseem.di").is_empty());
+    assert!(dex
+        .application_classes("")
+        .iter()
+        .all(|class| !class.name().ends_with("/R;")));
+    assert_eq!(20, dex.application_classes("jakhar").len());
+}
+
+#[cfg(feature = "sha2")]
+#[test]
+fn it_hashes_the_contents_of_the_file() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    assert_eq!(None, dex.content_sha256());
+
+    let bytes = fs::read("test.dex").unwrap();
+    let options = dalvik::ParseOptions::new().with_retain_raw_bytes(true);
+    let hash = dalvik::Dex::from_reader_with_options(&bytes[..], bytes.len(), options)
+        .unwrap()
+        .content_sha256()
+        .unwrap();
+    let again = dalvik::Dex::from_reader_with_options(&bytes[..], bytes.len(), options)
+        .unwrap()
+        .content_sha256()
+        .unwrap();
+    assert_eq!(hash, again);
+    assert_eq!(
+        "0944d75a5af7ff316dc8844ca0684a8ee308c75058457bf614b997443b0d0361",
+        hash.iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>()
+    );
+}
+
+#[test]
+fn it_finds_the_accessors_of_a_field() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let accessors = dex
+        .accessors_of("Ljakhar/aseem/diva/NotesProvider;.CONTENT_URI:Landroid/net/Uri;")
+        .into_iter()
+        .map(|(class, method, pc, operation)| (class.name(), method.name(), pc, operation))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            (
+                "jakhar/aseem/diva/NotesProvider;",
+                "<clinit>",
+                6,
+                ArrayOperation::PutObject
+            ),
+            (
+                "jakhar/aseem/diva/NotesProvider;",
+                "insert",
+                16,
+                ArrayOperation::GetObject
+            ),
+            (
+                "jakhar/aseem/diva/AccessControl3NotesActivity;",
+                "accessNotes",
+                63,
+                ArrayOperation::GetObject
+            ),
+        ],
+        accessors
+    );
+
+    let sdk_int = dex.accessors_of("Landroid/os/Build$VERSION;.SDK_INT:I");
+    assert!(!sdk_int.is_empty());
+    assert!(sdk_int
+        .iter()
+        .all(|(_, _, _, operation)| *operation == ArrayOperation::Get));
+    assert!(dex.accessors_of("Lcom/example/Missing;.field:I").is_empty());
+}
+
+#[test]
+fn it_generates_a_report() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let report = dex.report();
+    let expected = "\
+dex version 035, little endian, 2920368 bytes
+checksum 0xa0576d4c, signature 910273720ddaf0755b48d809fd6a6f018fc92915
+strings: 19939
+types: 2419
+prototypes: 3522
+fields: 9942
+methods: 19282
+classes: 1791
+Landroid/support/annotation/AnimRes; [public abstract interface] extends Ljava/lang/Object;, \
+interfaces: 1, fields: 0, methods: 0
+Landroid/support/annotation/AnimatorRes; [public abstract interface] extends Ljava/lang/Object;, \
+interfaces: 1, fields: 0, methods: 0";
+    assert_eq!(
+        expected,
+        report.lines().take(10).collect::<Vec<_>>().join("\n")
+    );
+    assert_eq!(8 + 1791, report.lines().count());
+    assert!(report.lines().any(|line| {
+        line
+        == "Ljakhar/aseem/diva/NotesProvider; [public] extends Landroid/content/ContentProvider;, \
+            interfaces: 0, fields: 14, methods: 8"
+    }));
+}
+
+#[test]
+fn it_rejects_void_outside_of_return_types() {
+    let original = fs::read("test.dex").unwrap();
+    // Type 2345 is `V`.
+    let void_index = 2345_u16.to_le_bytes();
+
+    // The parameter list of prototype 1, `(I)`, is at offset 0x7a0dc.
+    let mut bytes = original.clone();
+    bytes[0x7_a0e0..0x7_a0e2].copy_from_slice(&void_index);
+    let error = dalvik::Dex::from_reader(&bytes[..], bytes.len()).unwrap_err();
+    assert!(matches!(
+        error.root_cause(),
+        dalvik::error::Error::Parse(dalvik::error::Parse::UnexpectedVoid("type_list"))
+    ));
+
+    // The type index of the first field ID.
+    let mut bytes = original;
+    bytes[0x2_02e2..0x2_02e4].copy_from_slice(&void_index);
+    let error = dalvik::Dex::from_reader(&bytes[..], bytes.len()).unwrap_err();
+    assert!(matches!(
+        error.root_cause(),
+        dalvik::error::Error::Parse(dalvik::error::Parse::UnexpectedVoid("field_id_item"))
+    ));
+    assert_eq!(
+        "`void` is not a valid type in a `field_id_item`",
+        error.root_cause().to_string()
+    );
+}
+
+#[test]
+fn it_visits_each_string() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let bytes = fs::read("test.dex").unwrap();
+
+    let mut strings = Vec::new();
+    dalvik::Dex::for_each_string(&bytes[..], bytes.len(), |index, string| {
+        assert_eq!(strings.len(), index as usize);
+        strings.push(string.to_owned());
+    })
+    .unwrap();
+    assert_eq!(dex.strings().len(), strings.len());
+    assert!(dex
+        .strings()
+        .zip(&strings)
+        .all(|(parsed, visited)| parsed == visited));
+
+    let mut count = 0;
+    dalvik::Dex::for_each_string(&empty_dex()[..], None, |_, _| count += 1).unwrap();
+    assert_eq!(0, count);
+}
+
+#[test]
+fn it_decodes_strings_on_demand() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let bytes = fs::read("test.dex").unwrap();
+
+    let strings = dalvik::LazyStrings::from_reader(&bytes[..], bytes.len()).unwrap();
+    assert_eq!(dex.strings().len(), strings.len());
+    assert_eq!(0, strings.decoded_count());
+
+    let first = strings.string_at(1_234).unwrap();
+    assert_eq!(dex.string_at(1_234).unwrap(), &*first);
+    assert_eq!(1, strings.decoded_count());
+
+    // The second request is served from the cache.
+    let second = strings.string_at(1_234).unwrap();
+    assert!(Arc::ptr_eq(&first, &second));
+    assert_eq!(1, strings.decoded_count());
+
+    assert_eq!(
+        dex.string_at(dex.strings().len() as u32 - 1).unwrap(),
+        &*strings.string_at(strings.len() as u32 - 1).unwrap()
+    );
+    assert!(strings.string_at(strings.len() as u32).is_err());
+    assert_eq!(2, strings.decoded_count());
+}
+
+#[test]
+fn it_checks_the_declared_number_of_strings() {
+    let mut bytes = fs::read("test.dex").unwrap();
+    // The size of the string ID section in the map, so that the header declares 5 strings more.
+    write_u32(&mut bytes, 0x7_a00c, 19_939 - 5);
+
+    let error = dalvik::Dex::from_reader(&bytes[..], bytes.len()).unwrap_err();
+    assert!(matches!(
+        error.root_cause(),
+        dalvik::error::Error::Parse(dalvik::error::Parse::StringCountMismatch {
+            declared: 19_939,
+            actual: 19_934,
+        })
+    ));
+
+    let (dex, warnings) = dalvik::Dex::from_reader_lenient(&bytes[..], bytes.len()).unwrap();
+    assert_eq!(
+        vec![dalvik::Warning::StringCountMismatch {
+            declared: 19_939,
+            actual: 19_934,
+        }],
+        warnings
+    );
+    assert_eq!(
+        "the header declares 19939 strings, but the map declares 19934",
+        warnings[0].to_string()
+    );
+    assert_eq!(19_934, dex.strings().len());
+    assert_eq!(19_939, dex.declared_string_count());
+}
+
+#[test]
+fn it_keeps_handlers_of_unknown_types() {
+    let mut bytes = fs::read("test.dex").unwrap();
+    // The type index of the first handler of `BundleCompatDonut.getBinder()`, whose code item is
+    // at offset 0xb6a7c, replaced by 16383, with the same ULEB128 length.
+    bytes[0xb_6b36..0xb_6b38].copy_from_slice(&[0xff, 0x7f]);
+
+    let (dex, warnings) = dalvik::Dex::from_reader_lenient(&bytes[..], bytes.len()).unwrap();
+    assert_eq!(
+        vec![dalvik::Warning::Issue(dalvik::Issue::UnknownHandlerType {
+            offset: 0xb_6a7c,
+            type_index: 16_383,
+        })],
+        warnings
+    );
+    assert_eq!(
+        "a catch handler of the code item at offset 0x000b6a7c references unknown type 16383",
+        warnings[0].to_string()
+    );
+
+    let try_ranges = dex.code_item(0xb_6a7c).unwrap().try_ranges();
+    let handler = try_ranges[0].handlers()[0];
+    assert_eq!(16_383, handler.type_index());
+    assert_eq!(51, handler.address());
+    assert!(dex.resolve_type(handler.type_index()).is_none());
+}
+
+#[test]
+fn it_resolves_the_parameter_types_of_prototypes() {
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    let class = dex.find_class("Ljakhar/aseem/diva/NotesProvider;").unwrap();
+    let method = class
+        .methods()
+        .find(|method| method.name() == "query")
+        .unwrap();
+
+    assert_eq!(
+        &[
+            "android/net/Uri;",
+            "java/lang/String;[1]",
+            "java/lang/String;",
+            "java/lang/String;[1]",
+            "java/lang/String;",
+        ],
+        &*method
+            .parameters()
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        "query(Landroid/net/Uri;[Ljava/lang/String;Ljava/lang/String;[Ljava/lang/String;\
+         Ljava/lang/String;)Landroid/database/Cursor;",
+        method.descriptor()
+    );
+}
+
+#[test]
+fn it_detects_high_entropy_data_sections() {
+    let options = dalvik::ParseOptions::new().with_retain_raw_bytes(true);
+    let dex = dalvik::Dex::from_file("test.dex").unwrap();
+    assert_eq!(None, dex.data_section_entropy());
+
+    let bytes = fs::read("test.dex").unwrap();
+    let dex = dalvik::Dex::from_reader_with_options(&bytes[..], bytes.len(), options).unwrap();
+    let entropy = dex.data_section_entropy().unwrap();
+    assert!(entropy > 5.0 && entropy < 5.2);
+    assert_eq!(Some(false), dex.is_likely_packed());
+
+    // An empty dex file with 64 KiB of pseudo-random bytes before the map, in its data section.
+    let payload_size = 0x1_0000;
+    let mut bytes = empty_dex();
+    let map = bytes.split_off(0x70);
+    let mut state = 0x2545_f491_u32;
+    for _ in 0..payload_size {
+        // Xorshift, which is random enough to look encrypted.
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        bytes.push(state.to_le_bytes()[0]);
+    }
+    bytes.extend_from_slice(&map);
+    let map_offset = 0x70 + payload_size as u32;
+    let file_size = bytes.len() as u32;
+    write_u32(&mut bytes, 0x20, file_size);
+    write_u32(&mut bytes, 0x34, map_offset);
+    write_u32(&mut bytes, 0x68, payload_size as u32 + map.len() as u32);
+    write_u32(&mut bytes, map_offset as usize + 4 + 12 + 8, map_offset);
+
+    let dex = dalvik::Dex::from_reader_with_options(&bytes[..], bytes.len(), options).unwrap();
+    assert!(dex.data_section_entropy().unwrap() > 7.9);
+    assert_eq!(Some(true), dex.is_likely_packed());
+}
+
+#[test]
+fn it_skips_sections_of_unknown_item_types() {
+    let mut bytes = empty_dex();
+    // A vendor section, of type 0x7000, at the end of the map.
+    write_u32(&mut bytes, 0x70, 3);
+    for value in &[0x7000, 1, 0x70] {
+        bytes.extend_from_slice(&u32::to_le_bytes(*value));
+    }
+    let file_size = bytes.len() as u32;
+    write_u32(&mut bytes, 0x20, file_size);
+    write_u32(&mut bytes, 0x68, file_size - 0x70);
+
+    let error = dalvik::Dex::from_reader(&bytes[..], bytes.len()).unwrap_err();
+    assert!(matches!(
+        error.root_cause(),
+        dalvik::error::Error::Parse(dalvik::error::Parse::InvalidItemType(0x7000))
+    ));
+
+    let (dex, warnings) = dalvik::Dex::from_reader_lenient(&bytes[..], bytes.len()).unwrap();
+    assert_eq!(
+        vec![dalvik::Warning::UnknownItemType {
+            item_type: 0x7000,
+            offset: 0x70,
+        }],
+        warnings
+    );
+    let vendor = dex.map_items()[2];
+    assert_eq!(ItemType::Unknown(0x7000), vendor.item_type());
+    assert_eq!(1, vendor.size());
+    assert_eq!(0x70, vendor.offset());
+    assert_eq!((ItemType::Unknown(0x7000), 1), dex.section_sizes()[2]);
+}